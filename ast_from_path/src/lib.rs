@@ -1,116 +1,836 @@
 // halts/ast_from_path/src/lib.rs
-// use quote::quote;
+use quote::quote;
 use std::env;
-use syn::{File, ItemFn, Path};
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use syn::visit::{self, Visit};
+use syn::{
+    Attribute, Block, Expr, ExprClosure, ExprLit, ExprPath, Ident, ImplItem, Item, ItemFn, Lit,
+    Meta, MetaNameValue, Pat, Signature, TraitItem, TraitItemFn, Type, Visibility,
+};
+
+/// Why the function at a `file_path::function_path` couldn't be had.
+#[derive(Debug)]
+pub enum AstFromPathError {
+    /// The query isn't a file path and a function path joined by `::`.
+    MalformedQuery {
+        /// The query as given
+        query: String,
+        /// What is wrong with it
+        reason: String,
+    },
+    /// The file couldn't be read.
+    Io {
+        /// The file
+        path: PathBuf,
+        /// Why it couldn't be read
+        error: io::Error,
+    },
+    /// The file isn't valid Rust.
+    Parse {
+        /// The file
+        path: PathBuf,
+        /// The 1-indexed line the error is on
+        line: usize,
+        /// The 1-indexed column the error is at
+        column: usize,
+        /// What the parser expected
+        message: String,
+    },
+    /// Nothing along the function path is a function.
+    NotFound {
+        /// The function path looked for
+        function_path: String,
+        /// The deepest module or function of the path which was found, empty for the file itself
+        module: String,
+        /// What a path could go on through from there
+        candidates: Vec<String>,
+        /// The candidates closest to the rest of the path, closest first
+        suggestions: Vec<String>,
+    },
+}
+
+impl fmt::Display for AstFromPathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AstFromPathError::MalformedQuery { query, reason } => write!(
+                f,
+                "malformed query `{}`: {}; expected `file_path::function_path`",
+                query, reason
+            ),
+            AstFromPathError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            AstFromPathError::Parse {
+                path,
+                line,
+                column,
+                message,
+            } => write!(f, "{}:{}:{}: {}", path.display(), line, column, message),
+            AstFromPathError::NotFound {
+                function_path,
+                module,
+                candidates,
+                suggestions,
+            } => {
+                let place = if module.is_empty() {
+                    "the file".to_string()
+                } else {
+                    format!("module `{}`", module)
+                };
+                write!(f, "no function `{}` in {}", function_path, place)?;
+                if !suggestions.is_empty() {
+                    let quoted: Vec<String> =
+                        suggestions.iter().map(|s| format!("`{}`", s)).collect();
+                    write!(f, "; did you mean {}?", quoted.join(" or "))?;
+                }
+                if candidates.is_empty() {
+                    write!(f, " (it has no functions or modules)")
+                } else {
+                    write!(f, " (it has: {})", candidates.join(", "))
+                }
+            }
+        }
+    }
+}
+
+impl Error for AstFromPathError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AstFromPathError::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// A function, method or closure found at a path, and what kind of callable it is.
+#[derive(Debug, Clone)]
+pub enum Callable {
+    /// A function at the top of a module, or nested in another function's body, like `outer::inner_fn`.
+    FreeFn(ItemFn),
+    /// A method of an inherent `impl` block, like `Type::method`.
+    InherentMethod {
+        /// The name of the type the `impl` block is for
+        self_ty: String,
+        /// The method, as a function
+        method: ItemFn,
+    },
+    /// A method of a trait, like `<Type as Trait>::method`: from an `impl Trait for Type` block,
+    /// or the trait's default for it, or, like `Trait::method`, the default asked of the trait itself.
+    TraitMethod {
+        /// The name of the trait
+        trait_name: String,
+        /// The name of the type the method is for, `None` for a default asked of the trait itself
+        self_ty: Option<String>,
+        /// The method, as a function
+        method: ItemFn,
+    },
+    /// A closure in a function's body, like `outer::{closure#0}`, numbered from the outside in, in the order they're written.
+    Closure {
+        /// Which closure of the function's body it is
+        index: usize,
+        /// The closure
+        closure: ExprClosure,
+    },
+}
+
+impl Callable {
+    /// The callable as a free function; a closure's parameters without types are given `_`
+    pub fn to_item_fn(&self) -> ItemFn {
+        match self {
+            Callable::FreeFn(func)
+            | Callable::InherentMethod { method: func, .. }
+            | Callable::TraitMethod { method: func, .. } => func.clone(),
+            Callable::Closure { closure, .. } => item_fn_from_closure(closure),
+        }
+    }
+
+    /// The body the callable runs, in which nested functions and closures are looked for
+    fn block(&self) -> Option<&Block> {
+        match self {
+            Callable::FreeFn(func)
+            | Callable::InherentMethod { method: func, .. }
+            | Callable::TraitMethod { method: func, .. } => Some(&func.block),
+            Callable::Closure { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for Callable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Callable::FreeFn(_) => write!(f, "free fn"),
+            Callable::InherentMethod { .. } => write!(f, "inherent method"),
+            Callable::TraitMethod { .. } => write!(f, "trait method"),
+            Callable::Closure { .. } => write!(f, "closure"),
+        }
+    }
+}
+
+/// A segment of a function path
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    /// A name, of a module, a type, a trait or a function
+    Name(String),
+    /// A qualified type, like `<Type as Trait>` or `<Type>`, by the last segments of the paths in it
+    Qualified {
+        /// The name of the type
+        self_ty: String,
+        /// The name of the trait, if there is one
+        trait_name: Option<String>,
+    },
+    /// The closure with an index, like `{closure#0}`
+    Closure(usize),
+}
+
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Segment::Name(name) => write!(f, "{}", name),
+            Segment::Qualified {
+                self_ty,
+                trait_name: Some(trait_name),
+            } => write!(f, "<{} as {}>", self_ty, trait_name),
+            Segment::Qualified { self_ty, .. } => write!(f, "<{}>", self_ty),
+            Segment::Closure(index) => write!(f, "{{closure#{}}}", index),
+        }
+    }
+}
+
+/// Split a function path into segments, at the `::`s outside angle brackets, or tell what is wrong with it
+fn segments_from_path(function_path: &str) -> Result<Vec<Segment>, String> {
+    let mut pieces = vec![String::new()];
+    let mut depth = 0usize;
+    let mut chars = function_path.trim().chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth = depth.checked_sub(1).ok_or("a `>` closes nothing")?,
+            ':' if depth == 0 && chars.peek() == Some(&':') => {
+                chars.next();
+                pieces.push(String::new());
+                continue;
+            }
+            _ => {}
+        }
+        if let Some(piece) = pieces.last_mut() {
+            piece.push(c);
+        }
+    }
+    if depth > 0 {
+        return Err("a `<` is never closed".to_string());
+    }
+    pieces
+        .iter()
+        .map(|piece| segment_from_piece(piece.trim()))
+        .collect()
+}
+
+/// Read one segment of a function path
+fn segment_from_piece(piece: &str) -> Result<Segment, String> {
+    if let Some(index) = piece
+        .strip_prefix("{closure#")
+        .and_then(|rest| rest.strip_suffix('}'))
+    {
+        let index = index
+            .parse()
+            .map_err(|_| format!("`{}` is not a closure's number", index))?;
+        return Ok(Segment::Closure(index));
+    }
+    if piece.starts_with('<') {
+        // `<Type as Trait>::f` is a path expression, which knows which part is the type and which the trait
+        let expr = syn::parse_str::<ExprPath>(&format!("{}::f", piece))
+            .map_err(|_| format!("`{}` is not a qualified type", piece))?;
+        let qself = expr
+            .qself
+            .ok_or_else(|| format!("`{}` is not a qualified type", piece))?;
+        let self_ty = match &*qself.ty {
+            Type::Path(ty) => ty.path.segments.last().map(|s| s.ident.to_string()),
+            _ => None,
+        }
+        .ok_or_else(|| format!("`{}` is not a named type", piece))?;
+        let trait_name = expr
+            .path
+            .segments
+            .iter()
+            .nth(qself.position.saturating_sub(1));
+        let trait_name = (qself.position > 0)
+            .then(|| trait_name.map(|s| s.ident.to_string()))
+            .flatten();
+        return Ok(Segment::Qualified {
+            self_ty,
+            trait_name,
+        });
+    }
+    syn::parse_str::<Ident>(piece)
+        .map(|ident| Segment::Name(ident.to_string()))
+        .map_err(|_| format!("`{}` is not a name", piece))
+}
 
 // #[proc_macro]
 // pub fn ast_from_path(input: TokenStream) -> TokenStream {
-pub fn ast_from_path(input_str: &str) -> Result<ItemFn, String> {
-    // Parse the input token stream into a string.
-    // let input_str = input.to_string();
+/// Find the function at a path like `src/foo.rs::module::func`, with the file relative to the crate root.
+///
+/// Methods are found as `Type::method`, or `<Type as Trait>::method` for a trait's, functions nested in another
+/// function's body as `outer::inner_fn`, and closures as `outer::{closure#0}`, which comes back as a function.
+///
+/// # Errors
+///
+/// If the query is malformed, the file can't be read or parsed, or nothing is at the path.
+pub fn ast_from_path(input_str: &str) -> Result<ItemFn, AstFromPathError> {
+    callable_from_path(input_str).map(|callable| callable.to_item_fn())
+}
 
+/// Find the function, method or closure at a path like `src/foo.rs::Type::method`,
+/// with the file relative to the crate root.
+///
+/// # Errors
+///
+/// If the query is malformed, the file can't be read or parsed, or nothing is at the path.
+pub fn callable_from_path(input_str: &str) -> Result<Callable, AstFromPathError> {
+    resolved_from_path(input_str).map(|resolved| resolved.callable)
+}
+
+/// A callable found at a path, with the file it is written in, which isn't the queried file
+/// when the path goes through an out-of-line module.
+#[derive(Debug, Clone)]
+pub struct Resolved {
+    /// The function, method or closure
+    pub callable: Callable,
+    /// The file it is written in
+    pub path: PathBuf,
+    /// That file's syntax tree
+    pub file: syn::File,
+    /// The path of the function within that file, like `["module", "Type", "method"]` or `["outer", "{closure#0}"]`
+    pub location: Vec<String>,
+}
+
+/// Find the function, method or closure at a path like `src/foo.rs::module::Type::method`,
+/// with the file relative to the crate root, and the file it is written in.
+///
+/// Modules are followed into their files, `foo.rs` or `foo/mod.rs`, or the file of a `#[path = "..."]`.
+///
+/// # Errors
+///
+/// If the query is malformed, a file can't be read or parsed, or nothing is at the path.
+pub fn resolved_from_path(input_str: &str) -> Result<Resolved, AstFromPathError> {
     // Remove the double quotes from the beginning and end of the file path string.
     let input_str = input_str.trim_matches('"');
+    let malformed = |reason: String| AstFromPathError::MalformedQuery {
+        query: input_str.to_string(),
+        reason,
+    };
 
-    // Split the input string into the file path and the function name.
-    let (file_path, function_path) = match input_str.split_once("::") {
-        Some((file_path, function_name)) => (file_path, function_name),
-        None => {
-            return Err("Whatever!".to_string());
-            // return syn::Error::new(
-            //     span.into(),
-            //     "Expected input in the form 'file_path::function_name'",
-            // )
-            // .into_compile_error()
-            // .into();
+    // Split the input string into the file path and the function path.
+    let (file_path, function_path) = input_str
+        .split_once("::")
+        .ok_or_else(|| malformed("there is no `::`".to_string()))?;
+    if file_path.is_empty() {
+        return Err(malformed("the file path is empty".to_string()));
+    }
+    let segments = segments_from_path(function_path).map_err(malformed)?;
+
+    // Construct an absolute file path from the path relative to the crate root.
+    let root = crate_root().map_err(|error| AstFromPathError::Io {
+        path: PathBuf::from("."),
+        error,
+    })?;
+    let mut path = root.join(file_path);
+    let mut file = file_from_path(&path)?;
+
+    let not_found = |depth: usize, candidates: Vec<String>| {
+        let wanted: Vec<String> = segments[depth..].iter().map(Segment::to_string).collect();
+        let suggestions = suggestions_from_candidates(&wanted, &candidates);
+        AstFromPathError::NotFound {
+            function_path: function_path.to_string(),
+            module: segments[..depth]
+                .iter()
+                .map(Segment::to_string)
+                .collect::<Vec<_>>()
+                .join("::"),
+            candidates,
+            suggestions,
         }
     };
 
-    // Get the current working directory.
-    let current_dir = match env::current_dir() {
-        Ok(dir) => dir,
-        Err(e) => return Err(e.to_string()),
+    // Traverse the AST to the deepest module along the path, into the files of out-of-line modules.
+    let mut module = ModuleDir::from_file(&path);
+    let mut items = file.items.clone();
+    let mut depth = 0;
+    let mut in_file = 0;
+    while depth + 1 < segments.len() {
+        let Segment::Name(name) = &segments[depth] else {
+            break;
+        };
+        let Some(inner) = items.iter().find_map(|item| match item {
+            Item::Mod(inner) if inner.ident == name => Some(inner),
+            _ => None,
+        }) else {
+            break;
+        };
+        (module, items) = match &inner.content {
+            Some((_, content)) => (module.inline(name), content.clone()),
+            None => {
+                let (located, dir) = module.locate(name, &inner.attrs);
+                file = file_from_path(&located)?;
+                path = located;
+                in_file = depth + 1;
+                (dir, file.items.clone())
+            }
+        };
+        depth += 1;
+    }
+    let items = &items;
+
+    // Then to a method of a type or trait there, or a function.
+    let (found, consumed) = match (&segments[depth], segments.get(depth + 1)) {
+        (
+            Segment::Qualified {
+                self_ty,
+                trait_name,
+            },
+            Some(Segment::Name(method)),
+        ) => (
+            method_in_items(items, self_ty, Some(trait_name.as_deref()), method),
+            2,
+        ),
+        (Segment::Name(owner), Some(Segment::Name(method))) => {
+            match method_in_items(items, owner, None, method) {
+                Some(callable) => (Some(callable), 2),
+                None => (function_in_items(items, owner), 1),
+            }
+        }
+        (Segment::Name(name), _) => (function_in_items(items, name), 1),
+        _ => (None, 1),
     };
+    let mut callable = found.ok_or_else(|| not_found(depth, candidates_from_items(items)))?;
+    depth += consumed;
+
+    // Then to the functions nested in its body, and its closures.
+    while let Some(segment) = segments.get(depth) {
+        let nested = callable.block().map(Nested::from_block).unwrap_or_default();
+        let next = match segment {
+            Segment::Name(name) => nested
+                .functions
+                .iter()
+                .find(|func| func.sig.ident == name)
+                .map(|func| Callable::FreeFn(func.clone())),
+            Segment::Closure(index) => {
+                nested
+                    .closures
+                    .get(*index)
+                    .map(|closure| Callable::Closure {
+                        index: *index,
+                        closure: closure.clone(),
+                    })
+            }
+            Segment::Qualified { .. } => None,
+        };
+        callable = next.ok_or_else(|| not_found(depth, nested.candidates()))?;
+        depth += 1;
+    }
+    let location = segments[in_file..]
+        .iter()
+        .map(|segment| match segment {
+            Segment::Qualified { self_ty, .. } => self_ty.clone(),
+            segment => segment.to_string(),
+        })
+        .collect();
+    Ok(Resolved {
+        callable,
+        path,
+        file,
+        location,
+    })
+}
+
+/// The root of the crate being looked in: `CARGO_MANIFEST_DIR` when Cargo sets it,
+/// else the nearest directory with a `Cargo.toml` from the working directory up, else the working directory.
+///
+/// # Errors
+///
+/// If there is no `CARGO_MANIFEST_DIR` and the working directory can't be had.
+pub fn crate_root() -> io::Result<PathBuf> {
+    if let Some(dir) = env::var_os("CARGO_MANIFEST_DIR") {
+        return Ok(PathBuf::from(dir));
+    }
+    let current_dir = env::current_dir()?;
+    let root = current_dir
+        .ancestors()
+        .find(|dir| dir.join("Cargo.toml").is_file())
+        .map(Path::to_path_buf);
+    Ok(root.unwrap_or(current_dir))
+}
+
+/// Read and parse a file of Rust
+fn file_from_path(path: &Path) -> Result<syn::File, AstFromPathError> {
+    let file_content = std::fs::read_to_string(path).map_err(|error| AstFromPathError::Io {
+        path: path.to_path_buf(),
+        error,
+    })?;
+    syn::parse_file(&file_content).map_err(|error| {
+        let start = error.span().start();
+        AstFromPathError::Parse {
+            path: path.to_path_buf(),
+            line: start.line,
+            column: start.column + 1,
+            message: error.to_string(),
+        }
+    })
+}
+
+/// Where the out-of-line modules of a module are, found the way `rustc` finds them.
+#[derive(Debug, Clone)]
+pub struct ModuleDir {
+    /// The directory the module's `mod foo;` declarations are found in
+    dir: PathBuf,
+    /// The directory of the file the module is written in
+    file_dir: PathBuf,
+    /// Whether the module is written inline, as `mod foo { ... }`
+    is_inline: bool,
+}
+
+impl ModuleDir {
+    /// The module of a crate root, which declares its modules beside it
+    pub fn from_root(file: &Path) -> Self {
+        let file_dir = file.parent().unwrap_or(Path::new(".")).to_path_buf();
+        ModuleDir {
+            dir: file_dir.clone(),
+            file_dir,
+            is_inline: false,
+        }
+    }
+
+    /// The module of a whole file: a crate root or a `mod.rs` declares its modules beside it, a file `foo.rs` in `foo/`.
+    ///
+    /// Crate roots are told by their names, `lib.rs`, `main.rs` and `build.rs`,
+    /// or by being in a `bin`, `tests`, `examples` or `benches` directory.
+    pub fn from_file(file: &Path) -> Self {
+        let root = ModuleDir::from_root(file);
+        let is_root = file.file_name().is_some_and(|name| {
+            ["lib.rs", "main.rs", "mod.rs", "build.rs"].contains(&name.to_str().unwrap_or(""))
+        }) || root.file_dir.file_name().is_some_and(|name| {
+            ["bin", "tests", "examples", "benches"].contains(&name.to_str().unwrap_or(""))
+        });
+        match file.file_stem() {
+            Some(stem) if !is_root => ModuleDir {
+                dir: root.file_dir.join(stem),
+                ..root
+            },
+            _ => root,
+        }
+    }
+
+    /// The module written inline in this one as `mod name { ... }`
+    pub fn inline(&self, name: &str) -> Self {
+        ModuleDir {
+            dir: self.dir.join(name),
+            file_dir: self.file_dir.clone(),
+            is_inline: true,
+        }
+    }
+
+    /// The file of an out-of-line module, and where its own modules are:
+    /// in `foo/` for `foo.rs` or `foo/mod.rs`, and beside it, like a `mod.rs`, for the file of a `#[path]`.
+    /// It's `foo.rs` if there is no `foo/mod.rs`, so a module with neither is an error about `foo.rs`
+    pub fn locate(&self, name: &str, attrs: &[Attribute]) -> (PathBuf, ModuleDir) {
+        if let Some(path) = path_from_attrs(attrs) {
+            // outside inline modules, a `#[path]` is relative to the file it's written in
+            let base = if self.is_inline {
+                &self.dir
+            } else {
+                &self.file_dir
+            };
+            let file = base.join(path);
+            let module = ModuleDir::from_root(&file);
+            return (file, module);
+        }
+        let nested = self.dir.join(name).join("mod.rs");
+        let file = if nested.is_file() {
+            nested
+        } else {
+            self.dir.join(format!("{}.rs", name))
+        };
+        let module = ModuleDir {
+            dir: self.dir.join(name),
+            file_dir: file.parent().unwrap_or(&self.dir).to_path_buf(),
+            is_inline: false,
+        };
+        (file, module)
+    }
+}
 
-    // Construct an absolute file path from the relative file path.
-    let absolute_file_path = current_dir.join(file_path);
+/// The file of a `#[path = "..."]` attribute, if there is one
+fn path_from_attrs(attrs: &[Attribute]) -> Option<String> {
+    attrs.iter().find_map(|attr| match &attr.meta {
+        Meta::NameValue(MetaNameValue {
+            path,
+            value:
+                Expr::Lit(ExprLit {
+                    lit: Lit::Str(file),
+                    ..
+                }),
+            ..
+        }) if path.is_ident("path") => Some(file.value()),
+        _ => None,
+    })
+}
 
-    // println!("absolute_file_path: \n {:?}", absolute_file_path);
+/// The function of a name among some items
+fn function_in_items(items: &[Item], name: &str) -> Option<Callable> {
+    items.iter().find_map(|item| match item {
+        Item::Fn(func) if func.sig.ident == name => Some(Callable::FreeFn(func.clone())),
+        _ => None,
+    })
+}
 
-    // Read the source file.
-    let file_content = match std::fs::read_to_string(&absolute_file_path) {
-        Ok(content) => content,
-        Err(e) => return Err(e.to_string()),
+/// A method among some items, of an `impl` block for a type or a trait's default.
+///
+/// `trait_name` is `None` for `Type::method`, which is an inherent method first, then any trait's,
+/// then, if `Type` is a trait, its default; `Some(None)` for `<Type>::method`, which is an inherent method only;
+/// and `Some(Some(trait_name))` for `<Type as Trait>::method`, from the trait's `impl` block or its default.
+fn method_in_items(
+    items: &[Item],
+    owner: &str,
+    trait_name: Option<Option<&str>>,
+    method: &str,
+) -> Option<Callable> {
+    let impls = items.iter().filter_map(|item| match item {
+        Item::Impl(implementation)
+            if last_segment_of_type(&implementation.self_ty).as_deref() == Some(owner) =>
+        {
+            Some(implementation)
+        }
+        _ => None,
+    });
+    let mut inherent = None;
+    let mut of_trait = None;
+    for implementation in impls {
+        let Some(func) = implementation.items.iter().find_map(|item| match item {
+            ImplItem::Fn(func) if func.sig.ident == method => Some(func),
+            _ => None,
+        }) else {
+            continue;
+        };
+        let method = item_fn_from_parts(&func.attrs, &func.vis, &func.sig, &func.block);
+        match &implementation.trait_ {
+            None => {
+                inherent.get_or_insert(Callable::InherentMethod {
+                    self_ty: owner.to_string(),
+                    method,
+                });
+            }
+            Some((_, path, _)) => {
+                let name = path.segments.last().map(|s| s.ident.to_string());
+                let wanted = match trait_name {
+                    Some(Some(wanted)) => name.as_deref() == Some(wanted),
+                    Some(None) => false,
+                    None => true,
+                };
+                if wanted && of_trait.is_none() {
+                    of_trait = name.map(|trait_name| Callable::TraitMethod {
+                        trait_name,
+                        self_ty: Some(owner.to_string()),
+                        method,
+                    });
+                }
+            }
+        }
+    }
+    match trait_name {
+        Some(None) => inherent,
+        Some(Some(wanted)) => of_trait.or_else(|| {
+            default_in_items(items, wanted, method).map(|method| Callable::TraitMethod {
+                trait_name: wanted.to_string(),
+                self_ty: Some(owner.to_string()),
+                method,
+            })
+        }),
+        None => inherent.or(of_trait).or_else(|| {
+            default_in_items(items, owner, method).map(|method| Callable::TraitMethod {
+                trait_name: owner.to_string(),
+                self_ty: None,
+                method,
+            })
+        }),
+    }
+}
+
+/// The default of a method of a trait among some items, as a function
+fn default_in_items(items: &[Item], trait_name: &str, method: &str) -> Option<ItemFn> {
+    items.iter().find_map(|item| match item {
+        Item::Trait(trait_item) if trait_item.ident == trait_name => {
+            trait_item.items.iter().find_map(|item| match item {
+                TraitItem::Fn(TraitItemFn {
+                    attrs,
+                    sig,
+                    default: Some(block),
+                    ..
+                }) if sig.ident == method => Some(item_fn_from_parts(
+                    attrs,
+                    &Visibility::Inherited,
+                    sig,
+                    block,
+                )),
+                _ => None,
+            })
+        }
+        _ => None,
+    })
+}
+
+/// The name of the type an `impl` block is for, e.g. `List` for `impl<T> List<T>`
+pub fn last_segment_of_type(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(path) => Some(path.path.segments.last()?.ident.to_string()),
+        Type::Reference(reference) => last_segment_of_type(&reference.elem),
+        _ => None,
+    }
+}
+
+/// A free function with the attributes, visibility, signature and body of a method
+pub fn item_fn_from_parts(
+    attrs: &[Attribute],
+    vis: &Visibility,
+    sig: &Signature,
+    block: &Block,
+) -> ItemFn {
+    ItemFn {
+        attrs: attrs.to_vec(),
+        vis: vis.clone(),
+        sig: sig.clone(),
+        block: Box::new(block.clone()),
+    }
+}
+
+/// A closure as a free function, with `_` for the types of parameters which have none
+pub fn item_fn_from_closure(closure: &ExprClosure) -> ItemFn {
+    let params = closure.inputs.iter().map(|input| match input {
+        Pat::Type(typed) => quote!(#typed),
+        pat => quote!(#pat: _),
+    });
+    let body = &closure.body;
+    let block: Block = match &**body {
+        Expr::Block(block) => block.block.clone(),
+        _ => syn::parse_quote!({ #body }),
     };
+    syn::parse_quote!(fn __closure(#(#params),*) #block)
+}
 
-    // println!("file_content:\n {}", file_content);
+/// The functions nested in a function's body, and its closures, outermost first
+#[derive(Debug, Default)]
+pub struct Nested {
+    /// The functions declared in the body
+    pub functions: Vec<ItemFn>,
+    /// The closures, in the order they're written, numbered as in `outer::{closure#0}`
+    pub closures: Vec<ExprClosure>,
+}
 
-    // Parse the file_content into a token stream.
-    let file_ast: syn::File = syn::parse_file(&file_content).unwrap();
-    // println!("{:?}", file_ast);
+impl Nested {
+    /// Collect what is nested in a body, but not what is nested in what is nested in it
+    pub fn from_block(block: &Block) -> Self {
+        let mut nested = Nested::default();
+        nested.visit_block(block);
+        nested
+    }
 
-    // Traverse the AST to locate the subtree at `function_path`
-    // Define a recursive function to traverse the AST and find the subtree at the specified path.
-    fn get_function(function_path: &str, ast: &File) -> Option<ItemFn> {
-        let parsed_function_path = syn::parse_str::<Path>(function_path).unwrap();
-        let path_segments = parsed_function_path
-            .segments
-            .iter()
-            .map(|s| &s.ident)
-            .collect::<Vec<_>>();
-
-        fn find_function_helper<'a>(
-            items: &'a [syn::Item],
-            path_segments: &'a [&syn::Ident],
-        ) -> Option<&'a ItemFn> {
-            for item in items {
-                let current_segment = path_segments[0].to_string();
-                match item {
-                    syn::Item::Fn(func) => {
-                        // println!("func={:?}", func);
-                        let function_identifier = func.sig.ident.to_string();
-                        // println!(
-                        //     "function_identifier={}, current_segment={}",
-                        //     function_identifier, current_segment
-                        // );
-                        if function_identifier == current_segment {
-                            if path_segments.len() == 1 {
-                                return Some(func);
-                            }
-                        }
+    /// What a path can go on through from the body
+    fn candidates(&self) -> Vec<String> {
+        let functions = self.functions.iter().map(|func| func.sig.ident.to_string());
+        let closures = (0..self.closures.len()).map(|index| Segment::Closure(index).to_string());
+        functions.chain(closures).collect()
+    }
+}
+
+impl<'ast> Visit<'ast> for Nested {
+    fn visit_expr_closure(&mut self, closure: &'ast ExprClosure) {
+        self.closures.push(closure.clone());
+        visit::visit_expr_closure(self, closure);
+    }
+
+    fn visit_item(&mut self, item: &'ast Item) {
+        // a nested function's own closures are its own
+        if let Item::Fn(func) = item {
+            self.functions.push(func.clone());
+        }
+    }
+}
+
+/// The names a function path can go on through from some items: functions, modules, and methods as `Type::method`
+fn candidates_from_items(items: &[Item]) -> Vec<String> {
+    let mut candidates = vec![];
+    for item in items {
+        match item {
+            Item::Fn(func) => candidates.push(func.sig.ident.to_string()),
+            Item::Mod(module) => candidates.push(module.ident.to_string()),
+            Item::Impl(implementation) => {
+                let Some(self_ty) = last_segment_of_type(&implementation.self_ty) else {
+                    continue;
+                };
+                let owner = match &implementation.trait_ {
+                    Some((_, path, _)) => match path.segments.last() {
+                        Some(trait_name) => format!("<{} as {}>", self_ty, trait_name.ident),
+                        None => self_ty,
+                    },
+                    None => self_ty,
+                };
+                for item in &implementation.items {
+                    if let ImplItem::Fn(func) = item {
+                        candidates.push(format!("{}::{}", owner, func.sig.ident));
                     }
-                    syn::Item::Mod(module) => {
-                        let module_identifier = module.ident.to_string();
-                        if module_identifier == current_segment {
-                            if let Some(content) = &module.content {
-                                return find_function_helper(&content.1, &path_segments[1..]);
-                            }
-                        }
+                }
+            }
+            Item::Trait(trait_item) => {
+                for item in &trait_item.items {
+                    if let TraitItem::Fn(TraitItemFn {
+                        sig,
+                        default: Some(_),
+                        ..
+                    }) = item
+                    {
+                        candidates.push(format!("{}::{}", trait_item.ident, sig.ident));
                     }
-                    _ => {}
                 }
             }
-            None
+            _ => {}
         }
+    }
+    candidates
+}
 
-        find_function_helper(&ast.items, &path_segments).cloned()
+/// The candidates within a few edits of the rest of a path, closest first,
+/// each against as many of the path's segments as it has itself
+fn suggestions_from_candidates(wanted: &[String], candidates: &[String]) -> Vec<String> {
+    let mut close: Vec<(usize, &String)> = candidates
+        .iter()
+        .filter_map(|candidate| {
+            let segments = segments_from_path(candidate).map_or(1, |segments| segments.len());
+            let name = wanted.get(..segments)?.join("::");
+            let distance = edit_distance(&name, candidate);
+            let most = (name.chars().count() / 3).max(1);
+            (distance <= most).then_some((distance, candidate))
+        })
+        .collect();
+    close.sort();
+    close
+        .into_iter()
+        .map(|(_, candidate)| candidate.clone())
+        .collect()
+}
+
+/// The Levenshtein distance between two strings: how many characters must be inserted, deleted or replaced
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let replaced = previous[j] + usize::from(ca != *cb);
+            current.push(replaced.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
     }
-    let subtree = get_function(function_path, &file_ast).unwrap();
-    // println!("subtree={:?}", subtree);
-
-    // Generate the code for creating the AST from the function.
-    // let target_function_token_stream = quote! {
-    //     #subtree
-    // };
-
-    // println!(
-    //     "target_function_token_stream={}",
-    //     target_function_token_stream
-    // );
-    // let output = target_function_token_stream.into();
-    // println!("output={}", output);
-    // output
-    Ok(subtree)
+    previous[b.len()]
 }
 
 // return syn::Error::new(
@@ -1,6 +1,8 @@
 // halts/ast_from_path/tests/ast_from_path_test.rs
 
-use ast_from_path::ast_from_path;
+use ast_from_path::{
+    ast_from_path, callable_from_path, crate_root, resolved_from_path, AstFromPathError, Callable,
+};
 
 // This is the function you want to test your proc macro on
 fn test_function() {
@@ -18,6 +20,44 @@ mod nested {
     }
 }
 
+// A type with an inherent method and a trait's, and a trait with a default
+#[allow(dead_code)]
+struct Counter(u32);
+
+#[allow(dead_code)]
+impl Counter {
+    fn tick(&mut self) {
+        self.0 += 1;
+    }
+}
+
+#[allow(dead_code)]
+trait Step {
+    fn step(&mut self) {
+        self.reset();
+    }
+    fn reset(&mut self);
+}
+
+impl Step for Counter {
+    fn reset(&mut self) {
+        self.0 = 0;
+    }
+}
+
+// A function with a function and closures in its body
+#[allow(dead_code)]
+fn outer() {
+    fn inner_fn() {}
+    let add = |a, b: u32| a + b;
+    let twice = |f: fn()| {
+        f();
+        f();
+    };
+    twice(inner_fn);
+    add(1, 2);
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -32,4 +72,194 @@ mod test {
         //     ast_from_path!("tests/ast_from_path_test.rs::nested::deeply::deeply_nested_test_function");
         // println!("{}", ast3.to_string());
     }
+
+    #[test]
+    fn test_malformed_query() {
+        let error = ast_from_path("tests/ast_from_path_test.rs").unwrap_err();
+        assert!(matches!(error, AstFromPathError::MalformedQuery { .. }));
+        let error = ast_from_path("tests/ast_from_path_test.rs::not a path").unwrap_err();
+        assert!(matches!(error, AstFromPathError::MalformedQuery { .. }));
+    }
+
+    #[test]
+    fn test_io_and_parse_errors() {
+        let error = ast_from_path("tests/missing.rs::f").unwrap_err();
+        assert!(matches!(error, AstFromPathError::Io { .. }));
+
+        let path = std::env::temp_dir().join(format!("ast_from_path_{}.rs", std::process::id()));
+        std::fs::write(&path, "fn f() {}\nfn g( {}\n").unwrap();
+        let error = ast_from_path(&format!("{}::f", path.display())).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        match error {
+            AstFromPathError::Parse { line, .. } => assert_eq!(line, 2),
+            error => panic!("expected a parse error, got {}", error),
+        }
+    }
+
+    #[test]
+    fn test_not_found_suggestions() {
+        let error =
+            ast_from_path("tests/ast_from_path_test.rs::nested::nested_tset_function").unwrap_err();
+        match &error {
+            AstFromPathError::NotFound {
+                module,
+                candidates,
+                suggestions,
+                ..
+            } => {
+                assert_eq!(module, "nested");
+                assert_eq!(candidates, &["nested_test_function", "deeply"]);
+                assert_eq!(suggestions, &["nested_test_function"]);
+            }
+            error => panic!("expected not found, got {}", error),
+        }
+        assert!(error
+            .to_string()
+            .contains("did you mean `nested_test_function`?"));
+
+        let error = ast_from_path("tests/ast_from_path_test.rs::nestde::deeply::f").unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .starts_with("no function `nestde::deeply::f` in the file; did you mean `nested`?"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_callables() {
+        let file = "tests/ast_from_path_test.rs";
+        let callable = |path: &str| callable_from_path(&format!("{}::{}", file, path)).unwrap();
+        match callable("Counter::tick") {
+            Callable::InherentMethod { self_ty, method } => {
+                assert_eq!(self_ty, "Counter");
+                assert_eq!(method.sig.ident, "tick");
+            }
+            callable => panic!("expected an inherent method, got a {}", callable),
+        }
+        match callable("<Counter as Step>::reset") {
+            Callable::TraitMethod {
+                trait_name,
+                self_ty,
+                ..
+            } => assert_eq!(
+                (trait_name.as_str(), self_ty.as_deref()),
+                ("Step", Some("Counter"))
+            ),
+            callable => panic!("expected a trait method, got a {}", callable),
+        }
+        assert!(matches!(
+            callable("<Counter as Step>::step"),
+            Callable::TraitMethod {
+                self_ty: Some(_),
+                ..
+            }
+        ));
+        assert!(matches!(
+            callable("Step::step"),
+            Callable::TraitMethod { self_ty: None, .. }
+        ));
+        assert!(matches!(
+            callable("Counter::reset"),
+            Callable::TraitMethod { .. }
+        ));
+        assert!(matches!(callable("outer::inner_fn"), Callable::FreeFn(_)));
+        match callable("outer::{closure#0}") {
+            Callable::Closure { index, .. } => assert_eq!(index, 0),
+            callable => panic!("expected a closure, got a {}", callable),
+        }
+        let location = |path: &str| {
+            resolved_from_path(&format!("{}::{}", file, path))
+                .unwrap()
+                .location
+        };
+        assert_eq!(location("<Counter as Step>::reset"), ["Counter", "reset"]);
+        assert_eq!(location("outer::{closure#0}"), ["outer", "{closure#0}"]);
+        let closure = ast_from_path(&format!("{}::outer::{{closure#0}}", file)).unwrap();
+        assert_eq!(closure.sig.ident, "__closure");
+        assert_eq!(closure.sig.inputs.len(), 2);
+        assert!(ast_from_path(&format!("{}::<Counter>::reset", file)).is_err());
+    }
+
+    #[test]
+    fn test_callables_not_found() {
+        let error = ast_from_path("tests/ast_from_path_test.rs::Counter::tikc").unwrap_err();
+        match &error {
+            AstFromPathError::NotFound { suggestions, .. } => {
+                assert_eq!(
+                    suggestions.first().map(String::as_str),
+                    Some("Counter::tick")
+                )
+            }
+            error => panic!("expected not found, got {}", error),
+        }
+        let error = ast_from_path("tests/ast_from_path_test.rs::outer::{closure#2}").unwrap_err();
+        match &error {
+            AstFromPathError::NotFound {
+                module, candidates, ..
+            } => {
+                assert_eq!(module, "outer");
+                assert_eq!(candidates, &["inner_fn", "{closure#0}", "{closure#1}"]);
+            }
+            error => panic!("expected not found, got {}", error),
+        }
+    }
+
+    #[test]
+    fn test_out_of_line_modules() {
+        assert_eq!(
+            crate_root().unwrap(),
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        );
+
+        let root = std::env::temp_dir().join(format!("ast_from_path_mods_{}", std::process::id()));
+        let files = [
+            ("src/lib.rs", "mod a;\n#[path = \"elsewhere.rs\"]\nmod b;\nmod c {\n    mod d;\n}\nmod missing;\nmod x;\n"),
+            ("src/a/mod.rs", "mod e;\n"),
+            ("src/a/e.rs", "fn f() {}\n"),
+            ("src/elsewhere.rs", "fn g() {}\n"),
+            ("src/c/d.rs", "pub fn h() {}\n"),
+            ("src/x.rs", "mod y;\n#[path = \"other/z.rs\"]\nmod z;\n"),
+            ("src/x/y.rs", "fn f() {}\n"),
+            ("src/other/z.rs", "mod w;\n"),
+            ("src/other/w.rs", "fn k() {}\n"),
+        ];
+        for (path, code) in files {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, code).unwrap();
+        }
+        let lib = root.join("src/lib.rs").display().to_string();
+        let found = |path: &str| ast_from_path(&format!("{}::{}", lib, path));
+        let names: Vec<String> = ["a::e::f", "b::g", "c::d::h", "x::y::f", "x::z::w::k"]
+            .iter()
+            .map(|path| found(path).unwrap().sig.ident.to_string())
+            .collect();
+        let location = |path: &str| {
+            resolved_from_path(&format!("{}::{}", lib, path))
+                .unwrap()
+                .location
+        };
+        let locations = [location("c::d::h"), location("x::z::w::k")];
+        let missing = found("missing::f").unwrap_err();
+        let not_found = found("a::e::g").unwrap_err();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(names, ["f", "g", "h", "f", "k"]);
+        assert_eq!(locations, [["h"], ["k"]]);
+        match missing {
+            AstFromPathError::Io { path, .. } => assert!(path.ends_with("src/missing.rs")),
+            error => panic!("expected an io error, got {}", error),
+        }
+        match not_found {
+            AstFromPathError::NotFound {
+                module, candidates, ..
+            } => {
+                assert_eq!(module, "a::e");
+                assert_eq!(candidates, &["f"]);
+            }
+            error => panic!("expected not found, got {}", error),
+        }
+    }
 }
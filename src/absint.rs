@@ -0,0 +1,194 @@
+//! halts/src/absint.rs
+//! Abstract interpretation over the control-flow graph of a body.
+//! An analysis describes what it knows about the locals at a point as an element of a lattice,
+//! and says how statements and branch conditions change it.
+//! The solver runs it to a fixpoint with a worklist, widening at the heads of loops so that it gets there,
+//! then narrowing to win back some of what widening gave away.
+//! The result over-approximates every run of the body: a block or edge whose state is bottom is never taken.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt;
+
+use crate::ir::{BlockId, Body, Expr, Statement, Terminator, ENTRY};
+
+/// How many times the state at the head of a loop grows by joins before it is widened
+const WIDENING_DELAY: usize = 2;
+
+/// How many times the fixpoint is recomputed downwards after widening
+const NARROWING_PASSES: usize = 2;
+
+/// What an analysis knows at a point, ordered by how much it allows.
+pub trait Lattice: Clone + PartialEq + fmt::Debug {
+    /// The element which allows nothing, as at a block which is never reached
+    fn bottom() -> Self;
+
+    /// Whether this is the bottom element
+    fn is_bottom(&self) -> bool;
+
+    /// The least element which allows everything either allows
+    fn join(&self, other: &Self) -> Self;
+
+    /// An element which allows everything either allows,
+    /// and which stops growing after finitely many widenings
+    fn widen(&self, next: &Self) -> Self;
+
+    /// An element between `next` and `self`, for `next` below `self`,
+    /// which stops shrinking after finitely many narrowings
+    fn narrow(&self, next: &Self) -> Self;
+}
+
+/// An abstract interpretation: where it starts, and how each statement and condition changes what it knows.
+pub trait Analysis {
+    /// What is known at a point
+    type State: Lattice;
+
+    /// What is known when the body is entered
+    fn entry(&self, body: &Body) -> Self::State;
+
+    /// What is known after a statement, given what was known before it
+    fn statement(&self, state: &Self::State, statement: &Statement) -> Self::State;
+
+    /// What is known once a condition is found to have a value, given what was known before
+    fn assume(&self, state: &Self::State, condition: &Expr, holds: bool) -> Self::State;
+}
+
+/// The result of running an analysis to a fixpoint over a body.
+#[derive(Debug, Clone)]
+pub struct Fixpoint<S> {
+    /// What is known at the start of each block
+    pub states: Vec<S>,
+    /// What is known along each edge, after the statements of the block it leaves and the branch it takes
+    pub edges: BTreeMap<(BlockId, BlockId), S>,
+}
+
+impl<S: Lattice> Fixpoint<S> {
+    /// Whether some run of the body may reach a block
+    pub fn is_reached(&self, block: BlockId) -> bool {
+        !self.states[block].is_bottom()
+    }
+
+    /// Whether some run of the body may go from one block to another
+    pub fn is_taken(&self, from: BlockId, to: BlockId) -> bool {
+        self.edges
+            .get(&(from, to))
+            .is_some_and(|state| !state.is_bottom())
+    }
+}
+
+/// What is known at the end of a block, given what is known at its start
+fn after_statements<A: Analysis>(
+    analysis: &A,
+    body: &Body,
+    block: BlockId,
+    state: &A::State,
+) -> A::State {
+    body.blocks[block]
+        .statements
+        .iter()
+        .fold(state.clone(), |state, statement| {
+            analysis.statement(&state, statement)
+        })
+}
+
+/// What is known along each edge out of a block, given what is known at the end of it
+fn along_edges<A: Analysis>(
+    analysis: &A,
+    body: &Body,
+    block: BlockId,
+    state: &A::State,
+) -> Vec<(BlockId, A::State)> {
+    match &body.blocks[block].terminator {
+        Terminator::Goto(target) => vec![(*target, state.clone())],
+        Terminator::Branch {
+            then, otherwise, ..
+        } if then == otherwise => vec![(*then, state.clone())],
+        Terminator::Branch {
+            condition,
+            then,
+            otherwise,
+        } => vec![
+            (*then, analysis.assume(state, condition, true)),
+            (*otherwise, analysis.assume(state, condition, false)),
+        ],
+        Terminator::Return | Terminator::Diverge(_) | Terminator::Unreachable => vec![],
+    }
+}
+
+/// Run an analysis over a body until what it knows at every block stops changing.
+///
+/// # Arguments
+///
+/// * `analysis` - The abstract interpretation to run.
+/// * `body` - The control-flow graph to run it over.
+///
+/// # Returns
+///
+/// What is known at the start of each block and along each edge, in every run of the body.
+pub fn fixpoint<A: Analysis>(analysis: &A, body: &Body) -> Fixpoint<A::State> {
+    let order = body.reverse_postorder();
+    let mut rank = vec![usize::MAX; body.blocks.len()];
+    for (i, &block) in order.iter().enumerate() {
+        rank[block] = i;
+    }
+    let headers: HashSet<BlockId> = body
+        .loop_forest()
+        .loops
+        .iter()
+        .map(|looped| looped.header)
+        .collect();
+    let mut states = vec![A::State::bottom(); body.blocks.len()];
+    states[ENTRY] = analysis.entry(body);
+    let mut joins = vec![0; body.blocks.len()];
+    // blocks are taken in reverse postorder, so a loop is done before what comes after it
+    let mut work = BTreeSet::from([(rank[ENTRY], ENTRY)]);
+    while let Some((_, block)) = work.pop_first() {
+        let state = after_statements(analysis, body, block, &states[block]);
+        for (target, incoming) in along_edges(analysis, body, block, &state) {
+            let joined = states[target].join(&incoming);
+            let next = if headers.contains(&target) && joins[target] >= WIDENING_DELAY {
+                states[target].widen(&joined)
+            } else {
+                joined
+            };
+            if next != states[target] {
+                states[target] = next;
+                joins[target] += 1;
+                work.insert((rank[target], target));
+            }
+        }
+    }
+    let predecessors = body.predecessors();
+    for _ in 0..NARROWING_PASSES {
+        for &block in &order {
+            let mut incoming = if block == ENTRY {
+                analysis.entry(body)
+            } else {
+                A::State::bottom()
+            };
+            for &from in &predecessors[block] {
+                let state = after_statements(analysis, body, from, &states[from]);
+                for (target, along) in along_edges(analysis, body, from, &state) {
+                    if target == block {
+                        incoming = incoming.join(&along);
+                    }
+                }
+            }
+            states[block] = if headers.contains(&block) {
+                states[block].narrow(&incoming)
+            } else {
+                incoming
+            };
+        }
+    }
+    let mut edges = BTreeMap::new();
+    for (block, state) in states.iter().enumerate() {
+        let state = after_statements(analysis, body, block, state);
+        for (target, along) in along_edges(analysis, body, block, &state) {
+            edges
+                .entry((block, target))
+                .and_modify(|edge: &mut A::State| *edge = edge.join(&along))
+                .or_insert(along);
+        }
+    }
+    Fixpoint { states, edges }
+}
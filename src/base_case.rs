@@ -0,0 +1,1324 @@
+//! halts/src/base_case.rs
+//! Base cases of recursion, and whether they are ever reached.
+//! A base case is a path through a recursive function which returns without recursing.
+//! Recursion without one can only go on forever (modulo stack overflow),
+//! and recursion with one halts if every chain of calls gets there:
+//! in `if n == 0 { return 1 } n * factorial(n - 1)` on `u32`, `n` shrinks at every call
+//! and can't shrink below zero, so `n == 0` is bound to hold sooner or later.
+//! Integers are mathematical integers here: overflow is neither a base case nor a way around one.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::absint::Fixpoint;
+use crate::call_graph::{CallGraph, Recursion};
+use crate::interval::{intervals_from_body, Intervals};
+use crate::ir::{
+    body_from_ast, BinOp, BlockId, Body, Expr, LoopForest, Statement, Terminator, Ty, UnOp, ENTRY,
+};
+use crate::linear::{atoms_from_condition, linear_from_expr, Atom, LinearExpr, Relation};
+use crate::omega::{satisfiability, Satisfiability};
+use crate::verdict::SourceSpan;
+
+/// The most paths followed through one function before giving up
+const MAX_PATHS: usize = 256;
+
+/// The most calls simulated from one entry into a recursion before giving up
+const MAX_CALLS: usize = 100_000;
+
+/// A condition along a path, and the value it takes there.
+#[derive(Debug, Clone)]
+pub struct Guard {
+    /// The condition in terms of the function's parameters,
+    /// or `None` if it depends on something else, like whether a loop returns early
+    pub condition: Option<Expr>,
+    /// Whether the condition is true along the path
+    pub holds: bool,
+}
+
+/// A recursive call along a path.
+#[derive(Debug, Clone)]
+pub struct RecursiveCall {
+    /// The index of the called function in the call graph
+    pub callee: usize,
+    /// Where the call happens
+    pub span: SourceSpan,
+    /// The arguments in terms of the caller's parameters, receiver first,
+    /// or `None` where they depend on something else
+    pub args: Vec<Option<Expr>>,
+}
+
+/// A path through the body of a function, from its entry to wherever it stops.
+#[derive(Debug, Clone, Default)]
+pub struct Path {
+    /// The conditions met along the path, in order
+    pub guards: Vec<Guard>,
+    /// The recursive calls made along the path, in order
+    pub calls: Vec<RecursiveCall>,
+    /// Whether the path stops by a panic or `process::exit` rather than by returning
+    pub diverges: bool,
+    /// What the locals stand for so far, or `None` for those which can't be told
+    bindings: HashMap<String, Option<Expr>>,
+}
+
+impl Path {
+    /// Whether the path returns without a recursive call
+    pub fn is_base_case(&self) -> bool {
+        self.calls.is_empty()
+    }
+
+    /// Whether the path can't be taken, because its guards contradict each other,
+    /// on their face or by the Omega test on those which are linear
+    pub fn is_infeasible(&self) -> bool {
+        let mut seen: HashMap<String, bool> = HashMap::new();
+        let mut linear = vec![];
+        for guard in &self.guards {
+            let Some(condition) = &guard.condition else {
+                continue;
+            };
+            if let Some(atoms) = atoms_from_condition(condition, guard.holds) {
+                if atoms
+                    .iter()
+                    .any(|atom| atom.holds(&|_| None) == Some(false))
+                {
+                    return true;
+                }
+                linear.extend(atoms);
+            }
+            if seen.insert(condition.to_string(), guard.holds) == Some(!guard.holds) {
+                return true;
+            }
+        }
+        linear.len() > 1 && satisfiability(&linear) == Satisfiability::Unsatisfiable
+    }
+
+    /// The conditions of the path in words, like `n == 0` or `!(n < 2) && n % 2 == 0`
+    pub fn condition(&self) -> String {
+        let conditions: Vec<String> = self
+            .guards
+            .iter()
+            .map(|guard| match (&guard.condition, guard.holds) {
+                (Some(condition), true) => condition.to_string(),
+                (Some(condition), false) => format!("!({})", condition),
+                (None, _) => "…".to_string(),
+            })
+            .collect();
+        if conditions.is_empty() {
+            "always".to_string()
+        } else {
+            conditions.join(" && ")
+        }
+    }
+
+    /// An expression with the locals replaced by what they stand for,
+    /// or `None` if one of them can't be told
+    fn substitute(&self, expr: &Expr) -> Option<Expr> {
+        expr.substitute(&|name| match self.bindings.get(name) {
+            Some(binding) => binding.clone(),
+            // parameters, and free names like constants, stand for themselves
+            None => Some(Expr::Var(name.to_string())),
+        })
+    }
+
+    /// Add a condition and the value it takes
+    fn guard(&mut self, condition: Option<&Expr>, holds: bool) {
+        let condition = condition.and_then(|condition| self.substitute(condition));
+        self.guards.push(Guard { condition, holds });
+    }
+
+    /// Bind a local to what it stands for, already in terms of the parameters
+    fn bind(&mut self, local: &str, value: Option<Expr>) {
+        self.bindings.insert(local.to_string(), value);
+    }
+}
+
+/// Follows every path through a body, splitting at each branch.
+///
+/// Loops are not followed inside: the locals they change can't be told after them,
+/// and the calls in them may or may not happen, so a path through one is split into a path where they happen
+/// and a path where they don't, each of which carries on at every way out of the loop.
+/// Branches and ways out which interval analysis shows no run takes are not followed.
+struct PathEnumerator<'a> {
+    /// The body followed
+    body: &'a Body,
+    /// The loops of the body
+    forest: LoopForest,
+    /// The recursive call sites of the function, with the function each one calls
+    sites: &'a HashMap<SourceSpan, usize>,
+    /// The call sites met as calls, rather than as functions passed around as values
+    met: HashSet<SourceSpan>,
+    /// The ranges of the integer locals, which tell the edges no run takes
+    intervals: Fixpoint<Intervals>,
+}
+
+impl PathEnumerator<'_> {
+    /// Note a call made along a path, if it is a recursive one
+    fn call(&mut self, path: &mut Path, args: &[Expr], span: SourceSpan) {
+        if let Some(&callee) = self.sites.get(&span) {
+            self.met.insert(span);
+            let args = args.iter().map(|arg| path.substitute(arg)).collect();
+            path.calls.push(RecursiveCall { callee, span, args });
+        }
+    }
+
+    /// Follow the statements of a block along a path
+    fn statements(&mut self, block: BlockId, path: &mut Path) {
+        for statement in &self.body.blocks[block].statements {
+            match statement {
+                Statement::Assign { local, value } => {
+                    let value = path.substitute(value);
+                    path.bind(local, value);
+                }
+                Statement::Call {
+                    dest, args, span, ..
+                } => {
+                    self.call(path, args, *span);
+                    path.bind(dest, None);
+                }
+                Statement::Havoc(local) => path.bind(local, None),
+                // what is assumed is about locals which can't be told anyway
+                Statement::Assume(_) => {}
+            }
+        }
+    }
+
+    /// The ways a path carries on past a loop it enters at its head
+    fn through_loop(&mut self, index: usize, path: Path) -> Vec<(BlockId, Path)> {
+        let looped = self.forest.loops[index].clone();
+        let mut skipped = path;
+        let mut calls = vec![];
+        for &block in &looped.blocks {
+            for statement in &self.body.blocks[block].statements {
+                match statement {
+                    Statement::Assign { local, .. } | Statement::Havoc(local) => {
+                        skipped.bind(local, None);
+                    }
+                    Statement::Call {
+                        dest, args, span, ..
+                    } => {
+                        skipped.bind(dest, None);
+                        calls.push((args, *span));
+                    }
+                    Statement::Assume(_) => {}
+                }
+            }
+        }
+        let mut called = skipped.clone();
+        for (args, span) in calls {
+            self.call(&mut called, args, span);
+        }
+        let mut variants = vec![skipped];
+        if !called.calls.is_empty() && called.calls.len() > variants[0].calls.len() {
+            variants.push(called);
+        }
+        let mut exits: Vec<BlockId> = looped
+            .exits(self.body)
+            .into_iter()
+            .filter(|&(from, to)| self.intervals.is_taken(from, to))
+            .map(|(_, to)| to)
+            .collect();
+        exits.dedup();
+        // which way out a path takes, and whether it made the calls, depends on how the loop went
+        let opaque = variants.len() * exits.len() > 1;
+        let mut next = vec![];
+        for variant in &variants {
+            for &exit in &exits {
+                let mut path = variant.clone();
+                if opaque {
+                    path.guard(None, true);
+                }
+                next.push((exit, path));
+            }
+        }
+        next
+    }
+
+    /// Follow every path from the entry, giving up if there are too many
+    fn paths(&mut self) -> Option<Vec<Path>> {
+        let headers: HashMap<BlockId, usize> = self
+            .forest
+            .loops
+            .iter()
+            .enumerate()
+            .filter(|(_, looped)| looped.parent.is_none())
+            .map(|(index, looped)| (looped.header, index))
+            .collect();
+        let mut paths = vec![];
+        let mut work = vec![(ENTRY, Path::default())];
+        while let Some((block, mut path)) = work.pop() {
+            if work.len() + paths.len() > MAX_PATHS {
+                return None;
+            }
+            if let Some(&index) = headers.get(&block) {
+                let mut next = self.through_loop(index, path);
+                next.reverse();
+                work.extend(next);
+                continue;
+            }
+            self.statements(block, &mut path);
+            match &self.body.blocks[block].terminator {
+                Terminator::Goto(target) => work.push((*target, path)),
+                Terminator::Branch {
+                    condition,
+                    then,
+                    otherwise,
+                } => {
+                    let mut refuted = path.clone();
+                    refuted.guard(Some(condition), false);
+                    path.guard(Some(condition), true);
+                    // a side no run takes, like `n < 0` on `u32`, is no path
+                    if self.intervals.is_taken(block, *otherwise) {
+                        work.push((*otherwise, refuted));
+                    }
+                    if self.intervals.is_taken(block, *then) {
+                        work.push((*then, path));
+                    }
+                }
+                Terminator::Return => paths.push(path),
+                Terminator::Diverge(_) => {
+                    path.diverges = true;
+                    paths.push(path);
+                }
+                Terminator::Unreachable => {}
+            }
+        }
+        Some(paths)
+    }
+}
+
+/// Every path through a body, noting the recursive calls at `sites` along the way.
+///
+/// # Returns
+///
+/// The paths, and the call sites met as calls rather than as functions passed around as values,
+/// or `None` if there are too many paths to follow.
+fn paths_from_body(
+    body: &Body,
+    sites: &HashMap<SourceSpan, usize>,
+) -> Option<(Vec<Path>, HashSet<SourceSpan>)> {
+    let mut enumerator = PathEnumerator {
+        body,
+        forest: body.loop_forest(),
+        sites,
+        met: HashSet::new(),
+        intervals: intervals_from_body(body),
+    };
+    let paths = enumerator.paths()?;
+    Some((paths, enumerator.met))
+}
+
+/// The recursive call sites made by one member of a recursion, with the member each one calls
+fn sites_from_recursion(recursion: &Recursion, caller: usize) -> HashMap<SourceSpan, usize> {
+    recursion
+        .call_sites
+        .iter()
+        .filter(|(from, _)| *from == caller)
+        .map(|(_, call)| (call.span, call.callee))
+        .collect()
+}
+
+/// Find the base cases of a recursion: paths through its members which make no recursive call.
+///
+/// # Arguments
+///
+/// * `recursion` - The recursion to find the base cases of.
+/// * `graph` - The call graph the recursion is part of.
+///
+/// # Returns
+///
+/// Each base case with the member it belongs to,
+/// or `None` if some member has paths too many or too tangled to follow.
+pub fn find_base_cases(recursion: &Recursion, graph: &CallGraph) -> Option<Vec<(usize, Path)>> {
+    let mut base_cases = vec![];
+    for &member in &recursion.members {
+        let sites = sites_from_recursion(recursion, member);
+        let body = body_from_ast(&graph.functions[member].ast);
+        let (paths, _) = paths_from_body(&body, &sites)?;
+        base_cases.extend(
+            paths
+                .into_iter()
+                .filter(|path| path.is_base_case() && !path.is_infeasible())
+                .map(|path| (member, path)),
+        );
+    }
+    Some(base_cases)
+}
+
+/// Follow every path through every member of a recursion.
+///
+/// # Arguments
+///
+/// * `recursion` - The recursion to follow.
+/// * `graph` - The call graph the recursion is part of.
+///
+/// # Returns
+///
+/// The paths through each member, by its index in the call graph.
+///
+/// # Errors
+///
+/// Why the members can't be followed: too many paths, or a member passed around as a value.
+pub fn paths_from_recursion(
+    recursion: &Recursion,
+    graph: &CallGraph,
+) -> Result<Vec<(usize, Vec<Path>)>, String> {
+    let mut members = vec![];
+    for &member in &recursion.members {
+        let function = &graph.functions[member];
+        let sites = sites_from_recursion(recursion, member);
+        let body = body_from_ast(&function.ast);
+        let Some((paths, met)) = paths_from_body(&body, &sites) else {
+            return Err(format!(
+                "`{}` has too many paths to follow",
+                function.name()
+            ));
+        };
+        if met.len() < sites.len() {
+            return Err(format!(
+                "a function is passed around as a value in `{}`, so where it is called can't be told",
+                function.name()
+            ));
+        }
+        members.push((member, paths));
+    }
+    Ok(members)
+}
+
+/// Whether the base cases of a recursion are reached, and why.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reachability {
+    /// Every chain of recursive calls reaches a base case.
+    Reachable(String),
+    /// Some chain of recursive calls never reaches a base case.
+    Unreachable(String),
+    /// Neither could be shown.
+    Unknown(String),
+}
+
+/// A parameter of a function
+#[derive(Debug, Clone)]
+struct Param {
+    /// The local the parameter is bound to, `self` for a receiver
+    name: String,
+    /// What the parameter can hold
+    ty: Ty,
+}
+
+/// The parameters of a function, receiver first
+fn params_from_body(body: &Body) -> Vec<Param> {
+    body.params
+        .iter()
+        .map(|name| Param {
+            name: name.clone(),
+            ty: body.local(name).map_or(Ty::Other, |local| local.ty),
+        })
+        .collect()
+}
+
+/// A value of a parameter, as far as simulating calls is concerned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Value {
+    /// An integer, of whatever type
+    Int(i128),
+    /// A boolean
+    Bool(bool),
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Int(int) => write!(f, "{}", int),
+            Value::Bool(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+/// Evaluate an expression of integers and booleans, or `None` if it is anything else or overflows
+fn value_from_expr(expr: &Expr, env: &HashMap<String, Value>) -> Option<Value> {
+    match expr {
+        Expr::Int(int) => Some(Value::Int(*int)),
+        Expr::Bool(b) => Some(Value::Bool(*b)),
+        Expr::Var(name) => env.get(name).copied(),
+        Expr::Unary(op, inner) => match (op, value_from_expr(inner, env)?) {
+            (UnOp::Neg, Value::Int(int)) => Some(Value::Int(int.checked_neg()?)),
+            (UnOp::Not, Value::Bool(b)) => Some(Value::Bool(!b)),
+            _ => None,
+        },
+        Expr::Binary(op, left, right) => {
+            let left = value_from_expr(left, env)?;
+            // `&&` and `||` don't evaluate their right side if the left decides
+            match (op, left) {
+                (BinOp::And, Value::Bool(false)) => return Some(left),
+                (BinOp::Or, Value::Bool(true)) => return Some(left),
+                _ => {}
+            }
+            let right = value_from_expr(right, env)?;
+            match (left, right) {
+                (Value::Int(l), Value::Int(r)) => Some(match op {
+                    BinOp::Add => Value::Int(l.checked_add(r)?),
+                    BinOp::Sub => Value::Int(l.checked_sub(r)?),
+                    BinOp::Mul => Value::Int(l.checked_mul(r)?),
+                    BinOp::Div => Value::Int(l.checked_div(r)?),
+                    BinOp::Rem => Value::Int(l.checked_rem(r)?),
+                    BinOp::BitAnd => Value::Int(l & r),
+                    BinOp::BitOr => Value::Int(l | r),
+                    BinOp::BitXor => Value::Int(l ^ r),
+                    BinOp::Eq => Value::Bool(l == r),
+                    BinOp::Ne => Value::Bool(l != r),
+                    BinOp::Lt => Value::Bool(l < r),
+                    BinOp::Le => Value::Bool(l <= r),
+                    BinOp::Gt => Value::Bool(l > r),
+                    BinOp::Ge => Value::Bool(l >= r),
+                    _ => return None,
+                }),
+                (Value::Bool(l), Value::Bool(r)) => Some(Value::Bool(match op {
+                    BinOp::And | BinOp::BitAnd => l && r,
+                    BinOp::Or | BinOp::BitOr => l || r,
+                    BinOp::BitXor | BinOp::Ne => l != r,
+                    BinOp::Eq => l == r,
+                    _ => return None,
+                })),
+                _ => None,
+            }
+        }
+        Expr::Part(..) | Expr::Opaque(_) => None,
+    }
+}
+
+/// A call into a member of a recursion, with the values of its arguments
+type State = (usize, Vec<Value>);
+
+/// The paths and parameters of every member of a recursion
+struct Members<'a> {
+    /// The call graph the recursion is part of
+    graph: &'a CallGraph,
+    /// The paths through each member
+    paths: HashMap<usize, Vec<Path>>,
+    /// The parameters of each member
+    params: HashMap<usize, Vec<Param>>,
+    /// The members whose paths exclude one another, each told apart by conditions which can be evaluated
+    exclusive: HashSet<usize>,
+    /// Linear constraints on the parameters of each member which hold at every call to it from the root on
+    invariants: HashMap<usize, Vec<Atom>>,
+}
+
+impl<'a> Members<'a> {
+    /// Follow the paths of every member of a recursion.
+    ///
+    /// # Errors
+    ///
+    /// Why the members can't be followed: too many paths, or a member passed around as a value.
+    fn from_recursion(recursion: &Recursion, graph: &'a CallGraph) -> Result<Self, String> {
+        let mut members = Members {
+            graph,
+            paths: HashMap::new(),
+            params: HashMap::new(),
+            exclusive: HashSet::new(),
+            invariants: HashMap::new(),
+        };
+        for (member, paths) in paths_from_recursion(recursion, graph)? {
+            let body = body_from_ast(&graph.functions[member].ast);
+            let mut guards = HashSet::new();
+            let exclusive = paths.iter().all(|path| {
+                path.guards.iter().all(|guard| guard.condition.is_some())
+                    && guards.insert(Path::condition(path))
+            });
+            if exclusive {
+                members.exclusive.insert(member);
+            }
+            members.paths.insert(member, paths);
+            members.params.insert(member, params_from_body(&body));
+        }
+        Ok(members)
+    }
+
+    /// The name of a member
+    fn name(&self, member: usize) -> String {
+        self.graph.functions[member].name()
+    }
+
+    /// A call in words, like `count_down(3)`
+    fn describe(&self, (member, values): &State) -> String {
+        let values: Vec<String> = values.iter().map(Value::to_string).collect();
+        format!("{}({})", self.name(*member), values.join(", "))
+    }
+
+    /// Constraints every call to a member satisfies, by the types of its parameters
+    fn domain(&self, member: usize) -> Vec<Atom> {
+        self.params[&member]
+            .iter()
+            .filter(|param| param.ty == Ty::Unsigned)
+            .map(|param| Atom::ge_zero(LinearExpr::variable(&param.name)))
+            .collect()
+    }
+
+    /// The linear constraints of a path's guards, skipping those which aren't linear,
+    /// with those which hold at every call to the member
+    fn atoms(&self, member: usize, path: &Path) -> Vec<Atom> {
+        let mut atoms = self.domain(member);
+        atoms.extend(self.invariants.get(&member).into_iter().flatten().cloned());
+        for guard in &path.guards {
+            if let Some(condition) = &guard.condition {
+                atoms.extend(atoms_from_condition(condition, guard.holds).unwrap_or_default());
+            }
+        }
+        atoms
+    }
+
+    /// Show every chain of recursive calls reaches a base case, whatever the arguments,
+    /// by finding a measure of the parameters which every recursive call decreases
+    /// and which is bounded below wherever the recursion goes on,
+    /// or failing that a tuple of measures which every recursive call decreases lexicographically,
+    /// like `(m, n)` for Ackermann's function, where `n` may grow whenever `m` shrinks.
+    ///
+    /// # Returns
+    ///
+    /// The measure and its bound in words, or `None` if no such measure was found.
+    fn prove_reachable(&self) -> Option<String> {
+        let candidates = self.candidates()?;
+        candidates
+            .iter()
+            .find_map(|candidate| self.check_measure(candidate))
+            .or_else(|| self.check_lexicographic(&candidates))
+    }
+
+    /// The measures tried: each integer parameter, its negation, and the difference of two
+    fn candidates(&self) -> Option<Vec<Vec<(usize, i128)>>> {
+        let arity = self.params.values().map(Vec::len).min()?;
+        let integer: Vec<usize> = (0..arity)
+            .filter(|&k| self.params.values().all(|params| params[k].ty.is_integer()))
+            .collect();
+        let mut candidates: Vec<Vec<(usize, i128)>> = vec![];
+        for &k in &integer {
+            candidates.push(vec![(k, 1)]);
+            candidates.push(vec![(k, -1)]);
+            for &j in &integer {
+                if j != k {
+                    candidates.push(vec![(j, 1), (k, -1)]);
+                }
+            }
+        }
+        Some(candidates)
+    }
+
+    /// A measure over the parameters of a member, by position and coefficient
+    fn measure(&self, member: usize, candidate: &[(usize, i128)]) -> Option<LinearExpr> {
+        candidate
+            .iter()
+            .try_fold(LinearExpr::default(), |sum, &(k, coefficient)| {
+                let param = LinearExpr::variable(&self.params[&member][k].name);
+                sum.checked_add(&param.checked_scale(coefficient)?)
+            })
+    }
+
+    /// How much a recursive call from a member changes a measure, if that is a constant
+    fn change(
+        &self,
+        member: usize,
+        call: &RecursiveCall,
+        candidate: &[(usize, i128)],
+    ) -> Option<i128> {
+        let after =
+            candidate
+                .iter()
+                .try_fold(LinearExpr::default(), |sum, &(k, coefficient)| {
+                    let arg = linear_from_expr(call.args.get(k)?.as_ref()?)?;
+                    sum.checked_add(&arg.checked_scale(coefficient)?)
+                })?;
+        let change = after.checked_sub(&self.measure(member, candidate)?)?;
+        change.is_constant().then_some(change.constant)
+    }
+
+    /// The recursive paths of each member which can be taken
+    fn recursive_paths(&self) -> impl Iterator<Item = (usize, &Path)> {
+        self.paths.iter().flat_map(|(&member, paths)| {
+            paths
+                .iter()
+                .filter(|path| !path.is_base_case() && !path.is_infeasible())
+                .map(move |path| (member, path))
+        })
+    }
+
+    /// Check a measure decreases at every recursive call and is bounded below wherever the recursion goes on
+    fn check_measure(&self, candidate: &[(usize, i128)]) -> Option<String> {
+        let mut lowest_bound: Option<i128> = None;
+        for (member, path) in self.recursive_paths() {
+            let measure = self.measure(member, candidate)?;
+            let bound = lower_bound(&self.atoms(member, path), &measure)?;
+            lowest_bound = Some(lowest_bound.map_or(bound, |lowest| lowest.min(bound)));
+            for call in &path.calls {
+                if self.change(member, call, candidate)? > -1 {
+                    return None;
+                }
+            }
+        }
+        let first = *self.paths.keys().min()?;
+        Some(format!(
+            "`{}` decreases at every recursive call and is at least {} wherever the recursion goes on",
+            self.measure(first, candidate)?,
+            lowest_bound?
+        ))
+    }
+
+    /// Check some measures, in order, make a tuple which every recursive call decreases lexicographically:
+    /// each call leaves the measures before some measure as they are or smaller, and decreases that one,
+    /// which is bounded below on the path the call is on.
+    /// The tuple is built one measure at a time, each the first to decrease some of the calls left.
+    fn check_lexicographic(&self, candidates: &[Vec<(usize, i128)>]) -> Option<String> {
+        let mut remaining: Vec<(usize, &Path, &RecursiveCall)> = self
+            .recursive_paths()
+            .flat_map(|(member, path)| path.calls.iter().map(move |call| (member, path, call)))
+            .collect();
+        let mut tuple = vec![];
+        while !remaining.is_empty() {
+            let (candidate, decreased) = candidates.iter().find_map(|candidate| {
+                let mut decreased = vec![];
+                for (i, &(member, path, call)) in remaining.iter().enumerate() {
+                    match self.change(member, call, candidate) {
+                        Some(change) if change <= -1 => {
+                            let measure = self.measure(member, candidate)?;
+                            lower_bound(&self.atoms(member, path), &measure)?;
+                            decreased.push(i);
+                        }
+                        Some(0) => {}
+                        _ => return None,
+                    }
+                }
+                (!decreased.is_empty()).then_some((candidate, decreased))
+            })?;
+            let mut i = 0;
+            remaining.retain(|_| {
+                i += 1;
+                !decreased.contains(&(i - 1))
+            });
+            let first = *self.paths.keys().min()?;
+            tuple.push(self.measure(first, candidate)?.to_string());
+        }
+        Some(format!(
+            "`({})` decreases lexicographically at every recursive call, each part bounded below where it decreases",
+            tuple.join(", ")
+        ))
+    }
+
+    /// The path a call takes, or why it can't be told
+    fn enabled_path(&self, (member, values): &State) -> Result<&Path, String> {
+        let env = self.env(&(*member, values.clone()));
+        let mut candidates = vec![];
+        for path in &self.paths[member] {
+            let mut known = true;
+            let mut enabled = true;
+            for guard in &path.guards {
+                let value = guard
+                    .condition
+                    .as_ref()
+                    .and_then(|condition| value_from_expr(condition, &env));
+                match value {
+                    Some(Value::Bool(b)) if b == guard.holds => {}
+                    Some(Value::Bool(_)) => {
+                        enabled = false;
+                        break;
+                    }
+                    _ => known = false,
+                }
+            }
+            if enabled {
+                candidates.push((path, known));
+            }
+        }
+        match candidates.as_slice() {
+            [(path, _)] => Ok(path),
+            [(path, _), ..] if candidates.iter().all(|(path, _)| path.is_base_case()) => Ok(path),
+            _ => Err(format!(
+                "which way {} goes can't be told",
+                self.describe(&(*member, values.clone()))
+            )),
+        }
+    }
+
+    /// The parameters of a call, bound to its arguments
+    fn env(&self, (member, values): &State) -> HashMap<String, Value> {
+        self.params[member]
+            .iter()
+            .zip(values)
+            .map(|(param, value)| (param.name.clone(), *value))
+            .collect()
+    }
+
+    /// Show a call goes on calling itself forever, with arguments that move in a straight line
+    /// along which the path it takes stays open, so no base case is ever reached.
+    ///
+    /// # Returns
+    ///
+    /// The line in words, or `None` if it couldn't be shown.
+    fn diverges_from(&self, state: &State, path: &Path) -> Option<String> {
+        let member = &state.0;
+        let [call] = path.calls.as_slice() else {
+            return None;
+        };
+        if call.callee != *member || !self.exclusive.contains(member) {
+            return None;
+        }
+        let params = &self.params[member];
+        let mut steps = vec![];
+        let mut direction: HashMap<&str, i128> = HashMap::new();
+        for (param, arg) in params.iter().zip(&call.args) {
+            let arg = arg.as_ref()?;
+            if !param.ty.is_integer() {
+                // anything but an integer has to be passed on as it is
+                if *arg != Expr::Var(param.name.clone()) {
+                    return None;
+                }
+                continue;
+            }
+            let step = linear_from_expr(arg)?.checked_sub(&LinearExpr::variable(&param.name))?;
+            if !step.is_constant() {
+                return None;
+            }
+            direction.insert(&param.name, step.constant);
+            if step.constant != 0 {
+                steps.push(format!(
+                    "{}",
+                    LinearExpr::variable(&param.name).checked_add(&step)?
+                ));
+            }
+        }
+        let env = self.env(state);
+        let value_of = |name: &str| match env.get(name) {
+            Some(Value::Int(int)) => Some(*int),
+            _ => None,
+        };
+        let mut atoms = self.domain(*member);
+        for guard in &path.guards {
+            atoms.extend(atoms_from_condition(
+                guard.condition.as_ref()?,
+                guard.holds,
+            )?);
+        }
+        for atom in &atoms {
+            let start = atom.expr.eval(&value_of)?;
+            let slope = atom
+                .expr
+                .terms
+                .iter()
+                .try_fold(0i128, |sum, (name, coefficient)| {
+                    sum.checked_add(coefficient.checked_mul(*direction.get(name.as_str())?)?)
+                })?;
+            let holds_forever = match atom.relation {
+                Relation::Ge => start >= 0 && slope >= 0,
+                Relation::Eq => start == 0 && slope == 0,
+                Relation::Ne => (start > 0 && slope >= 0) || (start < 0 && slope <= 0),
+            };
+            if !holds_forever {
+                return None;
+            }
+        }
+        Some(format!(
+            "{} goes on calling itself with {} forever, and no base case ever holds",
+            self.describe(state),
+            steps.join(", ")
+        ))
+    }
+
+    /// Keep only the invariants which every recursive call keeps: those which hold of its arguments,
+    /// given the guards of the path it's on and the invariants of its caller.
+    /// Dropping one may break another which relied on it, so this goes on until none is dropped.
+    fn keep_inductive(&mut self) {
+        loop {
+            let mut broken = vec![];
+            for (member, path) in self.recursive_paths() {
+                let premises = self.atoms(member, path);
+                for call in &path.calls {
+                    let params = &self.params[&call.callee];
+                    let arg = |name: &str| {
+                        let k = params.iter().position(|param| param.name == name)?;
+                        linear_from_expr(call.args.get(k)?.as_ref()?)
+                    };
+                    for atom in self.invariants.get(&call.callee).into_iter().flatten() {
+                        let kept = atom
+                            .expr
+                            .substitute(&arg)
+                            .and_then(|after| lower_bound(&premises, &after))
+                            .is_some_and(|bound| bound >= 0);
+                        if !kept {
+                            broken.push((call.callee, atom.clone()));
+                        }
+                    }
+                }
+            }
+            if broken.is_empty() {
+                return;
+            }
+            for (member, atom) in broken {
+                if let Some(invariants) = self.invariants.get_mut(&member) {
+                    invariants.retain(|invariant| *invariant != atom);
+                }
+            }
+        }
+    }
+
+    /// The invariants in words, like `` `n >= 0` at every call to `f` ``, or `None` if there are none
+    fn describe_invariants(&self) -> Option<String> {
+        let mut members: Vec<&usize> = self.invariants.keys().collect();
+        members.sort();
+        let mut described = vec![];
+        for &member in members {
+            let atoms: Vec<String> = self.invariants[&member]
+                .iter()
+                .map(Atom::to_string)
+                .collect();
+            if !atoms.is_empty() {
+                described.push(format!(
+                    "`{}` at every call to `{}`",
+                    atoms.join(" && "),
+                    self.name(member)
+                ));
+            }
+        }
+        (!described.is_empty()).then(|| described.join(", "))
+    }
+
+    /// Follow every chain of recursive calls from a call into the recursion.
+    fn simulate(&self, entry: State) -> Outcome {
+        /// A step of a depth-first walk over the tree of calls
+        enum Step {
+            /// Make a call
+            Enter(State),
+            /// Return from a call, having followed every call it makes
+            Exit(State),
+        }
+        let mut on_branch: HashSet<State> = HashSet::new();
+        let mut done: HashSet<State> = HashSet::new();
+        let mut work = vec![Step::Enter(entry)];
+        let mut calls = 0usize;
+        while let Some(step) = work.pop() {
+            let state = match step {
+                Step::Exit(state) => {
+                    on_branch.remove(&state);
+                    done.insert(state);
+                    continue;
+                }
+                Step::Enter(state) => state,
+            };
+            if done.contains(&state) {
+                continue;
+            }
+            if on_branch.contains(&state) {
+                return Outcome::Infinite(format!(
+                    "{} ends up calling itself again with the same arguments",
+                    self.describe(&state)
+                ));
+            }
+            calls += 1;
+            if calls > MAX_CALLS {
+                return Outcome::Unknown(format!("gave up after {} calls", MAX_CALLS));
+            }
+            let path = match self.enabled_path(&state) {
+                Ok(path) => path,
+                Err(why) => return Outcome::Unknown(why),
+            };
+            if path.is_base_case() {
+                done.insert(state);
+                continue;
+            }
+            if let Some(why) = self.diverges_from(&state, path) {
+                return Outcome::Infinite(why);
+            }
+            let env = self.env(&state);
+            let mut children = vec![];
+            for call in &path.calls {
+                let mut values = vec![];
+                let mut panics = false;
+                for (param, arg) in self.params[&call.callee].iter().zip(&call.args) {
+                    let Some(value) = arg.as_ref().and_then(|arg| value_from_expr(arg, &env))
+                    else {
+                        return Outcome::Unknown(format!(
+                            "the arguments of the call at {} can't be evaluated",
+                            call.span
+                        ));
+                    };
+                    // an unsigned argument below zero panics before the call is made
+                    panics |=
+                        param.ty == Ty::Unsigned && matches!(value, Value::Int(int) if int < 0);
+                    values.push(value);
+                }
+                if panics {
+                    break;
+                }
+                children.push((call.callee, values));
+            }
+            on_branch.insert(state.clone());
+            work.push(Step::Exit(state));
+            work.extend(children.into_iter().rev().map(Step::Enter));
+        }
+        Outcome::Reached(calls)
+    }
+}
+
+/// Where following the recursive calls from a call ended up
+#[derive(Debug)]
+enum Outcome {
+    /// Every chain of calls reached a base case, within this many calls in all
+    Reached(usize),
+    /// Some chain of calls goes on forever
+    Infinite(String),
+    /// Neither could be shown
+    Unknown(String),
+}
+
+/// The lowest value a measure can take, given constraints which hold
+pub fn lower_bound(atoms: &[Atom], measure: &LinearExpr) -> Option<i128> {
+    let (name, &unit) = measure.terms.iter().next()?;
+    let bound = atoms
+        .iter()
+        .flat_map(|atom| match atom.relation {
+            Relation::Ge => vec![atom.expr.clone()],
+            Relation::Eq => vec![
+                atom.expr.clone(),
+                atom.expr.checked_scale(-1).unwrap_or_default(),
+            ],
+            Relation::Ne => vec![],
+        })
+        .filter_map(|expr| {
+            // expr = q * measure + c >= 0, for some q > 0, means measure >= -c / q
+            let coefficient = expr.coefficient(name);
+            if coefficient == 0 || coefficient % unit != 0 {
+                return None;
+            }
+            let q = coefficient / unit;
+            let rest = expr.checked_sub(&measure.checked_scale(q)?)?;
+            (q > 0 && rest.is_constant()).then(|| {
+                let bound = -rest.constant;
+                bound.div_euclid(q) + i128::from(bound.rem_euclid(q) != 0)
+            })
+        })
+        .max()?;
+    // `n != 0` on top of `n >= 0` means `n >= 1`
+    let mut bound = bound;
+    while atoms.iter().any(|atom| {
+        let at_bound = measure.checked_sub(&LinearExpr::constant(bound));
+        atom.relation == Relation::Ne
+            && at_bound.is_some_and(|at_bound| {
+                atom.expr == at_bound || at_bound.checked_scale(-1).as_ref() == Some(&atom.expr)
+            })
+    }) {
+        bound = bound.checked_add(1)?;
+    }
+    Some(bound)
+}
+
+/// The calls into a recursion from outside it, with constant arguments.
+///
+/// # Errors
+///
+/// Why the calls can't be told, such as arguments which aren't constants.
+fn entries_from_root(
+    members: &Members<'_>,
+    recursion: &Recursion,
+    root: usize,
+) -> Result<Vec<State>, String> {
+    if recursion.members.contains(&root) {
+        return if members.params[&root].is_empty() {
+            Ok(vec![(root, vec![])])
+        } else {
+            Err(format!(
+                "it depends on the arguments `{}` is called with",
+                members.name(root)
+            ))
+        };
+    }
+    let mut entries = vec![];
+    for call in entry_paths(members, recursion, root)?
+        .iter()
+        .flat_map(|path| &path.calls)
+    {
+        let values: Option<Vec<Value>> = call
+            .args
+            .iter()
+            .map(|arg| value_from_expr(arg.as_ref()?, &HashMap::new()))
+            .collect();
+        let Some(values) = values else {
+            return Err(format!(
+                "the recursion is entered at {} with arguments which aren't constants",
+                call.span
+            ));
+        };
+        let entry = (call.callee, values);
+        if !entries.contains(&entry) {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+/// The paths through the functions outside a recursion, reachable from the root, which call into it.
+///
+/// # Errors
+///
+/// Why the paths can't be followed: too many of them, or the recursion passed around as a value.
+fn entry_paths(
+    members: &Members<'_>,
+    recursion: &Recursion,
+    root: usize,
+) -> Result<Vec<Path>, String> {
+    let graph = members.graph;
+    let mut entry_paths = vec![];
+    for caller in graph.reachable_from(root) {
+        if recursion.members.contains(&caller) {
+            continue;
+        }
+        let sites: HashMap<SourceSpan, usize> = graph.calls[caller]
+            .iter()
+            .filter(|call| recursion.members.contains(&call.callee))
+            .map(|call| (call.span, call.callee))
+            .collect();
+        if sites.is_empty() {
+            continue;
+        }
+        let name = graph.functions[caller].name();
+        let body = body_from_ast(&graph.functions[caller].ast);
+        let Some((paths, met)) = paths_from_body(&body, &sites) else {
+            return Err(format!("`{}` has too many paths to follow", name));
+        };
+        if met.len() < sites.len() {
+            return Err(format!("`{}` passes the recursion around as a value", name));
+        }
+        entry_paths.extend(paths.into_iter().filter(|path| !path.calls.is_empty()));
+    }
+    Ok(entry_paths)
+}
+
+/// Linear constraints on the parameters of each member of a recursion which hold whenever the root calls into it:
+/// the guards of the paths into the recursion, about arguments passed as they are,
+/// carried over to the parameters they're passed as, like `n >= 0` for `assert!(n >= 0); f(n)`.
+/// A constraint must hold at every call into a member to count.
+fn invariants_from_root(
+    members: &Members<'_>,
+    recursion: &Recursion,
+    root: usize,
+) -> HashMap<usize, Vec<Atom>> {
+    let mut invariants: HashMap<usize, Vec<Atom>> = HashMap::new();
+    let Ok(paths) = entry_paths(members, recursion, root) else {
+        return invariants;
+    };
+    for path in &paths {
+        let guards: Vec<Atom> = path
+            .guards
+            .iter()
+            .filter_map(|guard| atoms_from_condition(guard.condition.as_ref()?, guard.holds))
+            .flatten()
+            .filter(|atom| atom.relation == Relation::Ge)
+            .collect();
+        for call in &path.calls {
+            let params = &members.params[&call.callee];
+            let param = |name: &str| {
+                call.args
+                    .iter()
+                    .zip(params)
+                    .find_map(|(arg, param)| match arg {
+                        Some(Expr::Var(var)) if var == name => {
+                            Some(LinearExpr::variable(&param.name))
+                        }
+                        _ => None,
+                    })
+            };
+            let facts: Vec<Atom> = guards
+                .iter()
+                .filter_map(|atom| Some(Atom::ge_zero(atom.expr.substitute(&param)?)))
+                .collect();
+            match invariants.get_mut(&call.callee) {
+                Some(known) => known.retain(|atom| facts.contains(atom)),
+                None => {
+                    invariants.insert(call.callee, facts);
+                }
+            }
+        }
+    }
+    invariants
+}
+
+/// Decide whether the base cases of a recursion are reached.
+///
+/// First, look for a measure of the arguments which shrinks at every recursive call
+/// and can't shrink forever, which means every call reaches a base case whatever its arguments.
+/// Failing that, follow the recursive calls from each call into the recursion with constant arguments:
+/// a call which comes around again with the same arguments,
+/// or whose arguments move forever in a direction no base case lies in, never reaches one.
+///
+/// # Arguments
+///
+/// * `recursion` - The recursion to check.
+/// * `graph` - The call graph the recursion is part of.
+/// * `root` - The function under analysis, which the recursion is reachable from.
+///
+/// # Returns
+///
+/// Whether the base cases are reached, never reached, or neither could be shown, and why.
+pub fn reachability(recursion: &Recursion, graph: &CallGraph, root: usize) -> Reachability {
+    let mut members = match Members::from_recursion(recursion, graph) {
+        Ok(members) => members,
+        Err(why) => return Reachability::Unknown(why),
+    };
+    members.invariants = invariants_from_root(&members, recursion, root);
+    members.keep_inductive();
+    if let Some(why) = members.prove_reachable() {
+        return Reachability::Reachable(match members.describe_invariants() {
+            Some(invariants) => format!("{}, given {}", why, invariants),
+            None => why,
+        });
+    }
+    let entries = match entries_from_root(&members, recursion, root) {
+        Ok(entries) => entries,
+        Err(why) => return Reachability::Unknown(why),
+    };
+    if entries.is_empty() {
+        return Reachability::Unknown("no call into the recursion was found".to_string());
+    }
+    let mut reached = vec![];
+    let mut unknown = None;
+    for entry in entries {
+        let described = members.describe(&entry);
+        match members.simulate(entry) {
+            Outcome::Infinite(why) => return Reachability::Unreachable(why),
+            Outcome::Unknown(why) => unknown = unknown.or(Some(why)),
+            Outcome::Reached(calls) => reached.push(format!(
+                "{} reaches base cases within {} calls",
+                described, calls
+            )),
+        }
+    }
+    match unknown {
+        Some(why) => Reachability::Unknown(why),
+        None => Reachability::Reachable(reached.join("; ")),
+    }
+}
+
+/// tests for base cases and their reachability
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The reachability of the one recursion reachable from `main` in some code
+    fn reachability_from_code(code: &str) -> Reachability {
+        let file = syn::parse_file(code).unwrap();
+        let graph = CallGraph::from_file(&file);
+        let root = graph.find("main").unwrap();
+        let recursions = graph.recursions_reachable_from(root);
+        assert_eq!(recursions.len(), 1, "{}", code);
+        reachability(&recursions[0], &graph, root)
+    }
+
+    /// Test base cases are found along with their conditions, and missing ones aren't
+    #[test]
+    fn test_find_base_cases() {
+        let file = syn::parse_file(
+            "
+            fn factorial(n: u32) -> u32 { if n == 0 { return 1; } n * factorial(n - 1) }
+            fn fib(n: u64) -> u64 { match n { 0 | 1 => n, _ => fib(n - 1) + fib(n - 2) } }
+            fn forever(n: u32) -> u32 { let m = n + 1; forever(m) }
+            fn negative(n: u32) -> u32 { if n < 0 { return 0; } negative(n - 1) }
+            fn apart(a: i64, b: i64) -> i64 { if a > b + 1 && b > a { return 0; } apart(a - 1, b) }
+            ",
+        )
+        .unwrap();
+        let graph = CallGraph::from_file(&file);
+        let recursions = graph.recursions();
+        let base_cases = |name: &str| {
+            let member = graph.find(name).unwrap();
+            let recursion = recursions.iter().find(|r| r.members == [member]).unwrap();
+            find_base_cases(recursion, &graph).unwrap()
+        };
+        let factorial = base_cases("factorial");
+        assert_eq!(factorial.len(), 1);
+        assert_eq!(factorial[0].1.condition(), "n == 0");
+        assert_eq!(base_cases("fib")[0].1.condition(), "n == 0 || n == 1");
+        assert!(base_cases("forever").is_empty());
+        // an unsigned integer is never negative, so that path is never taken
+        assert!(base_cases("negative").is_empty());
+        // no integers are both more than one apart and the other way round
+        assert!(base_cases("apart").is_empty());
+    }
+
+    /// Test base cases are shown to be reached by a decreasing measure, or by following the calls
+    #[test]
+    fn test_reachable() {
+        let reachable = [
+            "fn main() { factorial(5); }
+             fn factorial(n: u32) -> u32 { if n == 0 { return 1; } n * factorial(n - 1) }",
+            "fn main() { fib(10); }
+             fn fib(n: u64) -> u64 { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } }",
+            "fn main() { is_even(7); }
+             fn is_even(n: u32) -> bool { if n == 0 { true } else { is_odd(n - 1) } }
+             fn is_odd(n: u32) -> bool { if n == 0 { false } else { is_even(n - 1) } }",
+            "fn main() { count(0, 10); }
+             fn count(i: i64, len: i64) -> i64 { if i >= len { return 0; } 1 + count(i + 1, len) }",
+            "fn main() { collatz(6); }
+             fn collatz(n: i64) -> i64 {
+                 if n <= 1 { return 0; }
+                 let next = if n % 2 == 0 { n / 2 } else { 3 * n + 1 };
+                 1 + collatz(next)
+             }",
+        ];
+        for code in reachable {
+            let reachability = reachability_from_code(code);
+            assert!(
+                matches!(reachability, Reachability::Reachable(_)),
+                "{}: {:?}",
+                code,
+                reachability
+            );
+        }
+        let Reachability::Reachable(why) = reachability_from_code(reachable[0]) else {
+            unreachable!()
+        };
+        assert_eq!(why, "`n` decreases at every recursive call and is at least 1 wherever the recursion goes on");
+
+        // the guard on the call into the recursion holds at every call after it, since each call keeps it
+        let guarded = "fn main(n: i64) { if n >= 0 { down(n); } }
+             fn down(n: i64) -> i64 { if n == 0 { 0 } else { down(n - 1) } }";
+        let Reachability::Reachable(why) = reachability_from_code(guarded) else {
+            panic!("{}", guarded)
+        };
+        assert!(
+            why.ends_with("given `n >= 0` at every call to `down`"),
+            "{}",
+            why
+        );
+    }
+
+    /// Test recursion which moves away from its base case, or comes back around, never reaches it
+    #[test]
+    fn test_unreachable() {
+        let unreachable = [
+            "fn main() { up(1); }
+             fn up(n: u32) -> u32 { if n == 0 { return 0; } up(n + 1) }",
+            "fn main() { stuck(3); }
+             fn stuck(n: i32) -> i32 { if n == 0 { 0 } else { stuck(n) } }",
+            "fn main() { ping(1); }
+             fn ping(n: i32) { if n > 5 { return; } pong(n + 1) }
+             fn pong(n: i32) { ping(n - 1) }",
+        ];
+        for code in unreachable {
+            let reachability = reachability_from_code(code);
+            assert!(
+                matches!(reachability, Reachability::Unreachable(_)),
+                "{}: {:?}",
+                code,
+                reachability
+            );
+        }
+        let Reachability::Unreachable(why) = reachability_from_code(unreachable[0]) else {
+            unreachable!()
+        };
+        assert_eq!(
+            why,
+            "up(1) goes on calling itself with n + 1 forever, and no base case ever holds"
+        );
+
+        // from a negative start, `n - 1` on a signed integer could go either way for all we know
+        let code = "fn main() { down(x()); } fn x() -> i32 { 3 }
+                    fn down(n: i32) { if n == 0 { return; } down(n - 1) }";
+        assert!(matches!(
+            reachability_from_code(code),
+            Reachability::Unknown(_)
+        ));
+    }
+}
@@ -0,0 +1,1267 @@
+//! halts/src/call_graph.rs
+//! A call graph over every function of a file or crate, with its strongly connected components.
+//! Recursion is a cycle in the call graph: a function calling itself directly,
+//! two functions calling each other, or a longer chain which comes back around.
+//! Tarjan's algorithm finds every such cycle at once, whichever function it starts from.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::rc::Rc;
+
+use ast_from_path::{item_fn_from_parts, last_segment_of_type};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::visit::Visit;
+
+use crate::paradox::OracleNames;
+use crate::structural::{generic_args, TypeDefinitions};
+use crate::verdict::SourceSpan;
+
+/// A function in the call graph.
+#[derive(Debug, Clone)]
+pub struct FunctionNode {
+    /// The module the function is defined in, relative to the root of the graph
+    pub module: Vec<String>,
+    /// The path of the function within its module: `[f]`, `[Type, method]` or `[outer, inner]`
+    pub path: Vec<String>,
+    /// The function itself; methods are converted to free functions with the same signature,
+    /// shared by every copy of the graph
+    pub ast: Rc<syn::ItemFn>,
+    /// The type or trait the function is implemented for, if it is an associated function
+    pub owner: Option<String>,
+}
+
+impl FunctionNode {
+    /// Whether the function is a method, callable as `receiver.method()`
+    pub fn is_method(&self) -> bool {
+        self.ast.sig.receiver().is_some()
+    }
+
+    /// The full path of the function, e.g. `test::recursive_cycle_a`
+    pub fn name(&self) -> String {
+        let mut segments = self.module.clone();
+        segments.extend(self.path.iter().cloned());
+        segments.join("::")
+    }
+}
+
+/// A call from one function to another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CallSite {
+    /// The index of the called function
+    pub callee: usize,
+    /// Where the call happens
+    pub span: SourceSpan,
+    /// Whether the callee was told by the method's name alone, the receiver's type being unknown,
+    /// so the call may be to a method of another type altogether
+    pub by_name: bool,
+}
+
+/// What is known of the type of the receiver of a method call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Receiver {
+    /// A type or trait of the crate, by name, whose own methods are called
+    Type(String),
+    /// A type of the standard library, whose methods aren't the crate's
+    Foreign,
+    /// A type which can't be told without type inference, like a generic parameter's or a call's result
+    Unknown,
+}
+
+/// The first segments of paths into the crates which come with Rust
+const STD_ROOTS: [&str; 3] = ["std", "core", "alloc"];
+
+/// Types of the standard library, which are never the crate's own, so neither are their methods
+const STD_TYPES: [&str; 35] = [
+    "bool",
+    "char",
+    "str",
+    "String",
+    "u8",
+    "u16",
+    "u32",
+    "u64",
+    "u128",
+    "usize",
+    "i8",
+    "i16",
+    "i32",
+    "i64",
+    "i128",
+    "isize",
+    "f32",
+    "f64",
+    "Vec",
+    "VecDeque",
+    "HashMap",
+    "HashSet",
+    "BTreeMap",
+    "BTreeSet",
+    "BinaryHeap",
+    "Option",
+    "Result",
+    "Path",
+    "PathBuf",
+    "OsString",
+    "OsStr",
+    "Cow",
+    "Duration",
+    "Instant",
+    "Ordering",
+];
+
+/// The kinds of recursion, by the length of the cycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RecursionKind {
+    /// A function calls itself.
+    Direct,
+    /// Two functions call each other.
+    Mutual,
+    /// Three or more functions call each other in a cycle.
+    Cyclic,
+}
+
+impl fmt::Display for RecursionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            RecursionKind::Direct => write!(f, "direct"),
+            RecursionKind::Mutual => write!(f, "mutual"),
+            RecursionKind::Cyclic => write!(f, "cyclic"),
+        }
+    }
+}
+
+/// A recursive strongly connected component of the call graph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recursion {
+    /// How many functions take part
+    pub kind: RecursionKind,
+    /// The functions in the component
+    pub members: Vec<usize>,
+    /// A shortest cycle through the component, starting and ending at the same function
+    pub cycle: Vec<usize>,
+    /// The calls between members of the component, by caller
+    pub call_sites: Vec<(usize, CallSite)>,
+}
+
+impl Recursion {
+    /// Whether a call within the recursion was told by a method's name alone,
+    /// so the recursion may not be there at all
+    pub fn is_by_name(&self) -> bool {
+        self.call_sites.iter().any(|(_, call)| call.by_name)
+    }
+}
+
+/// Where a function is decided: the functions it may call, and the place it is written at among them.
+#[derive(Debug, Clone, Default)]
+pub struct Context {
+    /// The functions of the file or crate, as they are written
+    pub graph: Rc<CallGraph>,
+    /// The module the function is written in, like `[a, b]` for `crate::a::b`
+    pub module: Vec<String>,
+    /// The full path of the function, like `[a, b, Type, method]`, if it is known
+    pub path: Option<Vec<String>>,
+}
+
+impl Context {
+    /// The context of a function written somewhere at the top of a file, found by its signature
+    pub fn from_file(file: &syn::File) -> Self {
+        Context {
+            graph: Rc::new(CallGraph::from_file(file)),
+            ..Context::default()
+        }
+    }
+
+    /// The context of a function somewhere among the files of a crate, each with the path of its module
+    pub fn from_files(files: &[(Vec<String>, &syn::File)]) -> Self {
+        Context {
+            graph: Rc::new(CallGraph::from_files(files)),
+            ..Context::default()
+        }
+    }
+
+    /// The context of the function at a full path, like `[a, b, Type, method]`,
+    /// which is in the deepest module of the graph the path goes through
+    pub fn at(&self, path: &[String]) -> Self {
+        let module = self
+            .graph
+            .functions
+            .iter()
+            .map(|function| &function.module)
+            .filter(|module| path.starts_with(module))
+            .max_by_key(|module| module.len())
+            .cloned()
+            .unwrap_or_default();
+        Context {
+            graph: Rc::clone(&self.graph),
+            module,
+            path: Some(path.to_vec()),
+        }
+    }
+
+    /// The context of a function written beside this one, with another name
+    pub fn beside(&self, name: &str) -> Self {
+        let path = self.path.as_ref().map(|path| {
+            let mut path = path.clone();
+            path.pop();
+            path.push(name.to_string());
+            path
+        });
+        Context {
+            path,
+            ..self.clone()
+        }
+    }
+
+    /// The call graph with `root` standing in for the function, and the index of `root` in it
+    pub fn graph_with_root(&self, root: &syn::ItemFn) -> (CallGraph, usize) {
+        self.graph
+            .with_root(self.path.as_deref(), &self.module, root)
+    }
+}
+
+/// The call graph of a file or crate.
+#[derive(Debug, Clone, Default)]
+pub struct CallGraph {
+    /// Every function found
+    pub functions: Vec<FunctionNode>,
+    /// The calls made by each function, indexed like `functions`
+    pub calls: Vec<Vec<CallSite>>,
+    /// The calls each function makes to paths of the crate which name no function in the graph, indexed like `functions`
+    pub unresolved: Vec<Vec<(String, SourceSpan)>>,
+    /// The structs, enums and type aliases defined alongside the functions
+    pub types: TypeDefinitions,
+    /// The names each module brings in by `use`, with the paths they stand for
+    imports: HashMap<Vec<String>, HashMap<String, Vec<String>>>,
+    /// The functions of each name, the last segment of their paths
+    names: HashMap<String, Vec<usize>>,
+    /// The names the oracle goes by in the graph's files
+    pub oracles: OracleNames,
+}
+
+impl CallGraph {
+    /// Build the call graph of every function in a file.
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - The file, including its inline modules, impls and nested functions.
+    ///
+    /// # Returns
+    ///
+    /// The call graph, with calls resolved by path between the file's own functions.
+    pub fn from_file(file: &syn::File) -> Self {
+        CallGraph::from_files(&[(vec![], file)])
+    }
+
+    /// Build the call graph of every function in several files, such as all the modules of a crate.
+    ///
+    /// # Arguments
+    ///
+    /// * `files` - Each file with the module path it is mounted at.
+    ///
+    /// # Returns
+    ///
+    /// The call graph, with calls resolved by path between the files' functions.
+    pub fn from_files(files: &[(Vec<String>, &syn::File)]) -> Self {
+        let mut graph = CallGraph::default();
+        for (module, file) in files {
+            graph.add_items(module, &file.items);
+            graph.types.add_items(&file.items);
+            graph.oracles.extend(&OracleNames::from_items(&file.items));
+        }
+        graph.resolve_calls();
+        graph
+    }
+
+    /// Build the call graph of a file, with `root` standing in for the function of the same signature.
+    ///
+    /// # Returns
+    ///
+    /// The call graph, and the index of `root` in it.
+    pub fn from_file_with_root(file: &syn::File, root: &syn::ItemFn) -> (Self, usize) {
+        CallGraph::from_file(file).with_root(None, &[], root)
+    }
+
+    /// A copy of the graph with `root` standing in for the function at a path, whose calls are resolved anew.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The full path of the function, like `[module, Type, method]`, if it is known.
+    ///   Without it, the one function of `module` with the signature of `root` is stood in for,
+    ///   or of those, the one with its body too.
+    /// * `module` - The module `root` is written in.
+    /// * `root` - The function, which may differ from what is written there, like a closure lifted out of its function.
+    ///
+    /// # Returns
+    ///
+    /// The call graph, and the index of `root` in it, which is added to the graph if nothing was stood in for:
+    /// nested in the function its path goes through, if there is one, else at the top of `module`.
+    pub fn with_root(
+        &self,
+        path: Option<&[String]>,
+        module: &[String],
+        root: &syn::ItemFn,
+    ) -> (Self, usize) {
+        let mut graph = self.clone();
+        let found = match path {
+            Some(path) => graph
+                .functions
+                .iter()
+                .position(|function| function.module.iter().chain(&function.path).eq(path.iter())),
+            None => {
+                let same_sig: Vec<usize> = (0..graph.functions.len())
+                    .filter(|&i| {
+                        let function = &graph.functions[i];
+                        function.module == module && function.ast.sig == root.sig
+                    })
+                    .collect();
+                match same_sig.as_slice() {
+                    [only] => Some(*only),
+                    _ => same_sig
+                        .into_iter()
+                        .find(|&i| graph.functions[i].ast.block == root.block),
+                }
+            }
+        };
+        let first_new = graph.functions.len();
+        let root_index = match found {
+            Some(index) => {
+                graph.functions[index].ast = Rc::new(root.clone());
+                index
+            }
+            None => {
+                let (module, path, owner) = match path.and_then(|path| graph.place_of(path)) {
+                    Some(place) => place,
+                    None => (module.to_vec(), vec![], None),
+                };
+                graph.add_function(&module, path, root.clone(), owner);
+                graph.functions.len() - 1
+            }
+        };
+        graph.calls.resize(graph.functions.len(), vec![]);
+        graph.unresolved.resize(graph.functions.len(), vec![]);
+        for caller in std::iter::once(root_index).chain(first_new..graph.functions.len()) {
+            (graph.calls[caller], graph.unresolved[caller]) = graph.calls_from_function(caller);
+        }
+        (graph, root_index)
+    }
+
+    /// Where a function which isn't in the graph would go, by its full path:
+    /// nested in the function it is written in, or beside the functions it is written next to.
+    ///
+    /// # Returns
+    ///
+    /// The module, the path within it without the function's own name, and the type the function would be a method of.
+    fn place_of(&self, path: &[String]) -> Option<(Vec<String>, Vec<String>, Option<String>)> {
+        let (_, parent) = path.split_last()?;
+        let full = |function: &FunctionNode| -> Vec<String> {
+            function
+                .module
+                .iter()
+                .chain(&function.path)
+                .cloned()
+                .collect()
+        };
+        if let Some(outer) = self
+            .functions
+            .iter()
+            .find(|function| full(function) == parent)
+        {
+            return Some((outer.module.clone(), outer.path.clone(), None));
+        }
+        let sibling = self
+            .functions
+            .iter()
+            .find(|function| full(function).split_last().map(|(_, rest)| rest) == Some(parent))?;
+        let (_, path) = sibling.path.split_last()?;
+        Some((sibling.module.clone(), path.to_vec(), sibling.owner.clone()))
+    }
+
+    /// Add a function, and every function nested inside it
+    fn add_function(
+        &mut self,
+        module: &[String],
+        path: Vec<String>,
+        ast: syn::ItemFn,
+        owner: Option<String>,
+    ) {
+        /// Collects the functions defined inside a function body
+        #[derive(Default)]
+        struct NestedFunctions {
+            /// The nested functions found so far
+            found: Vec<syn::ItemFn>,
+        }
+        impl<'ast> Visit<'ast> for NestedFunctions {
+            fn visit_item_fn(&mut self, item: &'ast syn::ItemFn) {
+                // functions nested deeper are found when this one is added
+                self.found.push(item.clone());
+            }
+        }
+
+        let mut nested = NestedFunctions::default();
+        nested.visit_block(&ast.block);
+        let mut path = path;
+        path.push(ast.sig.ident.to_string());
+        for inner in nested.found {
+            self.add_function(module, path.clone(), inner, None);
+        }
+        if let Some(name) = path.last() {
+            let index = self.functions.len();
+            self.names.entry(name.clone()).or_default().push(index);
+        }
+        self.functions.push(FunctionNode {
+            module: module.to_vec(),
+            path,
+            ast: Rc::new(ast),
+            owner,
+        });
+    }
+
+    /// Add every function among some items
+    fn add_items(&mut self, module: &[String], items: &[syn::Item]) {
+        for item in items {
+            match item {
+                syn::Item::Fn(function) => {
+                    self.add_function(module, vec![], function.clone(), None)
+                }
+                syn::Item::Mod(inner) => {
+                    if let Some((_, items)) = &inner.content {
+                        let mut module = module.to_vec();
+                        module.push(inner.ident.to_string());
+                        self.add_items(&module, items);
+                    }
+                }
+                syn::Item::Use(item) => {
+                    let imports = self.imports.entry(module.to_vec()).or_default();
+                    add_imports(&[], &item.tree, imports);
+                }
+                syn::Item::Impl(implementation) => {
+                    let Some(type_name) = last_segment_of_type(&implementation.self_ty) else {
+                        continue;
+                    };
+                    for impl_item in &implementation.items {
+                        if let syn::ImplItem::Fn(method) = impl_item {
+                            let ast = item_fn_from_parts(
+                                &method.attrs,
+                                &method.vis,
+                                &method.sig,
+                                &method.block,
+                            );
+                            let owner = Some(type_name.clone());
+                            self.add_function(module, vec![type_name.clone()], ast, owner);
+                        }
+                    }
+                }
+                syn::Item::Trait(trait_item) => {
+                    for item in &trait_item.items {
+                        if let syn::TraitItem::Fn(syn::TraitItemFn {
+                            attrs,
+                            sig,
+                            default: Some(block),
+                            ..
+                        }) = item
+                        {
+                            let ast =
+                                item_fn_from_parts(attrs, &syn::Visibility::Inherited, sig, block);
+                            let owner = trait_item.ident.to_string();
+                            self.add_function(module, vec![owner.clone()], ast, Some(owner));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Resolve the calls made by every function
+    fn resolve_calls(&mut self) {
+        (self.calls, self.unresolved) = (0..self.functions.len())
+            .map(|caller| self.calls_from_function(caller))
+            .unzip();
+    }
+
+    /// Resolve the calls made by one function
+    ///
+    /// # Returns
+    ///
+    /// The calls to functions of the graph, and the calls to paths of the crate which name none of them.
+    fn calls_from_function(&self, caller: usize) -> (Vec<CallSite>, Vec<(String, SourceSpan)>) {
+        let mut collector = CallCollector {
+            graph: self,
+            caller,
+            oracles: &self.oracles,
+            locals: HashSet::new(),
+            bindings: HashMap::new(),
+            paths: vec![],
+            called: HashSet::new(),
+            methods: vec![],
+        };
+        let ast = &self.functions[caller].ast;
+        for input in &ast.sig.inputs {
+            collector.visit_fn_arg(input);
+        }
+        collector.visit_block(&ast.block);
+        let paths = collector.paths.iter().filter_map(|(segments, span)| {
+            if let [single] = segments.as_slice() {
+                if collector.locals.contains(single) {
+                    return None;
+                }
+            }
+            let callee = self.resolve_path(caller, segments)?;
+            let span = *span;
+            let by_name = false;
+            Some(CallSite {
+                callee,
+                span,
+                by_name,
+            })
+        });
+        let methods = collector
+            .methods
+            .iter()
+            .filter_map(|(method, receiver, span)| {
+                let (callee, by_name) = self.resolve_method(method, receiver)?;
+                let span = *span;
+                Some(CallSite {
+                    callee,
+                    span,
+                    by_name,
+                })
+            });
+        let mut calls: Vec<CallSite> = paths.chain(methods).collect();
+        calls.sort_by_key(|call| call.span);
+        calls.dedup();
+        // a constructor, like `crate::Wrapper(x)`, is named like a type
+        let unresolved = collector
+            .paths
+            .iter()
+            .filter(|(segments, span)| {
+                collector.called.contains(span)
+                    && segments
+                        .last()
+                        .is_some_and(|last| !last.starts_with(char::is_uppercase))
+                    && self.resolve_path(caller, segments).is_none()
+                    && self.is_crate_path(caller, segments)
+            })
+            .map(|(segments, span)| (segments.join("::"), *span))
+            .collect();
+        (calls, unresolved)
+    }
+
+    /// Whether a path names something of the crate, by where it starts once `use` imports are applied:
+    /// at `crate`, `self` or `super`, or at one of the graph's modules
+    fn is_crate_path(&self, caller: usize, segments: &[String]) -> bool {
+        let Some(first) = segments.first() else {
+            return false;
+        };
+        let imported = self
+            .imports
+            .get(&self.functions[caller].module)
+            .and_then(|imports| imports.get(first))
+            .and_then(|path| path.first());
+        let first = imported.unwrap_or(first);
+        matches!(first.as_str(), "crate" | "self" | "super") || self.is_module(first)
+    }
+
+    /// The calls to paths of the crate which name no function in the graph,
+    /// made by a function or any function it reaches
+    pub fn unresolved_from(&self, root: usize) -> Vec<&(String, SourceSpan)> {
+        self.reachable_from(root)
+            .into_iter()
+            .flat_map(|function| &self.unresolved[function])
+            .collect()
+    }
+
+    /// The function a path refers to, from within `caller`, if it can be told.
+    ///
+    /// A name brought in by `use` stands for the path it was brought in from,
+    /// unless a function nested in the caller shadows it,
+    /// and a path into the standard library or another crate is never to one of the crate's own functions.
+    pub fn resolve_path(&self, caller: usize, segments: &[String]) -> Option<usize> {
+        let caller_index = caller;
+        let caller = &self.functions[caller];
+        let imported = match segments {
+            [name] if self.nested_function(caller_index, name).is_some() => None,
+            [first, rest @ ..] => self
+                .imports
+                .get(&caller.module)
+                .and_then(|imports| imports.get(first))
+                .map(|path| path.iter().chain(rest).cloned().collect::<Vec<String>>()),
+            [] => None,
+        };
+        if let Some(path) = &imported {
+            let first = path.first()?;
+            let is_local = matches!(first.as_str(), "crate" | "self" | "super")
+                || self.is_module(first)
+                || self.is_crate_type(first);
+            if !is_local {
+                return None;
+            }
+        }
+        let segments = imported.as_deref().unwrap_or(segments);
+        if segments
+            .first()
+            .is_some_and(|first| STD_ROOTS.contains(&first.as_str()))
+        {
+            return None;
+        }
+        let mut segments: Vec<&String> = segments
+            .iter()
+            .skip_while(|segment| matches!(segment.as_str(), "crate" | "self" | "super"))
+            .collect();
+        // a bare `self` is the receiver, and no function
+        if segments.is_empty() {
+            return None;
+        }
+        if segments.first().is_some_and(|first| *first == "Self") {
+            segments[0] = caller.owner.as_ref()?;
+        }
+        let candidates: Vec<usize> = self
+            .named(segments.last()?)
+            .iter()
+            .copied()
+            .filter(|&i| {
+                let function = &self.functions[i];
+                let full: Vec<&String> = function.module.iter().chain(&function.path).collect();
+                // a single identifier can't name an associated function
+                full.ends_with(&segments) && (segments.len() > 1 || function.owner.is_none())
+            })
+            .collect();
+        if candidates.len() < 2 {
+            return candidates.first().copied();
+        }
+        // prefer functions nested in the caller, then functions of the caller's own module
+        let nested = |&&i: &&usize| {
+            let function = &self.functions[i];
+            function.module == caller.module && function.path.starts_with(&caller.path)
+        };
+        let sibling = |&&i: &&usize| self.functions[i].module == caller.module;
+        candidates
+            .iter()
+            .find(nested)
+            .or_else(|| candidates.iter().find(sibling))
+            .copied()
+    }
+
+    /// The method a method call refers to, by what is known of the type of its receiver.
+    ///
+    /// A call on one of the crate's types means that type's own method.
+    /// A call on anything else the crate may have written a method for, or on one of its types without such a method,
+    /// like a trait's default, means the only method of that name in the graph,
+    /// unless the name is so common that it likely means a method of the standard library.
+    ///
+    /// # Returns
+    ///
+    /// The method, and whether it was told by its name alone.
+    pub fn resolve_method(&self, method: &str, receiver: &Receiver) -> Option<(usize, bool)> {
+        let only = |owner: Option<&String>| {
+            let mut candidates = self.named(method).iter().copied().filter(|&i| {
+                let function = &self.functions[i];
+                function.is_method()
+                    && function.ast.sig.ident == method
+                    && owner.is_none_or(|owner| function.owner.as_ref() == Some(owner))
+            });
+            match (candidates.next(), candidates.next()) {
+                (Some(only), None) => Some(only),
+                _ => None,
+            }
+        };
+        let by_name = || {
+            let is_common = COMMON_METHODS.contains(&method);
+            (!is_common).then(|| only(None)).flatten()
+        };
+        match receiver {
+            Receiver::Type(name) => only(Some(name))
+                .map(|callee| (callee, false))
+                .or_else(|| by_name().map(|callee| (callee, true))),
+            Receiver::Foreign => None,
+            Receiver::Unknown => by_name().map(|callee| (callee, true)),
+        }
+    }
+
+    /// What is known of a type, in the signature or body of `caller`
+    pub fn receiver_from_type(&self, caller: usize, ty: &syn::Type) -> Receiver {
+        let function = &self.functions[caller];
+        match ty {
+            syn::Type::Reference(reference) => self.receiver_from_type(caller, &reference.elem),
+            syn::Type::Paren(paren) => self.receiver_from_type(caller, &paren.elem),
+            syn::Type::Group(group) => self.receiver_from_type(caller, &group.elem),
+            syn::Type::Slice(_) | syn::Type::Array(_) | syn::Type::Tuple(_) | syn::Type::Ptr(_) => {
+                Receiver::Foreign
+            }
+            syn::Type::Path(path) if path.qself.is_none() => {
+                let Some(last) = path.path.segments.last() else {
+                    return Receiver::Unknown;
+                };
+                let name = last.ident.to_string();
+                let is_std = path
+                    .path
+                    .segments
+                    .first()
+                    .is_some_and(|first| STD_ROOTS.contains(&first.ident.to_string().as_str()));
+                match name.as_str() {
+                    "Self" => match &function.owner {
+                        Some(owner) => Receiver::Type(owner.clone()),
+                        None => Receiver::Unknown,
+                    },
+                    // methods are called through smart pointers on what they point to
+                    "Box" | "Rc" | "Arc" => match generic_args(last).first() {
+                        Some(inner) => self.receiver_from_type(caller, inner),
+                        None => Receiver::Unknown,
+                    },
+                    _ if self.is_crate_type(&name) => Receiver::Type(name),
+                    _ if is_std || STD_TYPES.contains(&name.as_str()) => Receiver::Foreign,
+                    _ => Receiver::Unknown,
+                }
+            }
+            _ => Receiver::Unknown,
+        }
+    }
+
+    /// Whether a name is of a type the crate defines, or implements methods for
+    fn is_crate_type(&self, name: &str) -> bool {
+        self.types.is_defined(name)
+            || self
+                .functions
+                .iter()
+                .any(|function| function.owner.as_deref() == Some(name))
+    }
+
+    /// Whether a name is of one of the graph's modules
+    fn is_module(&self, name: &str) -> bool {
+        self.functions
+            .iter()
+            .any(|function| function.module.iter().any(|module| module == name))
+    }
+
+    /// The functions of a name
+    fn named(&self, name: &str) -> &[usize] {
+        self.names.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    /// The function of a name nested directly in `caller`, if there is one
+    fn nested_function(&self, caller: usize, name: &str) -> Option<usize> {
+        let caller = &self.functions[caller];
+        self.named(name).iter().copied().find(|&i| {
+            let function = &self.functions[i];
+            function.module == caller.module
+                && function.path.len() == caller.path.len() + 1
+                && function.path.starts_with(&caller.path)
+                && function.path.last().is_some_and(|last| last == name)
+        })
+    }
+
+    /// The index of the function with the given full path, e.g. `test::g`
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.functions
+            .iter()
+            .position(|function| function.name() == name)
+    }
+
+    /// Every function reachable from `root` by calls, including `root` itself, in breadth-first order
+    pub fn reachable_from(&self, root: usize) -> Vec<usize> {
+        let mut seen = HashSet::from([root]);
+        let mut queue = VecDeque::from([root]);
+        let mut order = vec![];
+        while let Some(function) = queue.pop_front() {
+            order.push(function);
+            for call in &self.calls[function] {
+                if seen.insert(call.callee) {
+                    queue.push_back(call.callee);
+                }
+            }
+        }
+        order
+    }
+
+    /// The strongly connected components of the call graph, by Tarjan's algorithm.
+    ///
+    /// # Returns
+    ///
+    /// The components in reverse topological order: callees before their callers.
+    pub fn strongly_connected_components(&self) -> Vec<Vec<usize>> {
+        let n = self.functions.len();
+        let mut index = vec![None; n];
+        let mut lowlink = vec![0; n];
+        let mut on_stack = vec![false; n];
+        let mut stack = vec![];
+        let mut components = vec![];
+        let mut next_index = 0;
+        for start in 0..n {
+            if index[start].is_some() {
+                continue;
+            }
+            // an explicit stack of (function, next call to follow) instead of recursion
+            let mut work = vec![(start, 0)];
+            while let Some(&mut (v, ref mut next_call)) = work.last_mut() {
+                if *next_call == 0 && index[v].is_none() {
+                    index[v] = Some(next_index);
+                    lowlink[v] = next_index;
+                    next_index += 1;
+                    stack.push(v);
+                    on_stack[v] = true;
+                }
+                if let Some(call) = self.calls[v].get(*next_call) {
+                    *next_call += 1;
+                    let w = call.callee;
+                    match index[w] {
+                        None => work.push((w, 0)),
+                        Some(w_index) if on_stack[w] => lowlink[v] = lowlink[v].min(w_index),
+                        Some(_) => {}
+                    }
+                    continue;
+                }
+                work.pop();
+                if let Some(&(parent, _)) = work.last() {
+                    lowlink[parent] = lowlink[parent].min(lowlink[v]);
+                }
+                if Some(lowlink[v]) == index[v] {
+                    let mut component = vec![];
+                    while let Some(w) = stack.pop() {
+                        on_stack[w] = false;
+                        component.push(w);
+                        if w == v {
+                            break;
+                        }
+                    }
+                    component.sort_unstable();
+                    components.push(component);
+                }
+            }
+        }
+        components
+    }
+
+    /// Every recursive component of the call graph: those with a cycle in them.
+    pub fn recursions(&self) -> Vec<Recursion> {
+        self.strongly_connected_components()
+            .into_iter()
+            .filter_map(|members| self.recursion_from_component(members))
+            .collect()
+    }
+
+    /// Every recursive component reachable from `root`, including any `root` is part of.
+    pub fn recursions_reachable_from(&self, root: usize) -> Vec<Recursion> {
+        let reachable: HashSet<usize> = self.reachable_from(root).into_iter().collect();
+        self.recursions()
+            .into_iter()
+            .filter(|recursion| reachable.contains(&recursion.members[0]))
+            .collect()
+    }
+
+    /// Describe a component as recursion, unless it is a lone function which doesn't call itself
+    fn recursion_from_component(&self, members: Vec<usize>) -> Option<Recursion> {
+        let member_set: HashSet<usize> = members.iter().copied().collect();
+        let call_sites: Vec<(usize, CallSite)> = members
+            .iter()
+            .flat_map(|&caller| self.calls[caller].iter().map(move |call| (caller, *call)))
+            .filter(|(_, call)| member_set.contains(&call.callee))
+            .collect();
+        if call_sites.is_empty() {
+            return None;
+        }
+        let kind = match members.len() {
+            1 => RecursionKind::Direct,
+            2 => RecursionKind::Mutual,
+            _ => RecursionKind::Cyclic,
+        };
+        let cycle = self.shortest_cycle(members[0], &member_set);
+        Some(Recursion {
+            kind,
+            members,
+            cycle,
+            call_sites,
+        })
+    }
+
+    /// A shortest cycle from `start` back to itself within a component, by breadth-first search
+    fn shortest_cycle(&self, start: usize, component: &HashSet<usize>) -> Vec<usize> {
+        let mut parent = vec![None; self.functions.len()];
+        let mut queue = VecDeque::from([start]);
+        while let Some(v) = queue.pop_front() {
+            for call in &self.calls[v] {
+                let w = call.callee;
+                if w == start {
+                    let mut cycle = vec![start];
+                    let mut at = v;
+                    while at != start {
+                        cycle.push(at);
+                        at = parent[at].unwrap_or(start);
+                    }
+                    cycle.push(start);
+                    cycle.reverse();
+                    return cycle;
+                }
+                if component.contains(&w) && parent[w].is_none() {
+                    parent[w] = Some(v);
+                    queue.push_back(w);
+                }
+            }
+        }
+        vec![start]
+    }
+
+    /// Describe a cycle by function names, e.g. `a → b → c → a`
+    pub fn describe_cycle(&self, cycle: &[usize]) -> String {
+        cycle
+            .iter()
+            .map(|&i| self.functions[i].name())
+            .collect::<Vec<_>>()
+            .join(" → ")
+    }
+}
+
+/// Method names of the standard library too common to resolve to a crate's own method by name alone
+const COMMON_METHODS: [&str; 24] = [
+    "as_ref",
+    "borrow",
+    "borrow_mut",
+    "clone",
+    "cmp",
+    "contains",
+    "eq",
+    "fmt",
+    "get",
+    "hash",
+    "insert",
+    "into",
+    "into_iter",
+    "is_empty",
+    "iter",
+    "len",
+    "map",
+    "next",
+    "partial_cmp",
+    "pop",
+    "push",
+    "remove",
+    "to_string",
+    "unwrap",
+];
+
+/// Collects the paths a function calls or refers to, and the methods it calls
+struct CallCollector<'a> {
+    /// The graph the function is part of, whose types tell receivers apart
+    graph: &'a CallGraph,
+    /// The index of the function
+    caller: usize,
+    /// The names of the oracle, which analyzes functions rather than calling them
+    oracles: &'a OracleNames,
+    /// The names bound by patterns in the function, which shadow functions of the same name
+    locals: HashSet<String>,
+    /// The declared type of each name bound so far, `None` once it is bound without one, or with another
+    bindings: HashMap<String, Option<syn::Type>>,
+    /// Paths which are called, or passed around as values and so may be called
+    paths: Vec<(Vec<String>, SourceSpan)>,
+    /// Where the paths which are called, rather than passed around, are
+    called: HashSet<SourceSpan>,
+    /// Names of methods which are called, and what is known of their receivers
+    methods: Vec<(String, Receiver, SourceSpan)>,
+}
+
+impl CallCollector<'_> {
+    /// Bind a name, with the type it is declared with, if any
+    fn bind(&mut self, name: String, ty: Option<syn::Type>) {
+        self.locals.insert(name.clone());
+        match self.bindings.get(&name) {
+            Some(known) if *known != ty => {
+                self.bindings.insert(name, None);
+            }
+            Some(_) => {}
+            None => {
+                self.bindings.insert(name, ty);
+            }
+        }
+    }
+
+    /// The type of an expression, as far as it can be told without type inference
+    fn type_of(&self, expr: &syn::Expr) -> Option<syn::Type> {
+        match expr {
+            syn::Expr::Path(path) if path.path.is_ident("self") => Some(syn::parse_quote!(Self)),
+            syn::Expr::Path(path) => {
+                let ident = path.path.get_ident()?;
+                self.bindings.get(&ident.to_string())?.clone()
+            }
+            syn::Expr::Paren(paren) => self.type_of(&paren.expr),
+            syn::Expr::Group(group) => self.type_of(&group.expr),
+            syn::Expr::Reference(reference) => self.type_of(&reference.expr),
+            syn::Expr::Field(field) => {
+                let base = self.type_of(&field.base)?;
+                let owner = self.graph.functions[self.caller].owner.as_deref();
+                let name = self.graph.types.defined_type(&base, owner)?;
+                let member = match &field.member {
+                    syn::Member::Named(ident) => ident.to_string(),
+                    syn::Member::Unnamed(index) => index.index.to_string(),
+                };
+                self.graph.types.field_type(&name, &member).cloned()
+            }
+            syn::Expr::Index(index) => match self.type_of(&index.expr)? {
+                syn::Type::Slice(slice) => Some(*slice.elem),
+                syn::Type::Array(array) => Some(*array.elem),
+                syn::Type::Reference(reference) => match *reference.elem {
+                    syn::Type::Slice(slice) => Some(*slice.elem),
+                    _ => None,
+                },
+                syn::Type::Path(path) => {
+                    let last = path.path.segments.last()?;
+                    let is_vec = last.ident == "Vec" || last.ident == "VecDeque";
+                    is_vec.then(|| generic_args(last).first().cloned().cloned())?
+                }
+                _ => None,
+            },
+            syn::Expr::Lit(lit) => Some(match &lit.lit {
+                syn::Lit::Str(_) => syn::parse_quote!(&str),
+                syn::Lit::ByteStr(_) => syn::parse_quote!(&[u8]),
+                syn::Lit::Byte(_) => syn::parse_quote!(u8),
+                syn::Lit::Char(_) => syn::parse_quote!(char),
+                syn::Lit::Bool(_) => syn::parse_quote!(bool),
+                syn::Lit::Int(_) => syn::parse_quote!(i32),
+                syn::Lit::Float(_) => syn::parse_quote!(f64),
+                _ => return None,
+            }),
+            syn::Expr::Struct(expr) => Some(syn::Type::Path(syn::TypePath {
+                qself: None,
+                path: expr.path.clone(),
+            })),
+            _ => None,
+        }
+    }
+}
+
+impl<'a, 'ast> Visit<'ast> for CallCollector<'a> {
+    fn visit_expr_call(&mut self, call: &'ast syn::ExprCall) {
+        // calling the oracle is assumed to halt, and the function passed to it is analyzed rather than called
+        match &*call.func {
+            syn::Expr::Path(func) if self.oracles.is_oracle(&func.path) => {}
+            func => {
+                if let syn::Expr::Path(path) = func {
+                    self.called.insert(path.span().into());
+                }
+                syn::visit::visit_expr_call(self, call)
+            }
+        }
+    }
+
+    fn visit_expr_path(&mut self, path: &'ast syn::ExprPath) {
+        let segments = path
+            .path
+            .segments
+            .iter()
+            .map(|segment| segment.ident.to_string())
+            .collect();
+        self.paths.push((segments, path.span().into()));
+    }
+
+    fn visit_expr_method_call(&mut self, call: &'ast syn::ExprMethodCall) {
+        let receiver = match self.type_of(&call.receiver) {
+            Some(ty) => self.graph.receiver_from_type(self.caller, &ty),
+            None => Receiver::Unknown,
+        };
+        let span = call.method.span().into();
+        self.methods.push((call.method.to_string(), receiver, span));
+        syn::visit::visit_expr_method_call(self, call);
+    }
+
+    fn visit_pat_ident(&mut self, pat: &'ast syn::PatIdent) {
+        self.bind(pat.ident.to_string(), None);
+        syn::visit::visit_pat_ident(self, pat);
+    }
+
+    fn visit_pat_type(&mut self, pat: &'ast syn::PatType) {
+        match &*pat.pat {
+            syn::Pat::Ident(ident) if ident.subpat.is_none() => {
+                self.bind(ident.ident.to_string(), Some((*pat.ty).clone()))
+            }
+            _ => syn::visit::visit_pat_type(self, pat),
+        }
+    }
+
+    fn visit_local(&mut self, local: &'ast syn::Local) {
+        // `let x = Type { .. }` declares the type as surely as `let x: Type`
+        let built = local.init.as_ref().and_then(|init| match &*init.expr {
+            syn::Expr::Struct(_) => self.type_of(&init.expr),
+            _ => None,
+        });
+        if let Some(init) = &local.init {
+            self.visit_local_init(init);
+        }
+        match (&local.pat, built) {
+            (syn::Pat::Ident(ident), Some(ty)) if ident.subpat.is_none() => {
+                self.bind(ident.ident.to_string(), Some(ty))
+            }
+            (pat, _) => self.visit_pat(pat),
+        }
+    }
+
+    // nested functions are nodes of their own
+    fn visit_item_fn(&mut self, _: &'ast syn::ItemFn) {}
+
+    fn visit_macro(&mut self, mac: &'ast syn::Macro) {
+        let parser = Punctuated::<syn::Expr, syn::Token![,]>::parse_terminated;
+        if let Ok(args) = mac.parse_body_with(parser) {
+            for arg in &args {
+                self.visit_expr(arg);
+            }
+        }
+    }
+}
+
+/// Add the names a `use` tree brings in, with the paths they stand for; globs bring in nothing told apart
+fn add_imports(prefix: &[String], tree: &syn::UseTree, imports: &mut HashMap<String, Vec<String>>) {
+    let path = |last: &syn::Ident| {
+        let mut path = prefix.to_vec();
+        if last != "self" {
+            path.push(last.to_string());
+        }
+        path
+    };
+    match tree {
+        syn::UseTree::Path(inner) => {
+            add_imports(&path(&inner.ident), &inner.tree, imports);
+        }
+        syn::UseTree::Name(name) => {
+            let imported = path(&name.ident);
+            if let Some(last) = imported.last() {
+                imports.insert(last.clone(), imported.clone());
+            }
+        }
+        syn::UseTree::Rename(rename) => {
+            imports.insert(rename.rename.to_string(), path(&rename.ident));
+        }
+        syn::UseTree::Group(group) => {
+            for tree in &group.items {
+                add_imports(prefix, tree, imports);
+            }
+        }
+        syn::UseTree::Glob(_) => {}
+    }
+}
+
+/// tests for the call graph
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A file with every kind of recursion, and some calls which aren't recursion
+    const CODE: &str = "
+        fn unit() {}
+        fn chain_start() { chain_end(); }
+        fn chain_end() { unit(); }
+        fn direct() { direct() }
+        fn ping() { pong() }
+        fn pong() { self::ping() }
+        fn a() { b(); }
+        fn b() { println!(\"{:?}\", c()); }
+        fn c() { crate::a(); }
+        fn g() { let _ = halts(g); }
+        mod inner {
+            fn shadow() { unit() }
+            fn unit() {}
+        }
+        struct List;
+        impl List {
+            fn len(&self) -> usize { self.len_from(0) }
+            fn len_from(&self, n: usize) -> usize { self.len_from(n) }
+        }
+    ";
+
+    /// The names of the functions in a cycle
+    fn names(graph: &CallGraph, recursion: &Recursion) -> Vec<String> {
+        recursion
+            .cycle
+            .iter()
+            .map(|&i| graph.functions[i].name())
+            .collect()
+    }
+
+    /// Test that direct, mutual and cyclic recursion are found with their cycles
+    #[test]
+    fn test_recursions() {
+        let file = syn::parse_file(CODE).unwrap();
+        let graph = CallGraph::from_file(&file);
+        let recursions = graph.recursions();
+        let cycles: Vec<Vec<String>> = recursions.iter().map(|r| names(&graph, r)).collect();
+        assert!(cycles.contains(&vec!["direct".to_string(), "direct".to_string()]));
+        // `g` only passes itself to the oracle, which analyzes it rather than calling it
+        assert!(!cycles.iter().any(|cycle| cycle.contains(&"g".to_string())));
+        assert!(cycles.contains(&vec![
+            "List::len_from".to_string(),
+            "List::len_from".to_string()
+        ]));
+        let mutual = recursions
+            .iter()
+            .find(|r| r.kind == RecursionKind::Mutual)
+            .unwrap();
+        assert_eq!(graph.describe_cycle(&mutual.cycle), "ping → pong → ping");
+        let cyclic = recursions
+            .iter()
+            .find(|r| r.kind == RecursionKind::Cyclic)
+            .unwrap();
+        assert_eq!(graph.describe_cycle(&cyclic.cycle), "a → b → c → a");
+        assert_eq!(recursions.len(), 4);
+    }
+
+    /// Test that reachability follows calls but recursion only counts cycles
+    #[test]
+    fn test_reachable_recursions() {
+        let file = syn::parse_file(CODE).unwrap();
+        let graph = CallGraph::from_file(&file);
+        let chain_start = graph.find("chain_start").unwrap();
+        assert_eq!(graph.reachable_from(chain_start).len(), 3);
+        assert!(graph.recursions_reachable_from(chain_start).is_empty());
+        let len = graph.find("List::len").unwrap();
+        assert_eq!(graph.recursions_reachable_from(len).len(), 1);
+        // `unit` in `inner` means the module's own `unit`
+        let shadow = graph.find("inner::shadow").unwrap();
+        let callee = graph.calls[shadow][0].callee;
+        assert_eq!(graph.functions[callee].name(), "inner::unit");
+    }
+
+    /// Test that methods are told apart by the types of their receivers, and functions by what is imported
+    #[test]
+    fn test_receivers_and_imports() {
+        let file = syn::parse_file(
+            "
+            struct W { v: Vec<i32>, s: String }
+            impl W {
+                fn sort(&mut self) { self.v.sort() }
+                fn truncate(&mut self, n: usize) { let mut v: Vec<i32> = vec![]; v.truncate(n) }
+                fn trim(&self) -> String { self.s.trim().to_string() }
+                fn sort_all(ws: &mut [W]) { ws[0].sort() }
+                fn to_uppercase(&self) -> String { self.s.clone().to_uppercase() }
+            }
+            mod util {
+                use std::cmp::max;
+                pub fn larger(a: i32, b: i32) -> i32 { max(a, b) }
+            }
+            fn max(a: i32, b: i32) -> i32 { util::larger(a, b) }
+            fn min(a: i32, b: i32) -> i32 { std::cmp::min(a, b) }
+            ",
+        )
+        .unwrap();
+        let graph = CallGraph::from_file(&file);
+        let recursions = graph.recursions();
+        assert_eq!(recursions.len(), 1);
+        assert_eq!(
+            graph.describe_cycle(&recursions[0].cycle),
+            "W::to_uppercase → W::to_uppercase"
+        );
+        assert!(recursions[0].is_by_name());
+        let sort_all = graph.find("W::sort_all").unwrap();
+        let call = graph.calls[sort_all][0];
+        assert_eq!(graph.functions[call.callee].name(), "W::sort");
+        assert!(!call.by_name);
+        let max = graph.find("max").unwrap();
+        assert_eq!(graph.reachable_from(max).len(), 2);
+    }
+
+    /// Test that a root function stands in for the function with its signature
+    #[test]
+    fn test_root_replaces_function() {
+        let file = syn::parse_file(CODE).unwrap();
+        let root: syn::ItemFn = syn::parse_str("fn direct() { unit() }").unwrap();
+        let (graph, root) = CallGraph::from_file_with_root(&file, &root);
+        assert_eq!(graph.functions[root].name(), "direct");
+        assert!(graph.recursions_reachable_from(root).is_empty());
+    }
+}
@@ -0,0 +1,527 @@
+//! halts/src/driver.rs
+//! A counterexample-guided driver over the engines of the analyzer.
+//! The engines are tried in order, cheapest and surest first: syntactic checks for paradoxes and missing base cases,
+//! abstract interpretation, synthesis of ranking functions and recurrent sets, symbolic execution, and last the interpreter.
+//! Each but the last works on an abstraction of the program, like integers which never overflow, so a LOOP may be spurious.
+//! When one comes with a witness, an input it loops on, the driver replays it in the interpreter, which is ground truth:
+//! a run which returns or panics refutes the claim, and the abstraction is refined to rule it out before the engines are tried again.
+//! A run which overflows makes integers whose types are spelled out as wide as their types, and one which stops otherwise
+//! makes that claim count for nothing, leaving the loop to the engines after the one which made it.
+//! A run which does neither leaves the claim standing, unless it changes an integer whose type is spelled out,
+//! which the run may only have been too short to overflow, and then the claim is UNKNOWN.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+use quote::ToTokens;
+use syn::spanned::Spanned;
+use syn::visit::Visit;
+
+use crate::call_graph::{CallGraph, Context};
+use crate::input::Input;
+use crate::interp::{execution_from_graph, Execution, IntTy, Value};
+use crate::ir::{
+    body_from_ast, BasicBlock, BinOp, BlockId, Body, Divergence, Expr, Statement, Terminator, UnOp,
+};
+use crate::symbolic::paths_to_block;
+use crate::verdict::{Decision, Evidence, Reason, Rule, SourceSpan, Verdict};
+
+/// The most times the abstraction is refined before the driver gives up
+const MAX_ROUNDS: usize = 4;
+
+/// The most steps a witness is replayed for
+const MAX_STEPS: usize = 100_000;
+
+/// What the spurious witnesses so far have shown to be wrong with the abstraction.
+#[derive(Debug, Clone, Default)]
+pub struct Refinement {
+    /// Whether integers whose types are spelled out panic when they overflow, rather than being mathematical integers
+    pub machine_integers: bool,
+    /// The claims of LOOP which replaying their witness refuted, by rule and where they were made, with what the replay did
+    pub refuted: Vec<(Rule, Vec<SourceSpan>, String)>,
+}
+
+impl Refinement {
+    /// The control-flow graph of a function under the abstraction as refined,
+    /// with a branch to a panic before each assignment which would overflow, if integers are as wide as their types
+    pub fn body_from_ast(&self, ast: &syn::ItemFn) -> Body {
+        let body = body_from_ast(ast);
+        if self.machine_integers {
+            checked_from_body(&body, &ranges_from_ast(ast))
+        } else {
+            body
+        }
+    }
+
+    /// A verdict, or an UNKNOWN in its place if it's a claim of LOOP which was refuted
+    pub fn refute(&self, verdict: Verdict) -> Verdict {
+        let Verdict::Loops(evidence) = &verdict else {
+            return verdict;
+        };
+        match self
+            .refuted
+            .iter()
+            .find(|(rule, spans, _)| *rule == evidence.rule && *spans == evidence.spans)
+        {
+            Some((_, _, why)) => {
+                let mut evidence = evidence.clone();
+                evidence
+                    .notes
+                    .push(format!("the witness is spurious: {}", why));
+                Verdict::Unknown(Reason::Inconclusive, evidence)
+            }
+            None => verdict,
+        }
+    }
+}
+
+/// The range of each local whose type is spelled out as an integer narrower than 128 bits, by its name in the source,
+/// leaving out names bound more than once with different types, or without one
+#[derive(Default)]
+struct Ranges {
+    /// The least and greatest value of each name, or `None` if that isn't one range
+    ranges: HashMap<String, Option<(i128, i128)>>,
+}
+
+impl Ranges {
+    /// Note a name bound with a range, or with none
+    fn bind(&mut self, name: String, range: Option<(i128, i128)>) {
+        match self.ranges.entry(name) {
+            Entry::Occupied(mut entry) => {
+                if *entry.get() != range {
+                    entry.insert(None);
+                }
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(range);
+            }
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for Ranges {
+    fn visit_pat_type(&mut self, typed: &'ast syn::PatType) {
+        if let syn::Pat::Ident(ident) = &*typed.pat {
+            let range = IntTy::from_type(&typed.ty)
+                .filter(|ty| ty.bits() < 128)
+                .map(IntTy::bounds);
+            self.bind(ident.ident.to_string(), range);
+        }
+        syn::visit::visit_pat_type(self, typed);
+    }
+
+    fn visit_local(&mut self, local: &'ast syn::Local) {
+        if let syn::Pat::Ident(ident) = &local.pat {
+            self.bind(ident.ident.to_string(), None);
+        }
+        syn::visit::visit_local(self, local);
+    }
+}
+
+/// The range of each local of a function whose type is spelled out as an integer, by its name in the source
+fn ranges_from_ast(ast: &syn::ItemFn) -> HashMap<String, (i128, i128)> {
+    let mut ranges = Ranges::default();
+    ranges.visit_item_fn(ast);
+    ranges
+        .ranges
+        .into_iter()
+        .filter_map(|(name, range)| Some((name, range?)))
+        .collect()
+}
+
+/// The condition under which a statement doesn't overflow, if it does arithmetic into a local with a range
+fn overflow_check(statement: &Statement, ranges: &HashMap<String, (i128, i128)>) -> Option<Expr> {
+    let Statement::Assign { local, value } = statement else {
+        return None;
+    };
+    if !matches!(
+        value,
+        Expr::Binary(BinOp::Add | BinOp::Sub | BinOp::Mul, ..) | Expr::Unary(UnOp::Neg, _)
+    ) {
+        return None;
+    }
+    // a local shadowing another is renamed `x'1`, but declared as `x`
+    let name = local.split('\'').next()?;
+    let &(least, greatest) = ranges.get(name)?;
+    Some(Expr::binary(
+        BinOp::And,
+        Expr::binary(BinOp::Le, Expr::Int(least), value.clone()),
+        Expr::binary(BinOp::Le, value.clone(), Expr::Int(greatest)),
+    ))
+}
+
+/// A body with a branch to a panic before each assignment which would overflow the range of its local
+fn checked_from_body(body: &Body, ranges: &HashMap<String, (i128, i128)>) -> Body {
+    let mut body = body.clone();
+    let mut panic: Option<BlockId> = None;
+    let mut work: Vec<(BlockId, usize)> = (0..body.blocks.len()).map(|block| (block, 0)).collect();
+    while let Some((block, start)) = work.pop() {
+        let found = body.blocks[block]
+            .statements
+            .iter()
+            .enumerate()
+            .skip(start)
+            .find_map(|(i, statement)| Some((i, overflow_check(statement, ranges)?)));
+        let Some((i, condition)) = found else {
+            continue;
+        };
+        let span = body.blocks[block].span;
+        let panic = *panic.get_or_insert_with(|| {
+            body.blocks.push(BasicBlock {
+                statements: vec![],
+                terminator: Terminator::Diverge(Divergence::Panic),
+                span,
+            });
+            body.blocks.len() - 1
+        });
+        // the block is cut before the assignment, which starts the rest of it
+        let rest = body.blocks.len();
+        let statements = body.blocks[block].statements.split_off(i);
+        let terminator = std::mem::replace(
+            &mut body.blocks[block].terminator,
+            Terminator::Branch {
+                condition,
+                then: rest,
+                otherwise: panic,
+            },
+        );
+        body.blocks.push(BasicBlock {
+            statements,
+            terminator,
+            span,
+        });
+        work.push((rest, 1));
+    }
+    body
+}
+
+/// Finds arithmetic within some spans of a function, or in the arguments of calls to what they span,
+/// which changes an integer whose type is spelled out, and which may overflow it
+struct Arithmetic<'a> {
+    /// The range of each name whose type is spelled out as an integer
+    ranges: &'a HashMap<String, (i128, i128)>,
+    /// Where to look
+    spans: &'a [SourceSpan],
+    /// Whether the current expression is in the arguments of a call to a spanned function
+    in_call: bool,
+    /// The first such arithmetic found, as it is written
+    found: Option<String>,
+}
+
+impl Arithmetic<'_> {
+    /// Whether something is within the spans looked in
+    fn is_within(&self, spanned: &impl Spanned) -> bool {
+        let span = SourceSpan::from(spanned.span());
+        self.spans
+            .iter()
+            .any(|within| within.start <= span.start && span.end <= within.end)
+    }
+
+    /// Whether an expression reads a name with a range
+    fn reads_ranged(&self, expr: &syn::Expr) -> bool {
+        /// Collects the names an expression reads
+        #[derive(Default)]
+        struct Names(Vec<String>);
+        impl<'ast> Visit<'ast> for Names {
+            fn visit_path(&mut self, path: &'ast syn::Path) {
+                self.0.extend(path.get_ident().map(ToString::to_string));
+            }
+        }
+        let mut names = Names::default();
+        names.visit_expr(expr);
+        names.0.iter().any(|name| self.ranges.contains_key(name))
+    }
+}
+
+impl<'ast> Visit<'ast> for Arithmetic<'_> {
+    fn visit_expr_binary(&mut self, expr: &'ast syn::ExprBinary) {
+        let changes = matches!(
+            expr.op,
+            syn::BinOp::Add(_)
+                | syn::BinOp::Sub(_)
+                | syn::BinOp::Mul(_)
+                | syn::BinOp::AddAssign(_)
+                | syn::BinOp::SubAssign(_)
+                | syn::BinOp::MulAssign(_)
+        );
+        if self.found.is_none()
+            && changes
+            && (self.in_call || self.is_within(expr))
+            && (self.reads_ranged(&expr.left) || self.reads_ranged(&expr.right))
+        {
+            self.found = Some(expr.to_token_stream().to_string());
+        }
+        syn::visit::visit_expr_binary(self, expr);
+    }
+
+    fn visit_expr_call(&mut self, call: &'ast syn::ExprCall) {
+        let in_call = self.in_call;
+        self.in_call = in_call || self.is_within(&call.func);
+        syn::visit::visit_expr_call(self, call);
+        self.in_call = in_call;
+    }
+
+    fn visit_expr_method_call(&mut self, call: &'ast syn::ExprMethodCall) {
+        let in_call = self.in_call;
+        self.in_call = in_call || self.is_within(&call.method);
+        syn::visit::visit_expr_method_call(self, call);
+        self.in_call = in_call;
+    }
+}
+
+/// The arithmetic in the code a claim of LOOP is about, among the functions a run may call,
+/// which may overflow an integer whose type is spelled out, if there is any:
+/// a run which neither comes back to a state nor ends may only not have run long enough to overflow it
+fn overflow_from_claim(evidence: &Evidence, graph: &CallGraph, root: usize) -> Option<String> {
+    graph.reachable_from(root).into_iter().find_map(|function| {
+        let ast = &graph.functions[function].ast;
+        let ranges = ranges_from_ast(ast);
+        let mut arithmetic = Arithmetic {
+            ranges: &ranges,
+            spans: &evidence.spans,
+            in_call: false,
+            found: None,
+        };
+        arithmetic.visit_item_fn(ast);
+        arithmetic.found
+    })
+}
+
+/// A claim of LOOP which no run confirmed put down to UNKNOWN, if it's about arithmetic which may overflow
+fn unconfirmed(verdict: Verdict, graph: &CallGraph, root: usize, why: &str) -> Verdict {
+    match &verdict {
+        Verdict::Loops(evidence) if evidence.rule != Rule::Execution => {
+            match overflow_from_claim(evidence, graph, root) {
+                Some(arithmetic) => {
+                    let mut evidence = evidence.clone();
+                    evidence.notes.push(format!(
+                        "{}, and `{}` may overflow its type before the LOOP shows",
+                        why, arithmetic
+                    ));
+                    Verdict::Unknown(Reason::Inconclusive, evidence)
+                }
+                None => verdict,
+            }
+        }
+        _ => verdict,
+    }
+}
+
+/// The input a claim of LOOP says the function loops on, to run it on, if it can be told:
+/// none for a function without parameters, the start of the lasso for a recurrent set,
+/// or for a loop which can't be left, any input symbolic execution finds a way into it for
+fn witness_from_verdict(
+    ast: &syn::ItemFn,
+    verdict: &Verdict,
+    refinement: &Refinement,
+) -> Option<Vec<Value>> {
+    if ast.sig.inputs.is_empty() {
+        return Some(vec![]);
+    }
+    let evidence = verdict.evidence();
+    let values: HashMap<String, i128> = match &evidence.lasso {
+        Some(lasso) => lasso
+            .input
+            .iter()
+            .map(|(param, value)| Some((param.clone(), value.parse().ok()?)))
+            .collect::<Option<_>>()?,
+        None if evidence.rule == Rule::EndlessIteration => {
+            let body = refinement.body_from_ast(ast);
+            let iteration = body
+                .loop_forest()
+                .loops
+                .into_iter()
+                .find(|iteration| evidence.spans.first() == Some(&iteration.span))?;
+            paths_to_block(&body, iteration.header, 1)
+                .arrivals
+                .iter()
+                .find_map(|path| path.witness(&[]))?
+                .into_iter()
+                .collect()
+        }
+        None => return None,
+    };
+    ast.sig
+        .inputs
+        .iter()
+        .map(|input| match input {
+            syn::FnArg::Typed(param) => match &*param.pat {
+                syn::Pat::Ident(ident) => {
+                    let ty = IntTy::from_type(&param.ty)?;
+                    Some(Value::Int(*values.get(&ident.ident.to_string())?, Some(ty)))
+                }
+                _ => None,
+            },
+            syn::FnArg::Receiver(_) => None,
+        })
+        .collect()
+}
+
+/// Decide a function by its engines, replaying each claim of LOOP which comes with a witness,
+/// and refining the abstraction for as long as the witnesses turn out spurious.
+///
+/// A witness the run comes back to a state on confirms the LOOP, and one the run can't tell about leaves it standing,
+/// since the engines' proofs hold of the abstraction, which errs only where a run shows it does,
+/// unless the claim is about arithmetic on an integer whose type is spelled out, which the run may have been too short to overflow.
+/// So is a claim with no witness to run at all.
+///
+/// # Arguments
+///
+/// * `ast` - The AST of the function.
+/// * `context` - Where the function is defined, among the functions the run may call.
+/// * `judge` - Decides the function by its engines, under an abstraction as refined so far.
+///
+/// # Returns
+///
+/// The verdict which stood, with the engine which reached it and how many rounds of refinement it took,
+/// or an UNKNOWN if every claim of LOOP in `MAX_ROUNDS` rounds was refuted.
+pub fn decision_from_ast(
+    ast: &syn::ItemFn,
+    context: &Context,
+    judge: &mut dyn FnMut(&Refinement) -> Verdict,
+) -> Decision {
+    let (graph, root) = context.graph_with_root(ast);
+    let mut refinement = Refinement::default();
+    let mut history = vec![];
+    let mut rounds = 0;
+    loop {
+        let mut verdict = judge(&refinement);
+        let witness = match &verdict {
+            // a run which came back to a state is already ground truth
+            Verdict::Loops(evidence) if evidence.rule != Rule::Execution => {
+                witness_from_verdict(ast, &verdict, &refinement)
+            }
+            _ => None,
+        };
+        let Some(args) = witness else {
+            verdict = unconfirmed(verdict, &graph, root, "no input was found to replay it on");
+            verdict.evidence_mut().notes.extend(history);
+            return Decision::new(verdict, rounds);
+        };
+        let shown = Input::Args(args.clone());
+        let execution = execution_from_graph(&graph, root, args, MAX_STEPS);
+        let why = format!("on {}, {}", shown, execution);
+        let evidence = verdict.evidence().clone();
+        match &execution {
+            Execution::Repeats { .. } => {
+                verdict
+                    .evidence_mut()
+                    .notes
+                    .push(format!("replayed in the interpreter: {}", why));
+            }
+            Execution::Unknown(_) => verdict = unconfirmed(verdict, &graph, root, &why),
+            Execution::Panics { message, .. }
+                if message.contains("overflow") && !refinement.machine_integers =>
+            {
+                refinement.machine_integers = true;
+                history.push(format!(
+                    "LOOP by {} was spurious: {}, so integers were taken to be as wide as their types",
+                    evidence.rule, why
+                ));
+            }
+            Execution::Returns { .. } | Execution::Panics { .. } => {
+                history.push(format!("LOOP by {} was spurious: {}", evidence.rule, why));
+                refinement
+                    .refuted
+                    .push((evidence.rule, evidence.spans, why));
+            }
+        }
+        if matches!(execution, Execution::Repeats { .. } | Execution::Unknown(_)) {
+            verdict.evidence_mut().notes.extend(history);
+            return Decision::new(verdict, rounds);
+        }
+        rounds += 1;
+        if rounds > MAX_ROUNDS {
+            let mut evidence = Evidence::new(Rule::Execution);
+            evidence.notes = history;
+            evidence.sub_verdicts.push(verdict);
+            return Decision::new(Verdict::Unknown(Reason::Inconclusive, evidence), MAX_ROUNDS);
+        }
+    }
+}
+
+/// tests for the refinement driver
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Test that integers whose types are spelled out get a branch to a panic where they'd overflow
+    #[test]
+    fn test_overflow_checks() {
+        let ast: syn::ItemFn = syn::parse_str(
+            "fn f(n: u8) { let mut x: i8 = 1; while x > 0 { x += 1; } let y = n + 1; }",
+        )
+        .unwrap();
+        let refinement = Refinement {
+            machine_integers: true,
+            refuted: vec![],
+        };
+        let body = refinement.body_from_ast(&ast);
+        let panics = body
+            .blocks
+            .iter()
+            .filter(|block| block.terminator == Terminator::Diverge(Divergence::Panic))
+            .count();
+        assert_eq!(panics, 1);
+        let checks: Vec<String> = body
+            .blocks
+            .iter()
+            .filter_map(|block| match &block.terminator {
+                Terminator::Branch { condition, .. } => Some(condition.to_string()),
+                _ => None,
+            })
+            .filter(|condition| condition.contains("<= 127"))
+            .collect();
+        // `y` has no type of its own, so only `x` is checked
+        assert_eq!(checks.len(), 1, "{:?}", checks);
+        assert_eq!(
+            Refinement::default().body_from_ast(&ast),
+            body_from_ast(&ast)
+        );
+    }
+
+    /// Test that a claim of LOOP about arithmetic which a run is too short to overflow, or has no input to run on, is UNKNOWN
+    #[test]
+    fn test_unconfirmed_overflow() {
+        let file: syn::File = syn::parse_str(
+            "
+            fn up_i32() { let mut x: i32 = 1; while x > 0 { x += 1 } }
+            fn up_u64() { let mut x: u64 = 1; while x > 0 { x += 1 } }
+            fn rec(n: u8) { if n == 0 { return; } rec(n + 1) }
+            fn rec_from_one() { rec(1) }
+            fn spin() { let mut x = 1; while x > 0 { x += 0; } }
+            ",
+        )
+        .unwrap();
+        let context = Context::from_file(&file);
+        let verdict_of = |name: &str| {
+            let path = [name.to_string()];
+            let function = context.graph.functions.iter().find(|f| f.path == path);
+            crate::halts::decision_from_ast_in(&function.unwrap().ast, &context.at(&path)).verdict
+        };
+        for name in ["up_i32", "up_u64", "rec", "rec_from_one"] {
+            let verdict = verdict_of(name);
+            assert!(
+                !matches!(verdict, Verdict::Loops(_)),
+                "{}: {}",
+                name,
+                verdict
+            );
+        }
+        let verdict = verdict_of("spin");
+        assert!(matches!(verdict, Verdict::Loops(_)), "{}", verdict);
+    }
+
+    /// Test that a refuted claim of LOOP is put down to UNKNOWN, and others are left alone
+    #[test]
+    fn test_refute() {
+        let refinement = Refinement {
+            machine_integers: false,
+            refuted: vec![(Rule::EndlessIteration, vec![], "on (), returns".to_string())],
+        };
+        let verdict = refinement.refute(Verdict::Loops(Evidence::new(Rule::EndlessIteration)));
+        assert!(matches!(verdict, Verdict::Unknown(..)));
+        let verdict = refinement.refute(Verdict::Loops(Evidence::new(Rule::RecurrentSet)));
+        assert!(matches!(verdict, Verdict::Loops(_)));
+    }
+}
@@ -4,27 +4,56 @@
 //! Given the proof of 2-Undecidability relies heavily on the concept of paradox,
 //! I believe this approach to be promising to facilitate real-world static analysis.
 
-use std::collections;
 use std::error::Error;
 use std::fmt;
+use std::rc::Rc;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
 
 use quote::quote;
-use syn;
+
+use crate::base_case::{find_base_cases, reachability, Reachability};
+use crate::call_graph::{CallGraph, Context, FunctionNode, Recursion};
+use crate::driver::{decision_from_ast, Refinement};
+use crate::input::{args_from_input, condition_from_input, Input};
+use crate::interp::{execution_from_graph, Execution};
+use crate::interval::intervals_from_body;
+use crate::ir::{Body, Loop};
+use crate::paradox::{
+    find_self_references, verdict_from_self_references, OracleNames, SelfReference,
+};
+use crate::portfolio::Scope;
+use crate::precondition::precondition_from_ast;
+use crate::ranking::ranking_function;
+use crate::recurrent::lasso_from_loop;
+use crate::size_change::{size_change, SizeChange};
+use crate::source::SourceIndex;
+use crate::structural::{structural_recursion, Structure};
+use crate::symbolic::paths_to_block;
+use crate::verdict::{Decision, Engine, Evidence, Lasso, Precondition, Reason, Rule, Verdict};
+
+/// The most steps a function without parameters is run for, to see whether it halts or loops
+const MAX_STEPS: usize = 100_000;
 
 /// Convert a function into an AST
+///
+/// The function is identified by its type name, which `std::any::type_name` spells as its full path,
+/// and its source is recovered from the `src/` directory of the crate under analysis.
+/// A closure is only found if it is the one closure without arguments in the function it is written in.
 /// # Returns
 /// A result containing a `syn::ItemFn` root AST node
 /// # Errors
-/// If the conversion of the function into a token stream fails
-/// If the conversion of the code string into a syn AST fails
+/// If the type of the function does not name a function in the crate's source
+/// If the source file cannot be read or parsed into a syn AST
 pub fn ast_from_function<F>(_function: F) -> syn::Result<syn::ItemFn> {
-    let token_stream = quote! { function };
-    let code = token_stream.to_string();
-    ast_from_str(&code)
+    let type_name = std::any::type_name::<F>();
+    SourceIndex::for_current_crate().ast_from_type_name(type_name)
 }
 
 /// Convert function code string to AST
-fn ast_from_str(code: &str) -> syn::Result<syn::ItemFn> {
+/// # Errors
+/// If the conversion of the code string into a syn AST fails
+pub fn ast_from_str(code: &str) -> syn::Result<syn::ItemFn> {
     let ast: syn::ItemFn = syn::parse_str(code)?;
     Ok(ast)
 }
@@ -42,6 +71,8 @@ pub enum ParadoxError {
     CompileError,
     /// An inversion paradox was detected.
     InversionParadox,
+    /// The analyzer could not decide whether the function halts.
+    Undecided,
 }
 
 impl Error for ParadoxError {}
@@ -51,11 +82,13 @@ impl fmt::Display for ParadoxError {
         match *self {
             ParadoxError::CompileError => write!(f, "Compile error"),
             ParadoxError::InversionParadox => write!(f, "Inversion paradox detected"),
+            ParadoxError::Undecided => write!(f, "Could not decide"),
         }
     }
 }
 
 /// Determine if a function halts or loops.
+/// A compatibility wrapper around `verdict_from_function`.
 ///
 /// # Arguments
 ///
@@ -67,167 +100,740 @@ impl fmt::Display for ParadoxError {
 ///
 /// # Errors
 ///
-/// Returns an error if an inversion paradox is detected.
+/// Returns an error if an inversion paradox is detected,
+/// if the function could not be parsed, or if the analyzer could not decide.
 pub fn halts<F>(maybe_paradox_function: F) -> Result<bool, ParadoxError>
 where
     F: Fn(),
 {
-    // Parse the AST of the function
-    let ast = ast_from_function(maybe_paradox_function).unwrap();
-    // If the AST contains a call to halts, then it's an inversion paradox
-    // and we return a compile error
-    if ast_contains_halts(&ast) {
-        return Err(ParadoxError::InversionParadox);
+    match verdict_from_function(maybe_paradox_function) {
+        Verdict::Halts(_) => Ok(true),
+        Verdict::Loops(_) => Ok(false),
+        Verdict::HaltsWhen(precondition, _) if precondition.loops_otherwise => Ok(false),
+        Verdict::HaltsWhen(..) => Err(ParadoxError::Undecided),
+        Verdict::Paradox(..) => Err(ParadoxError::InversionParadox),
+        Verdict::Unknown(Reason::SourceUnavailable(_), _) => Err(ParadoxError::CompileError),
+        Verdict::Unknown(..) => Err(ParadoxError::Undecided),
     }
+}
 
-    // having categorized the ParadoxError cases, those which remain are
-    // non-paradoxical (thus hopefully decidable) instances of the binary halting problem
-    let loopy = loops(&ast);
+/// Decide if a function halts, loops, or creates a paradox.
+///
+/// # Arguments
+///
+/// * `maybe_paradox_function` - A function that may or may not halt.
+///
+/// # Returns
+///
+/// A `Verdict` carrying the evidence it was reached by.
+pub fn verdict_from_function<F>(maybe_paradox_function: F) -> Verdict
+where
+    F: Fn(),
+{
+    match source_from_function(maybe_paradox_function) {
+        Ok((ast, context)) => verdict_from_ast_in(&ast, &context),
+        Err(e) => Verdict::Unknown(
+            Reason::SourceUnavailable(e.to_string()),
+            Evidence::new(Rule::Unanalyzable),
+        ),
+    }
+}
+
+/// Determine if a function halts or loops on some of its inputs.
+/// A compatibility wrapper around `verdict_on`.
+///
+/// # Arguments
+///
+/// * `function` - A function that may or may not halt, with any parameters.
+/// * `input` - The arguments of one call, like `(3i64, true)`, or a `Domain` of them.
+///
+/// # Returns
+///
+/// A `Result` containing a boolean indicating if the function halts on every one of the inputs,
+/// or an error if an inversion paradox is detected.
+///
+/// # Errors
+///
+/// Returns an error if an inversion paradox is detected,
+/// if the function could not be parsed, or if the analyzer could not decide.
+pub fn halts_on<F, I>(function: F, input: I) -> Result<bool, ParadoxError>
+where
+    I: Into<Input>,
+{
+    match verdict_on(function, input) {
+        Verdict::Halts(_) => Ok(true),
+        Verdict::Loops(_) => Ok(false),
+        Verdict::HaltsWhen(precondition, _) if precondition.loops_otherwise => Ok(false),
+        Verdict::HaltsWhen(..) => Err(ParadoxError::Undecided),
+        Verdict::Paradox(..) => Err(ParadoxError::InversionParadox),
+        Verdict::Unknown(Reason::SourceUnavailable(_), _) => Err(ParadoxError::CompileError),
+        Verdict::Unknown(..) => Err(ParadoxError::Undecided),
+    }
+}
+
+/// Decide if a function halts, loops, or creates a paradox on some of its inputs.
+///
+/// # Arguments
+///
+/// * `function` - A function that may or may not halt, with any parameters.
+/// * `input` - The arguments of one call, like `(3i64, true)`, or a `Domain` of them.
+///
+/// # Returns
+///
+/// A `Verdict` for exactly those inputs: a HALT if the function halts on every one of them,
+/// and a LOOP if it loops on some one of them.
+pub fn verdict_on<F, I>(function: F, input: I) -> Verdict
+where
+    I: Into<Input>,
+{
+    match source_from_function(function) {
+        Ok((ast, context)) => verdict_from_ast_on(&ast, &context, &input.into()),
+        Err(e) => Verdict::Unknown(
+            Reason::SourceUnavailable(e.to_string()),
+            Evidence::new(Rule::Unanalyzable),
+        ),
+    }
+}
 
-    Ok(!loopy)
+/// Convert a function into its AST and the context it is defined in
+/// # Errors
+/// If the source of the function cannot be found, read or parsed
+pub fn source_from_function<F>(_function: F) -> syn::Result<(syn::ItemFn, Context)> {
+    let type_name = std::any::type_name::<F>();
+    let index = SourceIndex::for_current_crate();
+    let ast = index.ast_from_type_name(type_name)?;
+    let context = index.context_from_type_name(type_name)?;
+    Ok((ast, context))
 }
 
-/// Check if the AST of a function contains a call to `halts`.
+/// Decide if the function with the given AST halts, loops, or creates a paradox.
 ///
 /// # Arguments
 ///
-/// * `_` - A function to check.
+/// * `ast` - The AST of the function to decide.
 ///
 /// # Returns
 ///
-/// A boolean indicating if the AST of the function contains a call to `halts`.
-fn ast_contains_halts<F>(_: F) -> bool {
-    // TODO: Implement AST parsing and check for calls to halts
-    false
+/// A `Verdict` carrying the evidence it was reached by.
+pub fn verdict_from_ast(ast: &syn::ItemFn) -> Verdict {
+    verdict_from_ast_in(ast, &Context::default())
 }
 
-/// Determine if a function contains any loops.
+/// Decide if the function with the given AST halts, loops, or creates a paradox,
+/// in the context it is defined in.
 ///
 /// # Arguments
 ///
-/// * `function` - A function to check for loops.
+/// * `ast` - The AST of the function to decide.
+/// * `context` - Where the function is defined, for its `use` aliases and sibling items.
 ///
 /// # Returns
 ///
-/// A boolean indicating if the function contains any loops.
-fn loops(ast: &syn::ItemFn) -> bool {
-    let mut has_recursion = false;
-    let mut has_unreachable_base_case = false;
-    let mut has_endless_iteration = false;
+/// A `Verdict` carrying the evidence it was reached by,
+/// which is HALT WHEN if the function is only known to halt on the inputs which meet a condition.
+pub fn verdict_from_ast_in(ast: &syn::ItemFn, context: &Context) -> Verdict {
+    decision_from_ast_in(ast, context).verdict
+}
 
-    // Check for recursion
-    for recursion in iter_recursions(ast) {
-        has_recursion = true;
-        if !has_base_case(recursion) {
-            return true;
+/// Decide if the function with the given AST halts, loops, or creates a paradox,
+/// in the context it is defined in, and tell how.
+///
+/// The engines are tried in order, and each LOOP with a witness is replayed in the interpreter,
+/// refining the abstraction the engines work on whenever the witness turns out spurious.
+///
+/// # Arguments
+///
+/// * `ast` - The AST of the function to decide.
+/// * `context` - Where the function is defined, for its `use` aliases and sibling items.
+///
+/// # Returns
+///
+/// A `Decision`: the verdict, as for `verdict_from_ast_in`, the engine which reached it,
+/// and how many rounds of refinement it took.
+pub fn decision_from_ast_in(ast: &syn::ItemFn, context: &Context) -> Decision {
+    decision_from_ast_until(ast, context, &Arc::default())
+}
+
+/// Decide if the function with the given AST halts, loops, or creates a paradox,
+/// in the context it is defined in, and tell how, giving up once a flag is raised.
+///
+/// # Arguments
+///
+/// * `ast` - The AST of the function to decide.
+/// * `context` - Where the function is defined, for its `use` aliases and sibling items.
+/// * `cancelled` - A flag which, once raised, keeps any more rules from being tried,
+///   like when the decision is taking too long.
+///
+/// # Returns
+///
+/// A `Decision`, as for `decision_from_ast_in` if the flag is never raised,
+/// and otherwise whatever the rules tried before it was had found, or UNKNOWN.
+pub fn decision_from_ast_until(
+    ast: &syn::ItemFn,
+    context: &Context,
+    cancelled: &Arc<AtomicBool>,
+) -> Decision {
+    let scope = Scope {
+        cancelled: Arc::clone(cancelled),
+        ..Scope::default()
+    };
+    let decision = decision_from_ast_within(ast, context, &scope);
+    if scope.is_cancelled() {
+        return decision;
+    }
+    decision_from_ast_when(ast, context, decision, &scope)
+}
+
+/// Look for a condition on the parameters of a function under which it halts,
+/// if its decision for every input is a LOOP or inconclusive.
+///
+/// # Arguments
+///
+/// * `ast` - The AST of the function to decide.
+/// * `context` - Where the function is defined, for its `use` aliases and sibling items.
+/// * `decision` - The decision for every input, as from `decision_from_ast_within`.
+/// * `scope` - A flag which, once raised, keeps any more rules from being tried, like when the search takes too long.
+///
+/// # Returns
+///
+/// A HALT WHEN with the decision for every input under it, if a condition was found, and otherwise the decision as it was.
+pub fn decision_from_ast_when(
+    ast: &syn::ItemFn,
+    context: &Context,
+    decision: Decision,
+    scope: &Scope,
+) -> Decision {
+    if !matches!(
+        decision.verdict,
+        Verdict::Loops(_) | Verdict::Unknown(Reason::Inconclusive, _)
+    ) {
+        return decision;
+    }
+    // a function which halts on only some of its inputs may halt on all of those which meet a condition
+    match is_halting_when(ast, context, scope) {
+        Some((precondition, mut evidence)) => {
+            evidence.sub_verdicts.insert(0, decision.verdict);
+            Decision::new(Verdict::HaltsWhen(precondition, evidence), decision.rounds)
         }
-        if is_base_case_unreachable(recursion) {
-            has_unreachable_base_case = true;
+        None => decision,
+    }
+}
+
+/// Decide if the function with the given AST halts, loops, or creates a paradox on every input,
+/// in the context it is defined in, using only some of the engines, and tell how.
+///
+/// # Arguments
+///
+/// * `ast` - The AST of the function to decide.
+/// * `context` - Where the function is defined, for its `use` aliases and sibling items.
+/// * `scope` - The engines which may be used, and a flag which cancels the decision when raised,
+///   after which no more rules are tried.
+///
+/// # Returns
+///
+/// A `Decision`: the verdict, which is UNKNOWN unless the engines alone reach it, the engine which reached it,
+/// and how many rounds of refinement it took. No condition the function halts under is looked for.
+pub fn decision_from_ast_within(ast: &syn::ItemFn, context: &Context, scope: &Scope) -> Decision {
+    is_decided_by_refinement(ast, context, scope)
+}
+
+/// Decide if the function with the given AST halts, loops, or creates a paradox on every input,
+/// in the context it is defined in, without looking for a condition it halts under.
+///
+/// # Arguments
+///
+/// * `ast` - The AST of the function to decide.
+/// * `context` - Where the function is defined, for its `use` aliases and sibling items.
+/// * `refinement` - What spurious witnesses have shown to be wrong with the abstraction so far.
+/// * `scope` - The engines which may be used, until the decision is cancelled.
+///
+/// # Returns
+///
+/// A `Verdict` carrying the evidence it was reached by.
+fn decide(ast: &syn::ItemFn, context: &Context, refinement: &Refinement, scope: &Scope) -> Verdict {
+    // If the function asks halts about itself and inverts the answer, then it's an inversion paradox
+    let (graph, root) = context.graph_with_root(ast);
+    let self_references = ast_contains_halts(&graph.functions[root], &graph.oracles);
+    if !self_references.is_empty() && !scope.allows(Engine::Syntactic) {
+        // without the paradox rules, what the function does is up to the oracle
+        return Verdict::Unknown(Reason::OracleDependent, Evidence::new(Rule::SelfReference));
+    }
+    let mut judge = |stmts| {
+        let mut code_after_answer = ast.clone();
+        code_after_answer.block.stmts = stmts;
+        loops(&code_after_answer, context, refinement, scope)
+    };
+    if let Some(verdict) = verdict_from_self_references(&self_references, &mut judge) {
+        return verdict;
+    }
+
+    // having categorized the paradoxical cases, those which remain are
+    // non-paradoxical (thus hopefully decidable) instances of the binary halting problem
+    loops(ast, context, refinement, scope)
+}
+
+/// Decide if the function with the given AST halts, loops, or creates a paradox on some of its inputs,
+/// in the context it is defined in.
+///
+/// Inputs which can be listed out, like the arguments of one call or a small domain, are run first,
+/// since a run which returns or repeats a state is ground truth.
+/// Otherwise the function is analyzed as if it began by asserting the inputs are what they are,
+/// which tells the analyses about them. A function which calls itself is analyzed as called from a function which asserts it,
+/// since an assertion in the function itself would hold only of the first call.
+/// Inputs which can't be asserted leave only the verdict for every input, which tells of these too if it's a HALT.
+///
+/// # Arguments
+///
+/// * `ast` - The AST of the function to decide.
+/// * `context` - Where the function is defined, for its `use` aliases and sibling items.
+/// * `input` - The arguments of one call, or a domain of them.
+///
+/// # Returns
+///
+/// A `Verdict` for exactly those inputs, carrying the evidence it was reached by.
+pub fn verdict_from_ast_on(ast: &syn::ItemFn, context: &Context, input: &Input) -> Verdict {
+    verdict_from_ast_on_within(ast, context, input, &Scope::default())
+}
+
+/// Decide a function on some of its inputs as for `verdict_from_ast_on`, trying no more rules once the scope is cancelled
+fn verdict_from_ast_on_within(
+    ast: &syn::ItemFn,
+    context: &Context,
+    input: &Input,
+    scope: &Scope,
+) -> Verdict {
+    let (graph, root) = context.graph_with_root(ast);
+    if let Some(inputs) = args_from_input(input, ast, context) {
+        let count = inputs.len();
+        let mut notes = vec![];
+        for args in inputs {
+            let shown = Input::Args(args.clone());
+            match execution_from_graph(&graph, root, args, MAX_STEPS) {
+                execution @ Execution::Repeats { .. } => {
+                    let mut evidence = Evidence::new(Rule::Execution);
+                    evidence.notes.push(format!("on {}, {}", shown, execution));
+                    return Verdict::Loops(evidence);
+                }
+                execution @ Execution::Returns { .. } => {
+                    notes.push(format!("on {}, {}", shown, execution));
+                }
+                _ => {
+                    notes.clear();
+                    break;
+                }
+            }
+        }
+        if !notes.is_empty() || count == 0 {
+            let mut evidence = Evidence::new(Rule::Execution);
+            if count == 1 {
+                evidence.notes = notes;
+            } else {
+                evidence
+                    .notes
+                    .push(format!("returns on each of the {} inputs", count));
+            }
+            return Verdict::Halts(evidence);
         }
     }
 
-    // Check for iteration
-    for iteration in iter_iterations(ast) {
-        if is_endless_loop(iteration) {
-            has_endless_iteration = true;
+    let is_recursive = iter_recursions(&graph, root)
+        .iter()
+        .any(|recursion| recursion.members.contains(&root));
+    match condition_from_input(input, ast) {
+        Some(condition) => {
+            let mut verdict = if is_recursive {
+                let (entry, context) = entry_from_ast(ast, context, &condition);
+                is_decided_by_refinement(&entry, &context, scope).verdict
+            } else {
+                let mut assumed = ast.clone();
+                let assertion: syn::Stmt = syn::parse_quote!(assert!(#condition););
+                assumed.block.stmts.insert(0, assertion);
+                is_decided_by_refinement(&assumed, context, scope).verdict
+            };
+            verdict.evidence_mut().notes.push(format!("for {}", input));
+            verdict
         }
+        None if input.is_every_input() => verdict_from_ast_in(ast, context),
+        _ => match is_decided_by_refinement(ast, context, scope).verdict {
+            verdict @ (Verdict::Halts(_) | Verdict::Paradox(..)) => verdict,
+            verdict => {
+                let mut evidence = verdict.evidence().clone();
+                evidence.notes.push(format!(
+                    "the verdict for every input tells nothing of {}",
+                    input
+                ));
+                Verdict::Unknown(Reason::Inconclusive, evidence)
+            }
+        },
     }
+}
 
-    // If there is recursion with an unreachable base case or endless iteration,
-    // then the function is loopy
-    has_recursion && has_unreachable_base_case || has_endless_iteration
+/// A function which asserts a condition on the parameters of another, then calls it with them.
+///
+/// # Arguments
+///
+/// * `ast` - The AST of the function to call, whose parameters are all names.
+/// * `context` - Where the function is defined.
+/// * `condition` - The condition to assert.
+///
+/// # Returns
+///
+/// The AST of the calling function, and its context, written beside the function to call as it is given.
+fn entry_from_ast(
+    ast: &syn::ItemFn,
+    context: &Context,
+    condition: &syn::Expr,
+) -> (syn::ItemFn, Context) {
+    let (graph, root) = context.graph_with_root(ast);
+    let name = &ast.sig.ident;
+    let params = ast.sig.inputs.iter().filter_map(|input| match input {
+        syn::FnArg::Typed(param) => Some(&param.pat),
+        syn::FnArg::Receiver(_) => None,
+    });
+    let callee = match graph.functions[root].owner {
+        Some(_) => quote!(Self::#name),
+        None => quote!(#name),
+    };
+    let mut entry = ast.clone();
+    entry.sig.ident = quote::format_ident!("entry_to_{}", name);
+    entry.sig.output = syn::ReturnType::Default;
+    entry.block = syn::parse_quote!({
+        assert!(#condition);
+        #callee(#(#params),*);
+    });
+    let function = &graph.functions[root];
+    let path: Vec<String> = function
+        .module
+        .iter()
+        .chain(&function.path)
+        .cloned()
+        .collect();
+    let context = Context {
+        module: function.module.clone(),
+        path: Some(path),
+        graph: Rc::new(graph.clone()),
+    }
+    .beside(&entry.sig.ident.to_string());
+    (entry, context)
 }
 
-/// Get all recursive functions within a function.
+/// Find the calls to `halts` in the AST of a function which ask about the function itself.
 ///
 /// # Arguments
 ///
-/// * `ast` - The AST of the function from which to iterate recursions.
+/// * `function` - The function to check, with the path it is found at in its file.
+/// * `oracles` - The names `halts` goes by, including `use` aliases and re-exports.
 ///
 /// # Returns
 ///
-/// A vector of recursive functions within the given function.
-fn iter_recursions(ast: &syn::ItemFn) -> Vec<&syn::Expr> {
-    let mut visited = collections::HashSet::new();
-    let mut recursions = Vec::<&syn::Expr>::new();
-
-    let sig = &ast.sig;
-    let ident = &sig.ident;
-
-    // Check if the function calls itself directly
-    if let Some(syn::Stmt::Expr(syn::Expr::Call(call_expr), _)) = ast.block.stmts.last() {
-        if let syn::Expr::Path(path_expr) = &*call_expr.func {
-            if path_expr.path.segments.len() == 1 && path_expr.path.segments[0].ident == *ident {
-                for pair in call_expr.args.pairs() {
-                    match pair {
-                        syn::punctuated::Pair::Punctuated(t, _) => recursions.push(t),
-                        syn::punctuated::Pair::End(_) => continue,
-                    }
+/// Each call to `halts` about the function, with the `match`, `if` or `while` on its answer, if any.
+fn ast_contains_halts<'ast>(
+    function: &'ast FunctionNode,
+    oracles: &OracleNames,
+) -> Vec<SelfReference<'ast>> {
+    find_self_references(function, oracles)
+}
+
+/// Determine if a function loops.
+///
+/// # Arguments
+///
+/// * `ast` - The AST of the function to check for loops.
+/// * `context` - Where the function is defined, to follow its calls.
+/// * `refinement` - What spurious witnesses have shown to be wrong with the abstraction so far:
+///   claims of LOOP they refuted count for nothing, leaving the construct to the next rule.
+/// * `scope` - The engines whose rules may be tried, until the decision is cancelled.
+///
+/// # Returns
+///
+/// A LOOP verdict if any recursion or iteration within the function, or reachable from it, loops,
+/// UNKNOWN if some recursion could not be decided, otherwise HALT.
+/// Either way, the verdicts for each recursion and iteration are kept as sub-verdicts.
+fn loops(ast: &syn::ItemFn, context: &Context, refinement: &Refinement, scope: &Scope) -> Verdict {
+    let (graph, root) = context.graph_with_root(ast);
+    let mut sub_verdicts = vec![];
+
+    // Check for recursion
+    for recursion in iter_recursions(&graph, root) {
+        let mut evidence = Evidence::new(Rule::MissingBaseCase);
+        evidence
+            .spans
+            .extend(recursion.call_sites.iter().map(|(_, call)| call.span));
+        evidence.notes.push(format!(
+            "{} recursion: {}",
+            recursion.kind,
+            graph.describe_cycle(&recursion.cycle)
+        ));
+        // a call told by a method's name alone may be to another type's method, so can't show a LOOP
+        let is_by_name = recursion.is_by_name();
+        if is_by_name {
+            evidence
+                .notes
+                .push("a method call in the recursion was told by its name alone".to_string());
+        }
+        if scope.allows(Engine::Syntactic) && !has_base_case(&recursion, &graph) {
+            if is_by_name {
+                sub_verdicts.push(Verdict::Unknown(Reason::Inconclusive, evidence));
+                continue;
+            }
+            match refinement.refute(Verdict::Loops(evidence)) {
+                verdict @ Verdict::Loops(_) => return verdict,
+                refuted => {
+                    sub_verdicts.push(refuted);
+                    continue;
                 }
             }
         }
+        for (member, base_case) in find_base_cases(&recursion, &graph).unwrap_or_default() {
+            evidence.notes.push(format!(
+                "base case of {} when {}",
+                graph.functions[member].name(),
+                base_case.condition()
+            ));
+        }
+        let shared = match scope.run(Engine::Syntactic, || {
+            is_structural_recursion(&recursion, &graph)
+        }) {
+            Some(Structure::Descends(why)) => {
+                evidence.rule = Rule::StructuralRecursion;
+                evidence.notes.push(why);
+                sub_verdicts.push(Verdict::Halts(evidence));
+                continue;
+            }
+            Some(Structure::Shared(why)) => Some(why),
+            Some(Structure::NotStructural) | None => None,
+        };
+        let reachability = scope.run(Engine::AbstractInterpretation, || {
+            is_base_case_unreachable(&recursion, &graph, root)
+        });
+        sub_verdicts.push(match reachability {
+            Some(Reachability::Unreachable(why)) => {
+                evidence.rule = Rule::UnreachableBaseCase;
+                evidence.notes.push(why);
+                if is_by_name {
+                    Verdict::Unknown(Reason::Inconclusive, evidence)
+                } else {
+                    refinement.refute(Verdict::Loops(evidence))
+                }
+            }
+            Some(Reachability::Reachable(why)) => {
+                evidence.rule = Rule::ReachableBaseCase;
+                evidence.notes.push(why);
+                Verdict::Halts(evidence)
+            }
+            reachability => match scope.run(Engine::AbstractInterpretation, || {
+                is_size_change_terminating(&recursion, &graph)
+            }) {
+                Some(SizeChange::Terminates(proof)) => {
+                    evidence.rule = Rule::SizeChange;
+                    evidence.notes.push(proof);
+                    Verdict::Halts(evidence)
+                }
+                size_change => {
+                    evidence.rule = Rule::ReachableBaseCase;
+                    if let Some(Reachability::Unknown(why)) = reachability {
+                        evidence.notes.push(why);
+                    }
+                    if let Some(SizeChange::Cycle(cycle) | SizeChange::Unknown(cycle)) = size_change
+                    {
+                        evidence.notes.push(cycle);
+                    }
+                    evidence.notes.extend(shared);
+                    Verdict::Unknown(Reason::Inconclusive, evidence)
+                }
+            },
+        });
     }
 
-    // Recursively traverse the AST to find indirect recursion
-    for stmt in &ast.block.stmts {
-        if let syn::Stmt::Expr(syn::Expr::Call(call_expr), _) = stmt {
-            if let syn::Expr::Path(path_expr) = &*call_expr.func {
-                let path = &path_expr.path;
-                if let Some(last_segment) = path.segments.last() {
-                    let ident = &last_segment.ident;
-                    if !visited.contains(ident) {
-                        visited.insert(ident.clone());
-                        if let Some(called_fn) = find_fn_by_path(&ast.block, path) {
-                            recursions.extend(iter_recursions(called_fn));
-                        }
-                    }
+    // Check for iteration, in the function and in every function it calls
+    for function in graph.reachable_from(root) {
+        let body = refinement.body_from_ast(&graph.functions[function].ast);
+        for iteration in iter_iterations(&body) {
+            let is_endless =
+                scope.allows(Engine::AbstractInterpretation) && is_endless_loop(&body, &iteration);
+            // interval analysis can't tell when conditions contradict each other, but the path conditions can,
+            // which without it are the only way to tell of a loop
+            let is_checked = is_endless || !scope.engines.contains(&Engine::AbstractInterpretation);
+            if is_checked
+                && scope.allows(Engine::SymbolicExecution)
+                && is_unreachable(&body, &iteration)
+            {
+                let mut evidence = Evidence::at(Rule::SymbolicExecution, iteration.span);
+                evidence
+                    .notes
+                    .push("no path from the entry of the function reaches the loop".to_string());
+                sub_verdicts.push(Verdict::Halts(evidence));
+                continue;
+            }
+            if is_endless {
+                // a claim a witness refuted leaves the loop to the rules after
+                let claim = Verdict::Loops(Evidence::at(Rule::EndlessIteration, iteration.span));
+                if let claim @ Verdict::Loops(_) = refinement.refute(claim) {
+                    sub_verdicts.push(claim);
+                    continue;
                 }
             }
+            // a loop which can be left still needs a reason to believe it will be
+            let mut evidence = Evidence::at(Rule::RankingFunction, iteration.span);
+            let Some(ranking) = scope.run(Engine::RankingSynthesis, || {
+                ranking_function(&body, &iteration)
+            }) else {
+                evidence
+                    .notes
+                    .push("no ranking function was looked for".to_string());
+                sub_verdicts.push(Verdict::Unknown(Reason::Inconclusive, evidence));
+                continue;
+            };
+            sub_verdicts.push(match ranking {
+                Some(ranking) => {
+                    evidence.notes.push(format!(
+                        "{} ranking function {}",
+                        ranking.kind,
+                        ranking.in_source(&body)
+                    ));
+                    Verdict::Halts(evidence)
+                }
+                // a loop in the function itself can be run up to, to show it's caught for good
+                None => match (function == root)
+                    .then(|| is_recurrent(&body, &iteration))
+                    .flatten()
+                {
+                    Some(lasso) => {
+                        evidence.rule = Rule::RecurrentSet;
+                        evidence.notes.push(lasso.to_string());
+                        evidence.lasso = Some(lasso);
+                        refinement.refute(Verdict::Loops(evidence))
+                    }
+                    None => {
+                        evidence.notes.push(
+                            "no linear, lexicographic or multiphase ranking function was found"
+                                .to_string(),
+                        );
+                        Verdict::Unknown(Reason::Inconclusive, evidence)
+                    }
+                },
+            });
         }
     }
-    recursions
-}
 
-/// helper function to locate a function given its syn::Path
-fn find_fn_by_path<'a>(block: &'a syn::Block, path: &syn::Path) -> Option<&'a syn::ItemFn> {
-    for stmt in &block.stmts {
-        if let syn::Stmt::Item(syn::Item::Fn(fn_item)) = stmt {
-            if fn_item.sig.ident == path.segments.last()?.ident {
-                return Some(fn_item);
+    // If there is recursion with an unreachable base case or endless iteration,
+    // then the function is loopy
+    let looping = sub_verdicts.iter().find(|v| matches!(v, Verdict::Loops(_)));
+    let unknown = sub_verdicts.iter().find(|v| !v.is_conclusive());
+    let verdict = match (looping, unknown) {
+        (Some(looping), _) => {
+            let mut evidence = looping.evidence().clone();
+            evidence.sub_verdicts = sub_verdicts;
+            Verdict::Loops(evidence)
+        }
+        (None, Some(unknown)) => {
+            // a function without parameters does the same every time, so running it can tell
+            let execution = ast
+                .sig
+                .inputs
+                .is_empty()
+                .then(|| scope.run(Engine::Interpreter, || is_decided_by_running(&graph, root)))
+                .flatten();
+            let mut evidence = match &execution {
+                Some(Execution::Returns { .. } | Execution::Repeats { .. }) => {
+                    Evidence::new(Rule::Execution)
+                }
+                _ => unknown.evidence().clone(),
+            };
+            evidence.sub_verdicts = sub_verdicts;
+            match execution {
+                Some(execution @ Execution::Returns { .. }) => {
+                    evidence.notes.push(execution.to_string());
+                    Verdict::Halts(evidence)
+                }
+                Some(execution @ Execution::Repeats { .. }) => {
+                    evidence.notes.push(execution.to_string());
+                    Verdict::Loops(evidence)
+                }
+                _ => Verdict::Unknown(Reason::Inconclusive, evidence),
             }
         }
+        // the absence of looping constructs is itself a syntactic rule
+        (None, None) if sub_verdicts.is_empty() && !scope.allows(Engine::Syntactic) => {
+            Verdict::Unknown(
+                Reason::Inconclusive,
+                Evidence::new(Rule::NoLoopingConstructs),
+            )
+        }
+        (None, None) => {
+            let rule = match sub_verdicts.first() {
+                Some(halting) => halting.evidence().rule,
+                None => Rule::NoLoopingConstructs,
+            };
+            let mut evidence = Evidence::new(rule);
+            evidence.sub_verdicts = sub_verdicts;
+            Verdict::Halts(evidence)
+        }
+    };
+
+    // a call into the crate which isn't among its functions may do anything, so nothing which makes it halts for sure
+    let unresolved = graph.unresolved_from(root);
+    match verdict {
+        Verdict::Halts(mut evidence) if !unresolved.is_empty() => {
+            for (path, span) in unresolved {
+                evidence.spans.push(*span);
+                evidence.notes.push(format!(
+                    "calls `{}`, which is not among the functions of the crate",
+                    path
+                ));
+            }
+            Verdict::Unknown(Reason::Inconclusive, evidence)
+        }
+        verdict => verdict,
     }
-    None
+}
+
+/// Get all recursion reachable from a function.
+///
+/// # Arguments
+///
+/// * `graph` - The call graph the function is part of.
+/// * `root` - The index of the function in the call graph.
+///
+/// # Returns
+///
+/// The recursive strongly connected components of the call graph which the function reaches,
+/// including any it is itself part of.
+fn iter_recursions(graph: &CallGraph, root: usize) -> Vec<Recursion> {
+    graph.recursions_reachable_from(root)
 }
 
 /// Get all iterative functions within a function.
 ///
 /// # Arguments
 ///
-/// * `ast` - The AST of the function from which to iterate iterations.
+/// * `body` - The control-flow graph of the function from which to iterate iterations.
 ///
 /// # Returns
 ///
-/// A vector of iterative functions within the given function.
-fn iter_iterations(ast: &syn::ItemFn) -> Vec<&syn::Expr> {
-    // TODO: Implement iteration detection
-    vec![]
+/// Every loop of the function, outermost first, including nested and labeled ones.
+/// A `loop`, `while` or `for` which never gets back to its start, like `loop { break; }`, is no loop here.
+fn iter_iterations(body: &Body) -> Vec<Loop> {
+    body.loop_forest().loops
 }
 
 /// Determine if a function has a base case.
 /// Recursion with no base case is a LOOP (modulo stack overflow)
 /// (modulo stack overflow without tail loop optimization)
 ///
+/// A base case is a path through a member of the recursion which makes no recursive call.
+///
 /// # Arguments
 ///
 /// * `recursion` - An instance of recursion to check for a nonexistent base case.
+/// * `graph` - The call graph the recursion is part of.
 ///
 /// # Returns
 ///
 /// A boolean indicating if the function has a base case.
-fn has_base_case(recursion: &syn::Expr) -> bool {
-    // TODO: Implement base case detection
-    false
+fn has_base_case(recursion: &Recursion, graph: &CallGraph) -> bool {
+    // if the paths can't all be followed, a base case may be among those which weren't
+    find_base_cases(recursion, graph).is_none_or(|base_cases| !base_cases.is_empty())
 }
 
 /// Determine if a base case is unreachable within a function.
@@ -237,33 +843,195 @@ fn has_base_case(recursion: &syn::Expr) -> bool {
 /// # Arguments
 ///
 /// * `recursion` - An instance of recursion to check for an unreachable base case.
+/// * `graph` - The call graph the recursion is part of.
+/// * `root` - The function under analysis, whose calls into the recursion give its arguments.
+///
+/// # Returns
+///
+/// Whether the base case is reached, never reached, or neither could be shown, and why.
+fn is_base_case_unreachable(recursion: &Recursion, graph: &CallGraph, root: usize) -> Reachability {
+    reachability(recursion, graph, root)
+}
+
+/// Determine if a recursion only recurses on parts destructured from an argument, like a tree walker,
+/// which must reach the bottom of an owned tree, or of data which may be shared and cyclic, which need not.
+///
+/// # Arguments
+///
+/// * `recursion` - An instance of recursion to check for structural descent.
+/// * `graph` - The call graph the recursion is part of, with the crate's type definitions.
+///
+/// # Returns
+///
+/// Whether the recursion descends an owned tree, descends shared data, or isn't structural, and why.
+fn is_structural_recursion(recursion: &Recursion, graph: &CallGraph) -> Structure {
+    structural_recursion(recursion, graph)
+}
+
+/// Determine if a recursion ends whatever its arguments, by the size-change principle:
+/// if some parameter shrinks around every cycle of recursive calls, no chain of calls goes on forever.
+/// Unlike a measure, this needs no one quantity to shrink at every call, and it sees values as well as integers
+/// shrink, like `tail` out of `list` or `&xs[1..]` out of `xs`.
+///
+/// # Arguments
+///
+/// * `recursion` - An instance of recursion to check for termination.
+/// * `graph` - The call graph the recursion is part of.
 ///
 /// # Returns
 ///
-/// A boolean indicating if the base case is unreachable within the function.
-fn is_base_case_unreachable(recursion: &syn::Expr) -> bool {
-    // TODO: Implement unreachable base case detection
-    false
+/// That the recursion ends, or the cycle of calls after which nothing is sure to have shrunk, and why.
+fn is_size_change_terminating(recursion: &Recursion, graph: &CallGraph) -> SizeChange {
+    size_change(recursion, graph)
+}
+
+/// Determine if a function without parameters halts or loops by running it.
+/// A function which returns is a HALT, and one which comes back to exactly a state it was in before is a LOOP.
+///
+/// The run follows the function and everything it calls, step by step, comparing the whole state,
+/// every local of every call, at the head of each loop, so a repetition is certain, not a guess.
+/// It stops without telling if it runs into what the interpreter can't run,
+/// like a function of the standard library it doesn't know, or if it takes more than `MAX_STEPS` steps.
+///
+/// # Arguments
+///
+/// * `graph` - The call graph the function is part of.
+/// * `root` - The index of the function in the call graph.
+///
+/// # Returns
+///
+/// How the run ended.
+fn is_decided_by_running(graph: &CallGraph, root: usize) -> Execution {
+    execution_from_graph(graph, root, vec![], MAX_STEPS)
+}
+
+/// Decide a function by its engines, tried in order, and refine the abstraction they work on
+/// whenever a LOOP comes with a witness the interpreter shows to be spurious.
+///
+/// The engines are syntactic checks, abstract interpretation, ranking synthesis, symbolic execution and the interpreter.
+/// All but the last take integers to be mathematical and calls to return, so a LOOP they find may not be one:
+/// `while x > 0 { x += 1 }` on an `i8` panics at 127. Replaying the input the LOOP says loops, like the start of its lasso,
+/// tells: a run which returns or panics refutes it. If it overflowed, integers are taken to be as wide as their types,
+/// and otherwise the claim is dropped, and the engines are tried again.
+///
+/// # Arguments
+///
+/// * `ast` - The AST of the function.
+/// * `context` - Where the function is defined.
+/// * `scope` - The engines which may be used, until the decision is cancelled.
+///
+/// # Returns
+///
+/// The verdict which stood, the engine which reached it, and how many rounds of refinement it took.
+fn is_decided_by_refinement(ast: &syn::ItemFn, context: &Context, scope: &Scope) -> Decision {
+    decision_from_ast(ast, context, &mut |refinement| {
+        decide(ast, context, refinement, scope)
+    })
+}
+
+/// Determine a condition on the integer parameters of a function under which it halts.
+/// A function which halts on every input which meets the condition is a HALT WHEN.
+///
+/// The conditions tried bound a parameter by a constant of the function, like `n >= 0`,
+/// and the function is decided for the inputs which meet each, as for `verdict_from_ast_on`.
+/// Each is decided under the refinement driver, so a claim of LOOP for the inputs which fail them
+/// makes the condition needed as well as sufficient only if replaying its witness didn't refute it.
+///
+/// # Arguments
+///
+/// * `ast` - The AST of the function.
+/// * `context` - Where the function is defined.
+/// * `scope` - A flag which, once raised, keeps any more rules from being tried, so no more conditions are found.
+///
+/// # Returns
+///
+/// The condition and the evidence for it, or `None` if no condition was found.
+fn is_halting_when(
+    ast: &syn::ItemFn,
+    context: &Context,
+    scope: &Scope,
+) -> Option<(Precondition, Evidence)> {
+    precondition_from_ast(ast, &mut |input| {
+        verdict_from_ast_on_within(ast, context, input, scope)
+    })
+}
+
+/// Determine if a function runs into a loop and is then caught in it for good.
+/// Iteration which never leaves a recurrent set once it gets in is a LOOP.
+///
+/// A recurrent set is a set of states at the head of the loop which no exit can be taken from,
+/// and which every way round the loop leads back into, like `x <= -1` for `while x != 0 { x -= 2 }`.
+/// Running the function from its start on concrete values shows it gets in, like `x = 7` stepping to `x = -1`,
+/// with values for its parameters found by symbolic execution up to the loop if it takes any,
+/// and nothing in the loop may panic unseen, like a call, indexing or division by a variable.
+///
+/// # Arguments
+///
+/// * `body` - The control-flow graph of the function, which is run from its start.
+/// * `iteration` - An instance of iteration to check for a recurrent set.
+///
+/// # Returns
+///
+/// The lasso which witnesses the loop: the run into the loop, and the recurrent set it reaches,
+/// or `None` if none was found.
+fn is_recurrent(body: &Body, iteration: &Loop) -> Option<Lasso> {
+    lasso_from_loop(body, iteration)
 }
 
 /// Determine if an iteration contains an endless loop.
 /// Iteration with an infinite loop is, unsurprisingly, a LOOP.
 ///
+/// A loop is endless if it is reached and no edge of the control-flow graph which leaves it can be taken:
+/// it never stops on its own, like `loop`, `while true` or `for` over an infinite iterator,
+/// or its condition always holds, like `while i >= 0 { i += 1 }` from `i = 0`,
+/// and nothing in it can leave it: no `break` to it or an enclosing loop, no `return`, no `?`,
+/// no panic and no `process::exit`. Which edges can be taken is told by interval analysis.
+/// Calls to other functions are assumed to return, and integers never to overflow.
+///
 /// # Arguments
 ///
+/// * `body` - The control-flow graph of the function the iteration is part of.
 /// * `iteration` - An instance of iteration to check for an infinite loop.
 ///
 /// # Returns
 ///
 /// A boolean indicating if the function contains an endless loop.
-fn is_endless_loop(iteration: &syn::Expr) -> bool {
-    // TODO: Implement endless loop detection
-    false
+fn is_endless_loop(body: &Body, iteration: &Loop) -> bool {
+    let intervals = intervals_from_body(body);
+    intervals.is_reached(iteration.header)
+        && iteration
+            .exits(body)
+            .into_iter()
+            .all(|(from, to)| !intervals.is_taken(from, to))
+}
+
+/// Determine if no run of a function ever reaches a loop.
+/// A loop nothing reaches doesn't make its function LOOP, however endless it is.
+///
+/// Symbolic execution follows every path from the entry of the function, dropping a path as soon as
+/// the Omega test shows its conditions contradict each other, like `x > y` and then `y > x`.
+/// If no path is left which reaches the head of the loop, and none was cut off for being too long, it is never reached.
+///
+/// # Arguments
+///
+/// * `body` - The control-flow graph of the function the iteration is part of.
+/// * `iteration` - An instance of iteration to check for a path to it.
+///
+/// # Returns
+///
+/// A boolean indicating if the loop is never reached.
+fn is_unreachable(body: &Body, iteration: &Loop) -> bool {
+    let exploration = paths_to_block(body, iteration.header, 1);
+    exploration.is_complete && exploration.arrivals.is_empty()
 }
 
 /// tests for the ternary halting problem
 mod test {
     use super::*;
+    #[cfg(test)]
+    use crate::input::Domain;
+    #[cfg(test)]
+    use crate::ir::body_from_ast;
 
     // ------ Begin Cases ------
     #[allow(dead_code)]
@@ -313,6 +1081,92 @@ mod test {
         unit();
     }
 
+    // Ackermann's function, which does stop, though no one argument shrinks at every call
+
+    #[allow(dead_code)]
+    /// Ackermann's function, whose second argument grows whenever its first shrinks
+    fn ackermann(m: u64, n: u64) -> u64 {
+        if m == 0 {
+            return n + 1;
+        }
+        if n == 0 {
+            return ackermann(m - 1, 1);
+        }
+        ackermann(m - 1, ackermann(m, n - 1))
+    }
+
+    #[allow(dead_code)]
+    /// Ackermann's function of two and three
+    fn ackermann_two_three() {
+        ackermann(2, 3);
+    }
+
+    // Recursion over Values, which Shrink
+
+    #[allow(dead_code)]
+    /// A linked list, whose tail is in a box
+    enum List {
+        /// An item, and the rest of the list
+        Cons(u32, Box<List>),
+        /// The empty list
+        Nil,
+    }
+
+    #[allow(dead_code)]
+    /// The length of a linked list
+    fn length(list: &List) -> u32 {
+        match list {
+            List::Cons(_, tail) => 1 + length(tail),
+            List::Nil => 0,
+        }
+    }
+
+    #[allow(dead_code)]
+    /// The length of a list of two
+    fn length_of_pair() {
+        let list = List::Cons(1, Box::new(List::Cons(2, Box::new(List::Nil))));
+        length(&list);
+    }
+
+    #[allow(dead_code)]
+    /// A linked list whose tail may be shared with other lists
+    struct SharedList {
+        /// The rest of the list, if any
+        next: Option<std::rc::Rc<SharedList>>,
+    }
+
+    #[allow(dead_code)]
+    /// The length of a list which may share its tail
+    fn shared_length(list: &SharedList) -> u32 {
+        match &list.next {
+            Some(next) => 1 + shared_length(next),
+            None => 1,
+        }
+    }
+
+    #[allow(dead_code)]
+    /// The length of a shared list of one
+    fn shared_length_of_one() {
+        let list = SharedList { next: None };
+        shared_length(&list);
+    }
+
+    #[allow(dead_code)]
+    /// The sum of a slice, from its first element on
+    fn sum(xs: &[u32]) -> u32 {
+        if xs.is_empty() {
+            return 0;
+        }
+        xs[0] + sum(&xs[1..])
+    }
+
+    #[allow(dead_code)]
+    /// The sum of three numbers
+    fn sum_of_three() {
+        let xs = [1, 2, 3];
+        sum(&xs);
+    }
+
     // Recursive Cycle, which does not stop
 
     #[allow(dead_code)]
@@ -333,38 +1187,440 @@ mod test {
         recursive_cycle_a();
     }
 
+    // Recursion with a base case, which is reached counting down but not counting up
+
+    #[allow(dead_code)]
+    /// Counts down to zero, one call at a time
+    fn count_down(n: u32) -> u32 {
+        if n == 0 {
+            return 0;
+        }
+        count_down(n - 1)
+    }
+
+    #[allow(dead_code)]
+    /// Counts down from three
+    fn count_down_from_three() {
+        count_down(3);
+    }
+
+    #[allow(dead_code)]
+    /// Counts up, away from its base case
+    fn count_up(n: u32) -> u32 {
+        if n == 0 {
+            return 0;
+        }
+        count_up(n + 1)
+    }
+
+    #[allow(dead_code)]
+    /// Counts up from one, never getting back to zero
+    fn count_up_from_one() {
+        count_up(1);
+    }
+
+    // Iteration which ends because it counts up to a bound, and iteration which may not
+
+    #[allow(dead_code)]
+    /// Counts up to the length of an array
+    fn count_to_len() {
+        let v = [1, 2, 3];
+        let n = v.len();
+        let mut i = 0;
+        while i < n {
+            i += 1;
+        }
+    }
+
+    #[allow(dead_code)]
+    /// Steps down by two until it hits zero, which it never does from an odd start
+    fn step_to_zero() {
+        let mut x = 7;
+        while x != 0 {
+            x -= 2;
+        }
+    }
+
+    #[allow(dead_code)]
+    /// Steps down to zero by at most two, which halts, though the call hides how far each step goes
+    fn step_to_zero_by_min() {
+        let mut x: i64 = 7;
+        while x != 0 {
+            x -= std::cmp::min(x, 2);
+        }
+    }
+
+    #[allow(dead_code)]
+    /// Steps past its target by twos, for longer than it could be run for
+    fn step_past_a_million() {
+        let mut x: u64 = 0;
+        while x != 1_000_001 {
+            x += 2;
+        }
+    }
+
+    #[allow(dead_code)]
+    /// Doubles modulo seven from one, going round 1, 2, 4 without ever reaching zero
+    fn double_mod_seven() {
+        let mut x = 1;
+        while x != 0 {
+            x = (x * 2) % 7;
+        }
+    }
+
+    #[allow(dead_code)]
+    /// The Collatz sequence from 27, which reaches one after 111 steps
+    fn collatz_from_27() {
+        let mut n: u64 = 27;
+        while n != 1 {
+            n = if n % 2 == 1 { 3 * n + 1 } else { n / 2 };
+        }
+    }
+
+    #[allow(dead_code)]
+    /// Doubles modulo seven from `n`, which reaches zero only from a multiple of seven
+    fn double_mod_seven_from(n: i64) {
+        let mut x = n;
+        while x != 0 {
+            x = (x * 2) % 7;
+        }
+    }
+
+    #[allow(dead_code)]
+    /// Counts down from `n` to zero, which it reaches only from `n >= 0`
+    fn count_down_from(n: i64) {
+        let mut x = n;
+        while x != 0 {
+            x -= 1;
+        }
+    }
+
+    #[allow(dead_code)]
+    /// Doubles `n` while it is above one, which halts from `n <= 1`, and otherwise overflows and panics
+    fn double_past_one(n: u64) {
+        let mut x = n;
+        while x > 1 {
+            x *= 2;
+        }
+    }
+
+    #[allow(dead_code)]
+    /// Counts down from `n` to zero by calling itself, which it reaches only from `n >= 0`
+    fn count_down_by_recursion(n: i64) -> i64 {
+        if n == 0 {
+            0
+        } else {
+            count_down_by_recursion(n - 1)
+        }
+    }
+
+    #[allow(dead_code)]
+    /// Loops forever only when `x` and `y` are each greater than the other, which they never are
+    fn loop_when_apart(x: i64, y: i64) {
+        if x > y && y > x {
+            loop {
+                println!("Looping forever!")
+            }
+        }
+    }
+
+    #[allow(dead_code)]
+    /// Counts up from one while positive, which would be forever, but an `i8` overflows at 127
+    fn count_up_in_i8() {
+        let mut x: i8 = 1;
+        while x > 0 {
+            x += 1;
+        }
+    }
+
+    #[allow(dead_code)]
+    /// A wrapper which sorts, truncates and trims through what it wraps, under the same names
+    pub struct Wrapper {
+        /// The values it sorts and truncates
+        values: Vec<i64>,
+        /// The text it trims
+        text: String,
+    }
+
+    #[allow(dead_code)]
+    impl Wrapper {
+        /// Sorts the values, by `Vec::sort`
+        pub fn sort(&mut self) {
+            self.values.sort()
+        }
+
+        /// Keeps the first values, by `Vec::truncate`
+        pub fn truncate(&mut self, len: usize) {
+            self.values.truncate(len)
+        }
+
+        /// The text without whitespace around it, by `str::trim`
+        pub fn trim(&self) -> &str {
+            self.text.trim()
+        }
+
+        /// The text in upper case, by `str::to_uppercase` on what a call returns, whose type isn't told
+        pub fn to_uppercase(&self) -> String {
+            self.text_of().to_uppercase()
+        }
+
+        /// The text
+        fn text_of(&self) -> &str {
+            &self.text
+        }
+    }
+
+    /// Functions which call a function of the standard library they import
+    pub mod imports {
+        use std::cmp::max;
+
+        /// The larger of two numbers, by `std::cmp::max`
+        pub fn larger(a: i64, b: i64) -> i64 {
+            max(a, b)
+        }
+    }
+
+    #[allow(dead_code)]
+    /// The larger of two numbers, named like the function of the standard library it ends up calling
+    pub fn max(a: i64, b: i64) -> i64 {
+        imports::larger(a, b)
+    }
+
     // ------ End Cases, Begin Tests -------
 
+    #[allow(dead_code)]
+    /// The recursion reachable from a function, in the context of the file which defines it
+    fn recursions_from_function<F: Fn()>(function: F) -> Vec<Recursion> {
+        let (ast, context) = source_from_function(function).unwrap();
+        let (graph, root) = context.graph_with_root(&ast);
+        iter_recursions(&graph, root)
+    }
+
     #[test]
-    /// Test the `iter_recursions` function correctly traverses the AST to identify instances of recursion
+    /// Test the `iter_recursions` function correctly traverses the call graph to identify instances of recursion
     fn test_iter_recursions() {
-        let ast = ast_from_function(|| {}).unwrap();
-        let recursions = iter_recursions(&ast);
+        let recursions = recursions_from_function(|| {});
         assert_eq!(recursions.len(), 0);
 
-        let ast = ast_from_function(recurse_unconditionally).unwrap();
-        let recursions = iter_recursions(&ast);
+        let recursions = recursions_from_function(recurse_unconditionally);
         assert_eq!(recursions.len(), 1);
 
-        let ast = ast_from_function(g).unwrap();
-        let recursions = iter_recursions(&ast);
-        assert_eq!(recursions.len(), 1);
+        // `g` passes itself to `halts`, which analyzes `g` rather than calling it, so it is no recursion;
+        // the paradox is found by `ast_contains_halts` instead, see `test_g_is_inversion_paradox`
+        let recursions = recursions_from_function(g);
+        assert_eq!(recursions.len(), 0);
 
-        let ast = ast_from_function(loop_forever).unwrap();
-        let recursions = iter_recursions(&ast);
+        let recursions = recursions_from_function(loop_forever);
         assert_eq!(recursions.len(), 0);
 
-        let ast = ast_from_function(unit).unwrap();
-        let recursions = iter_recursions(&ast);
+        let recursions = recursions_from_function(unit);
         assert_eq!(recursions.len(), 0);
 
-        let ast = ast_from_function(recursive_chain_start).unwrap();
-        let recursions = iter_recursions(&ast);
-        assert_eq!(recursions.len(), 3);
+        // a chain of calls which ends is no recursion, however long it is; see `test_recursive_chain`
+        let recursions = recursions_from_function(recursive_chain_start);
+        assert_eq!(recursions.len(), 0);
 
-        let ast = ast_from_function(recursive_chain_end).unwrap();
-        let recursions = iter_recursions(&ast);
+        let recursions = recursions_from_function(recursive_chain_end);
         assert_eq!(recursions.len(), 0);
+
+        let recursions = recursions_from_function(recursive_cycle_a);
+        assert_eq!(recursions.len(), 1);
+        assert_eq!(recursions[0].kind, crate::call_graph::RecursionKind::Cyclic);
+        assert_eq!(recursions[0].cycle.len(), 4);
+    }
+
+    /// Test that all three steps of a chain of calls are followed, though none of them recurse.
+    /// `iter_recursions` used to count each step of the chain as a recursion, 3 for `recursive_chain_start`,
+    /// where now it counts only cycles in the call graph, and the steps are what the call graph reaches.
+    #[test]
+    fn test_recursive_chain() {
+        let (ast, context) = source_from_function(recursive_chain_start).unwrap();
+        let (graph, root) = context.graph_with_root(&ast);
+        let steps: Vec<String> = graph
+            .reachable_from(root)
+            .into_iter()
+            .map(|function| graph.functions[function].name())
+            .filter(|name| name.starts_with("halts::test::recursive_chain_"))
+            .collect();
+        assert_eq!(
+            steps,
+            vec![
+                "halts::test::recursive_chain_start",
+                "halts::test::recursive_chain_middle",
+                "halts::test::recursive_chain_end"
+            ]
+        );
+        assert_eq!(iter_recursions(&graph, root).len(), 0);
+    }
+
+    /// Test that a cycle of calls loops, and says which functions take part
+    #[test]
+    fn test_no_halts_if_recursive_cycle() {
+        let verdict = verdict_from_function(recursive_cycle_b);
+        assert!(matches!(verdict, Verdict::Loops(_)));
+        assert_eq!(
+            verdict.evidence().notes,
+            vec!["cyclic recursion: halts::test::recursive_cycle_a → halts::test::recursive_cycle_b → halts::test::recursive_cycle_c → halts::test::recursive_cycle_a"]
+        );
+        assert!(halts(recursive_chain_start).unwrap());
+    }
+
+    /// Test that a method is decided as itself, and not as another type's method with the same signature
+    #[test]
+    fn test_same_signature_methods() {
+        let file: syn::File = syn::parse_str(
+            "
+            struct A;
+            impl A { fn go(&self) { self.step() } fn step(&self) {} }
+            struct B;
+            impl B { fn go(&self) { self.step() } fn step(&self) { self.go() } }
+            ",
+        )
+        .unwrap();
+        let context = Context::from_file(&file);
+        let verdict_of = |owner: &str| {
+            let path = [owner.to_string(), "go".to_string()];
+            let function = context.graph.functions.iter().find(|f| f.path == path);
+            verdict_from_ast_in(&function.unwrap().ast, &context.at(&path))
+        };
+        let verdict = verdict_of("A");
+        assert!(matches!(verdict, Verdict::Halts(_)), "{}", verdict);
+        let verdict = verdict_of("B");
+        assert!(matches!(verdict, Verdict::Loops(_)), "{}", verdict);
+    }
+
+    /// Test that wrappers which call methods and functions of the standard library under their own names halt,
+    /// and that a recursion through a method told by its name alone is no LOOP
+    #[test]
+    fn test_delegating_wrappers() {
+        let verdict_of = |name: &str| {
+            let query = format!("src/halts.rs::test::{}", name);
+            let resolved = ast_from_path::resolved_from_path(&query).unwrap();
+            let context = Context::from_file(&resolved.file).at(&resolved.location);
+            verdict_from_ast_in(&resolved.callable.to_item_fn(), &context)
+        };
+        for name in ["Wrapper::sort", "Wrapper::truncate", "Wrapper::trim", "max"] {
+            let verdict = verdict_of(name);
+            assert!(
+                matches!(verdict, Verdict::Halts(_)),
+                "{}: {}",
+                name,
+                verdict
+            );
+        }
+        let verdict = verdict_of("Wrapper::to_uppercase");
+        assert!(
+            matches!(verdict, Verdict::Unknown(Reason::Inconclusive, _)),
+            "{}",
+            verdict
+        );
+        let recursion = &verdict.evidence().sub_verdicts[0];
+        assert!(recursion
+            .evidence()
+            .notes
+            .contains(&"a method call in the recursion was told by its name alone".to_string()));
+    }
+
+    /// Test that recursion halts if its base case is reached, and is not said to halt if it is not
+    #[test]
+    fn test_base_case_reachability() {
+        let verdict = verdict_from_function(count_down_from_three);
+        assert!(matches!(verdict, Verdict::Halts(_)), "{}", verdict);
+        assert_eq!(verdict.evidence().rule, Rule::ReachableBaseCase);
+        assert!(halts(count_down_from_three).unwrap());
+
+        // the base case is never reached by mathematical integers, but `n + 1` overflows a `u32`
+        // long before the replay could show a LOOP, so the claim is left UNKNOWN
+        let verdict = verdict_from_function(count_up_from_one);
+        assert!(
+            matches!(verdict, Verdict::Unknown(Reason::Inconclusive, _)),
+            "{}",
+            verdict
+        );
+        assert_eq!(verdict.evidence().rule, Rule::UnreachableBaseCase);
+        assert_eq!(
+            verdict.evidence().notes[..3],
+            [
+                "direct recursion: halts::test::count_up → halts::test::count_up",
+                "base case of halts::test::count_up when n == 0",
+                "halts::test::count_up(1) goes on calling itself with n + 1 forever, and no base case ever holds",
+            ]
+        );
+        assert!(verdict.evidence().notes[3]
+            .ends_with("and `n + 1` may overflow its type before the LOOP shows"));
+    }
+
+    /// Test that `ast_from_function` recovers the source of the very function passed in
+    #[test]
+    fn test_ast_from_function() {
+        assert_eq!(ast_from_function(unit).unwrap().sig.ident, "unit");
+        let ast = ast_from_function(loop_forever).unwrap();
+        assert_eq!(ast.sig.ident, "loop_forever");
+        assert!(matches!(
+            ast.block.stmts[0],
+            syn::Stmt::Expr(syn::Expr::Loop(_), _)
+        ));
+        assert!(ast_from_function(|| {}).unwrap().block.stmts.is_empty());
+    }
+
+    /// Test that Ackermann's function halts by a lexicographic measure, which says the order of its parts
+    #[test]
+    fn test_ackermann_halts() {
+        let verdict = verdict_from_function(ackermann_two_three);
+        assert!(matches!(verdict, Verdict::Halts(_)), "{}", verdict);
+        assert_eq!(verdict.evidence().rule, Rule::ReachableBaseCase);
+        let notes = &verdict.evidence().sub_verdicts[0].evidence().notes;
+        assert!(
+            notes.contains(&"`(m, n)` decreases lexicographically at every recursive call, each part bounded below where it decreases".to_string()),
+            "{:?}",
+            notes
+        );
+    }
+
+    /// Test that recursion over the tail of a slice halts by size-change, whatever the slice
+    #[test]
+    fn test_size_change_halts() {
+        let verdict = verdict_from_function(sum_of_three);
+        assert!(matches!(verdict, Verdict::Halts(_)), "{}", verdict);
+        let evidence = verdict.evidence().sub_verdicts[0].evidence();
+        assert_eq!(evidence.rule, Rule::SizeChange);
+        assert!(
+            evidence
+                .notes
+                .last()
+                .unwrap()
+                .contains("every cycle among them shrinks"),
+            "{:?}",
+            evidence.notes
+        );
+    }
+
+    /// Test that recursion over an owned list halts by structural descent, and over a shared one is unknown
+    #[test]
+    fn test_structural_recursion() {
+        let verdict = verdict_from_function(length_of_pair);
+        assert!(matches!(verdict, Verdict::Halts(_)), "{}", verdict);
+        let evidence = verdict.evidence().sub_verdicts[0].evidence();
+        assert_eq!(evidence.rule, Rule::StructuralRecursion);
+        assert_eq!(
+            evidence.notes.last().unwrap(),
+            "every recursive call is on a part destructured from `list`, a `List`, which owns its parts, so taking it apart reaches the bottom"
+        );
+
+        // shared data may be cyclic, though running this list of one shows it isn't
+        let verdict = verdict_from_function(shared_length_of_one);
+        assert_eq!(verdict.evidence().rule, Rule::Execution);
+        let recursion = &verdict.evidence().sub_verdicts[0];
+        assert!(matches!(recursion, Verdict::Unknown(..)), "{}", recursion);
+        let notes = &recursion.evidence().notes;
+        assert!(
+            notes
+                .last()
+                .unwrap()
+                .contains("which holds data through `Rc`"),
+            "{:?}",
+            notes
+        );
     }
 
     /// Test that the `halts` function correctly identifies a function that halts.
@@ -383,6 +1639,289 @@ mod test {
     // cargo install trybuild
     // trybuild ui/test_g_halts_compile_err.rs
 
+    /// Test that loops are only endless when nothing can leave them
+    #[test]
+    fn test_is_endless_loop() {
+        let endless = [
+            "loop { println!(\"Looping forever!\") }",
+            "while true {}",
+            "for i in 0.. { let _ = i; }",
+            "for _ in (0..3).cycle() {}",
+            "'outer: loop { loop { break; } continue 'outer; }",
+            "loop { 'inner: { break 'inner; } }",
+            "loop { let f = || { return 1; }; let _ = f; }",
+            "let mut i = 0; while i >= 0 { i += 1; }",
+        ];
+        let exits = [
+            "loop { break; }",
+            "while n > 0 { n -= 1; }",
+            "for i in 0..10 { let _ = i; }",
+            "for _ in [].iter().cycle() {}",
+            "loop { if done { return; } }",
+            "loop { std::process::exit(0); }",
+            "loop { panic!(\"stop\"); }",
+            "loop { next()?; }",
+            "loop { x.unwrap(); }",
+            "let mut i = 0; while i < 10 { i += 1; }",
+            "let k = 3; if k < 2 { loop {} }",
+        ];
+        let endless_loops = |code: &str| {
+            let body = body_from_ast(&ast_from_str(&format!("fn f() {{ {} }}", code)).unwrap());
+            iter_iterations(&body)
+                .iter()
+                .map(|iteration| is_endless_loop(&body, iteration))
+                .collect::<Vec<bool>>()
+        };
+        for code in endless {
+            assert!(endless_loops(code).contains(&true), "{}", code);
+        }
+        for code in exits {
+            assert!(!endless_loops(code).contains(&true), "{}", code);
+        }
+
+        // an inner loop may be endless even though the outer loop is left by `break 'outer`
+        let code = "'outer: loop { loop { if x { break 'outer; } } }";
+        assert_eq!(endless_loops(code), vec![false]);
+        // and an outer loop may be endless around an inner loop which is left
+        let code = "'outer: loop { while y { if x { continue 'outer; } } }";
+        assert_eq!(endless_loops(code), vec![true, false]);
+    }
+
+    /// Test that a loop halts with a ranking function as its certificate, loops with a lasso, and is unknown with neither
+    #[test]
+    fn test_ranking_function_certificate() {
+        let verdict = verdict_from_function(count_to_len);
+        assert!(matches!(verdict, Verdict::Halts(_)), "{}", verdict);
+        assert_eq!(verdict.evidence().rule, Rule::RankingFunction);
+        assert_eq!(
+            verdict.evidence().sub_verdicts[0].evidence().notes,
+            vec!["linear ranking function n - i"]
+        );
+
+        let verdict = verdict_from_function(step_to_zero);
+        assert!(matches!(verdict, Verdict::Loops(_)), "{}", verdict);
+        assert_eq!(verdict.evidence().rule, Rule::RecurrentSet);
+        let lasso = verdict.evidence().lasso.as_ref().unwrap();
+        assert_eq!(lasso.recurrent_set, vec!["x <= -1"]);
+        assert_eq!(lasso.state, vec![("x".to_string(), "-1".to_string())]);
+
+        let verdict = verdict_from_function(step_past_a_million);
+        assert!(
+            matches!(verdict, Verdict::Unknown(Reason::Inconclusive, _)),
+            "{}",
+            verdict
+        );
+    }
+
+    /// Test that only iterators known to run out have their items counted, and certificates name the source
+    #[test]
+    fn test_iterator_sources() {
+        let file: syn::File = syn::parse_str(
+            "
+            fn over_vec(v: Vec<i64>) { for x in v.iter() { let _ = x; } }
+            fn over_range(n: u32) { for i in 0..n { let _ = i; } }
+            fn cycled() { let v = vec![1, 2, 3]; for _ in v.iter().cycle() {} }
+            fn generated(mut f: impl FnMut() -> Option<u8>) { for _ in std::iter::from_fn(|| f()) {} }
+            fn implemented(items: impl Iterator<Item = u8>) { for _ in items {} }
+            ",
+        )
+        .unwrap();
+        let context = Context::from_file(&file);
+        let verdict_of = |name: &str| {
+            let path = [name.to_string()];
+            let function = context.graph.functions.iter().find(|f| f.path == path);
+            verdict_from_ast_in(&function.unwrap().ast, &context.at(&path))
+        };
+        let certificate =
+            |verdict: &Verdict| verdict.evidence().sub_verdicts[0].evidence().notes.clone();
+        let verdict = verdict_of("over_vec");
+        assert!(matches!(verdict, Verdict::Halts(_)), "{}", verdict);
+        assert_eq!(
+            certificate(&verdict),
+            vec!["linear ranking function remaining(v.iter())"]
+        );
+        let verdict = verdict_of("over_range");
+        assert!(matches!(verdict, Verdict::Halts(_)), "{}", verdict);
+        assert_eq!(
+            certificate(&verdict),
+            vec!["linear ranking function n - next(0..n)"]
+        );
+        for name in ["cycled", "generated", "implemented"] {
+            let verdict = verdict_of(name);
+            assert!(
+                !matches!(verdict, Verdict::Halts(_)),
+                "{}: {}",
+                name,
+                verdict
+            );
+        }
+    }
+
+    /// Test that functions without parameters which no proof decides are decided by running them
+    #[test]
+    fn test_execution() {
+        let verdict = verdict_from_function(step_to_zero_by_min);
+        assert!(matches!(verdict, Verdict::Halts(_)), "{}", verdict);
+        assert_eq!(verdict.evidence().rule, Rule::Execution);
+        let verdict = verdict_from_function(collatz_from_27);
+        assert!(matches!(verdict, Verdict::Halts(_)), "{}", verdict);
+        assert_eq!(verdict.evidence().rule, Rule::Execution);
+
+        let verdict = verdict_from_function(double_mod_seven);
+        assert!(matches!(verdict, Verdict::Loops(_)), "{}", verdict);
+        assert_eq!(verdict.evidence().rule, Rule::Execution);
+        assert!(
+            verdict.evidence().notes[0].starts_with("comes back to the same state every 3 times"),
+            "{}",
+            verdict
+        );
+    }
+
+    /// Test that verdicts on some inputs are for exactly those inputs
+    #[test]
+    fn test_verdict_on() {
+        assert!(matches!(
+            halts_on(double_mod_seven_from, (14i64,)),
+            Ok(true)
+        ));
+        let verdict = verdict_on(double_mod_seven_from, (3i64,));
+        assert!(matches!(verdict, Verdict::Loops(_)), "{}", verdict);
+        assert_eq!(verdict.evidence().rule, Rule::Execution);
+        assert!(verdict.evidence().notes[0].starts_with("on (3), comes back"));
+
+        let sevens = Domain::new()
+            .range("n", -100..=100)
+            .such_that("n % 7 == 0")
+            .unwrap();
+        let verdict = verdict_on(double_mod_seven_from, sevens);
+        assert!(matches!(verdict, Verdict::Halts(_)), "{}", verdict);
+        assert_eq!(
+            verdict.evidence().notes,
+            vec!["returns on each of the 29 inputs"]
+        );
+        let verdict = verdict_on(double_mod_seven_from, Domain::new().range("n", 0..=6));
+        assert!(matches!(verdict, Verdict::Loops(_)), "{}", verdict);
+        assert!(verdict.evidence().notes[0].starts_with("on (1), comes back"));
+    }
+
+    /// Test that the inputs of a domain too big to run are assumed, to tell the analyses about them
+    #[test]
+    fn test_domain_is_assumed() {
+        let naturals = Domain::new().range("n", 0..=1_000_000_000);
+        let verdict = verdict_on(count_down_from, naturals);
+        assert!(matches!(verdict, Verdict::Halts(_)), "{}", verdict);
+        assert_eq!(verdict.evidence().rule, Rule::RankingFunction);
+        let verdict = verdict_on(count_down_from, Domain::new());
+        assert!(!matches!(verdict, Verdict::Halts(_)), "{}", verdict);
+        assert!(matches!(halts_on(count_down_from, (-1i64,)), Ok(false)));
+    }
+
+    /// Test that functions which halt on only some inputs halt when a condition on their parameters holds
+    #[test]
+    fn test_precondition() {
+        let verdict = verdict_on(count_down_from, Domain::new());
+        let Verdict::HaltsWhen(precondition, evidence) = &verdict else {
+            panic!("{}", verdict);
+        };
+        assert_eq!(precondition.condition, "n >= 0");
+        assert!(precondition.loops_otherwise);
+        assert_eq!(evidence.rule, Rule::Precondition);
+        assert!(matches!(
+            halts_on(count_down_from, Domain::new()),
+            Ok(false)
+        ));
+
+        let verdict = verdict_on(count_down_by_recursion, Domain::new());
+        let Verdict::HaltsWhen(precondition, _) = &verdict else {
+            panic!("{}", verdict);
+        };
+        assert_eq!(precondition.condition, "n >= 0");
+
+        // the inputs which fail the condition overflow rather than loop, as replaying the claim shows
+        let verdict = verdict_on(double_past_one, Domain::new());
+        if let Verdict::HaltsWhen(precondition, _) = &verdict {
+            assert!(!precondition.loops_otherwise, "{}", verdict);
+        }
+        assert!(!matches!(
+            halts_on(double_past_one, Domain::new()),
+            Ok(false)
+        ));
+    }
+
+    /// Test that symbolic execution refutes loops no input reaches, and finds inputs which get caught in loops
+    #[test]
+    fn test_symbolic_execution() {
+        let verdict = verdict_on(loop_when_apart, Domain::new());
+        assert!(matches!(verdict, Verdict::Halts(_)), "{}", verdict);
+        let sub_verdicts = &verdict.evidence().sub_verdicts;
+        assert_eq!(sub_verdicts[0].evidence().rule, Rule::SymbolicExecution);
+
+        // the verdict before the precondition was inferred is a lasso from a negative `n`
+        let verdict = verdict_on(count_down_from, Domain::new());
+        let looping = &verdict.evidence().sub_verdicts[0];
+        assert_eq!(looping.evidence().rule, Rule::RecurrentSet, "{}", looping);
+        let lasso = looping.evidence().lasso.as_ref().unwrap();
+        assert!(lasso.state.contains(&("n".to_string(), "-1".to_string())));
+    }
+
+    /// Test that a LOOP whose witness the interpreter refutes is refined away, and that decisions tell how they were reached
+    #[test]
+    fn test_refinement() {
+        let (ast, context) = source_from_function(count_up_in_i8).unwrap();
+        let decision = decision_from_ast_in(&ast, &context);
+        assert!(
+            matches!(decision.verdict, Verdict::Halts(_)),
+            "{}",
+            decision
+        );
+        assert_eq!(decision.engine, crate::verdict::Engine::RankingSynthesis);
+        assert_eq!(decision.rounds, 1);
+        let notes = &decision.verdict.evidence().notes;
+        assert!(
+            notes
+                .iter()
+                .any(|note| note.contains("attempt to add with overflow")),
+            "{:?}",
+            notes
+        );
+
+        // a LOOP the interpreter can't refute stands
+        let (ast, context) = source_from_function(loop_forever).unwrap();
+        let decision = decision_from_ast_in(&ast, &context);
+        assert!(
+            matches!(decision.verdict, Verdict::Loops(_)),
+            "{}",
+            decision
+        );
+        assert_eq!(
+            decision.engine,
+            crate::verdict::Engine::AbstractInterpretation
+        );
+        assert_eq!(decision.rounds, 0);
+    }
+
+    /// Test that the diagonal function `g` is an inversion paradox, and why
+    #[test]
+    fn test_g_is_inversion_paradox() {
+        let verdict = verdict_from_function(g);
+        assert!(matches!(
+            verdict,
+            Verdict::Paradox(crate::verdict::ParadoxKind::Inversion, _)
+        ));
+        let sub_verdicts = &verdict.evidence().sub_verdicts;
+        assert!(matches!(sub_verdicts[0], Verdict::Loops(_)));
+        assert!(matches!(sub_verdicts[1], Verdict::Halts(_)));
+
+        // comparing the answer, or looping while it holds, inverts it as surely
+        for code in [
+            "fn g() { if halts(g) == Ok(true) { loop {} } }",
+            "fn g() { while halts(g).unwrap() {} }",
+        ] {
+            let verdict = verdict_from_ast(&ast_from_str(code).unwrap());
+            assert_eq!(verdict.category(), "PARADOX", "{}: {}", code, verdict);
+        }
+    }
+
     /// Test that the `halts` function correctly identifies a function that contains an inversion paradox.
     #[test]
     fn test_halts_g() {
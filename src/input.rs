@@ -0,0 +1,371 @@
+//! halts/src/input.rs
+//! The inputs a verdict may be asked for: the arguments of one call, or a domain of them.
+//! A domain bounds integer parameters by ranges, and narrows them by predicates,
+//! Rust expressions over the parameters like `n % 2 == 0`.
+//! Either kind of input can be written as a condition on the parameters, to assume at the start of the function,
+//! and a small enough domain can be listed out, to run the function on each of its inputs.
+
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use quote::ToTokens;
+
+use crate::call_graph::Context;
+use crate::interp::{execution_from_ast, Execution, IntTy, Value};
+
+/// The most inputs a domain may have and still be listed out
+const MAX_INPUTS: u128 = 1_000;
+
+/// The most steps the predicates of a domain may take to tell whether one input is in it
+const MAX_STEPS: usize = 10_000;
+
+/// A set of arguments to a function, bounded by ranges and narrowed by predicates.
+#[derive(Debug, Clone, Default)]
+pub struct Domain {
+    /// The least and greatest value of each bounded integer parameter, by name
+    ranges: Vec<(String, RangeInclusive<i128>)>,
+    /// Conditions on the parameters which every input meets
+    predicates: Vec<syn::Expr>,
+}
+
+impl Domain {
+    /// The domain of every input, which the ranges and predicates added to it narrow
+    pub fn new() -> Self {
+        Domain::default()
+    }
+
+    /// Bound an integer parameter.
+    ///
+    /// # Arguments
+    ///
+    /// * `param` - The name of the parameter.
+    /// * `range` - Its least and greatest values, like `0..=100`.
+    ///
+    /// # Returns
+    ///
+    /// The domain of the inputs in this one whose parameter is in the range.
+    pub fn range(mut self, param: &str, range: RangeInclusive<i128>) -> Self {
+        self.ranges.push((param.to_string(), range));
+        self
+    }
+
+    /// Narrow the domain by a condition on the parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `predicate` - A Rust expression of type `bool` over the parameters, like `n % 2 == 0`.
+    ///
+    /// # Returns
+    ///
+    /// The domain of the inputs in this one which meet the condition.
+    ///
+    /// # Errors
+    ///
+    /// If the predicate isn't an expression.
+    pub fn such_that(mut self, predicate: &str) -> syn::Result<Self> {
+        self.predicates.push(syn::parse_str(predicate)?);
+        Ok(self)
+    }
+
+    /// The ranges and predicates of the domain as one condition, or `None` if there are none
+    fn condition(&self) -> Option<syn::Expr> {
+        let ranges = self.ranges.iter().map(|(param, range)| {
+            format!(
+                "({} <= {} && {} <= {})",
+                range.start(),
+                param,
+                param,
+                range.end()
+            )
+        });
+        let predicates = self
+            .predicates
+            .iter()
+            .map(|predicate| format!("({})", predicate.to_token_stream()));
+        let parts: Vec<String> = ranges.chain(predicates).collect();
+        if parts.is_empty() {
+            return None;
+        }
+        syn::parse_str(&parts.join(" && ")).ok()
+    }
+}
+
+/// The inputs a function is asked about
+#[derive(Debug, Clone)]
+pub enum Input {
+    /// The arguments of one call, in order
+    Args(Vec<Value>),
+    /// Every input of a domain
+    Domain(Domain),
+}
+
+impl Input {
+    /// Whether the inputs are every input, as for a domain with neither ranges nor predicates
+    pub fn is_every_input(&self) -> bool {
+        matches!(self, Input::Domain(domain) if domain.ranges.is_empty() && domain.predicates.is_empty())
+    }
+}
+
+impl From<Domain> for Input {
+    fn from(domain: Domain) -> Self {
+        Input::Domain(domain)
+    }
+}
+
+impl From<Vec<Value>> for Input {
+    fn from(args: Vec<Value>) -> Self {
+        Input::Args(args)
+    }
+}
+
+impl From<()> for Input {
+    fn from(_: ()) -> Self {
+        Input::Args(vec![])
+    }
+}
+
+impl<A: Into<Value>> From<(A,)> for Input {
+    fn from((a,): (A,)) -> Self {
+        Input::Args(vec![a.into()])
+    }
+}
+
+impl<A: Into<Value>, B: Into<Value>> From<(A, B)> for Input {
+    fn from((a, b): (A, B)) -> Self {
+        Input::Args(vec![a.into(), b.into()])
+    }
+}
+
+impl<A: Into<Value>, B: Into<Value>, C: Into<Value>> From<(A, B, C)> for Input {
+    fn from((a, b, c): (A, B, C)) -> Self {
+        Input::Args(vec![a.into(), b.into(), c.into()])
+    }
+}
+
+impl<A: Into<Value>, B: Into<Value>, C: Into<Value>, D: Into<Value>> From<(A, B, C, D)> for Input {
+    fn from((a, b, c, d): (A, B, C, D)) -> Self {
+        Input::Args(vec![a.into(), b.into(), c.into(), d.into()])
+    }
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Input::Args(args) => {
+                let args: Vec<String> = args.iter().map(Value::to_string).collect();
+                write!(f, "({})", args.join(", "))
+            }
+            Input::Domain(domain) => match domain.condition() {
+                Some(condition) => write!(f, "inputs where `{}`", condition.to_token_stream()),
+                None => write!(f, "every input"),
+            },
+        }
+    }
+}
+
+/// The names of the parameters of a function, or `None` if one is a pattern or `self`
+fn params_from_ast(ast: &syn::ItemFn) -> Option<Vec<(String, &syn::Type)>> {
+    ast.sig
+        .inputs
+        .iter()
+        .map(|input| match input {
+            syn::FnArg::Typed(param) => match &*param.pat {
+                syn::Pat::Ident(ident) => Some((ident.ident.to_string(), &*param.ty)),
+                _ => None,
+            },
+            syn::FnArg::Receiver(_) => None,
+        })
+        .collect()
+}
+
+/// A value written as a literal expression, if it can be
+fn expr_from_value(value: &Value) -> Option<syn::Expr> {
+    match value {
+        Value::Int(int, _) => syn::parse_str(&int.to_string()).ok(),
+        Value::Bool(b) => syn::parse_str(&b.to_string()).ok(),
+        Value::Char(c) => syn::parse_str(&format!("{:?}", c)).ok(),
+        _ => None,
+    }
+}
+
+/// Write the inputs as a condition on the parameters of a function, which holds for exactly those inputs.
+///
+/// # Arguments
+///
+/// * `input` - The inputs.
+/// * `ast` - The AST of the function they're inputs to.
+///
+/// # Returns
+///
+/// The condition, like `n == 3` for the arguments `(3,)`, or `None` if there is no condition, as for every input,
+/// or the inputs can't be written as one, as for arguments which aren't integers, bools or chars.
+pub fn condition_from_input(input: &Input, ast: &syn::ItemFn) -> Option<syn::Expr> {
+    let params = params_from_ast(ast)?;
+    match input {
+        Input::Args(args) => {
+            if args.len() != params.len() || args.is_empty() {
+                return None;
+            }
+            let parts = params
+                .iter()
+                .zip(args)
+                .map(|((param, _), arg)| {
+                    let arg = expr_from_value(arg)?;
+                    Some(format!("{} == {}", param, arg.to_token_stream()))
+                })
+                .collect::<Option<Vec<String>>>()?;
+            syn::parse_str(&parts.join(" && ")).ok()
+        }
+        Input::Domain(domain) => {
+            // a range on what isn't a parameter would make a condition about nothing
+            let names_params = |name: &String| params.iter().any(|(param, _)| param == name);
+            if !domain.ranges.iter().all(|(name, _)| names_params(name)) {
+                return None;
+            }
+            domain.condition()
+        }
+    }
+}
+
+/// List the arguments of each call the inputs are made of.
+///
+/// A domain is listed out if every parameter is an integer with a range, there are at most `MAX_INPUTS` inputs in the ranges,
+/// and the predicates can be run on each of them, to keep only those which meet them.
+///
+/// # Arguments
+///
+/// * `input` - The inputs.
+/// * `ast` - The AST of the function they're inputs to.
+/// * `context` - Where the function is defined, among the functions the predicates may call.
+///
+/// # Returns
+///
+/// The arguments of each call, or `None` if the inputs can't be listed out.
+pub fn args_from_input(
+    input: &Input,
+    ast: &syn::ItemFn,
+    context: &Context,
+) -> Option<Vec<Vec<Value>>> {
+    let domain = match input {
+        Input::Args(args) => return Some(vec![args.clone()]),
+        Input::Domain(domain) => domain,
+    };
+    let params = params_from_ast(ast)?;
+    let mut ranges = vec![];
+    let mut count: u128 = 1;
+    for (param, ty) in &params {
+        let ty = IntTy::from_type(ty)?;
+        let (_, range) = domain.ranges.iter().find(|(name, _)| name == param)?;
+        let size = u128::try_from(range.end().checked_sub(*range.start())?.checked_add(1)?).ok()?;
+        count = count.checked_mul(size)?;
+        if count > MAX_INPUTS {
+            return None;
+        }
+        ranges.push((range.clone(), ty));
+    }
+    let mut inputs: Vec<Vec<Value>> = vec![vec![]];
+    for (range, ty) in ranges {
+        inputs = inputs
+            .into_iter()
+            .flat_map(|args| {
+                range.clone().map(move |int| {
+                    let mut args = args.clone();
+                    args.push(Value::Int(int, Some(ty)));
+                    args
+                })
+            })
+            .collect();
+    }
+    if domain.predicates.is_empty() {
+        return Some(inputs);
+    }
+
+    // the predicates become the body of a function with the same parameters, run on each input
+    let mut predicate = ast.clone();
+    predicate.sig.ident = syn::Ident::new(
+        &format!("domain_of_{}", ast.sig.ident),
+        ast.sig.ident.span(),
+    );
+    predicate.sig.output = syn::parse_quote!(-> bool);
+    let conditions = &domain.predicates;
+    predicate.block = syn::parse_quote!({ true #(&& (#conditions))* });
+    let beside = context.beside(&predicate.sig.ident.to_string());
+    let mut kept = vec![];
+    for args in inputs {
+        match execution_from_ast(&predicate, &beside, args.clone(), MAX_STEPS) {
+            Execution::Returns {
+                value: Value::Bool(true),
+                ..
+            } => kept.push(args),
+            Execution::Returns {
+                value: Value::Bool(false),
+                ..
+            } => {}
+            _ => return None,
+        }
+    }
+    Some(kept)
+}
+
+/// tests for inputs
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The function the inputs are for
+    fn function() -> syn::ItemFn {
+        syn::parse_str("fn f(n: i64, m: u8) { }").unwrap()
+    }
+
+    /// Test that inputs become conditions on the parameters
+    #[test]
+    fn test_condition() {
+        let condition = |input: Input| {
+            condition_from_input(&input, &function()).map(|c| c.to_token_stream().to_string())
+        };
+        assert_eq!(condition((3i64, 5u8).into()).unwrap(), "n == 3 && m == 5");
+        assert_eq!(
+            condition((-3i64, 5u8).into()).unwrap(),
+            "n == - 3 && m == 5"
+        );
+        let domain = Domain::new()
+            .range("n", 0..=9)
+            .such_that("n % 2 == 0")
+            .unwrap();
+        assert_eq!(
+            condition(domain.into()).unwrap(),
+            "(0 <= n && n <= 9) && (n % 2 == 0)"
+        );
+        assert_eq!(condition(Domain::new().into()), None);
+        assert_eq!(condition(Domain::new().range("k", 0..=1).into()), None);
+        assert_eq!(condition((3i64, vec![1u8]).into()), None);
+    }
+
+    /// Test that small domains are listed out, keeping the inputs which meet the predicates
+    #[test]
+    fn test_args() {
+        let file: syn::File = syn::parse_str("fn even(k: i64) -> bool { k % 2 == 0 }").unwrap();
+        let context = Context::from_file(&file);
+        let args = |domain: Domain| {
+            args_from_input(&domain.into(), &function(), &context).map(|inputs| {
+                inputs
+                    .iter()
+                    .map(|args| Value::Tuple(args.clone()).to_string())
+                    .collect::<Vec<String>>()
+            })
+        };
+        let domain = Domain::new().range("n", -1..=1).range("m", 0..=1);
+        assert_eq!(args(domain).unwrap().len(), 6);
+        let domain = Domain::new()
+            .range("n", 0..=5)
+            .range("m", 3..=3)
+            .such_that("even(n) && n > 0")
+            .unwrap();
+        assert_eq!(args(domain).unwrap(), vec!["(2, 3)", "(4, 3)"]);
+        assert_eq!(args(Domain::new().range("n", 0..=5)), None);
+        assert_eq!(
+            args(Domain::new().range("n", 0..=999).range("m", 0..=1)),
+            None
+        );
+    }
+}
@@ -0,0 +1,2341 @@
+//! halts/src/interp.rs
+//! A bounded interpreter which runs functions written in a subset of Rust, straight from their syntax trees.
+//! The subset has integers, bools, chars, tuples, arrays, `Vec`, structs and enums, `Option` and `Result`,
+//! `match` and `if let`, every kind of loop, calls between the functions of a file, and closures.
+//! A run gives ground truth, not an estimate: returning shows the function halts,
+//! and coming back to exactly a state it was in before shows it loops, since what it does next depends on nothing else.
+//! The state compared is all of it: every local of every frame, the place in the code of each frame,
+//! and the values held part way through evaluating an expression, like the left side of `a + f(b)` while `f` runs.
+//! It is compared at the head of each loop, with Brent's algorithm, which keeps only one earlier state and its hash.
+//! Integers are checked against their types, so overflow panics as in a debug build;
+//! an integer whose type can't be told counts as an `i32`, and leaving its range stops the run rather than guess.
+//! Anything outside the subset stops the run too, as does running out of steps, and the outcome is unknown.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use quote::ToTokens;
+use syn::punctuated::Punctuated;
+use syn::visit::Visit;
+
+use crate::call_graph::{CallGraph, Context, Receiver};
+
+/// The most frames on the call stack before the run stops, for want of a deeper stack to run it on
+const MAX_DEPTH: usize = 32;
+
+/// The most elements an array made at once may have, like `vec![0; n]` or `(0..n).rev()`
+const MAX_LENGTH: usize = 100_000;
+
+/// The methods of the standard library which change their receiver, which change it where it is
+const MUTATORS: [&str; 12] = [
+    "push",
+    "pop",
+    "insert",
+    "remove",
+    "clear",
+    "truncate",
+    "swap",
+    "reverse",
+    "sort",
+    "take",
+    "push_back",
+    "pop_front",
+];
+
+/// The type of an integer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IntTy {
+    /// `i8`
+    I8,
+    /// `i16`
+    I16,
+    /// `i32`
+    I32,
+    /// `i64`
+    I64,
+    /// `i128`
+    I128,
+    /// `isize`, taken to be 64 bits
+    Isize,
+    /// `u8`
+    U8,
+    /// `u16`
+    U16,
+    /// `u32`
+    U32,
+    /// `u64`
+    U64,
+    /// `u128`, which runs only while it fits in an `i128`
+    U128,
+    /// `usize`, taken to be 64 bits
+    Usize,
+}
+
+impl IntTy {
+    /// The integer type of a name, like `u8`
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "i8" => IntTy::I8,
+            "i16" => IntTy::I16,
+            "i32" => IntTy::I32,
+            "i64" => IntTy::I64,
+            "i128" => IntTy::I128,
+            "isize" => IntTy::Isize,
+            "u8" => IntTy::U8,
+            "u16" => IntTy::U16,
+            "u32" => IntTy::U32,
+            "u64" => IntTy::U64,
+            "u128" => IntTy::U128,
+            "usize" => IntTy::Usize,
+            _ => return None,
+        })
+    }
+
+    /// The integer type of a type, looking through references, or `None` if it isn't an integer
+    pub fn from_type(ty: &syn::Type) -> Option<Self> {
+        match ty {
+            syn::Type::Path(path) => IntTy::from_name(&path.path.get_ident()?.to_string()),
+            syn::Type::Reference(reference) => IntTy::from_type(&reference.elem),
+            syn::Type::Paren(paren) => IntTy::from_type(&paren.elem),
+            _ => None,
+        }
+    }
+
+    /// The number of bits of the type
+    pub fn bits(self) -> u32 {
+        match self {
+            IntTy::I8 | IntTy::U8 => 8,
+            IntTy::I16 | IntTy::U16 => 16,
+            IntTy::I32 | IntTy::U32 => 32,
+            IntTy::I64 | IntTy::U64 | IntTy::Isize | IntTy::Usize => 64,
+            IntTy::I128 | IntTy::U128 => 128,
+        }
+    }
+
+    /// Whether the type has negative values
+    fn is_signed(self) -> bool {
+        matches!(
+            self,
+            IntTy::I8 | IntTy::I16 | IntTy::I32 | IntTy::I64 | IntTy::I128 | IntTy::Isize
+        )
+    }
+
+    /// The least and greatest values of the type, as far as an `i128` reaches
+    pub fn bounds(self) -> (i128, i128) {
+        match (self.is_signed(), self.bits()) {
+            (true, 128) => (i128::MIN, i128::MAX),
+            (true, bits) => (-(1 << (bits - 1)), (1 << (bits - 1)) - 1),
+            (false, 128) => (0, i128::MAX),
+            (false, bits) => (0, (1 << bits) - 1),
+        }
+    }
+
+    /// Whether a value is in the range of the type
+    fn contains(self, value: i128) -> bool {
+        let (least, greatest) = self.bounds();
+        (least..=greatest).contains(&value)
+    }
+
+    /// A value wrapped around into the range of the type, as by `as`, or `None` past 128 bits
+    fn wrap(self, value: i128) -> Option<i128> {
+        if self.bits() == 128 {
+            return self.contains(value).then_some(value);
+        }
+        let modulus = 1i128 << self.bits();
+        let wrapped = value.rem_euclid(modulus);
+        Some(if self.is_signed() && wrapped >= modulus / 2 {
+            wrapped - modulus
+        } else {
+            wrapped
+        })
+    }
+}
+
+impl fmt::Display for IntTy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = format!("{:?}", self).to_lowercase();
+        write!(f, "{}", name)
+    }
+}
+
+/// A value the interpreter computes with
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Value {
+    /// An integer, with its type if it can be told
+    Int(i128, Option<IntTy>),
+    /// A boolean
+    Bool(bool),
+    /// A character
+    Char(char),
+    /// A tuple; the unit value is the empty tuple
+    Tuple(Vec<Value>),
+    /// An array, a slice or a `Vec`
+    Array(Vec<Value>),
+    /// The half-open range of integers from the first up to the second
+    Range(i128, i128, Option<IntTy>),
+    /// A variant of an enum or a tuple struct, by name, with its fields, like `Some(1)` or `None`
+    Variant(String, Vec<Value>),
+    /// A struct or struct-like variant, by name, with its named fields
+    Struct(String, Vec<(String, Value)>),
+    /// A function of the call graph, by index, used as a value
+    Function(usize),
+    /// A closure, by the place of its expression in the syntax tree, with the values it captured
+    Closure(usize, Vec<(String, Value)>),
+}
+
+impl Value {
+    /// The unit value, `()`
+    pub fn unit() -> Self {
+        Value::Tuple(vec![])
+    }
+
+    /// Whether two values are equal by `==`, which doesn't care whether the type of an integer was told
+    fn equals(&self, other: &Value) -> bool {
+        /// Whether two lists of values are equal by `==`
+        fn all_equal(left: &[Value], right: &[Value]) -> bool {
+            left.len() == right.len() && left.iter().zip(right).all(|(l, r)| l.equals(r))
+        }
+        match (self, other) {
+            (Value::Int(left, _), Value::Int(right, _)) => left == right,
+            (Value::Tuple(left), Value::Tuple(right))
+            | (Value::Array(left), Value::Array(right)) => all_equal(left, right),
+            (Value::Variant(left, l), Value::Variant(right, r)) => left == right && all_equal(l, r),
+            (Value::Struct(left, l), Value::Struct(right, r)) => {
+                left == right
+                    && l.len() == r.len()
+                    && l.iter()
+                        .zip(r)
+                        .all(|((a, x), (b, y))| a == b && x.equals(y))
+            }
+            (Value::Range(a, b, _), Value::Range(c, d, _)) => a == c && b == d,
+            _ => self == other,
+        }
+    }
+
+    /// `Some(value)`
+    fn some(value: Value) -> Self {
+        Value::Variant("Some".to_string(), vec![value])
+    }
+
+    /// `None`
+    fn none() -> Self {
+        Value::Variant("None".to_string(), vec![])
+    }
+
+    /// `Some(value)` if there is one, else `None`
+    fn option(value: Option<Value>) -> Self {
+        value.map_or_else(Value::none, Value::some)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        /// Values separated by commas
+        fn list(values: &[Value]) -> String {
+            values
+                .iter()
+                .map(Value::to_string)
+                .collect::<Vec<String>>()
+                .join(", ")
+        }
+        match self {
+            Value::Int(int, _) => write!(f, "{}", int),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Char(c) => write!(f, "{:?}", c),
+            Value::Tuple(values) if values.len() == 1 => write!(f, "({},)", values[0]),
+            Value::Tuple(values) => write!(f, "({})", list(values)),
+            Value::Array(values) => write!(f, "[{}]", list(values)),
+            Value::Range(start, end, _) => write!(f, "{}..{}", start, end),
+            Value::Variant(name, values) if values.is_empty() => write!(f, "{}", name),
+            Value::Variant(name, values) => write!(f, "{}({})", name, list(values)),
+            Value::Struct(name, fields) => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(field, value)| format!("{}: {}", field, value))
+                    .collect();
+                write!(f, "{} {{ {} }}", name, fields.join(", "))
+            }
+            Value::Function(_) => write!(f, "a function"),
+            Value::Closure(..) => write!(f, "a closure"),
+        }
+    }
+}
+
+/// `From` each integer type into `Value`, tagged with that type
+macro_rules! value_from_int {
+    ($($int:ty => $ty:ident),*) => {
+        $(
+            impl From<$int> for Value {
+                fn from(int: $int) -> Self {
+                    Value::Int(i128::from(int), Some(IntTy::$ty))
+                }
+            }
+        )*
+    };
+}
+
+value_from_int!(i8 => I8, i16 => I16, i32 => I32, i64 => I64, i128 => I128, u8 => U8, u16 => U16, u32 => U32, u64 => U64);
+
+impl From<isize> for Value {
+    fn from(int: isize) -> Self {
+        Value::Int(i128::try_from(int).unwrap_or(i128::MAX), Some(IntTy::Isize))
+    }
+}
+
+impl From<usize> for Value {
+    fn from(int: usize) -> Self {
+        Value::Int(i128::try_from(int).unwrap_or(i128::MAX), Some(IntTy::Usize))
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+impl From<char> for Value {
+    fn from(c: char) -> Self {
+        Value::Char(c)
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(values: Vec<T>) -> Self {
+        Value::Array(values.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        Value::option(value.map(Into::into))
+    }
+}
+
+/// How a run ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Execution {
+    /// The function returned, so it halts
+    Returns {
+        /// What it returned
+        value: Value,
+        /// How many steps it took
+        steps: usize,
+    },
+    /// The function came back to a state it was in before, at the head of a loop, so it loops
+    Repeats {
+        /// How many steps it took to see the repetition
+        steps: usize,
+        /// How many times round loops the state takes to come back
+        period: usize,
+    },
+    /// The function panicked
+    Panics {
+        /// Why, like `attempt to subtract with overflow`
+        message: String,
+        /// How many steps it took
+        steps: usize,
+    },
+    /// The run stopped without telling, for running out of steps or running into what's outside the subset
+    Unknown(String),
+}
+
+impl fmt::Display for Execution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Execution::Returns { value, steps } => {
+                write!(f, "returns {} after {} steps", value, steps)
+            }
+            Execution::Repeats { steps, period } => write!(
+                f,
+                "comes back to the same state every {} times round a loop, as seen after {} steps",
+                period, steps
+            ),
+            Execution::Panics { message, steps } => {
+                write!(f, "panics after {} steps: {}", steps, message)
+            }
+            Execution::Unknown(why) => write!(f, "{}", why),
+        }
+    }
+}
+
+/// The arguments of the macros of a program, parsed before the run so their syntax trees live as long as it
+#[derive(Debug, Default)]
+struct Macros {
+    /// The arguments of each macro, by its place in the syntax tree
+    args: HashMap<usize, MacroArgs>,
+}
+
+/// The arguments of a macro
+#[derive(Debug)]
+struct MacroArgs {
+    /// The expressions, or the element and the length of `vec![x; n]`, or the scrutinee and guard of `matches!`
+    exprs: Vec<syn::Expr>,
+    /// Whether it's `vec![x; n]`
+    is_repeat: bool,
+    /// The pattern of `matches!`
+    pat: Option<syn::Pat>,
+}
+
+impl<'ast> Visit<'ast> for Macros {
+    fn visit_macro(&mut self, mac: &'ast syn::Macro) {
+        let name = mac
+            .path
+            .segments
+            .last()
+            .map(|segment| segment.ident.to_string())
+            .unwrap_or_default();
+        let parsed = match name.as_str() {
+            "matches" => mac
+                .parse_body_with(|input: syn::parse::ParseStream<'_>| {
+                    let scrutinee: syn::Expr = input.parse()?;
+                    input.parse::<syn::Token![,]>()?;
+                    let pat = syn::Pat::parse_multi_with_leading_vert(input)?;
+                    let mut exprs = vec![scrutinee];
+                    if input.parse::<Option<syn::Token![if]>>()?.is_some() {
+                        exprs.push(input.parse()?);
+                    }
+                    input.parse::<Option<syn::Token![,]>>()?;
+                    Ok(MacroArgs {
+                        exprs,
+                        is_repeat: false,
+                        pat: Some(pat),
+                    })
+                })
+                .ok(),
+            _ => mac
+                .parse_body_with(|input: syn::parse::ParseStream<'_>| {
+                    let element: syn::Expr = input.parse()?;
+                    input.parse::<syn::Token![;]>()?;
+                    let length: syn::Expr = input.parse()?;
+                    Ok(MacroArgs {
+                        exprs: vec![element, length],
+                        is_repeat: true,
+                        pat: None,
+                    })
+                })
+                .or_else(|_| {
+                    mac.parse_body_with(Punctuated::<syn::Expr, syn::Token![,]>::parse_terminated)
+                        .map(|args| MacroArgs {
+                            exprs: args.into_iter().collect(),
+                            is_repeat: false,
+                            pat: None,
+                        })
+                })
+                .ok(),
+        };
+        if let Some(args) = parsed {
+            // the arguments keep their place on the heap as the map grows, so macros within them can be found now
+            for expr in &args.exprs {
+                self.visit_expr(expr);
+            }
+            self.args.insert(place_of(mac), args);
+        }
+    }
+}
+
+/// The place of a node in the syntax tree, which tells it apart from every other node alive at the same time
+fn place_of<T>(node: &T) -> usize {
+    std::ptr::from_ref(node).addr()
+}
+
+/// A local variable
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Local {
+    /// The name of the local
+    name: String,
+    /// Its value
+    value: Value,
+    /// The type it was declared with, if it's an integer type
+    ty: Option<IntTy>,
+}
+
+/// The locals of a call, in nested scopes
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Frame {
+    /// The index of the function running, in the call graph, which a closure shares with the function it's in
+    function: usize,
+    /// The locals of each scope, from the outermost; later locals shadow earlier ones
+    scopes: Vec<Vec<Local>>,
+}
+
+/// Everything the future of a run depends on
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct State {
+    /// The place in the syntax tree of each expression being evaluated, from the outermost
+    path: Vec<usize>,
+    /// The values held part way through evaluating expressions, like iterators of `for` loops
+    held: Vec<Value>,
+    /// The frames of the call stack
+    frames: Vec<Frame>,
+}
+
+/// Why evaluation stopped short of a value
+#[derive(Debug)]
+enum Interrupt {
+    /// `break`, with its label and value
+    Break(Option<String>, Value),
+    /// `continue`, with its label
+    Continue(Option<String>),
+    /// `return`, or `?` on `None` or `Err`, with the value returned
+    Return(Value),
+    /// A panic, and why
+    Panic(String),
+    /// The state came back around, after so many times round loops
+    Repeat(usize),
+    /// The run can't go on, and why
+    Stop(String),
+}
+
+/// The outcome of evaluating something
+type Flow<T> = Result<T, Interrupt>;
+
+/// Stop the run for something outside the subset
+fn unsupported<T>(what: impl ToTokens) -> Flow<T> {
+    let text = what.to_token_stream().to_string();
+    Err(Interrupt::Stop(format!("can't run `{}`", text)))
+}
+
+/// Check an integer computed for a type, which panics when out of its range
+fn checked(value: Option<i128>, ty: Option<IntTy>, what: &str) -> Flow<Value> {
+    match (value, ty) {
+        (Some(value), Some(ty)) if ty.contains(value) => Ok(Value::Int(value, Some(ty))),
+        (Some(value), None) if IntTy::I32.contains(value) => Ok(Value::Int(value, None)),
+        (_, None) => Err(Interrupt::Stop(format!(
+            "can't tell the type of an integer which leaves the range of i32 when trying to {}",
+            what
+        ))),
+        (None, Some(IntTy::U128)) => Err(Interrupt::Stop(format!(
+            "can't run u128 past the range of i128 when trying to {}",
+            what
+        ))),
+        (_, Some(_)) => Err(Interrupt::Panic(format!(
+            "attempt to {} with overflow",
+            what
+        ))),
+    }
+}
+
+/// Give an integer the type it's declared with, if it has none yet
+fn with_type(value: Value, ty: Option<IntTy>) -> Flow<Value> {
+    match (value, ty) {
+        (Value::Int(int, None), Some(ty)) if ty.contains(int) => Ok(Value::Int(int, Some(ty))),
+        (Value::Int(_, None), Some(ty)) => Err(Interrupt::Stop(format!(
+            "an integer out of the range of {}",
+            ty
+        ))),
+        (value, _) => Ok(value),
+    }
+}
+
+/// The integer in a value
+fn int_of(value: &Value) -> Flow<(i128, Option<IntTy>)> {
+    match value {
+        Value::Int(int, ty) => Ok((*int, *ty)),
+        value => Err(Interrupt::Stop(format!(
+            "expected an integer, found {}",
+            value
+        ))),
+    }
+}
+
+/// An integer used as an index or length
+fn index_of(value: &Value) -> Flow<usize> {
+    let (int, _) = int_of(value)?;
+    usize::try_from(int).map_err(|_| Interrupt::Stop(format!("can't index by {}", int)))
+}
+
+/// An index or length as an integer
+fn int_from_index(index: usize) -> Value {
+    Value::Int(
+        i128::try_from(index).unwrap_or(i128::MAX),
+        Some(IntTy::Usize),
+    )
+}
+
+/// The label of a loop or block
+fn label_of(label: Option<&syn::Label>) -> Option<String> {
+    label.map(|label| label.name.ident.to_string())
+}
+
+/// Whether a `break` or `continue` with some label is for a loop with another
+fn is_for(target: &Option<String>, label: &Option<String>) -> bool {
+    target.is_none() || target == label
+}
+
+/// The last segment of a path, like `Nil` in `List::Nil`
+fn last_segment(path: &syn::Path) -> String {
+    path.segments
+        .last()
+        .map(|segment| segment.ident.to_string())
+        .unwrap_or_default()
+}
+
+/// Whether a name is written like a type or variant, rather than a local or function
+fn is_capitalized(name: &str) -> bool {
+    name.starts_with(char::is_uppercase)
+}
+
+/// Apply a binary operator to two values
+fn binary(op: &syn::BinOp, left: &Value, right: &Value) -> Flow<Value> {
+    use syn::BinOp as B;
+    match (left, right) {
+        (Value::Int(a, ta), Value::Int(b, tb)) => {
+            let (a, b, ty) = (*a, *b, ta.or(*tb));
+            match op {
+                B::Add(_) | B::AddAssign(_) => checked(a.checked_add(b), ty, "add"),
+                B::Sub(_) | B::SubAssign(_) => checked(a.checked_sub(b), ty, "subtract"),
+                B::Mul(_) | B::MulAssign(_) => checked(a.checked_mul(b), ty, "multiply"),
+                B::Div(_) | B::DivAssign(_) | B::Rem(_) | B::RemAssign(_) if b == 0 => {
+                    Err(Interrupt::Panic("attempt to divide by zero".to_string()))
+                }
+                B::Div(_) | B::DivAssign(_) => checked(a.checked_div(b), ty, "divide"),
+                B::Rem(_) | B::RemAssign(_) => {
+                    checked(a.checked_rem(b), ty, "calculate the remainder")
+                }
+                B::BitAnd(_) | B::BitAndAssign(_) => Ok(Value::Int(a & b, ty)),
+                B::BitOr(_) | B::BitOrAssign(_) => Ok(Value::Int(a | b, ty)),
+                B::BitXor(_) | B::BitXorAssign(_) => Ok(Value::Int(a ^ b, ty)),
+                B::Shl(_) | B::ShlAssign(_) | B::Shr(_) | B::ShrAssign(_) => {
+                    let is_left = matches!(op, B::Shl(_) | B::ShlAssign(_));
+                    let width = ta.unwrap_or(IntTy::I32);
+                    let shift = u32::try_from(b).ok().filter(|&shift| shift < width.bits());
+                    let Some(shift) = shift else {
+                        let side = if is_left { "left" } else { "right" };
+                        return Err(Interrupt::Panic(format!(
+                            "attempt to shift {} with overflow",
+                            side
+                        )));
+                    };
+                    let shifted = if is_left {
+                        width.wrap(a.wrapping_shl(shift))
+                    } else {
+                        Some(a >> shift)
+                    };
+                    checked(shifted, Some(width), "shift").map(|value| match value {
+                        Value::Int(int, _) => Value::Int(int, *ta),
+                        value => value,
+                    })
+                }
+                B::Eq(_) => Ok(Value::Bool(a == b)),
+                B::Ne(_) => Ok(Value::Bool(a != b)),
+                B::Lt(_) => Ok(Value::Bool(a < b)),
+                B::Le(_) => Ok(Value::Bool(a <= b)),
+                B::Gt(_) => Ok(Value::Bool(a > b)),
+                B::Ge(_) => Ok(Value::Bool(a >= b)),
+                op => unsupported(op),
+            }
+        }
+        (Value::Bool(a), Value::Bool(b)) => match op {
+            B::BitAnd(_) | B::BitAndAssign(_) | B::And(_) => Ok(Value::Bool(*a && *b)),
+            B::BitOr(_) | B::BitOrAssign(_) | B::Or(_) => Ok(Value::Bool(*a || *b)),
+            B::BitXor(_) | B::BitXorAssign(_) | B::Ne(_) => Ok(Value::Bool(a != b)),
+            B::Eq(_) => Ok(Value::Bool(a == b)),
+            B::Lt(_) => Ok(Value::Bool(a < b)),
+            B::Le(_) => Ok(Value::Bool(a <= b)),
+            B::Gt(_) => Ok(Value::Bool(a > b)),
+            B::Ge(_) => Ok(Value::Bool(a >= b)),
+            op => unsupported(op),
+        },
+        (Value::Char(a), Value::Char(b)) => match op {
+            B::Eq(_) => Ok(Value::Bool(a == b)),
+            B::Ne(_) => Ok(Value::Bool(a != b)),
+            B::Lt(_) => Ok(Value::Bool(a < b)),
+            B::Le(_) => Ok(Value::Bool(a <= b)),
+            B::Gt(_) => Ok(Value::Bool(a > b)),
+            B::Ge(_) => Ok(Value::Bool(a >= b)),
+            op => unsupported(op),
+        },
+        (left, right) => match op {
+            B::Eq(_) => Ok(Value::Bool(left.equals(right))),
+            B::Ne(_) => Ok(Value::Bool(!left.equals(right))),
+            op => unsupported(op),
+        },
+    }
+}
+
+/// Whether one value is less than another, by `<`
+fn is_less(left: &Value, right: &Value) -> Flow<bool> {
+    Ok(binary(&syn::BinOp::Lt(Default::default()), left, right)? == Value::Bool(true))
+}
+
+/// What a loop does after running its body once: `None` to go round again, or else what the loop comes to
+fn after_body(result: Flow<Value>, label: &Option<String>) -> Option<Flow<Value>> {
+    match result {
+        Ok(_) => None,
+        Err(Interrupt::Break(target, value)) if is_for(&target, label) => Some(Ok(value)),
+        Err(Interrupt::Continue(target)) if is_for(&target, label) => None,
+        Err(interrupt) => Some(Err(interrupt)),
+    }
+}
+
+/// The elements a `for` loop goes through, or `None` if the value can't be iterated
+fn elements_of(value: Value) -> Option<Value> {
+    match value {
+        Value::Range(..) | Value::Array(_) => Some(value),
+        // an `Option` iterates over its value, if any
+        Value::Variant(name, mut fields) if name == "Some" && fields.len() == 1 => {
+            fields.pop().map(|value| Value::Array(vec![value]))
+        }
+        Value::Variant(name, fields) if name == "None" && fields.is_empty() => {
+            Some(Value::Array(vec![]))
+        }
+        _ => None,
+    }
+}
+
+/// Take the next element of what a `for` loop goes through
+fn next_element(elements: &mut Value) -> Option<Value> {
+    match elements {
+        Value::Range(start, end, ty) if *start < *end => {
+            let next = Value::Int(*start, *ty);
+            *start += 1;
+            Some(next)
+        }
+        Value::Array(values) if !values.is_empty() => Some(values.remove(0)),
+        _ => None,
+    }
+}
+
+/// Every element of an array, or of a range, made all at once
+fn materialize(value: &Value) -> Flow<Vec<Value>> {
+    match value {
+        Value::Array(values) => Ok(values.clone()),
+        Value::Range(start, end, ty) => {
+            let length = end.saturating_sub(*start).max(0);
+            if length > i128::try_from(MAX_LENGTH).unwrap_or(i128::MAX) {
+                return Err(Interrupt::Stop(format!(
+                    "can't make a range of {} elements at once",
+                    length
+                )));
+            }
+            Ok((*start..*end).map(|int| Value::Int(int, *ty)).collect())
+        }
+        value => Err(Interrupt::Stop(format!("can't iterate over {}", value))),
+    }
+}
+
+/// A step of a place: a named field, a field by position, or an element
+#[derive(Debug)]
+enum Projection {
+    /// A named field of a struct
+    Field(String),
+    /// A field of a tuple or tuple struct, by position, or an element of an array
+    Index(usize),
+}
+
+/// The step into a value a field access takes
+fn projection_of(member: &syn::Member) -> Projection {
+    match member {
+        syn::Member::Named(name) => Projection::Field(name.to_string()),
+        syn::Member::Unnamed(index) => {
+            Projection::Index(usize::try_from(index.index).unwrap_or(usize::MAX))
+        }
+    }
+}
+
+/// Follow a step into a value
+fn project<'v>(value: &'v mut Value, projection: &Projection) -> Flow<&'v mut Value> {
+    match (value, projection) {
+        (Value::Struct(_, fields), Projection::Field(name)) => {
+            match fields.iter_mut().find(|(field, _)| field == name) {
+                Some((_, value)) => Ok(value),
+                None => Err(Interrupt::Stop(format!("no field `{}`", name))),
+            }
+        }
+        (Value::Array(values), Projection::Index(index)) => {
+            let length = values.len();
+            values.get_mut(*index).ok_or_else(|| {
+                Interrupt::Panic(format!(
+                    "index out of bounds: the len is {} but the index is {}",
+                    length, index
+                ))
+            })
+        }
+        (Value::Tuple(values) | Value::Variant(_, values), Projection::Index(index)) => values
+            .get_mut(*index)
+            .ok_or_else(|| Interrupt::Stop(format!("no field {}", index))),
+        (value, projection) => Err(Interrupt::Stop(format!(
+            "can't take {:?} of {}",
+            projection, value
+        ))),
+    }
+}
+
+/// The interpreter, part way through a run
+#[derive(Debug)]
+struct Machine<'a> {
+    /// The functions it can call
+    graph: &'a CallGraph,
+    /// The arguments of every macro, parsed
+    macros: &'a Macros,
+    /// The closures made so far, by place, with the function each is in
+    closures: HashMap<usize, (&'a syn::ExprClosure, usize)>,
+    /// The state of the run
+    state: State,
+    /// How many steps it has taken
+    steps: usize,
+    /// The most steps it may take
+    budget: usize,
+    /// The state Brent's algorithm compares against, and its hash
+    tortoise: Option<(u64, State)>,
+    /// How far ahead of the tortoise the state may get before the tortoise jumps to it
+    power: usize,
+    /// How far ahead of the tortoise the state is
+    lambda: usize,
+}
+
+impl<'a> Machine<'a> {
+    /// Count a step, stopping the run when there are none left
+    fn step(&mut self) -> Flow<()> {
+        self.steps += 1;
+        if self.steps > self.budget {
+            return Err(Interrupt::Stop(format!(
+                "neither returns nor repeats a state within {} steps",
+                self.budget
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check whether the state at the head of a loop was seen before, by Brent's algorithm
+    fn loop_head(&mut self) -> Flow<()> {
+        let mut hasher = DefaultHasher::new();
+        self.state.hash(&mut hasher);
+        let hash = hasher.finish();
+        if let Some((seen, tortoise)) = &self.tortoise {
+            if *seen == hash && *tortoise == self.state {
+                return Err(Interrupt::Repeat(self.lambda));
+            }
+        }
+        if self.tortoise.is_none() || self.power == self.lambda {
+            self.tortoise = Some((hash, self.state.clone()));
+            self.power *= 2;
+            self.lambda = 0;
+        }
+        self.lambda += 1;
+        Ok(())
+    }
+
+    /// The frame of the call running
+    fn frame(&mut self) -> &mut Frame {
+        self.state
+            .frames
+            .last_mut()
+            .expect("a run always has a frame")
+    }
+
+    /// The innermost scope of the call running
+    fn scope(&mut self) -> &mut Vec<Local> {
+        self.frame()
+            .scopes
+            .last_mut()
+            .expect("a frame always has a scope")
+    }
+
+    /// The local of a name, from the innermost scope out
+    fn local(&mut self, name: &str) -> Option<&mut Local> {
+        self.frame()
+            .scopes
+            .iter_mut()
+            .rev()
+            .flat_map(|scope| scope.iter_mut().rev())
+            .find(|local| local.name == name)
+    }
+
+    /// Run something in a new scope with some locals, dropping the scope after
+    fn scoped<T>(&mut self, locals: Vec<Local>, run: impl FnOnce(&mut Self) -> Flow<T>) -> Flow<T> {
+        self.frame().scopes.push(locals);
+        let result = run(self);
+        self.frame().scopes.pop();
+        result
+    }
+
+    /// Evaluate something while holding a value, which is part of the state until it's done
+    fn holding<T>(
+        &mut self,
+        value: Value,
+        run: impl FnOnce(&mut Self) -> Flow<T>,
+    ) -> Flow<(Value, T)> {
+        self.state.held.push(value);
+        let result = run(self);
+        let value = self.state.held.pop().expect("a held value is still held");
+        result.map(|result| (value, result))
+    }
+
+    /// Evaluate expressions in order, holding each value until the last is done
+    fn eval_all(&mut self, exprs: impl IntoIterator<Item = &'a syn::Expr>) -> Flow<Vec<Value>> {
+        let depth = self.state.held.len();
+        let mut result = Ok(());
+        for expr in exprs {
+            match self.eval(expr) {
+                Ok(value) => self.state.held.push(value),
+                Err(interrupt) => {
+                    result = Err(interrupt);
+                    break;
+                }
+            }
+        }
+        let values = self.state.held.split_off(depth);
+        result.map(|()| values)
+    }
+
+    /// Call a function of the call graph with some arguments
+    fn call_function(&mut self, function: usize, args: Vec<Value>) -> Flow<Value> {
+        let ast = &self.graph.functions[function].ast;
+        if ast.sig.inputs.len() != args.len() {
+            return Err(Interrupt::Stop(format!(
+                "`{}` takes {} arguments, not {}",
+                ast.sig.ident,
+                ast.sig.inputs.len(),
+                args.len()
+            )));
+        }
+        let mut locals = vec![];
+        for (input, arg) in ast.sig.inputs.iter().zip(args) {
+            match input {
+                syn::FnArg::Receiver(receiver) if receiver.mutability.is_some() => {
+                    return unsupported(receiver);
+                }
+                syn::FnArg::Receiver(_) => locals.push(Local {
+                    name: "self".to_string(),
+                    value: arg,
+                    ty: None,
+                }),
+                syn::FnArg::Typed(param) => {
+                    if matches!(&*param.ty, syn::Type::Reference(reference) if reference.mutability.is_some())
+                    {
+                        return unsupported(param);
+                    }
+                    let ty = IntTy::from_type(&param.ty);
+                    let arg = with_type(arg, ty)?;
+                    if !self.bind(&param.pat, &arg, ty, &mut locals)? {
+                        return unsupported(&param.pat);
+                    }
+                }
+            }
+        }
+        let returns = match &ast.sig.output {
+            syn::ReturnType::Type(_, ty) => IntTy::from_type(ty),
+            syn::ReturnType::Default => None,
+        };
+        let frame = Frame {
+            function,
+            scopes: vec![locals],
+        };
+        let (_, value) = self.in_frame(frame, |machine| machine.block(&ast.block))?;
+        with_type(value, returns)
+    }
+
+    /// Call a closure with some arguments
+    fn call_closure(
+        &mut self,
+        id: usize,
+        captured: &[(String, Value)],
+        args: Vec<Value>,
+    ) -> Flow<Value> {
+        let Some(&(closure, function)) = self.closures.get(&id) else {
+            return Err(Interrupt::Stop(
+                "a closure from outside the run".to_string(),
+            ));
+        };
+        if closure.inputs.len() != args.len() {
+            return Err(Interrupt::Stop(format!(
+                "a closure of {} arguments called with {}",
+                closure.inputs.len(),
+                args.len()
+            )));
+        }
+        let mut locals = vec![];
+        for (pat, arg) in closure.inputs.iter().zip(args) {
+            if !self.bind(pat, &arg, None, &mut locals)? {
+                return unsupported(pat);
+            }
+        }
+        let captured_locals = captured
+            .iter()
+            .map(|(name, value)| Local {
+                name: name.clone(),
+                value: value.clone(),
+                ty: None,
+            })
+            .collect();
+        let frame = Frame {
+            function,
+            scopes: vec![captured_locals, locals],
+        };
+        let (frame, value) = self.in_frame(frame, |machine| machine.eval(&closure.body))?;
+        // a closure which changes what it captures would change the locals of its caller, which isn't run
+        let unchanged = frame.scopes[0]
+            .iter()
+            .zip(captured)
+            .all(|(local, (_, value))| local.value == *value);
+        if !unchanged {
+            return Err(Interrupt::Stop(
+                "can't run a closure which changes what it captures".to_string(),
+            ));
+        }
+        Ok(value)
+    }
+
+    /// Run the body of a function or closure in a frame of its own, and take what it returns
+    fn in_frame(
+        &mut self,
+        frame: Frame,
+        run: impl FnOnce(&mut Self) -> Flow<Value>,
+    ) -> Flow<(Frame, Value)> {
+        if self.state.frames.len() >= MAX_DEPTH {
+            return Err(Interrupt::Stop(format!(
+                "the call stack grows past {} frames",
+                MAX_DEPTH
+            )));
+        }
+        self.state.frames.push(frame);
+        let result = run(self);
+        let frame = self.state.frames.pop().expect("the frame just pushed");
+        match result {
+            Ok(value) | Err(Interrupt::Return(value)) => Ok((frame, value)),
+            Err(Interrupt::Break(..) | Interrupt::Continue(_)) => Err(Interrupt::Stop(
+                "a `break` or `continue` outside of a loop".to_string(),
+            )),
+            Err(interrupt) => Err(interrupt),
+        }
+    }
+
+    /// Run a block in a scope of its own
+    fn block(&mut self, block: &'a syn::Block) -> Flow<Value> {
+        self.scoped(vec![], |machine| {
+            let mut value = Value::unit();
+            for stmt in &block.stmts {
+                value = match stmt {
+                    syn::Stmt::Local(local) => {
+                        machine.local_statement(local)?;
+                        Value::unit()
+                    }
+                    // nested functions are in the call graph already
+                    syn::Stmt::Item(syn::Item::Fn(_)) => Value::unit(),
+                    syn::Stmt::Item(item) => return unsupported(item),
+                    syn::Stmt::Expr(expr, None) => machine.eval(expr)?,
+                    syn::Stmt::Expr(expr, Some(_)) => {
+                        machine.eval(expr)?;
+                        Value::unit()
+                    }
+                    syn::Stmt::Macro(stmt) => {
+                        machine.step()?;
+                        machine.state.path.push(place_of(stmt));
+                        let result = machine.macro_call(&stmt.mac);
+                        machine.state.path.pop();
+                        result?;
+                        Value::unit()
+                    }
+                };
+            }
+            Ok(value)
+        })
+    }
+
+    /// Run a `let` statement
+    fn local_statement(&mut self, local: &'a syn::Local) -> Flow<()> {
+        let Some(init) = &local.init else {
+            return unsupported(local);
+        };
+        let (pat, ty) = match &local.pat {
+            syn::Pat::Type(typed) => (&*typed.pat, IntTy::from_type(&typed.ty)),
+            pat => (pat, None),
+        };
+        let value = with_type(self.eval(&init.expr)?, ty)?;
+        let mut locals = vec![];
+        if self.bind(pat, &value, ty, &mut locals)? {
+            self.scope().extend(locals);
+            return Ok(());
+        }
+        match &init.diverge {
+            Some((_, diverge)) => {
+                self.eval(diverge)?;
+                Err(Interrupt::Stop(
+                    "the `else` of a `let` which doesn't diverge".to_string(),
+                ))
+            }
+            None => Err(Interrupt::Stop(format!(
+                "a `let` whose pattern doesn't match {}",
+                value
+            ))),
+        }
+    }
+
+    /// Match a value against a pattern, adding the locals it binds
+    fn bind(
+        &mut self,
+        pat: &'a syn::Pat,
+        value: &Value,
+        ty: Option<IntTy>,
+        locals: &mut Vec<Local>,
+    ) -> Flow<bool> {
+        /// Match values against patterns with at most one `..` among them
+        fn bind_all<'a>(
+            machine: &mut Machine<'a>,
+            pats: impl IntoIterator<Item = &'a syn::Pat>,
+            values: &[Value],
+            locals: &mut Vec<Local>,
+        ) -> Flow<bool> {
+            let pats: Vec<&syn::Pat> = pats.into_iter().collect();
+            let rest = pats.iter().position(|pat| match pat {
+                syn::Pat::Rest(_) => true,
+                syn::Pat::Ident(ident) => {
+                    matches!(&ident.subpat, Some((_, subpat)) if matches!(subpat.as_ref(), syn::Pat::Rest(_)))
+                }
+                _ => false,
+            });
+            let Some(rest) = rest else {
+                if pats.len() != values.len() {
+                    return Ok(false);
+                }
+                for (pat, value) in pats.iter().zip(values) {
+                    if !machine.bind(pat, value, None, locals)? {
+                        return Ok(false);
+                    }
+                }
+                return Ok(true);
+            };
+            let after = pats.len() - rest - 1;
+            if values.len() < rest + after {
+                return Ok(false);
+            }
+            for (pat, value) in pats[..rest].iter().zip(values) {
+                if !machine.bind(pat, value, None, locals)? {
+                    return Ok(false);
+                }
+            }
+            for (pat, value) in pats[rest + 1..].iter().zip(&values[values.len() - after..]) {
+                if !machine.bind(pat, value, None, locals)? {
+                    return Ok(false);
+                }
+            }
+            // `rest @ ..` binds what's left over, as a slice
+            if let syn::Pat::Ident(ident) = pats[rest] {
+                locals.push(Local {
+                    name: ident.ident.to_string(),
+                    value: Value::Array(values[rest..values.len() - after].to_vec()),
+                    ty: None,
+                });
+            }
+            Ok(true)
+        }
+
+        Ok(match pat {
+            syn::Pat::Wild(_) => true,
+            syn::Pat::Ident(ident) if !is_capitalized(&ident.ident.to_string()) => {
+                if let Some((_, subpat)) = &ident.subpat {
+                    if !self.bind(subpat, value, ty, locals)? {
+                        return Ok(false);
+                    }
+                }
+                locals.push(Local {
+                    name: ident.ident.to_string(),
+                    value: value.clone(),
+                    ty,
+                });
+                true
+            }
+            // a unit variant or unit struct, like `None`
+            syn::Pat::Ident(ident) => {
+                matches!(value, Value::Variant(name, fields) if ident.ident == name && fields.is_empty())
+            }
+            syn::Pat::Path(path) => {
+                let name = last_segment(&path.path);
+                matches!(value, Value::Variant(variant, fields) if *variant == name && fields.is_empty())
+            }
+            syn::Pat::Lit(lit) => {
+                let literal = self.literal(&lit.lit)?;
+                literal.equals(value)
+            }
+            syn::Pat::Range(range) => {
+                let (int, _) = int_of(value)?;
+                let start = match &range.start {
+                    Some(start) => int_of(&self.eval(start)?)?.0,
+                    None => i128::MIN,
+                };
+                let end = match &range.end {
+                    Some(end) => int_of(&self.eval(end)?)?.0,
+                    None => i128::MAX,
+                };
+                match range.limits {
+                    syn::RangeLimits::HalfOpen(_) => start <= int && int < end,
+                    syn::RangeLimits::Closed(_) => start <= int && int <= end,
+                }
+            }
+            syn::Pat::Or(or) => {
+                let before = locals.len();
+                for case in &or.cases {
+                    if self.bind(case, value, ty, locals)? {
+                        return Ok(true);
+                    }
+                    locals.truncate(before);
+                }
+                false
+            }
+            syn::Pat::Paren(paren) => self.bind(&paren.pat, value, ty, locals)?,
+            syn::Pat::Reference(reference) => self.bind(&reference.pat, value, ty, locals)?,
+            syn::Pat::Type(typed) => {
+                let ty = IntTy::from_type(&typed.ty);
+                self.bind(&typed.pat, value, ty, locals)?
+            }
+            syn::Pat::Tuple(tuple) => match value {
+                Value::Tuple(values) => bind_all(self, &tuple.elems, values, locals)?,
+                _ => false,
+            },
+            syn::Pat::TupleStruct(tuple) => match value {
+                Value::Variant(name, values) if *name == last_segment(&tuple.path) => {
+                    bind_all(self, &tuple.elems, values, locals)?
+                }
+                _ => false,
+            },
+            syn::Pat::Struct(strukt) => {
+                let name = last_segment(&strukt.path);
+                let Value::Struct(variant, fields) = value else {
+                    return Ok(false);
+                };
+                if *variant != name && name != "Self" {
+                    return Ok(false);
+                }
+                for field in &strukt.fields {
+                    let syn::Member::Named(member) = &field.member else {
+                        return unsupported(field);
+                    };
+                    let Some((_, value)) = fields.iter().find(|(name, _)| member == name) else {
+                        return Ok(false);
+                    };
+                    if !self.bind(&field.pat, value, None, locals)? {
+                        return Ok(false);
+                    }
+                }
+                true
+            }
+            syn::Pat::Slice(slice) => match value {
+                Value::Array(values) => bind_all(self, &slice.elems, values, locals)?,
+                _ => false,
+            },
+            pat => return unsupported(pat),
+        })
+    }
+
+    /// The value of a literal
+    fn literal(&mut self, lit: &syn::Lit) -> Flow<Value> {
+        match lit {
+            syn::Lit::Int(int) => {
+                let ty = IntTy::from_name(int.suffix());
+                match int.base10_parse::<i128>() {
+                    Ok(value) => checked(Some(value), ty, "write a literal"),
+                    Err(_) => unsupported(lit),
+                }
+            }
+            syn::Lit::Bool(b) => Ok(Value::Bool(b.value)),
+            syn::Lit::Char(c) => Ok(Value::Char(c.value())),
+            lit => unsupported(lit),
+        }
+    }
+
+    /// Evaluate an expression
+    fn eval(&mut self, expr: &'a syn::Expr) -> Flow<Value> {
+        self.step()?;
+        self.state.path.push(place_of(expr));
+        let result = self.eval_here(expr);
+        self.state.path.pop();
+        result
+    }
+
+    /// Evaluate an expression, whose place is already on the path.
+    /// Each kind of expression is evaluated by a method of its own, which keeps the frames of the recursion small.
+    fn eval_here(&mut self, expr: &'a syn::Expr) -> Flow<Value> {
+        match expr {
+            syn::Expr::Lit(lit) => self.literal(&lit.lit),
+            syn::Expr::Paren(paren) => self.eval(&paren.expr),
+            syn::Expr::Group(group) => self.eval(&group.expr),
+            syn::Expr::Path(path) => self.path(path),
+            syn::Expr::Unary(unary) => self.unary(unary),
+            syn::Expr::Binary(binary) => self.binary(binary),
+            syn::Expr::Assign(assign) => {
+                let value = self.eval(&assign.right)?;
+                self.assign(&assign.left, value)?;
+                Ok(Value::unit())
+            }
+            syn::Expr::Block(block) => self.labeled_block(block),
+            syn::Expr::If(expr_if) => self.if_expr(expr_if),
+            syn::Expr::While(expr_while) => self.while_expr(expr_while),
+            syn::Expr::Loop(expr_loop) => self.loop_expr(expr_loop),
+            syn::Expr::ForLoop(expr_for) => self.for_expr(expr_for),
+            syn::Expr::Break(expr_break) => {
+                let value = self.eval_optional(expr_break.expr.as_deref())?;
+                let label = expr_break
+                    .label
+                    .as_ref()
+                    .map(|label| label.ident.to_string());
+                Err(Interrupt::Break(label, value))
+            }
+            syn::Expr::Continue(expr_continue) => {
+                let label = expr_continue
+                    .label
+                    .as_ref()
+                    .map(|label| label.ident.to_string());
+                Err(Interrupt::Continue(label))
+            }
+            syn::Expr::Return(expr_return) => {
+                let value = self.eval_optional(expr_return.expr.as_deref())?;
+                Err(Interrupt::Return(value))
+            }
+            syn::Expr::Call(call) => self.call_expr(call),
+            syn::Expr::MethodCall(call) => self.method_call(call),
+            syn::Expr::Closure(closure) => Ok(self.closure(closure)),
+            syn::Expr::Tuple(tuple) => Ok(Value::Tuple(self.eval_all(&tuple.elems)?)),
+            syn::Expr::Array(array) => Ok(Value::Array(self.eval_all(&array.elems)?)),
+            syn::Expr::Repeat(repeat) => {
+                let values = self.eval_all([&*repeat.expr, &*repeat.len])?;
+                self.repeat(&values[0], &values[1])
+            }
+            syn::Expr::Index(index) => self.index(index),
+            syn::Expr::Field(field) => {
+                let mut base = self.eval(&field.base)?;
+                project(&mut base, &projection_of(&field.member)).cloned()
+            }
+            syn::Expr::Struct(strukt) => self.struct_expr(strukt),
+            syn::Expr::Match(expr_match) => self.match_expr(expr_match),
+            syn::Expr::Range(range) => self.range(range),
+            syn::Expr::Reference(reference) if reference.mutability.is_none() => {
+                self.eval(&reference.expr)
+            }
+            syn::Expr::Cast(cast) => self.cast(cast),
+            syn::Expr::Macro(mac) => self.macro_call(&mac.mac),
+            syn::Expr::Try(expr_try) => self.try_expr(expr_try),
+            expr => unsupported(expr),
+        }
+    }
+
+    /// Evaluate an expression if there is one, like the value of `break` or `return`, or else give `()`
+    fn eval_optional(&mut self, expr: Option<&'a syn::Expr>) -> Flow<Value> {
+        match expr {
+            Some(expr) => self.eval(expr),
+            None => Ok(Value::unit()),
+        }
+    }
+
+    /// Evaluate a unary expression
+    fn unary(&mut self, unary: &'a syn::ExprUnary) -> Flow<Value> {
+        let value = self.eval(&unary.expr)?;
+        match (&unary.op, value) {
+            (syn::UnOp::Deref(_), value) => Ok(value),
+            (syn::UnOp::Not(_), Value::Bool(b)) => Ok(Value::Bool(!b)),
+            (syn::UnOp::Not(_), Value::Int(int, ty)) => {
+                let width = ty.unwrap_or(IntTy::I32);
+                let not = if width.is_signed() {
+                    Some(-int - 1)
+                } else {
+                    width.bounds().1.checked_sub(int)
+                };
+                checked(not, ty, "negate")
+            }
+            (syn::UnOp::Neg(_), Value::Int(int, ty)) => checked(int.checked_neg(), ty, "negate"),
+            _ => unsupported(unary),
+        }
+    }
+
+    /// Evaluate a block, which `break` with its label leaves with a value
+    fn labeled_block(&mut self, block: &'a syn::ExprBlock) -> Flow<Value> {
+        let label = label_of(block.label.as_ref());
+        match self.block(&block.block) {
+            Err(Interrupt::Break(Some(target), value)) if Some(&target) == label.as_ref() => {
+                Ok(value)
+            }
+            result => result,
+        }
+    }
+
+    /// Evaluate `if` or `if let`
+    fn if_expr(&mut self, expr_if: &'a syn::ExprIf) -> Flow<Value> {
+        match self.condition(&expr_if.cond)? {
+            Some(locals) => self.scoped(locals, |machine| machine.block(&expr_if.then_branch)),
+            None => self.eval_optional(
+                expr_if
+                    .else_branch
+                    .as_ref()
+                    .map(|(_, otherwise)| &**otherwise),
+            ),
+        }
+    }
+
+    /// Evaluate `while` or `while let`
+    fn while_expr(&mut self, expr_while: &'a syn::ExprWhile) -> Flow<Value> {
+        let label = label_of(expr_while.label.as_ref());
+        loop {
+            self.loop_head()?;
+            let Some(locals) = self.condition(&expr_while.cond)? else {
+                return Ok(Value::unit());
+            };
+            let result = self.scoped(locals, |machine| machine.block(&expr_while.body));
+            if let Some(value) = after_body(result, &label) {
+                return value;
+            }
+        }
+    }
+
+    /// Evaluate `loop`
+    fn loop_expr(&mut self, expr_loop: &'a syn::ExprLoop) -> Flow<Value> {
+        let label = label_of(expr_loop.label.as_ref());
+        loop {
+            self.loop_head()?;
+            let result = self.block(&expr_loop.body);
+            if let Some(value) = after_body(result, &label) {
+                return value;
+            }
+        }
+    }
+
+    /// Evaluate `for`
+    fn for_expr(&mut self, expr_for: &'a syn::ExprForLoop) -> Flow<Value> {
+        let label = label_of(expr_for.label.as_ref());
+        let iterable = self.eval(&expr_for.expr)?;
+        let Some(elements) = elements_of(iterable) else {
+            return unsupported(&expr_for.expr);
+        };
+        // what's left to go through is part of the state
+        let (_, value) = self.holding(elements, |machine| loop {
+            machine.loop_head()?;
+            let elements = machine
+                .state
+                .held
+                .last_mut()
+                .expect("the elements are held");
+            let Some(element) = next_element(elements) else {
+                return Ok(Value::unit());
+            };
+            let mut locals = vec![];
+            if !machine.bind(&expr_for.pat, &element, None, &mut locals)? {
+                return unsupported(&expr_for.pat);
+            }
+            let result = machine.scoped(locals, |machine| machine.block(&expr_for.body));
+            if let Some(value) = after_body(result, &label) {
+                return value;
+            }
+        })?;
+        Ok(value)
+    }
+
+    /// Make a closure, which captures a copy of every local it can see
+    fn closure(&mut self, closure: &'a syn::ExprClosure) -> Value {
+        let function = self.frame().function;
+        self.closures.insert(place_of(closure), (closure, function));
+        let mut captured: Vec<(String, Value)> = vec![];
+        for local in self.frame().scopes.iter().flatten() {
+            captured.retain(|(name, _)| *name != local.name);
+            captured.push((local.name.clone(), local.value.clone()));
+        }
+        Value::Closure(place_of(closure), captured)
+    }
+
+    /// Evaluate indexing, by a position or a range
+    fn index(&mut self, index: &'a syn::ExprIndex) -> Flow<Value> {
+        let values = self.eval_all([&*index.expr, &*index.index])?;
+        match (&values[0], &values[1]) {
+            (Value::Array(elements), Value::Range(start, end, _)) => {
+                let (start, end) = (
+                    usize::try_from(*start).unwrap_or(usize::MAX),
+                    usize::try_from(*end).unwrap_or(usize::MAX),
+                );
+                if start > end || end > elements.len() {
+                    return Err(Interrupt::Panic(format!(
+                        "range {}..{} out of range for slice of length {}",
+                        start,
+                        end,
+                        elements.len()
+                    )));
+                }
+                Ok(Value::Array(elements[start..end].to_vec()))
+            }
+            (Value::Array(elements), position) => {
+                let position = index_of(position)?;
+                elements.get(position).cloned().ok_or_else(|| {
+                    Interrupt::Panic(format!(
+                        "index out of bounds: the len is {} but the index is {}",
+                        elements.len(),
+                        position
+                    ))
+                })
+            }
+            _ => unsupported(index),
+        }
+    }
+
+    /// Evaluate a struct literal
+    fn struct_expr(&mut self, strukt: &'a syn::ExprStruct) -> Flow<Value> {
+        if strukt.rest.is_some() {
+            return unsupported(strukt);
+        }
+        let mut name = last_segment(&strukt.path);
+        if name == "Self" {
+            let function = self.frame().function;
+            name = self.graph.functions[function].owner.clone().unwrap_or(name);
+        }
+        let values = self.eval_all(strukt.fields.iter().map(|field| &field.expr))?;
+        let mut fields = vec![];
+        for (field, value) in strukt.fields.iter().zip(values) {
+            let syn::Member::Named(member) = &field.member else {
+                return unsupported(field);
+            };
+            fields.push((member.to_string(), value));
+        }
+        Ok(Value::Struct(name, fields))
+    }
+
+    /// Evaluate `match`, taking the first arm whose pattern matches and whose guard holds
+    fn match_expr(&mut self, expr_match: &'a syn::ExprMatch) -> Flow<Value> {
+        let value = self.eval(&expr_match.expr)?;
+        for arm in &expr_match.arms {
+            let mut locals = vec![];
+            if !self.bind(&arm.pat, &value, None, &mut locals)? {
+                continue;
+            }
+            let taken = self.scoped(locals, |machine| {
+                if let Some((_, guard)) = &arm.guard {
+                    if machine.eval(guard)? != Value::Bool(true) {
+                        return Ok(None);
+                    }
+                }
+                machine.eval(&arm.body).map(Some)
+            })?;
+            if let Some(value) = taken {
+                return Ok(value);
+            }
+        }
+        Err(Interrupt::Stop(format!(
+            "no arm of a `match` matches {}",
+            value
+        )))
+    }
+
+    /// Evaluate a range with both ends
+    fn range(&mut self, range: &'a syn::ExprRange) -> Flow<Value> {
+        let (Some(start), Some(end)) = (&range.start, &range.end) else {
+            return unsupported(range);
+        };
+        let values = self.eval_all([&**start, &**end])?;
+        let ((start, ts), (end, te)) = (int_of(&values[0])?, int_of(&values[1])?);
+        let end = match range.limits {
+            syn::RangeLimits::HalfOpen(_) => end,
+            syn::RangeLimits::Closed(_) => end.saturating_add(1),
+        };
+        Ok(Value::Range(start, end, ts.or(te)))
+    }
+
+    /// Evaluate `as`, between integers, bools and chars
+    fn cast(&mut self, cast: &'a syn::ExprCast) -> Flow<Value> {
+        let value = self.eval(&cast.expr)?;
+        let Some(ty) = IntTy::from_type(&cast.ty) else {
+            return unsupported(cast);
+        };
+        let int = match value {
+            Value::Int(int, _) => int,
+            Value::Bool(b) => i128::from(b),
+            Value::Char(c) => i128::from(u32::from(c)),
+            _ => return unsupported(cast),
+        };
+        match ty.wrap(int) {
+            Some(int) => Ok(Value::Int(int, Some(ty))),
+            None => unsupported(cast),
+        }
+    }
+
+    /// Evaluate `?`, which returns `None` and `Err` from the function
+    fn try_expr(&mut self, expr_try: &'a syn::ExprTry) -> Flow<Value> {
+        match self.eval(&expr_try.expr)? {
+            Value::Variant(name, mut fields)
+                if (name == "Some" || name == "Ok") && fields.len() == 1 =>
+            {
+                Ok(fields.pop().expect("one field"))
+            }
+            Value::Variant(name, fields) if name == "None" && fields.is_empty() => {
+                Err(Interrupt::Return(Value::none()))
+            }
+            Value::Variant(name, fields) if name == "Err" && fields.len() == 1 => {
+                Err(Interrupt::Return(Value::Variant(name, fields)))
+            }
+            _ => unsupported(expr_try),
+        }
+    }
+
+    /// The value of a path: a local, a function, or a unit variant or struct
+    fn path(&mut self, path: &'a syn::ExprPath) -> Flow<Value> {
+        if let Some(ident) = path.path.get_ident() {
+            if let Some(local) = self.local(&ident.to_string()) {
+                return Ok(local.value.clone());
+            }
+        }
+        let segments: Vec<String> = path
+            .path
+            .segments
+            .iter()
+            .map(|segment| segment.ident.to_string())
+            .collect();
+        let caller = self.frame().function;
+        if let Some(function) = self.graph.resolve_path(caller, &segments) {
+            return Ok(Value::Function(function));
+        }
+        let name = last_segment(&path.path);
+        if let ([ty, bound], Some(int_ty)) = (segments.as_slice(), IntTy::from_name(&segments[0])) {
+            let (least, greatest) = int_ty.bounds();
+            match bound.as_str() {
+                "MIN" => return Ok(Value::Int(least, Some(int_ty))),
+                "MAX" if ty != "u128" => return Ok(Value::Int(greatest, Some(int_ty))),
+                _ => {}
+            }
+        }
+        // `List::Nil` is a variant, but `Self::LIMIT` is a constant
+        let is_constant = name.len() > 1 && !name.contains(char::is_lowercase);
+        let is_unit = segments.len() > 1 || name == "None" || self.graph.types.is_defined(&name);
+        if is_capitalized(&name) && is_unit && !is_constant {
+            return Ok(Value::Variant(name, vec![]));
+        }
+        unsupported(path)
+    }
+
+    /// Evaluate a binary expression, short-circuiting `&&` and `||`, and assigning for `+=` and the like
+    fn binary(&mut self, expr: &'a syn::ExprBinary) -> Flow<Value> {
+        use syn::BinOp as B;
+        match expr.op {
+            B::And(_) | B::Or(_) => {
+                let is_and = matches!(expr.op, B::And(_));
+                match self.eval(&expr.left)? {
+                    Value::Bool(left) if left != is_and => Ok(Value::Bool(left)),
+                    Value::Bool(_) => match self.eval(&expr.right)? {
+                        Value::Bool(right) => Ok(Value::Bool(right)),
+                        _ => unsupported(expr),
+                    },
+                    _ => unsupported(expr),
+                }
+            }
+            B::AddAssign(_)
+            | B::SubAssign(_)
+            | B::MulAssign(_)
+            | B::DivAssign(_)
+            | B::RemAssign(_)
+            | B::BitXorAssign(_)
+            | B::BitAndAssign(_)
+            | B::BitOrAssign(_)
+            | B::ShlAssign(_)
+            | B::ShrAssign(_) => {
+                let right = self.eval(&expr.right)?;
+                let left = self.eval(&expr.left)?;
+                let value = binary(&expr.op, &left, &right)?;
+                self.assign(&expr.left, value)?;
+                Ok(Value::unit())
+            }
+            _ => {
+                let values = self.eval_all([&*expr.left, &*expr.right])?;
+                binary(&expr.op, &values[0], &values[1])
+            }
+        }
+    }
+
+    /// The condition of an `if` or `while`: the locals it binds if it holds, or `None` if it doesn't
+    fn condition(&mut self, cond: &'a syn::Expr) -> Flow<Option<Vec<Local>>> {
+        if let syn::Expr::Let(expr_let) = cond {
+            let value = self.eval(&expr_let.expr)?;
+            let mut locals = vec![];
+            let matched = self.bind(&expr_let.pat, &value, None, &mut locals)?;
+            return Ok(matched.then_some(locals));
+        }
+        match self.eval(cond)? {
+            Value::Bool(true) => Ok(Some(vec![])),
+            Value::Bool(false) => Ok(None),
+            _ => unsupported(cond),
+        }
+    }
+
+    /// The steps into a local which an expression assigned to takes, evaluating any indices on the way
+    fn place(&mut self, expr: &'a syn::Expr) -> Flow<(String, Vec<Projection>)> {
+        match expr {
+            syn::Expr::Path(path) => match path.path.get_ident() {
+                Some(ident) => Ok((ident.to_string(), vec![])),
+                None => unsupported(expr),
+            },
+            syn::Expr::Paren(paren) => self.place(&paren.expr),
+            syn::Expr::Unary(unary) if matches!(unary.op, syn::UnOp::Deref(_)) => {
+                self.place(&unary.expr)
+            }
+            syn::Expr::Field(field) => {
+                let (local, mut projections) = self.place(&field.base)?;
+                projections.push(projection_of(&field.member));
+                Ok((local, projections))
+            }
+            syn::Expr::Index(index) => {
+                let (local, mut projections) = self.place(&index.expr)?;
+                let position = self.eval(&index.index)?;
+                projections.push(Projection::Index(index_of(&position)?));
+                Ok((local, projections))
+            }
+            _ => unsupported(expr),
+        }
+    }
+
+    /// The value at the end of some steps into a local, and the integer type the local was declared with
+    fn target(
+        &mut self,
+        local: &str,
+        projections: &[Projection],
+    ) -> Flow<(&mut Value, Option<IntTy>)> {
+        let Some(local) = self.local(local) else {
+            return Err(Interrupt::Stop(format!("no local `{}`", local)));
+        };
+        let ty = local.ty;
+        let mut value = &mut local.value;
+        for projection in projections {
+            value = project(value, projection)?;
+        }
+        Ok((value, ty))
+    }
+
+    /// Assign a value to the place an expression names
+    fn assign(&mut self, expr: &'a syn::Expr, value: Value) -> Flow<()> {
+        let (local, projections) = self.place(expr)?;
+        let (target, declared) = self.target(&local, &projections)?;
+        // an integer takes on the type of the place it's assigned to
+        let ty = match target {
+            Value::Int(_, ty) => ty.or(declared),
+            _ => declared,
+        };
+        *target = with_type(value, ty)?;
+        Ok(())
+    }
+
+    /// `[element; length]`
+    fn repeat(&mut self, element: &Value, length: &Value) -> Flow<Value> {
+        let length = index_of(length)?;
+        if length > MAX_LENGTH {
+            return Err(Interrupt::Stop(format!(
+                "can't make an array of {} elements",
+                length
+            )));
+        }
+        Ok(Value::Array(vec![element.clone(); length]))
+    }
+
+    /// Evaluate a call: of a function of the call graph, a closure, a constructor, or a function of the standard library
+    fn call_expr(&mut self, call: &'a syn::ExprCall) -> Flow<Value> {
+        let callee = match &*call.func {
+            syn::Expr::Path(path) => {
+                let name = last_segment(&path.path);
+                match self.path(path) {
+                    // a tuple struct or a variant with fields, like `List::Cons(1, tail)`
+                    Ok(Value::Variant(name, fields)) if fields.is_empty() => {
+                        let values = self.eval_all(&call.args)?;
+                        return Ok(Value::Variant(name, values));
+                    }
+                    Ok(callee) => callee,
+                    Err(Interrupt::Stop(_)) if is_capitalized(&name) => {
+                        // a tuple struct or a variant with fields, like `Some(x)` or `List::Cons(1, tail)`
+                        let values = self.eval_all(&call.args)?;
+                        return Ok(Value::Variant(name, values));
+                    }
+                    Err(Interrupt::Stop(_)) => {
+                        let args = self.eval_all(&call.args)?;
+                        return self.library_call(call, &path.path, args);
+                    }
+                    Err(interrupt) => return Err(interrupt),
+                }
+            }
+            func => self.eval(func)?,
+        };
+        let (callee, args) = self.holding(callee, |machine| machine.eval_all(&call.args))?;
+        match callee {
+            Value::Function(function) => self.call_function(function, args),
+            Value::Closure(id, captured) => self.call_closure(id, &captured, args),
+            _ => unsupported(call),
+        }
+    }
+
+    /// Call a function of the standard library, of those the interpreter knows
+    fn library_call(
+        &mut self,
+        call: &'a syn::ExprCall,
+        path: &syn::Path,
+        mut args: Vec<Value>,
+    ) -> Flow<Value> {
+        let segments: Vec<String> = path
+            .segments
+            .iter()
+            .map(|segment| segment.ident.to_string())
+            .collect();
+        let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+        match (segments.as_slice(), args.as_slice()) {
+            ([.., "Box" | "Rc" | "Arc", "new"], [_]) => Ok(args.remove(0)),
+            ([.., "Vec" | "VecDeque", "new"], []) => Ok(Value::Array(vec![])),
+            ([.., "Vec" | "VecDeque", "with_capacity"], [_]) => Ok(Value::Array(vec![])),
+            ([.., "drop"], [_]) => Ok(Value::unit()),
+            ([.., "min" | "max"], [left, right]) => {
+                let is_min = segments.last() == Some(&"min");
+                Ok(if is_less(right, left)? == is_min {
+                    args.remove(1)
+                } else {
+                    args.remove(0)
+                })
+            }
+            _ => unsupported(call),
+        }
+    }
+
+    /// Evaluate a method call: of a method of the call graph, or of a type of the standard library
+    fn method_call(&mut self, call: &'a syn::ExprMethodCall) -> Flow<Value> {
+        let method = call.method.to_string();
+        if MUTATORS.contains(&method.as_str()) {
+            let (local, projections) = self.place(&call.receiver)?;
+            let args = self.eval_all(&call.args)?;
+            let (receiver, _) = self.target(&local, &projections)?;
+            return mutate(receiver, &method, args).unwrap_or_else(|| unsupported(call));
+        }
+        let receiver = self.eval(&call.receiver)?;
+        let (receiver, args) = self.holding(receiver, |machine| machine.eval_all(&call.args))?;
+        let is_library = match &receiver {
+            Value::Variant(name, _) => ["Some", "None", "Ok", "Err"].contains(&name.as_str()),
+            Value::Struct(..) => false,
+            _ => true,
+        };
+        if !is_library {
+            let on_self =
+                matches!(&*call.receiver, syn::Expr::Path(path) if path.path.is_ident("self"));
+            let caller = self.frame().function;
+            let types = &self.graph.types;
+            let of_type = match &receiver {
+                _ if on_self => self
+                    .graph
+                    .receiver_from_type(caller, &syn::parse_quote!(Self)),
+                Value::Struct(name, _) | Value::Variant(name, _) if types.is_defined(name) => {
+                    Receiver::Type(name.clone())
+                }
+                Value::Variant(name, _) => match types.enum_of_variant(name) {
+                    Some(enum_name) => Receiver::Type(enum_name.to_string()),
+                    None => Receiver::Unknown,
+                },
+                _ => Receiver::Unknown,
+            };
+            // a method told by its name alone may be another type's, which no execution should follow
+            let Some((function, false)) = self.graph.resolve_method(&method, &of_type) else {
+                return unsupported(call);
+            };
+            let mut args = args;
+            args.insert(0, receiver);
+            return self.call_function(function, args);
+        }
+        self.library_method(call, receiver, &method, args)
+    }
+
+    /// Call a method of a type of the standard library, of those the interpreter knows
+    fn library_method(
+        &mut self,
+        call: &'a syn::ExprMethodCall,
+        receiver: Value,
+        method: &str,
+        mut args: Vec<Value>,
+    ) -> Flow<Value> {
+        match (receiver, method, args.as_mut_slice()) {
+            (
+                receiver,
+                "clone" | "to_owned" | "iter" | "into_iter" | "copied" | "cloned" | "to_vec",
+                [],
+            ) => Ok(receiver),
+            (Value::Int(int, ty), "abs", []) => checked(int.checked_abs(), ty, "negate"),
+            (Value::Int(int, ty), "signum", []) => Ok(Value::Int(int.signum(), ty)),
+            (Value::Int(int, _), "is_positive", []) => Ok(Value::Bool(int > 0)),
+            (Value::Int(int, _), "is_negative", []) => Ok(Value::Bool(int < 0)),
+            (Value::Int(int, ty), "pow", [exponent]) => {
+                let (exponent, _) = int_of(exponent)?;
+                let power = u32::try_from(exponent)
+                    .ok()
+                    .and_then(|exponent| int.checked_pow(exponent));
+                checked(power, ty, "multiply")
+            }
+            (left @ Value::Int(..), "min" | "max", [right]) => {
+                Ok(if is_less(right, &left)? == (method == "min") {
+                    right.clone()
+                } else {
+                    left
+                })
+            }
+            (Value::Int(int, ty), "rem_euclid", [divisor]) => {
+                let (divisor, _) = int_of(divisor)?;
+                if divisor == 0 {
+                    return Err(Interrupt::Panic("attempt to divide by zero".to_string()));
+                }
+                checked(
+                    int.checked_rem_euclid(divisor),
+                    ty,
+                    "calculate the remainder",
+                )
+            }
+            (
+                Value::Int(int, ty),
+                "checked_add" | "checked_sub" | "checked_mul" | "checked_div",
+                [other],
+            ) => {
+                let (other, other_ty) = int_of(other)?;
+                let ty = ty.or(other_ty);
+                let Some(width) = ty else {
+                    return unsupported(call);
+                };
+                let result = match method {
+                    "checked_add" => int.checked_add(other),
+                    "checked_sub" => int.checked_sub(other),
+                    "checked_mul" => int.checked_mul(other),
+                    _ => int.checked_div(other),
+                };
+                Ok(Value::option(
+                    result
+                        .filter(|&result| width.contains(result))
+                        .map(|result| Value::Int(result, ty)),
+                ))
+            }
+            (Value::Int(int, ty), "wrapping_add" | "wrapping_sub" | "wrapping_mul", [other]) => {
+                let (other, other_ty) = int_of(other)?;
+                let ty = ty.or(other_ty);
+                let result = match method {
+                    "wrapping_add" => int.checked_add(other),
+                    "wrapping_sub" => int.checked_sub(other),
+                    _ => int.checked_mul(other),
+                };
+                match (result, ty) {
+                    (Some(result), Some(width)) => match width.wrap(result) {
+                        Some(result) => Ok(Value::Int(result, ty)),
+                        None => unsupported(call),
+                    },
+                    _ => unsupported(call),
+                }
+            }
+            (Value::Int(int, ty), "saturating_add" | "saturating_sub", [other]) => {
+                let (other, other_ty) = int_of(other)?;
+                let ty = ty.or(other_ty);
+                let Some(width) = ty else {
+                    return unsupported(call);
+                };
+                let (least, greatest) = width.bounds();
+                let result = match method {
+                    "saturating_add" => int.checked_add(other),
+                    _ => int.checked_sub(other),
+                };
+                match result {
+                    Some(result) => Ok(Value::Int(result.clamp(least, greatest), ty)),
+                    None => unsupported(call),
+                }
+            }
+            (Value::Variant(name, fields), "is_some" | "is_ok", []) => Ok(Value::Bool(
+                (name == "Some" || name == "Ok") && fields.len() == 1,
+            )),
+            (Value::Variant(name, _), "is_none" | "is_err", []) => {
+                Ok(Value::Bool(name == "None" || name == "Err"))
+            }
+            (Value::Variant(name, mut fields), "unwrap" | "expect", _)
+                if (name == "Some" || name == "Ok") && fields.len() == 1 =>
+            {
+                Ok(fields.pop().expect("one field"))
+            }
+            (Value::Variant(name, _), "unwrap" | "expect", _) => Err(Interrupt::Panic(format!(
+                "called `{}()` on {} value",
+                method,
+                if name == "None" {
+                    "a `None`"
+                } else {
+                    "an `Err`"
+                }
+            ))),
+            (Value::Variant(name, mut fields), "unwrap_or", [default]) => {
+                match (name.as_str(), fields.len()) {
+                    ("Some" | "Ok", 1) => Ok(fields.pop().expect("one field")),
+                    _ => Ok(default.clone()),
+                }
+            }
+            (Value::Array(values), "len", []) => Ok(int_from_index(values.len())),
+            (Value::Array(values), "is_empty", []) => Ok(Value::Bool(values.is_empty())),
+            (Value::Array(values), "contains", [value]) => Ok(Value::Bool(
+                values.iter().any(|element| element.equals(value)),
+            )),
+            (Value::Array(values), "first", []) => Ok(Value::option(values.first().cloned())),
+            (Value::Array(values), "last", []) => Ok(Value::option(values.last().cloned())),
+            (Value::Array(values), "get", [index]) => {
+                Ok(Value::option(values.get(index_of(index)?).cloned()))
+            }
+            (Value::Range(start, end, _), "contains", [value]) => {
+                let (int, _) = int_of(value)?;
+                Ok(Value::Bool(start <= int && int < end))
+            }
+            (Value::Range(start, end, _), "len", []) => Ok(int_from_index(
+                usize::try_from(end.saturating_sub(start).max(0)).unwrap_or(usize::MAX),
+            )),
+            (receiver @ (Value::Array(_) | Value::Range(..)), "rev", []) => {
+                let mut values = materialize(&receiver)?;
+                values.reverse();
+                Ok(Value::Array(values))
+            }
+            (receiver @ (Value::Array(_) | Value::Range(..)), "enumerate", []) => {
+                let values = materialize(&receiver)?;
+                Ok(Value::Array(
+                    values
+                        .into_iter()
+                        .enumerate()
+                        .map(|(index, value)| Value::Tuple(vec![int_from_index(index), value]))
+                        .collect(),
+                ))
+            }
+            (receiver @ (Value::Array(_) | Value::Range(..)), "sum", []) => {
+                let mut sum = Value::Int(0, None);
+                for value in materialize(&receiver)? {
+                    sum = binary(&syn::BinOp::Add(Default::default()), &sum, &value)?;
+                }
+                Ok(sum)
+            }
+            (receiver @ (Value::Array(_) | Value::Range(..)), "count", []) => {
+                Ok(int_from_index(materialize(&receiver)?.len()))
+            }
+            (receiver @ (Value::Array(_) | Value::Range(..)), "collect", []) => {
+                Ok(Value::Array(materialize(&receiver)?))
+            }
+            _ => unsupported(call),
+        }
+    }
+
+    /// Evaluate a macro, of those the interpreter knows
+    fn macro_call(&mut self, mac: &'a syn::Macro) -> Flow<Value> {
+        let name = last_segment(&mac.path);
+        // printing doesn't change whether a function halts
+        if ["print", "println", "eprint", "eprintln"].contains(&name.as_str()) {
+            return Ok(Value::unit());
+        }
+        let Some(args) = self.macros.args.get(&place_of(mac)) else {
+            return unsupported(mac);
+        };
+        let message = || match args.exprs.first() {
+            Some(syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(message),
+                ..
+            })) => message.value(),
+            _ => format!("explicit {}", name),
+        };
+        match name.as_str() {
+            "vec" if args.is_repeat => {
+                let values = self.eval_all(&args.exprs)?;
+                self.repeat(&values[0], &values[1])
+            }
+            "vec" => Ok(Value::Array(self.eval_all(&args.exprs)?)),
+            "dbg" if args.exprs.len() == 1 => self.eval(&args.exprs[0]),
+            "panic" | "unreachable" | "todo" | "unimplemented" => Err(Interrupt::Panic(message())),
+            "assert" | "debug_assert" => match args.exprs.first() {
+                Some(condition) => match self.eval(condition)? {
+                    Value::Bool(true) => Ok(Value::unit()),
+                    Value::Bool(false) => Err(Interrupt::Panic(format!(
+                        "assertion failed: {}",
+                        condition.to_token_stream()
+                    ))),
+                    _ => unsupported(mac),
+                },
+                None => unsupported(mac),
+            },
+            "assert_eq" | "assert_ne" | "debug_assert_eq" | "debug_assert_ne"
+                if args.exprs.len() >= 2 =>
+            {
+                let values = self.eval_all(&args.exprs[..2])?;
+                let is_eq = name.ends_with("eq");
+                if values[0].equals(&values[1]) == is_eq {
+                    return Ok(Value::unit());
+                }
+                let relation = if is_eq { "==" } else { "!=" };
+                Err(Interrupt::Panic(format!(
+                    "assertion `left {} right` failed: left = {}, right = {}",
+                    relation, values[0], values[1]
+                )))
+            }
+            "matches" => {
+                let Some(pat) = &args.pat else {
+                    return unsupported(mac);
+                };
+                let value = self.eval(&args.exprs[0])?;
+                let mut locals = vec![];
+                if !self.bind(pat, &value, None, &mut locals)? {
+                    return Ok(Value::Bool(false));
+                }
+                match args.exprs.get(1) {
+                    Some(guard) => self.scoped(locals, |machine| machine.eval(guard)),
+                    None => Ok(Value::Bool(true)),
+                }
+            }
+            _ => unsupported(mac),
+        }
+    }
+}
+
+/// Call a method which changes its receiver, of those the interpreter knows
+fn mutate(receiver: &mut Value, method: &str, mut args: Vec<Value>) -> Option<Flow<Value>> {
+    Some(match (receiver, method, args.as_mut_slice()) {
+        (Value::Array(values), "push" | "push_back", [_]) => {
+            values.push(args.remove(0));
+            Ok(Value::unit())
+        }
+        (Value::Array(values), "pop", []) => Ok(Value::option(values.pop())),
+        (Value::Array(values), "pop_front", []) => Ok(Value::option(
+            (!values.is_empty()).then(|| values.remove(0)),
+        )),
+        (Value::Array(values), "insert", [index, _]) => {
+            let index = match index_of(index) {
+                Ok(index) => index,
+                Err(interrupt) => return Some(Err(interrupt)),
+            };
+            if index > values.len() {
+                return Some(Err(Interrupt::Panic(format!(
+                    "insertion index (is {}) should be <= len (is {})",
+                    index,
+                    values.len()
+                ))));
+            }
+            values.insert(index, args.remove(1));
+            Ok(Value::unit())
+        }
+        (Value::Array(values), "remove", [index]) => match index_of(index) {
+            Ok(index) if index < values.len() => Ok(values.remove(index)),
+            Ok(index) => Err(Interrupt::Panic(format!(
+                "removal index (is {}) should be < len (is {})",
+                index,
+                values.len()
+            ))),
+            Err(interrupt) => Err(interrupt),
+        },
+        (Value::Array(values), "clear", []) => {
+            values.clear();
+            Ok(Value::unit())
+        }
+        (Value::Array(values), "truncate", [length]) => match index_of(length) {
+            Ok(length) => {
+                values.truncate(length);
+                Ok(Value::unit())
+            }
+            Err(interrupt) => Err(interrupt),
+        },
+        (Value::Array(values), "swap", [a, b]) => match (index_of(a), index_of(b)) {
+            (Ok(a), Ok(b)) if a < values.len() && b < values.len() => {
+                values.swap(a, b);
+                Ok(Value::unit())
+            }
+            (Ok(_), Ok(_)) => Err(Interrupt::Panic("index out of bounds".to_string())),
+            (Err(interrupt), _) | (_, Err(interrupt)) => Err(interrupt),
+        },
+        (Value::Array(values), "reverse", []) => {
+            values.reverse();
+            Ok(Value::unit())
+        }
+        (Value::Array(values), "sort", [])
+            if values.iter().all(|value| matches!(value, Value::Int(..))) =>
+        {
+            values.sort_by_key(|value| match value {
+                Value::Int(int, _) => *int,
+                _ => 0,
+            });
+            Ok(Value::unit())
+        }
+        (option @ Value::Variant(..), "take", []) => Ok(std::mem::replace(option, Value::none())),
+        _ => return None,
+    })
+}
+
+/// Run a function of a call graph on some arguments, until it returns, repeats a state, or runs out of steps.
+///
+/// # Arguments
+///
+/// * `graph` - The call graph the function is part of, whose functions it may call.
+/// * `function` - The index of the function in the call graph.
+/// * `args` - The arguments to call it with.
+/// * `budget` - The most steps to take, where a step evaluates an expression.
+///
+/// # Returns
+///
+/// How the run ended: returning, coming back to a state it was in, panicking, or stopping without telling.
+pub fn execution_from_graph(
+    graph: &CallGraph,
+    function: usize,
+    args: Vec<Value>,
+    budget: usize,
+) -> Execution {
+    // only the functions the run may call can reach the macros
+    let mut macros = Macros::default();
+    for node in graph.reachable_from(function) {
+        macros.visit_item_fn(&graph.functions[node].ast);
+    }
+    let mut machine = Machine {
+        graph,
+        macros: &macros,
+        closures: HashMap::new(),
+        state: State {
+            path: vec![],
+            held: vec![],
+            frames: vec![],
+        },
+        steps: 0,
+        budget,
+        tortoise: None,
+        power: 1,
+        lambda: 1,
+    };
+    let result = machine.call_function(function, args);
+    let steps = machine.steps;
+    match result {
+        Ok(value) => Execution::Returns { value, steps },
+        Err(Interrupt::Repeat(period)) => Execution::Repeats { steps, period },
+        Err(Interrupt::Panic(message)) => Execution::Panics { message, steps },
+        Err(Interrupt::Stop(why)) => Execution::Unknown(why),
+        Err(Interrupt::Return(_) | Interrupt::Break(..) | Interrupt::Continue(_)) => {
+            Execution::Unknown("control flow escaped the function".to_string())
+        }
+    }
+}
+
+/// Run a function on some arguments, until it returns, repeats a state, or runs out of steps.
+///
+/// # Arguments
+///
+/// * `ast` - The AST of the function to run.
+/// * `context` - Where the function is defined, among the functions it may call.
+/// * `args` - The arguments to call it with.
+/// * `budget` - The most steps to take, where a step evaluates an expression.
+///
+/// # Returns
+///
+/// How the run ended: returning, coming back to a state it was in, panicking, or stopping without telling.
+pub fn execution_from_ast(
+    ast: &syn::ItemFn,
+    context: &Context,
+    args: Vec<Value>,
+    budget: usize,
+) -> Execution {
+    let (graph, root) = context.graph_with_root(ast);
+    execution_from_graph(&graph, root, args, budget)
+}
+
+/// tests for the interpreter
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Run a function of a file, by name, without arguments
+    fn run(code: &str, name: &str) -> Execution {
+        let file: syn::File = syn::parse_str(code).unwrap();
+        let ast = file
+            .items
+            .iter()
+            .find_map(|item| match item {
+                syn::Item::Fn(function) if function.sig.ident == name => Some(function.clone()),
+                _ => None,
+            })
+            .unwrap();
+        execution_from_ast(&ast, &Context::from_file(&file), vec![], 100_000)
+    }
+
+    /// What a function returns, or a panic naming how its run ended instead
+    fn returned(code: &str, name: &str) -> String {
+        match run(code, name) {
+            Execution::Returns { value, .. } => value.to_string(),
+            execution => panic!("{}: {}", name, execution),
+        }
+    }
+
+    /// Test that runs which return give what they return
+    #[test]
+    fn test_returns() {
+        let code = "
+            enum List { Cons(u32, Box<List>), Nil }
+            fn length(list: &List) -> u32 {
+                match list { List::Cons(_, tail) => 1 + length(tail), List::Nil => 0 }
+            }
+            fn two() -> u32 { length(&List::Cons(1, Box::new(List::Cons(2, Box::new(List::Nil))))) }
+            fn factorial(n: u64) -> u64 { if n == 0 { 1 } else { n * factorial(n - 1) } }
+            fn big() -> u64 { factorial(20) }
+            fn closures() -> i32 {
+                let double = |x: i32| x * 2;
+                let mut total = 0;
+                for (i, x) in vec![1, 2, 3].iter().enumerate() { total += double(*x) + i as i32; }
+                total
+            }
+            fn labels() -> i32 {
+                let mut total = 0;
+                'outer: for i in 0..5 {
+                    for j in 0..5 {
+                        if j > i { continue 'outer; }
+                        if i == 4 { break 'outer; }
+                        total += j;
+                    }
+                }
+                total
+            }
+            fn tries() -> Option<usize> {
+                let mut v = Vec::new();
+                while let None = v.get(3) { v.push(v.len()); }
+                let last = v.pop()?;
+                Some(last + v.len())
+            }
+        ";
+        assert_eq!(returned(code, "two"), "2");
+        assert_eq!(returned(code, "big"), "2432902008176640000");
+        assert_eq!(returned(code, "closures"), "15");
+        assert_eq!(returned(code, "labels"), "10");
+        assert_eq!(returned(code, "tries"), "Some(6)");
+    }
+
+    /// Test that a run which comes back to a state is seen to, with how often it does
+    #[test]
+    fn test_repeats() {
+        let code = "
+            fn doubling() { let mut x = 1; while x != 0 { x = (x * 2) % 7; } }
+            fn counters() {
+                let mut counts = [0u8; 4];
+                let mut i = 0;
+                loop { counts[i] = counts[i].wrapping_add(1); i = (i + 1) % 4; }
+            }
+            fn nested() { loop { for _ in 0..3 {} } }
+            fn grows() { let mut x: u64 = 0; loop { x += 1; } }
+        ";
+        let period = |name| match run(code, name) {
+            Execution::Repeats { period, .. } => period,
+            execution => panic!("{}: {}", name, execution),
+        };
+        assert_eq!(period("doubling"), 3);
+        // four counters of 256 values each
+        assert_eq!(period("counters"), 1024);
+        // what's left of the range of the inner loop is part of the state
+        assert_eq!(period("nested"), 5);
+        assert!(matches!(run(code, "grows"), Execution::Unknown(_)));
+    }
+
+    /// Test that runs which panic, or leave the subset, or go too deep, say so
+    #[test]
+    fn test_stops() {
+        let code = "
+            fn underflow() { let mut x: u32 = 7; while x != 0 { x -= 2; } }
+            fn out_of_bounds() -> i32 { let v = vec![1, 2]; v[2] }
+            fn strings() -> usize { String::new().len() }
+            fn deep(n: u64) -> u64 { if n == 0 { 0 } else { deep(n - 1) } }
+            fn deeper() -> u64 { deep(1000) }
+        ";
+        assert_eq!(
+            run(code, "underflow"),
+            Execution::Panics {
+                message: "attempt to subtract with overflow".to_string(),
+                steps: 26,
+            }
+        );
+        assert!(matches!(
+            run(code, "out_of_bounds"),
+            Execution::Panics { .. }
+        ));
+        assert_eq!(
+            run(code, "strings"),
+            Execution::Unknown("can't run `String :: new ()`".to_string())
+        );
+        assert_eq!(
+            run(code, "deeper"),
+            Execution::Unknown("the call stack grows past 32 frames".to_string())
+        );
+    }
+}
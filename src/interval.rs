@@ -0,0 +1,648 @@
+//! halts/src/interval.rs
+//! The interval domain: a range of values for each integer local, like `0 <= i <= 10`.
+//! Intervals can't relate one local to another, but they are cheap, and they are enough to see that
+//! `let mut i = 0; while i < 10 { i += 1 }` has `0 <= i <= 10` at the head of its loop,
+//! or that `while i >= 0 { i += 1 }` from `i = 0` never leaves.
+//! Branch conditions which are linear refine the ranges of every local in them.
+//! Integers are mathematical integers here: overflow is not modeled.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use crate::absint::{fixpoint, Analysis, Fixpoint, Lattice};
+use crate::ir::{BinOp, BlockId, Body, Expr, Statement, Ty, UnOp};
+use crate::linear::{atoms_from_condition, Atom, LinearExpr, Relation};
+
+/// A range of integers, each end of which may be unbounded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Interval {
+    /// The least value, or `None` if there is no least value
+    pub lo: Option<i128>,
+    /// The greatest value, or `None` if there is no greatest value
+    pub hi: Option<i128>,
+}
+
+impl Interval {
+    /// Every integer
+    pub const TOP: Interval = Interval { lo: None, hi: None };
+
+    /// The integers from zero up, which an unsigned integer can hold
+    pub const NATURAL: Interval = Interval {
+        lo: Some(0),
+        hi: None,
+    };
+
+    /// A single integer
+    pub fn constant(value: i128) -> Self {
+        Interval {
+            lo: Some(value),
+            hi: Some(value),
+        }
+    }
+
+    /// Whether no integer is in the range
+    pub fn is_empty(&self) -> bool {
+        matches!((self.lo, self.hi), (Some(lo), Some(hi)) if lo > hi)
+    }
+
+    /// Whether an integer is in the range
+    pub fn contains(&self, value: i128) -> bool {
+        self.lo.is_none_or(|lo| lo <= value) && self.hi.is_none_or(|hi| value <= hi)
+    }
+
+    /// The single integer in the range, if there is only one
+    pub fn as_constant(&self) -> Option<i128> {
+        match (self.lo, self.hi) {
+            (Some(lo), Some(hi)) if lo == hi => Some(lo),
+            _ => None,
+        }
+    }
+
+    /// The integers in both ranges
+    pub fn meet(&self, other: &Interval) -> Interval {
+        Interval {
+            lo: max_lower(self.lo, other.lo),
+            hi: min_upper(self.hi, other.hi),
+        }
+    }
+
+    /// The least range holding both ranges
+    pub fn join(&self, other: &Interval) -> Interval {
+        Interval {
+            lo: self.lo.zip(other.lo).map(|(a, b)| a.min(b)),
+            hi: self.hi.zip(other.hi).map(|(a, b)| a.max(b)),
+        }
+    }
+
+    /// The range with each end which moved out from `self` in `next` made unbounded
+    pub fn widen(&self, next: &Interval) -> Interval {
+        Interval {
+            lo: self
+                .lo
+                .zip(next.lo)
+                .and_then(|(a, b)| (b >= a).then_some(a)),
+            hi: self
+                .hi
+                .zip(next.hi)
+                .and_then(|(a, b)| (b <= a).then_some(a)),
+        }
+    }
+
+    /// The range with each unbounded end of `self` taken from `next`
+    pub fn narrow(&self, next: &Interval) -> Interval {
+        Interval {
+            lo: self.lo.or(next.lo),
+            hi: self.hi.or(next.hi),
+        }
+    }
+
+    /// The values of `-x` for `x` in the range
+    pub fn neg(&self) -> Interval {
+        Interval {
+            lo: self.hi.and_then(i128::checked_neg),
+            hi: self.lo.and_then(i128::checked_neg),
+        }
+    }
+
+    /// The values of `x + y` for `x` and `y` in the ranges
+    pub fn add(&self, other: &Interval) -> Interval {
+        Interval {
+            lo: self.lo.zip(other.lo).and_then(|(a, b)| a.checked_add(b)),
+            hi: self.hi.zip(other.hi).and_then(|(a, b)| a.checked_add(b)),
+        }
+    }
+
+    /// The values of `x - y` for `x` and `y` in the ranges
+    pub fn sub(&self, other: &Interval) -> Interval {
+        self.add(&other.neg())
+    }
+
+    /// The values of `x * y` for `x` and `y` in the ranges
+    pub fn mul(&self, other: &Interval) -> Interval {
+        if let Some(factor) = other.as_constant() {
+            return self.scale(factor);
+        }
+        if let Some(factor) = self.as_constant() {
+            return other.scale(factor);
+        }
+        let (Some(a), Some(b), Some(c), Some(d)) = (self.lo, self.hi, other.lo, other.hi) else {
+            return Interval::TOP;
+        };
+        let products = [
+            a.checked_mul(c),
+            a.checked_mul(d),
+            b.checked_mul(c),
+            b.checked_mul(d),
+        ];
+        if products.iter().any(Option::is_none) {
+            return Interval::TOP;
+        }
+        let products = products.into_iter().flatten();
+        Interval {
+            lo: products.clone().min(),
+            hi: products.max(),
+        }
+    }
+
+    /// The values of `x * factor` for `x` in the range
+    fn scale(&self, factor: i128) -> Interval {
+        let lo = self.lo.and_then(|lo| lo.checked_mul(factor));
+        let hi = self.hi.and_then(|hi| hi.checked_mul(factor));
+        match factor {
+            0 => Interval::constant(0),
+            factor if factor > 0 => Interval { lo, hi },
+            _ => Interval { lo: hi, hi: lo },
+        }
+    }
+
+    /// The values of `x / y`, rounded towards zero, for `x` and `y` in the ranges
+    pub fn div(&self, other: &Interval) -> Interval {
+        match other.as_constant() {
+            Some(divisor) if divisor > 0 => Interval {
+                lo: self.lo.and_then(|lo| lo.checked_div(divisor)),
+                hi: self.hi.and_then(|hi| hi.checked_div(divisor)),
+            },
+            Some(divisor) if divisor < 0 => Interval {
+                lo: self.hi.and_then(|hi| hi.checked_div(divisor)),
+                hi: self.lo.and_then(|lo| lo.checked_div(divisor)),
+            },
+            _ => Interval::TOP,
+        }
+    }
+
+    /// The values of `x % y`, which takes the sign of `x`, for `x` and `y` in the ranges
+    pub fn rem(&self, other: &Interval) -> Interval {
+        // `x % y` is less than `|y|`, so a positive divisor bounds it
+        if other.lo.is_none_or(|lo| lo <= 0) {
+            return Interval::TOP;
+        }
+        let bound = other.hi.map(|hi| hi - 1);
+        let magnitude = Interval {
+            lo: bound.map(|bound| -bound),
+            hi: bound,
+        };
+        match (self.lo, self.hi) {
+            (Some(lo), _) if lo >= 0 => Interval {
+                lo: Some(0),
+                hi: min_upper(self.hi, bound),
+            },
+            (_, Some(hi)) if hi <= 0 => Interval {
+                lo: max_lower(self.lo, magnitude.lo),
+                hi: Some(0),
+            },
+            _ => magnitude,
+        }
+    }
+
+    /// Whether `x op y` holds for every `x` and `y` in the ranges, for none of them,
+    /// or `None` if it holds for some and not others or the operator doesn't compare
+    pub fn compare(&self, op: BinOp, other: &Interval) -> Option<bool> {
+        // whether every x is below every y, and whether every x is at most every y
+        let below = |x: &Interval, y: &Interval| x.hi.zip(y.lo).is_some_and(|(a, b)| a < b);
+        let at_most = |x: &Interval, y: &Interval| x.hi.zip(y.lo).is_some_and(|(a, b)| a <= b);
+        match op {
+            BinOp::Lt if below(self, other) => Some(true),
+            BinOp::Lt if at_most(other, self) => Some(false),
+            BinOp::Le if at_most(self, other) => Some(true),
+            BinOp::Le if below(other, self) => Some(false),
+            BinOp::Gt => other.compare(BinOp::Lt, self),
+            BinOp::Ge => other.compare(BinOp::Le, self),
+            BinOp::Eq | BinOp::Ne => {
+                let equal = match (self.as_constant(), other.as_constant()) {
+                    (Some(a), Some(b)) if a == b => Some(true),
+                    _ if self.meet(other).is_empty() => Some(false),
+                    _ => None,
+                };
+                equal.map(|equal| equal == (op == BinOp::Eq))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.lo {
+            Some(lo) => write!(f, "[{}, ", lo)?,
+            None => write!(f, "[-inf, ")?,
+        }
+        match self.hi {
+            Some(hi) => write!(f, "{}]", hi),
+            None => write!(f, "+inf]"),
+        }
+    }
+}
+
+/// The greater of two lower bounds, where `None` is no bound
+fn max_lower(a: Option<i128>, b: Option<i128>) -> Option<i128> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.max(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// The lesser of two upper bounds, where `None` is no bound
+fn min_upper(a: Option<i128>, b: Option<i128>) -> Option<i128> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// `a / b` rounded down, or `None` on overflow
+fn floor_div(a: i128, b: i128) -> Option<i128> {
+    let quotient = a.checked_div(b)?;
+    if a % b != 0 && (a < 0) != (b < 0) {
+        quotient.checked_sub(1)
+    } else {
+        Some(quotient)
+    }
+}
+
+/// `a / b` rounded up, or `None` on overflow
+fn ceil_div(a: i128, b: i128) -> Option<i128> {
+    floor_div(a.checked_neg()?, b)?.checked_neg()
+}
+
+/// The range of each integer local at a point, or bottom where no run of the body gets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Intervals {
+    /// The range of each local known to be bounded; the locals missing may hold anything,
+    /// and `None` is the bottom element
+    ranges: Option<BTreeMap<String, Interval>>,
+}
+
+impl Intervals {
+    /// The state which allows every value of every local
+    pub fn top() -> Self {
+        Intervals {
+            ranges: Some(BTreeMap::new()),
+        }
+    }
+
+    /// The range of a local, or of a free name such as a constant
+    pub fn get(&self, local: &str) -> Interval {
+        match &self.ranges {
+            Some(ranges) => ranges.get(local).copied().unwrap_or(Interval::TOP),
+            None => Interval {
+                lo: Some(1),
+                hi: Some(0),
+            },
+        }
+    }
+
+    /// The locals known to be bounded, with their ranges; none at all if this is bottom
+    pub fn bounded(&self) -> impl Iterator<Item = (&String, &Interval)> {
+        self.ranges.iter().flatten()
+    }
+
+    /// Give a local a range, which becomes bottom if the range is empty
+    fn set(&mut self, local: &str, range: Interval) {
+        if range.is_empty() {
+            self.ranges = None;
+        } else if let Some(ranges) = &mut self.ranges {
+            if range == Interval::TOP {
+                ranges.remove(local);
+            } else {
+                ranges.insert(local.to_string(), range);
+            }
+        }
+    }
+
+    /// Combine the ranges of each local with an operation on intervals
+    fn combine(&self, other: &Self, op: impl Fn(&Interval, &Interval) -> Interval) -> Self {
+        let (Some(ours), Some(theirs)) = (&self.ranges, &other.ranges) else {
+            return if self.is_bottom() {
+                other.clone()
+            } else {
+                self.clone()
+            };
+        };
+        let mut combined = Intervals::top();
+        for local in ours.keys().chain(theirs.keys()) {
+            combined.set(local, op(&self.get(local), &other.get(local)));
+        }
+        combined
+    }
+
+    /// The range of the values of an expression
+    pub fn eval(&self, expr: &Expr) -> Interval {
+        match expr {
+            Expr::Int(int) => Interval::constant(*int),
+            Expr::Var(name) => self.get(name),
+            Expr::Unary(UnOp::Neg, inner) => self.eval(inner).neg(),
+            Expr::Binary(op, left, right) => {
+                let (left, right) = (self.eval(left), self.eval(right));
+                match op {
+                    BinOp::Add => left.add(&right),
+                    BinOp::Sub => left.sub(&right),
+                    BinOp::Mul => left.mul(&right),
+                    BinOp::Div => left.div(&right),
+                    BinOp::Rem => left.rem(&right),
+                    _ => Interval::TOP,
+                }
+            }
+            Expr::Bool(_) | Expr::Unary(UnOp::Not, _) | Expr::Part(..) | Expr::Opaque(_) => {
+                Interval::TOP
+            }
+        }
+    }
+
+    /// The range of the values of a linear expression
+    fn eval_linear(&self, expr: &LinearExpr) -> Interval {
+        expr.terms.iter().fold(
+            Interval::constant(expr.constant),
+            |sum, (name, &coefficient)| sum.add(&self.get(name).scale(coefficient)),
+        )
+    }
+
+    /// Narrow the ranges of the locals in a linear constraint to those which can satisfy it
+    fn refine(&mut self, atom: &Atom) {
+        let range = self.eval_linear(&atom.expr);
+        let infeasible = match atom.relation {
+            Relation::Ge => range.hi.is_some_and(|hi| hi < 0),
+            Relation::Eq => !range.contains(0),
+            Relation::Ne => range.as_constant() == Some(0),
+        };
+        if infeasible {
+            self.ranges = None;
+            return;
+        }
+        match atom.relation {
+            Relation::Ge => self.refine_ge(&atom.expr),
+            Relation::Eq => {
+                self.refine_ge(&atom.expr);
+                if let Some(negated) = atom.expr.checked_scale(-1) {
+                    self.refine_ge(&negated);
+                }
+            }
+            Relation::Ne => {
+                // only a single local against a constant can be narrowed, and only at an end of its range
+                if let [(name, &coefficient)] = atom.expr.terms.iter().collect::<Vec<_>>()[..] {
+                    if atom.expr.constant % coefficient == 0 {
+                        let excluded = -(atom.expr.constant / coefficient);
+                        let mut range = self.get(name);
+                        if range.lo == Some(excluded) {
+                            range.lo = excluded.checked_add(1);
+                        }
+                        if range.hi == Some(excluded) {
+                            range.hi = excluded.checked_sub(1);
+                        }
+                        self.set(name, range);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Narrow the ranges of the locals in `expr >= 0`
+    fn refine_ge(&mut self, expr: &LinearExpr) {
+        for (name, &coefficient) in &expr.terms {
+            let mut rest = expr.clone();
+            rest.terms.remove(name);
+            // coefficient * name >= -rest, which is weakest at the greatest value of rest
+            let Some(bound) = self.eval_linear(&rest).hi.and_then(i128::checked_neg) else {
+                continue;
+            };
+            let mut range = self.get(name);
+            if coefficient > 0 {
+                range.lo = max_lower(range.lo, ceil_div(bound, coefficient));
+            } else {
+                range.hi = min_upper(range.hi, floor_div(bound, coefficient));
+            }
+            self.set(name, range);
+        }
+    }
+}
+
+impl Lattice for Intervals {
+    fn bottom() -> Self {
+        Intervals { ranges: None }
+    }
+
+    fn is_bottom(&self) -> bool {
+        self.ranges.is_none()
+    }
+
+    fn join(&self, other: &Self) -> Self {
+        self.combine(other, Interval::join)
+    }
+
+    fn widen(&self, next: &Self) -> Self {
+        self.combine(next, Interval::widen)
+    }
+
+    fn narrow(&self, next: &Self) -> Self {
+        if next.is_bottom() {
+            return next.clone();
+        }
+        self.combine(next, Interval::narrow)
+    }
+}
+
+impl fmt::Display for Intervals {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(ranges) = &self.ranges else {
+            return write!(f, "unreachable");
+        };
+        let ranges: Vec<String> = ranges
+            .iter()
+            .map(|(local, range)| format!("{} in {}", local, range))
+            .collect();
+        write!(f, "{{{}}}", ranges.join(", "))
+    }
+}
+
+/// The interval analysis of a body, which knows the integer types of its locals.
+#[derive(Debug, Clone)]
+pub struct IntervalAnalysis {
+    /// The type of each local
+    types: HashMap<String, Ty>,
+}
+
+impl IntervalAnalysis {
+    /// The analysis of a body
+    pub fn new(body: &Body) -> Self {
+        IntervalAnalysis {
+            types: body
+                .locals
+                .iter()
+                .map(|local| (local.name.clone(), local.ty))
+                .collect(),
+        }
+    }
+
+    /// The values a local can hold whatever happens, which is where its range starts
+    fn bounds(&self, local: &str) -> Interval {
+        match self.types.get(local) {
+            Some(Ty::Unsigned) => Interval::NATURAL,
+            _ => Interval::TOP,
+        }
+    }
+
+    /// Give a local a value in a range
+    fn set(&self, state: &mut Intervals, local: &str, range: Interval) {
+        state.set(local, range.meet(&self.bounds(local)));
+    }
+}
+
+impl Analysis for IntervalAnalysis {
+    type State = Intervals;
+
+    fn entry(&self, body: &Body) -> Intervals {
+        let mut state = Intervals::top();
+        for param in &body.params {
+            self.set(&mut state, param, Interval::TOP);
+        }
+        state
+    }
+
+    fn statement(&self, state: &Intervals, statement: &Statement) -> Intervals {
+        let mut state = state.clone();
+        if state.is_bottom() {
+            return state;
+        }
+        match statement {
+            Statement::Assign { local, value } => {
+                let range = state.eval(value);
+                self.set(&mut state, local, range);
+            }
+            Statement::Call { dest, .. } => self.set(&mut state, dest, Interval::TOP),
+            Statement::Havoc(local) => self.set(&mut state, local, Interval::TOP),
+            Statement::Assume(condition) => return self.assume(&state, condition, true),
+        }
+        state
+    }
+
+    fn assume(&self, state: &Intervals, condition: &Expr, holds: bool) -> Intervals {
+        if state.is_bottom() {
+            return state.clone();
+        }
+        match condition {
+            Expr::Bool(b) if *b != holds => Intervals::bottom(),
+            Expr::Unary(UnOp::Not, inner) => self.assume(state, inner, !holds),
+            Expr::Binary(op @ (BinOp::And | BinOp::Or), left, right) => {
+                if (*op == BinOp::And) == holds {
+                    let state = self.assume(state, left, holds);
+                    self.assume(&state, right, holds)
+                } else {
+                    self.assume(state, left, holds)
+                        .join(&self.assume(state, right, holds))
+                }
+            }
+            Expr::Binary(op, left, right) if op.is_boolean() => {
+                if state.eval(left).compare(*op, &state.eval(right)) == Some(!holds) {
+                    return Intervals::bottom();
+                }
+                let mut state = state.clone();
+                for atom in atoms_from_condition(condition, holds).unwrap_or_default() {
+                    state.refine(&atom);
+                }
+                state
+            }
+            _ => state.clone(),
+        }
+    }
+}
+
+/// The ranges of the integer locals of a body at every block and along every edge.
+///
+/// # Arguments
+///
+/// * `body` - The control-flow graph of a function.
+///
+/// # Returns
+///
+/// The fixpoint of the interval analysis over the body, starting from parameters which may hold anything of their type.
+pub fn intervals_from_body(body: &Body) -> Fixpoint<Intervals> {
+    fixpoint(&IntervalAnalysis::new(body), body)
+}
+
+/// The invariant at the head of each loop of a body, outermost loop first, for debugging.
+///
+/// # Returns
+///
+/// The head of each loop with the ranges its integer locals are in whenever the loop starts an iteration.
+pub fn loop_invariants(body: &Body) -> Vec<(BlockId, Intervals)> {
+    let intervals = intervals_from_body(body);
+    body.loop_forest()
+        .loops
+        .iter()
+        .map(|looped| (looped.header, intervals.states[looped.header].clone()))
+        .collect()
+}
+
+/// tests for the interval domain
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ir::body_from_ast;
+
+    /// Lower a function for the tests
+    fn body(code: &str) -> Body {
+        body_from_ast(&syn::parse_str(code).unwrap())
+    }
+
+    /// Test interval arithmetic, comparison and widening
+    #[test]
+    fn test_interval() {
+        let small = Interval {
+            lo: Some(-2),
+            hi: Some(3),
+        };
+        assert_eq!(small.mul(&small).to_string(), "[-6, 9]");
+        assert_eq!(small.sub(&Interval::NATURAL).to_string(), "[-inf, 3]");
+        assert_eq!(
+            Interval::NATURAL.rem(&Interval::constant(4)).to_string(),
+            "[0, 3]"
+        );
+        assert_eq!(
+            Interval::constant(7)
+                .div(&Interval::constant(-2))
+                .to_string(),
+            "[-3, -3]"
+        );
+        assert_eq!(small.compare(BinOp::Lt, &Interval::constant(4)), Some(true));
+        assert_eq!(
+            Interval::NATURAL.compare(BinOp::Lt, &Interval::constant(0)),
+            Some(false)
+        );
+        assert_eq!(small.compare(BinOp::Eq, &Interval::constant(0)), None);
+        let grown = Interval {
+            lo: Some(-2),
+            hi: Some(4),
+        };
+        assert_eq!(small.widen(&grown).to_string(), "[-2, +inf]");
+        assert_eq!(small.widen(&grown).narrow(&grown), grown);
+    }
+
+    /// Test the invariant at the head of a counting loop is found, narrowed back from widening
+    #[test]
+    fn test_loop_invariants() {
+        let counting = body("fn f() { let mut i = 0; while i < 10 { i += 1; } }");
+        let invariants = loop_invariants(&counting);
+        assert_eq!(invariants.len(), 1);
+        assert_eq!(invariants[0].1.get("i").to_string(), "[0, 10]");
+
+        let down = body("fn f(n: u32) { let mut k = n; while k > 0 { k -= 1; } }");
+        let invariants = loop_invariants(&down);
+        assert_eq!(invariants[0].1.get("k").to_string(), "[0, +inf]");
+        assert_eq!(invariants[0].1.get("n").to_string(), "[0, +inf]");
+    }
+
+    /// Test conditions which can't hold cut off the blocks and edges behind them
+    #[test]
+    fn test_intervals_from_body() {
+        let body = body("fn f(n: u32) -> u32 { if n < 0 { return 1; } let mut i = 0; while i >= 0 { i += 1; } 0 }");
+        let intervals = intervals_from_body(&body);
+        let unreached: Vec<BlockId> = (0..body.blocks.len())
+            .filter(|&block| !intervals.is_reached(block))
+            .collect();
+        // the early return, and the code after the loop
+        assert_eq!(unreached.len(), 2, "{}", body);
+        let looped = &body.loop_forest().loops[0];
+        for (from, to) in looped.exits(&body) {
+            assert!(!intervals.is_taken(from, to));
+        }
+    }
+}
@@ -0,0 +1,2605 @@
+//! halts/src/ir.rs
+//! A control-flow graph of a function body, lowered from its `syn` AST once `normalize` has desugared it.
+//! Each function becomes basic blocks of straight-line statements joined by explicit edges,
+//! so `if`, `match`, `loop`, labeled `break` and `continue`, `return` and calls which never return
+//! are all plain jumps, and analyses can follow control flow without re-deriving it from syntax.
+//! Values are kept as small expression trees over the locals, so conditions read like the source:
+//! `if n == 0 goto bb1 else bb2`.
+//! Whatever the IR can't express, like a closure or a field of a struct, is kept as an opaque value.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+
+use quote::ToTokens;
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::visit::Visit;
+
+use crate::normalize::{normalized_from_ast, normalized_from_expr};
+use crate::verdict::SourceSpan;
+
+/// Macros which always panic
+const PANICS: [&str; 4] = ["panic", "unreachable", "todo", "unimplemented"];
+
+/// Macros which panic unless their condition holds
+const ASSERTS: [&str; 6] = [
+    "assert",
+    "assert_eq",
+    "assert_ne",
+    "debug_assert",
+    "debug_assert_eq",
+    "debug_assert_ne",
+];
+
+/// Functions which never return, and how they leave
+const DIVERGES: [(&str, Divergence); 4] = [
+    ("exit", Divergence::Exit),
+    ("abort", Divergence::Exit),
+    ("panic_any", Divergence::Panic),
+    ("resume_unwind", Divergence::Panic),
+];
+
+/// Methods which may panic instead of returning
+const UNWRAPS: [&str; 4] = ["unwrap", "expect", "unwrap_err", "expect_err"];
+
+/// Methods which don't change their receiver
+const PURE_METHODS: [&str; 31] = [
+    "abs",
+    "as_ref",
+    "checked_add",
+    "checked_div",
+    "checked_mul",
+    "checked_sub",
+    "clone",
+    "cmp",
+    "contains",
+    "count_ones",
+    "div_euclid",
+    "eq",
+    "first",
+    "get",
+    "is_empty",
+    "is_err",
+    "is_none",
+    "is_ok",
+    "is_some",
+    "iter",
+    "last",
+    "len",
+    "max",
+    "min",
+    "partial_cmp",
+    "pow",
+    "rem_euclid",
+    "signum",
+    "to_owned",
+    "to_string",
+    "trailing_zeros",
+];
+
+/// The index of a basic block within a body
+pub type BlockId = usize;
+
+/// What a local can hold, as far as the analyses are concerned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Ty {
+    /// An unsigned integer, which can't go below zero without panicking
+    Unsigned,
+    /// A signed integer, or an integer whose type isn't spelled out
+    Signed,
+    /// A boolean
+    Bool,
+    /// Anything else
+    Other,
+}
+
+impl Ty {
+    /// What a value of a type can hold, seeing through references
+    pub fn from_type(ty: &syn::Type) -> Self {
+        match ty {
+            syn::Type::Path(path) => {
+                match path.path.get_ident().map(|i| i.to_string()).as_deref() {
+                    Some("u8" | "u16" | "u32" | "u64" | "u128" | "usize") => Ty::Unsigned,
+                    Some("i8" | "i16" | "i32" | "i64" | "i128" | "isize") => Ty::Signed,
+                    Some("bool") => Ty::Bool,
+                    _ => Ty::Other,
+                }
+            }
+            syn::Type::Reference(reference) => Ty::from_type(&reference.elem),
+            syn::Type::Paren(paren) => Ty::from_type(&paren.elem),
+            syn::Type::Group(group) => Ty::from_type(&group.elem),
+            _ => Ty::Other,
+        }
+    }
+
+    /// Whether the type is an integer
+    pub fn is_integer(&self) -> bool {
+        matches!(self, Ty::Unsigned | Ty::Signed)
+    }
+}
+
+/// A local variable of a body: a parameter, a binding, or a temporary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Local {
+    /// The name of the local, unique within its body: shadowed bindings get a `'1`, `'2`… suffix,
+    /// temporaries are `_1`, `_2`…, and the return value is `return`
+    pub name: String,
+    /// What the local can hold
+    pub ty: Ty,
+    /// Whether the local is a parameter
+    pub is_param: bool,
+    /// What a temporary stands for in the source, like `next(0..n)` for the counter of a range, to name it in certificates
+    pub source: Option<String>,
+}
+
+/// A unary operator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnOp {
+    /// `-`
+    Neg,
+    /// `!`
+    Not,
+}
+
+/// A binary operator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinOp {
+    /// `+`
+    Add,
+    /// `-`
+    Sub,
+    /// `*`
+    Mul,
+    /// `/`
+    Div,
+    /// `%`
+    Rem,
+    /// `&`
+    BitAnd,
+    /// `|`
+    BitOr,
+    /// `^`
+    BitXor,
+    /// `<<`
+    Shl,
+    /// `>>`
+    Shr,
+    /// `==`
+    Eq,
+    /// `!=`
+    Ne,
+    /// `<`
+    Lt,
+    /// `<=`
+    Le,
+    /// `>`
+    Gt,
+    /// `>=`
+    Ge,
+    /// `&&`, which doesn't evaluate its right side if the left is false
+    And,
+    /// `||`, which doesn't evaluate its right side if the left is true
+    Or,
+}
+
+impl BinOp {
+    /// The operator of a `syn` binary expression, with compound assignments like `+=` as their operator
+    pub fn from_syn(op: &syn::BinOp) -> Option<Self> {
+        Some(match op {
+            syn::BinOp::Add(_) | syn::BinOp::AddAssign(_) => BinOp::Add,
+            syn::BinOp::Sub(_) | syn::BinOp::SubAssign(_) => BinOp::Sub,
+            syn::BinOp::Mul(_) | syn::BinOp::MulAssign(_) => BinOp::Mul,
+            syn::BinOp::Div(_) | syn::BinOp::DivAssign(_) => BinOp::Div,
+            syn::BinOp::Rem(_) | syn::BinOp::RemAssign(_) => BinOp::Rem,
+            syn::BinOp::BitAnd(_) | syn::BinOp::BitAndAssign(_) => BinOp::BitAnd,
+            syn::BinOp::BitOr(_) | syn::BinOp::BitOrAssign(_) => BinOp::BitOr,
+            syn::BinOp::BitXor(_) | syn::BinOp::BitXorAssign(_) => BinOp::BitXor,
+            syn::BinOp::Shl(_) | syn::BinOp::ShlAssign(_) => BinOp::Shl,
+            syn::BinOp::Shr(_) | syn::BinOp::ShrAssign(_) => BinOp::Shr,
+            syn::BinOp::Eq(_) => BinOp::Eq,
+            syn::BinOp::Ne(_) => BinOp::Ne,
+            syn::BinOp::Lt(_) => BinOp::Lt,
+            syn::BinOp::Le(_) => BinOp::Le,
+            syn::BinOp::Gt(_) => BinOp::Gt,
+            syn::BinOp::Ge(_) => BinOp::Ge,
+            syn::BinOp::And(_) => BinOp::And,
+            syn::BinOp::Or(_) => BinOp::Or,
+            _ => return None,
+        })
+    }
+
+    /// How the operator is written
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Rem => "%",
+            BinOp::BitAnd => "&",
+            BinOp::BitOr => "|",
+            BinOp::BitXor => "^",
+            BinOp::Shl => "<<",
+            BinOp::Shr => ">>",
+            BinOp::Eq => "==",
+            BinOp::Ne => "!=",
+            BinOp::Lt => "<",
+            BinOp::Le => "<=",
+            BinOp::Gt => ">",
+            BinOp::Ge => ">=",
+            BinOp::And => "&&",
+            BinOp::Or => "||",
+        }
+    }
+
+    /// How tightly the operator binds, as in Rust: higher binds tighter
+    fn precedence(&self) -> u8 {
+        match self {
+            BinOp::Mul | BinOp::Div | BinOp::Rem => 10,
+            BinOp::Add | BinOp::Sub => 9,
+            BinOp::Shl | BinOp::Shr => 8,
+            BinOp::BitAnd => 7,
+            BinOp::BitXor => 6,
+            BinOp::BitOr => 5,
+            BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => 4,
+            BinOp::And => 3,
+            BinOp::Or => 2,
+        }
+    }
+
+    /// Whether the operator compares its operands or combines booleans, giving a boolean
+    pub fn is_boolean(&self) -> bool {
+        self.precedence() <= 4
+    }
+}
+
+/// A value computed without side effects, in terms of the locals of a body.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Expr {
+    /// An integer literal
+    Int(i128),
+    /// A boolean literal
+    Bool(bool),
+    /// A local, or a free name such as a constant
+    Var(String),
+    /// A unary operation
+    Unary(UnOp, Box<Expr>),
+    /// A binary operation
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    /// A part of a value strictly smaller than it, like a field or a variant's payload it was destructured into,
+    /// or a slice of it without its first elements, with how the part is got: `Cons.1`, `next` or `[1..]`
+    Part(Box<Expr>, String),
+    /// Something the IR doesn't look into, in words
+    Opaque(String),
+}
+
+impl Expr {
+    /// A binary operation on two expressions
+    pub fn binary(op: BinOp, left: Expr, right: Expr) -> Self {
+        Expr::Binary(op, Box::new(left), Box::new(right))
+    }
+
+    /// The negation of a condition
+    pub fn negated(self) -> Self {
+        match self {
+            Expr::Bool(b) => Expr::Bool(!b),
+            Expr::Unary(UnOp::Not, inner) => *inner,
+            expr => Expr::Unary(UnOp::Not, Box::new(expr)),
+        }
+    }
+
+    /// Whether the expression depends on something opaque
+    pub fn is_opaque(&self) -> bool {
+        match self {
+            Expr::Opaque(_) => true,
+            Expr::Unary(_, inner) | Expr::Part(inner, _) => inner.is_opaque(),
+            Expr::Binary(_, left, right) => left.is_opaque() || right.is_opaque(),
+            Expr::Int(_) | Expr::Bool(_) | Expr::Var(_) => false,
+        }
+    }
+
+    /// The names the expression reads
+    pub fn variables(&self) -> BTreeSet<String> {
+        let mut names = BTreeSet::new();
+        self.visit_variables(&mut |name| {
+            names.insert(name.to_string());
+        });
+        names
+    }
+
+    /// Call `f` on every name the expression reads
+    fn visit_variables(&self, f: &mut dyn FnMut(&str)) {
+        match self {
+            Expr::Var(name) => f(name),
+            Expr::Unary(_, inner) | Expr::Part(inner, _) => inner.visit_variables(f),
+            Expr::Binary(_, left, right) => {
+                left.visit_variables(f);
+                right.visit_variables(f);
+            }
+            Expr::Int(_) | Expr::Bool(_) | Expr::Opaque(_) => {}
+        }
+    }
+
+    /// The expression with each name replaced by what `value_of` says it stands for.
+    ///
+    /// # Returns
+    ///
+    /// The substituted expression, or `None` if `value_of` can't tell what some name stands for
+    /// or the expression depends on something opaque.
+    pub fn substitute(&self, value_of: &dyn Fn(&str) -> Option<Expr>) -> Option<Expr> {
+        Some(match self {
+            Expr::Int(_) | Expr::Bool(_) => self.clone(),
+            Expr::Var(name) => value_of(name)?,
+            Expr::Unary(op, inner) => Expr::Unary(*op, Box::new(inner.substitute(value_of)?)),
+            Expr::Binary(op, left, right) => {
+                Expr::binary(*op, left.substitute(value_of)?, right.substitute(value_of)?)
+            }
+            Expr::Part(inner, part) => {
+                Expr::Part(Box::new(inner.substitute(value_of)?), part.clone())
+            }
+            Expr::Opaque(_) => return None,
+        })
+    }
+
+    /// How tightly the expression binds, for parenthesizing it inside another
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Binary(op, ..) => op.precedence(),
+            Expr::Unary(..) => 11,
+            Expr::Int(int) if *int < 0 => 11,
+            Expr::Opaque(_) => 0,
+            _ => 12,
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        /// Write an operand, parenthesized if it binds less tightly than `min`
+        fn operand(f: &mut fmt::Formatter<'_>, expr: &Expr, min: u8) -> fmt::Result {
+            if expr.precedence() < min {
+                write!(f, "({})", expr)
+            } else {
+                write!(f, "{}", expr)
+            }
+        }
+        match self {
+            Expr::Int(int) => write!(f, "{}", int),
+            Expr::Bool(b) => write!(f, "{}", b),
+            Expr::Var(name) => write!(f, "{}", name),
+            Expr::Opaque(text) => write!(f, "{}", text),
+            Expr::Part(inner, part) => {
+                operand(f, inner, 12)?;
+                if part.starts_with('[') {
+                    write!(f, "{}", part)
+                } else {
+                    write!(f, ".{}", part)
+                }
+            }
+            Expr::Unary(op, inner) => {
+                write!(f, "{}", if *op == UnOp::Neg { "-" } else { "!" })?;
+                operand(f, inner, 12)
+            }
+            Expr::Binary(op, left, right) => {
+                // comparisons don't chain, so a comparison inside another gets parentheses
+                let chains = u8::from(op.precedence() != 4);
+                operand(f, left, op.precedence() + 1 - chains)?;
+                write!(f, " {} ", op.symbol())?;
+                operand(f, right, op.precedence() + 1)
+            }
+        }
+    }
+}
+
+/// What a call calls
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Callee {
+    /// A function, by its path as written
+    Path(Vec<String>),
+    /// A method, by name, with the receiver as the first argument
+    Method(String),
+    /// Something else, like a closure, in words
+    Opaque(String),
+}
+
+/// A straight-line step of a basic block.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    /// Set a local to a value
+    Assign {
+        /// The local set
+        local: String,
+        /// The value it is set to
+        value: Expr,
+    },
+    /// Call a function and keep its result in a local
+    Call {
+        /// The local the result is kept in
+        dest: String,
+        /// What is called
+        callee: Callee,
+        /// The arguments, receiver first
+        args: Vec<Expr>,
+        /// Where the function or method is named, as the call graph records it
+        span: SourceSpan,
+    },
+    /// Set a local to a value which can't be told, like one changed through a reference
+    Havoc(String),
+    /// Carry on only where a condition holds, like the length of an iterator not being negative
+    Assume(Expr),
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Statement::Assign { local, value } => write!(f, "{} = {}", local, value),
+            Statement::Call {
+                dest, callee, args, ..
+            } => {
+                let args: Vec<String> = args.iter().map(Expr::to_string).collect();
+                match callee {
+                    Callee::Path(path) => {
+                        write!(f, "{} = {}({})", dest, path.join("::"), args.join(", "))
+                    }
+                    Callee::Method(method) => write!(
+                        f,
+                        "{} = {}.{}({})",
+                        dest,
+                        args.first().map_or("", String::as_str),
+                        method,
+                        args.get(1..).unwrap_or_default().join(", ")
+                    ),
+                    Callee::Opaque(text) => write!(f, "{} = ({})({})", dest, text, args.join(", ")),
+                }
+            }
+            Statement::Havoc(local) => write!(f, "havoc {}", local),
+            Statement::Assume(condition) => write!(f, "assume {}", condition),
+        }
+    }
+}
+
+/// How a body stops without returning
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Divergence {
+    /// A panic, which unwinds or aborts
+    Panic,
+    /// The process exits
+    Exit,
+}
+
+/// How a basic block ends.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Terminator {
+    /// Jump to a block
+    Goto(BlockId),
+    /// Jump to one block if a condition holds, and to another if not
+    Branch {
+        /// The condition
+        condition: Expr,
+        /// Where to go if it holds
+        then: BlockId,
+        /// Where to go if it doesn't
+        otherwise: BlockId,
+    },
+    /// Return from the body, with its value in the `return` local
+    Return,
+    /// Stop without returning
+    Diverge(Divergence),
+    /// Control never gets here, like past the last arm of a `match`
+    Unreachable,
+}
+
+impl fmt::Display for Terminator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Terminator::Goto(block) => write!(f, "goto bb{}", block),
+            Terminator::Branch {
+                condition,
+                then,
+                otherwise,
+            } => write!(f, "if {} goto bb{} else bb{}", condition, then, otherwise),
+            Terminator::Return => write!(f, "return"),
+            Terminator::Diverge(Divergence::Panic) => write!(f, "panic"),
+            Terminator::Diverge(Divergence::Exit) => write!(f, "exit"),
+            Terminator::Unreachable => write!(f, "unreachable"),
+        }
+    }
+}
+
+/// Straight-line statements and the jump at their end.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BasicBlock {
+    /// The statements, in order
+    pub statements: Vec<Statement>,
+    /// How the block ends
+    pub terminator: Terminator,
+    /// The code the block was lowered from, like the whole loop for the head of a loop
+    pub span: SourceSpan,
+}
+
+/// A function body lowered to a control-flow graph, entered at block 0.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Body {
+    /// The name of the function
+    pub name: String,
+    /// The locals bound to the parameters, receiver first
+    pub params: Vec<String>,
+    /// Every local of the body, parameters first
+    pub locals: Vec<Local>,
+    /// The basic blocks, every one of them reachable from the entry
+    pub blocks: Vec<BasicBlock>,
+    /// Where the function is named
+    pub span: SourceSpan,
+}
+
+/// The entry block of every body
+pub const ENTRY: BlockId = 0;
+
+impl Body {
+    /// A local by name
+    pub fn local(&self, name: &str) -> Option<&Local> {
+        self.locals.iter().find(|local| local.name == name)
+    }
+
+    /// The blocks a block may jump to
+    pub fn successors(&self, block: BlockId) -> Vec<BlockId> {
+        match &self.blocks[block].terminator {
+            Terminator::Goto(target) => vec![*target],
+            Terminator::Branch {
+                then, otherwise, ..
+            } => vec![*then, *otherwise],
+            Terminator::Return | Terminator::Diverge(_) | Terminator::Unreachable => vec![],
+        }
+    }
+
+    /// The blocks which may jump to each block
+    pub fn predecessors(&self) -> Vec<Vec<BlockId>> {
+        let mut predecessors = vec![vec![]; self.blocks.len()];
+        for block in 0..self.blocks.len() {
+            for successor in self.successors(block) {
+                predecessors[successor].push(block);
+            }
+        }
+        predecessors
+    }
+
+    /// The blocks in reverse postorder from the entry: every block comes before its successors,
+    /// except along the back edges of loops
+    pub fn reverse_postorder(&self) -> Vec<BlockId> {
+        let mut visited = vec![false; self.blocks.len()];
+        let mut postorder = vec![];
+        // each block on the stack with the successors it has yet to visit
+        let mut stack = vec![(ENTRY, self.successors(ENTRY))];
+        visited[ENTRY] = true;
+        while let Some((block, successors)) = stack.last_mut() {
+            match successors.pop() {
+                Some(successor) if !visited[successor] => {
+                    visited[successor] = true;
+                    let successors = self.successors(successor);
+                    stack.push((successor, successors.into_iter().rev().collect()));
+                }
+                Some(_) => {}
+                None => {
+                    postorder.push(*block);
+                    stack.pop();
+                }
+            }
+        }
+        postorder.reverse();
+        postorder
+    }
+
+    /// The dominator tree of the body, by the algorithm of Cooper, Harvey and Kennedy.
+    pub fn dominators(&self) -> Dominators {
+        let order = self.reverse_postorder();
+        let mut rank = vec![usize::MAX; self.blocks.len()];
+        for (i, &block) in order.iter().enumerate() {
+            rank[block] = i;
+        }
+        let predecessors = self.predecessors();
+        let mut idom: Vec<Option<BlockId>> = vec![None; self.blocks.len()];
+        idom[ENTRY] = Some(ENTRY);
+        let intersect = |idom: &[Option<BlockId>], mut a: BlockId, mut b: BlockId| {
+            while a != b {
+                while rank[a] > rank[b] {
+                    a = idom[a].unwrap_or(ENTRY);
+                }
+                while rank[b] > rank[a] {
+                    b = idom[b].unwrap_or(ENTRY);
+                }
+            }
+            a
+        };
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in order.iter().skip(1) {
+                let mut new_idom = None;
+                for &predecessor in &predecessors[block] {
+                    if idom[predecessor].is_none() {
+                        continue;
+                    }
+                    new_idom = Some(match new_idom {
+                        None => predecessor,
+                        Some(other) => intersect(&idom, predecessor, other),
+                    });
+                }
+                if new_idom.is_some() && idom[block] != new_idom {
+                    idom[block] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+        Dominators { idom }
+    }
+
+    /// The natural loops of the body, nested in a forest.
+    ///
+    /// A loop is a header block and every block which can get back to it without leaving,
+    /// found from the back edges which jump to a block dominating them.
+    /// A `loop` whose body always leaves, like `loop { break; }`, has no back edge and so is no loop here.
+    pub fn loop_forest(&self) -> LoopForest {
+        let dominators = self.dominators();
+        let predecessors = self.predecessors();
+        let mut headers: Vec<BlockId> = vec![];
+        let mut latches: HashMap<BlockId, Vec<BlockId>> = HashMap::new();
+        for block in self.reverse_postorder() {
+            for successor in self.successors(block) {
+                if dominators.dominates(successor, block) {
+                    if !headers.contains(&successor) {
+                        headers.push(successor);
+                    }
+                    latches.entry(successor).or_default().push(block);
+                }
+            }
+        }
+        let order = self.reverse_postorder();
+        headers.sort_by_key(|header| order.iter().position(|block| block == header));
+        let mut loops: Vec<Loop> = vec![];
+        for header in headers {
+            let mut blocks = BTreeSet::from([header]);
+            let mut work = latches[&header].clone();
+            while let Some(block) = work.pop() {
+                if blocks.insert(block) {
+                    work.extend(predecessors[block].iter().copied());
+                }
+            }
+            // the innermost loop found so far which holds the header, since outer loops come first
+            let parent = (0..loops.len())
+                .filter(|&i| loops[i].blocks.contains(&header))
+                .min_by_key(|&i| loops[i].blocks.len());
+            loops.push(Loop {
+                header,
+                latches: latches[&header].clone(),
+                depth: parent.map_or(0, |parent| loops[parent].depth + 1),
+                parent,
+                blocks,
+                span: self.blocks[header].span,
+            });
+        }
+        LoopForest { loops }
+    }
+}
+
+impl fmt::Display for Body {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "fn {}({}) {{", self.name, self.params.join(", "))?;
+        for (i, block) in self.blocks.iter().enumerate() {
+            writeln!(f, "    bb{}: {{", i)?;
+            for statement in &block.statements {
+                writeln!(f, "        {};", statement)?;
+            }
+            writeln!(f, "        {};", block.terminator)?;
+            writeln!(f, "    }}")?;
+        }
+        write!(f, "}}")
+    }
+}
+
+/// The immediate dominator of each block of a body.
+///
+/// A block dominates another if every path from the entry to the other goes through it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dominators {
+    /// The immediate dominator of each block, the entry itself for the entry,
+    /// and `None` for blocks which can't be reached
+    idom: Vec<Option<BlockId>>,
+}
+
+impl Dominators {
+    /// The closest block which dominates a block other than itself, or `None` for the entry
+    pub fn immediate_dominator(&self, block: BlockId) -> Option<BlockId> {
+        self.idom[block].filter(|&idom| idom != block)
+    }
+
+    /// Whether every path from the entry to `block` goes through `dominator`
+    pub fn dominates(&self, dominator: BlockId, block: BlockId) -> bool {
+        if self.idom[block].is_none() {
+            return false;
+        }
+        let mut block = block;
+        loop {
+            if block == dominator {
+                return true;
+            }
+            match self.immediate_dominator(block) {
+                Some(idom) => block = idom,
+                None => return false,
+            }
+        }
+    }
+}
+
+/// A natural loop of a body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Loop {
+    /// The block every iteration starts at, which dominates the rest of the loop
+    pub header: BlockId,
+    /// The blocks which jump back to the header
+    pub latches: Vec<BlockId>,
+    /// Every block of the loop, the header and nested loops included
+    pub blocks: BTreeSet<BlockId>,
+    /// The index of the innermost loop this one is nested in, if any
+    pub parent: Option<usize>,
+    /// How many loops this one is nested in
+    pub depth: usize,
+    /// The code of the loop
+    pub span: SourceSpan,
+}
+
+impl Loop {
+    /// The edges which leave the loop, from a block inside it to a block outside
+    pub fn exits(&self, body: &Body) -> Vec<(BlockId, BlockId)> {
+        self.blocks
+            .iter()
+            .flat_map(|&block| {
+                body.successors(block)
+                    .into_iter()
+                    .filter(|successor| !self.blocks.contains(successor))
+                    .map(move |successor| (block, successor))
+            })
+            .collect()
+    }
+}
+
+/// The natural loops of a body, outermost first.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct LoopForest {
+    /// The loops, each after the loop it is nested in
+    pub loops: Vec<Loop>,
+}
+
+impl LoopForest {
+    /// The innermost loop a block is part of, if any
+    pub fn innermost(&self, block: BlockId) -> Option<usize> {
+        (0..self.loops.len())
+            .filter(|&i| self.loops[i].blocks.contains(&block))
+            .max_by_key(|&i| self.loops[i].depth)
+    }
+}
+
+/// Where `break` and `continue` go from inside a loop or labeled block
+#[derive(Debug)]
+struct Target {
+    /// The label of the loop or block, if any
+    label: Option<String>,
+    /// Where `break` goes
+    break_to: BlockId,
+    /// Where `continue` goes, or `None` for a labeled block, which can't be continued
+    continue_to: Option<BlockId>,
+    /// The local a `break` with a value sets, once there is one
+    result: Option<String>,
+}
+
+/// How the items of an iterator bound to a local are counted
+#[derive(Debug, Clone)]
+struct Iteration {
+    /// The next item
+    item: Expr,
+    /// The condition for there being a next item, or `None` if there always is one
+    condition: Option<Expr>,
+    /// The counter and its value once an item is taken, if there is one
+    step: Option<(String, Expr)>,
+}
+
+/// Lowers a function body into basic blocks, one expression at a time
+#[derive(Debug, Default)]
+struct Builder {
+    /// The locals declared so far
+    locals: Vec<Local>,
+    /// The blocks made so far, including dead ones which are pruned at the end
+    blocks: Vec<BasicBlock>,
+    /// The block statements are added to
+    current: BlockId,
+    /// The locals each source name stands for, innermost scope last
+    scopes: Vec<HashMap<String, String>>,
+    /// The loops and labeled blocks around the current expression, innermost last
+    targets: Vec<Target>,
+    /// The source names which are borrowed mutably or changed by a closure somewhere in the body,
+    /// whose values can't be told from their assignments
+    escaped: HashSet<String>,
+    /// How many temporaries have been declared
+    temps: usize,
+    /// The heads of loops, which are kept even when empty so each loop keeps a block of its own
+    headers: HashSet<BlockId>,
+    /// The locals bound to iterators whose items are counted
+    iterators: HashMap<String, Iteration>,
+    /// The locals which hold collections, like a `Vec`, a slice or an array, which have only so many items
+    collections: HashSet<String>,
+    /// The temporaries holding what `next()` gave, with the condition for it being `Some` and the item in it
+    options: HashMap<String, (Expr, Expr)>,
+}
+
+impl Builder {
+    /// A builder with an empty entry block
+    fn new(span: SourceSpan, escaped: HashSet<String>) -> Self {
+        let mut builder = Builder {
+            scopes: vec![HashMap::new()],
+            escaped,
+            ..Builder::default()
+        };
+        builder.new_block(span);
+        builder
+    }
+
+    /// Make an empty block, ending as unreachable until told otherwise
+    fn new_block(&mut self, span: impl Into<SourceSpan>) -> BlockId {
+        self.blocks.push(BasicBlock {
+            statements: vec![],
+            terminator: Terminator::Unreachable,
+            span: span.into(),
+        });
+        self.blocks.len() - 1
+    }
+
+    /// Add a statement to the current block
+    fn push(&mut self, statement: Statement) {
+        self.blocks[self.current].statements.push(statement);
+    }
+
+    /// End the current block, carrying on in a fresh block which nothing jumps to yet
+    fn terminate(&mut self, terminator: Terminator) {
+        self.blocks[self.current].terminator = terminator;
+        let span = self.blocks[self.current].span;
+        self.current = self.new_block(span);
+    }
+
+    /// End the current block with a jump
+    fn goto(&mut self, target: BlockId) {
+        self.terminate(Terminator::Goto(target));
+    }
+
+    /// End the current block with a branch, or a jump if the condition is a constant
+    fn branch(&mut self, condition: Expr, then: BlockId, otherwise: BlockId) {
+        match condition {
+            Expr::Bool(true) => self.goto(then),
+            Expr::Bool(false) => self.goto(otherwise),
+            condition => self.terminate(Terminator::Branch {
+                condition,
+                then,
+                otherwise,
+            }),
+        }
+    }
+
+    /// Carry on adding statements to a block
+    fn switch_to(&mut self, block: BlockId) {
+        self.current = block;
+    }
+
+    /// Declare a local for a source name in the innermost scope, renamed if the name is taken
+    fn declare(&mut self, name: &str, ty: Ty, is_param: bool) -> String {
+        let mut unique = name.to_string();
+        let mut k = 0;
+        while self.locals.iter().any(|local| local.name == unique) {
+            k += 1;
+            unique = format!("{}'{}", name, k);
+        }
+        self.locals.push(Local {
+            name: unique.clone(),
+            ty,
+            is_param,
+            source: None,
+        });
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), unique.clone());
+        }
+        unique
+    }
+
+    /// Declare a temporary, which no source name stands for
+    fn temp(&mut self, ty: Ty) -> String {
+        self.temps += 1;
+        let name = format!("_{}", self.temps);
+        self.locals.push(Local {
+            name: name.clone(),
+            ty,
+            is_param: false,
+            source: None,
+        });
+        name
+    }
+
+    /// Declare a temporary which stands for something in the source, like the counter of a range
+    fn temp_for(&mut self, ty: Ty, source: String) -> String {
+        let name = self.temp(ty);
+        if let Some(local) = self.locals.last_mut() {
+            local.source = Some(source);
+        }
+        name
+    }
+
+    /// The local a source name stands for, if it is bound in the body
+    fn resolve(&self, name: &str) -> Option<String> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .cloned()
+    }
+
+    /// What the value of an expression can hold
+    fn ty_of(&self, expr: &Expr) -> Ty {
+        match expr {
+            Expr::Int(_) | Expr::Unary(UnOp::Neg, _) => Ty::Signed,
+            Expr::Bool(_) | Expr::Unary(UnOp::Not, _) => Ty::Bool,
+            Expr::Var(name) => self
+                .locals
+                .iter()
+                .find(|local| &local.name == name)
+                .map_or(Ty::Other, |local| local.ty),
+            Expr::Binary(op, _, _) if op.is_boolean() => Ty::Bool,
+            Expr::Binary(_, left, right) => match (self.ty_of(left), self.ty_of(right)) {
+                (Ty::Unsigned, _) | (_, Ty::Unsigned) => Ty::Unsigned,
+                (Ty::Signed, _) | (_, Ty::Signed) => Ty::Signed,
+                _ => Ty::Other,
+            },
+            Expr::Part(..) | Expr::Opaque(_) => Ty::Other,
+        }
+    }
+
+    /// Set the local bound to a source name, or forget its value if it may change behind our back
+    fn set(&mut self, name: &str, local: String, value: Expr) {
+        if self.escaped.contains(name) {
+            self.push(Statement::Havoc(local));
+        } else {
+            self.push(Statement::Assign { local, value });
+        }
+    }
+
+    /// Keep the value of a `match` or `if` arm, declaring the result local on first use
+    fn set_result(&mut self, result: &mut Option<String>, value: Expr) {
+        if value == unit() || value == never() {
+            return;
+        }
+        let local = match result {
+            Some(local) => local.clone(),
+            None => {
+                let local = self.temp(self.ty_of(&value));
+                *result = Some(local.clone());
+                local
+            }
+        };
+        self.push(Statement::Assign { local, value });
+    }
+
+    /// Keep a computed value in a temporary, so it is computed once however often it is read
+    fn stable(&mut self, value: Expr) -> Expr {
+        match value {
+            Expr::Unary(..) | Expr::Binary(..) => {
+                let local = self.temp(self.ty_of(&value));
+                self.push(Statement::Assign {
+                    local: local.clone(),
+                    value,
+                });
+                Expr::Var(local)
+            }
+            value => value,
+        }
+    }
+
+    /// Lower a block in a scope of its own, giving the value of its tail expression
+    fn block(&mut self, block: &syn::Block) -> Expr {
+        self.scopes.push(HashMap::new());
+        let mut value = unit();
+        for (i, stmt) in block.stmts.iter().enumerate() {
+            value = match stmt {
+                syn::Stmt::Expr(tail, None) if i + 1 == block.stmts.len() => self.expr(tail),
+                stmt => {
+                    self.stmt(stmt);
+                    unit()
+                }
+            };
+        }
+        self.scopes.pop();
+        value
+    }
+
+    /// Lower a statement
+    fn stmt(&mut self, stmt: &syn::Stmt) {
+        match stmt {
+            syn::Stmt::Local(local) => self.local(local),
+            syn::Stmt::Expr(expr, _) => {
+                self.expr(expr);
+            }
+            syn::Stmt::Macro(stmt) => {
+                self.mac(&stmt.mac, stmt.span().into());
+            }
+            // nested items are functions of their own
+            syn::Stmt::Item(_) => {}
+        }
+    }
+
+    /// Lower a `let`, counting the items of an iterator it binds if it can,
+    /// and telling which locals hold collections
+    fn local(&mut self, local: &syn::Local) {
+        let (pat, ty) = match &local.pat {
+            syn::Pat::Type(typed) => (&*typed.pat, Some(Ty::from_type(&typed.ty))),
+            pat => (pat, None),
+        };
+        let collection = match &local.pat {
+            syn::Pat::Type(typed) => is_collection_type(&typed.ty),
+            _ => local
+                .init
+                .as_ref()
+                .is_some_and(|init| is_collection(&init.expr)),
+        };
+        let Some(init) = &local.init else {
+            for name in names_from_pat(pat) {
+                let local = self.declare(&name, ty.unwrap_or(Ty::Other), false);
+                self.push(Statement::Havoc(local));
+            }
+            return;
+        };
+        if let (syn::Pat::Ident(ident), Some(iterator)) = (pat, iterator_from_expr(&init.expr)) {
+            let iteration = self.iteration(iterator);
+            self.bind_pat(pat, Expr::Opaque(text(&init.expr)), ty);
+            if let Some(local) = self.resolve(&ident.ident.to_string()) {
+                self.iterators.insert(local, iteration);
+            }
+            return;
+        }
+        let value = self.expr(&init.expr);
+        self.bind_pat(pat, value, ty);
+        if let (syn::Pat::Ident(ident), true) = (pat, collection) {
+            self.collections
+                .extend(self.resolve(&ident.ident.to_string()));
+        }
+    }
+
+    /// Bind the names of a pattern which matched a value, in the innermost scope
+    fn bind_pat(&mut self, pat: &syn::Pat, value: Expr, ty: Option<Ty>) {
+        match pat {
+            syn::Pat::Ident(ident) if is_binding(&ident.ident) => {
+                if let Some((_, subpat)) = &ident.subpat {
+                    self.bind_pat(subpat, value.clone(), ty);
+                }
+                let name = ident.ident.to_string();
+                let ty = ty.unwrap_or_else(|| self.ty_of(&value));
+                let local = self.declare(&name, ty, false);
+                self.set(&name, local, value);
+            }
+            syn::Pat::Type(typed) => {
+                self.bind_pat(&typed.pat, value, Some(Ty::from_type(&typed.ty)));
+            }
+            syn::Pat::Paren(paren) => self.bind_pat(&paren.pat, value, ty),
+            syn::Pat::Reference(reference) => self.bind_pat(&reference.pat, value, ty),
+            syn::Pat::TupleStruct(some) if self.option(&value, pat).is_some() => {
+                if let (Some((_, item)), Some(inner)) =
+                    (self.option(&value, pat), some.elems.first())
+                {
+                    self.bind_pat(inner, item, ty);
+                }
+            }
+            syn::Pat::TupleStruct(tuple) => {
+                let variant = variant_name(&tuple.path);
+                for (i, elem) in tuple.elems.iter().enumerate() {
+                    self.bind_pat(elem, part_of(&value, format!("{}.{}", variant, i)), None);
+                }
+            }
+            syn::Pat::Struct(strukt) => {
+                let variant = variant_name(&strukt.path);
+                for field in &strukt.fields {
+                    let member = text(&field.member);
+                    self.bind_pat(
+                        &field.pat,
+                        part_of(&value, format!("{}.{}", variant, member)),
+                        None,
+                    );
+                }
+            }
+            syn::Pat::Tuple(tuple) => {
+                for (i, elem) in tuple.elems.iter().enumerate() {
+                    self.bind_pat(elem, part_of(&value, i.to_string()), None);
+                }
+            }
+            syn::Pat::Slice(slice) => {
+                let len = slice.elems.len();
+                for (i, elem) in slice.elems.iter().enumerate() {
+                    let rest = match elem {
+                        syn::Pat::Ident(ident) => {
+                            ident.subpat.as_ref().is_some_and(|(_, subpat)| {
+                                matches!(subpat.as_ref(), syn::Pat::Rest(_))
+                            })
+                        }
+                        _ => false,
+                    };
+                    // the rest of a slice is only smaller than it if some other element was matched
+                    let part = match (rest, len) {
+                        (true, 1) => value.clone(),
+                        (true, _) if i + 1 == len => part_of(&value, format!("[{}..]", i)),
+                        (true, _) => part_of(&value, format!("[{}..len - {}]", i, len - i - 1)),
+                        (false, _) => part_of(&value, format!("[{}]", i)),
+                    };
+                    match elem {
+                        syn::Pat::Ident(ident) if rest => self.bind_pat(
+                            &syn::Pat::Ident(syn::PatIdent {
+                                subpat: None,
+                                ..ident.clone()
+                            }),
+                            part,
+                            None,
+                        ),
+                        elem => self.bind_pat(elem, part, None),
+                    }
+                }
+            }
+            // whatever an or-pattern matched isn't followed
+            pat => {
+                for name in names_from_pat(pat) {
+                    let local = self.declare(&name, Ty::Other, false);
+                    self.push(Statement::Havoc(local));
+                }
+            }
+        }
+    }
+
+    /// The condition under which a pattern matches a value
+    fn pattern_condition(&mut self, value: &Expr, pat: &syn::Pat) -> Expr {
+        let opaque = || Expr::Opaque(format!("{} matches {}", value, text(pat)));
+        if let Some((is_some, item)) = self.option(value, pat) {
+            return match pat {
+                syn::Pat::TupleStruct(some) => match some.elems.first() {
+                    Some(inner) => match self.pattern_condition(&item, inner) {
+                        Expr::Bool(true) => is_some,
+                        inner => Expr::binary(BinOp::And, is_some, inner),
+                    },
+                    None => opaque(),
+                },
+                _ => is_some.negated(),
+            };
+        }
+        match pat {
+            syn::Pat::Wild(_) | syn::Pat::Rest(_) => Expr::Bool(true),
+            syn::Pat::Ident(ident) => match &ident.subpat {
+                Some((_, subpat)) => self.pattern_condition(value, subpat),
+                None if is_binding(&ident.ident) => Expr::Bool(true),
+                None => opaque(),
+            },
+            syn::Pat::Lit(lit) => Expr::binary(BinOp::Eq, value.clone(), literal(&lit.lit)),
+            syn::Pat::Range(range) => {
+                let mut conditions = vec![];
+                if let Some(start) = &range.start {
+                    let start = self.expr(start);
+                    conditions.push(Expr::binary(BinOp::Ge, value.clone(), start));
+                }
+                if let Some(end) = &range.end {
+                    let op = match range.limits {
+                        syn::RangeLimits::HalfOpen(_) => BinOp::Lt,
+                        syn::RangeLimits::Closed(_) => BinOp::Le,
+                    };
+                    let end = self.expr(end);
+                    conditions.push(Expr::binary(op, value.clone(), end));
+                }
+                conditions
+                    .into_iter()
+                    .reduce(|left, right| Expr::binary(BinOp::And, left, right))
+                    .unwrap_or(Expr::Bool(true))
+            }
+            syn::Pat::Or(or) => {
+                let mut cases = vec![];
+                for case in &or.cases {
+                    match self.pattern_condition(value, case) {
+                        Expr::Bool(true) => return Expr::Bool(true),
+                        condition => cases.push(condition),
+                    }
+                }
+                cases
+                    .into_iter()
+                    .reduce(|left, right| Expr::binary(BinOp::Or, left, right))
+                    .unwrap_or(Expr::Bool(false))
+            }
+            syn::Pat::Paren(paren) => self.pattern_condition(value, &paren.pat),
+            syn::Pat::Reference(reference) => self.pattern_condition(value, &reference.pat),
+            syn::Pat::Type(typed) => self.pattern_condition(value, &typed.pat),
+            syn::Pat::Tuple(tuple) if tuple.elems.iter().all(is_irrefutable) => Expr::Bool(true),
+            _ => opaque(),
+        }
+    }
+
+    /// What `next()` gave, as the condition for it being `Some` and the item in it,
+    /// if the value is from an iterator whose items are counted and the pattern is `Some(_)` or `None`
+    fn option(&self, value: &Expr, pat: &syn::Pat) -> Option<(Expr, Expr)> {
+        let Expr::Var(option) = value else {
+            return None;
+        };
+        let is_variant = match pat {
+            syn::Pat::TupleStruct(some) => some.elems.len() == 1 && some.path.is_ident("Some"),
+            syn::Pat::Path(none) => none.path.is_ident("None"),
+            syn::Pat::Ident(none) => none.subpat.is_none() && none.ident == "None",
+            _ => false,
+        };
+        is_variant
+            .then(|| self.options.get(option).cloned())
+            .flatten()
+    }
+
+    /// Lower an expression, adding the statements and blocks it takes to compute,
+    /// and giving its value
+    fn expr(&mut self, expr: &syn::Expr) -> Expr {
+        match expr {
+            syn::Expr::Lit(lit) => literal(&lit.lit),
+            syn::Expr::Path(path) => match path.path.get_ident() {
+                Some(ident) if path.qself.is_none() => self.read(&ident.to_string()),
+                _ => Expr::Opaque(text(path)),
+            },
+            syn::Expr::Paren(paren) => self.expr(&paren.expr),
+            syn::Expr::Group(group) => self.expr(&group.expr),
+            // integers are mathematical integers, whatever their width
+            syn::Expr::Cast(cast) => self.expr(&cast.expr),
+            syn::Expr::Reference(reference) => self.expr(&reference.expr),
+            syn::Expr::Unary(unary) => {
+                let inner = self.expr(&unary.expr);
+                match (unary.op, inner) {
+                    (syn::UnOp::Neg(_), Expr::Int(int)) => int
+                        .checked_neg()
+                        .map_or_else(|| Expr::Opaque(text(unary)), Expr::Int),
+                    (syn::UnOp::Neg(_), inner) => Expr::Unary(UnOp::Neg, Box::new(inner)),
+                    (syn::UnOp::Not(_), inner) => inner.negated(),
+                    (syn::UnOp::Deref(_), inner) => inner,
+                    _ => Expr::Opaque(text(unary)),
+                }
+            }
+            syn::Expr::Binary(binary) => self.binary(binary),
+            syn::Expr::Assign(assign) => {
+                let value = self.expr(&assign.right);
+                self.assign(&assign.left, value);
+                unit()
+            }
+            syn::Expr::Block(block) => match &block.label {
+                Some(label) => self.labeled_block(label, &block.block, block.span()),
+                None => self.block(&block.block),
+            },
+            syn::Expr::Unsafe(block) => self.block(&block.block),
+            syn::Expr::Const(block) => self.block(&block.block),
+            syn::Expr::If(expr) => self.if_else(expr),
+            syn::Expr::Match(expr) => self.match_arms(expr),
+            // `for`, `while` and `?` were desugared into `loop` and `match` by `normalize`
+            syn::Expr::Loop(expr) => self.loop_body(expr),
+            syn::Expr::Break(expr) => self.break_to(expr),
+            syn::Expr::Continue(expr) => self.continue_to(expr),
+            syn::Expr::Return(expr) => {
+                if let Some(value) = &expr.expr {
+                    let value = self.expr(value);
+                    self.push(Statement::Assign {
+                        local: RETURN.to_string(),
+                        value,
+                    });
+                }
+                self.terminate(Terminator::Return);
+                never()
+            }
+            syn::Expr::Call(call) => self.call(call),
+            syn::Expr::MethodCall(call) => self.method_call(call),
+            syn::Expr::Macro(expr) => self.mac(&expr.mac, expr.span().into()),
+            // closures run whenever they are called, which isn't followed
+            syn::Expr::Closure(_) | syn::Expr::Async(_) => {
+                Expr::Opaque(format!("closure@{}", SourceSpan::from(expr.span())))
+            }
+            syn::Expr::Let(expr) => {
+                let value = self.expr(&expr.expr);
+                self.pattern_condition(&value, &expr.pat)
+            }
+            syn::Expr::Tuple(tuple) if tuple.elems.is_empty() => unit(),
+            syn::Expr::Field(field) => {
+                let base = self.expr(&field.base);
+                part_of(&base, text(&field.member))
+            }
+            // a slice from a positive literal on is shorter than what it is sliced from
+            syn::Expr::Index(index) if is_tail_range(&index.index) => {
+                let base = self.expr(&index.expr);
+                self.effects(&index.index);
+                part_of(&base, format!("[{}]", text(&index.index)))
+            }
+            expr => {
+                self.effects(expr);
+                Expr::Opaque(text(expr))
+            }
+        }
+    }
+
+    /// Lower the parts of an expression which isn't followed, for the calls in them
+    fn effects(&mut self, expr: &syn::Expr) {
+        let parts: Vec<&syn::Expr> = match expr {
+            syn::Expr::Tuple(tuple) => tuple.elems.iter().collect(),
+            syn::Expr::Array(array) => array.elems.iter().collect(),
+            syn::Expr::Struct(expr) => expr
+                .fields
+                .iter()
+                .map(|field| &field.expr)
+                .chain(expr.rest.as_deref())
+                .collect(),
+            syn::Expr::Field(field) => vec![&field.base],
+            syn::Expr::Index(index) => vec![&index.expr, &index.index],
+            syn::Expr::Range(range) => range.start.iter().chain(&range.end).map(|e| &**e).collect(),
+            syn::Expr::Repeat(repeat) => vec![&repeat.expr],
+            syn::Expr::Await(expr) => vec![&expr.base],
+            _ => vec![],
+        };
+        for part in parts {
+            self.expr(part);
+        }
+    }
+
+    /// The value of a name: a local, or a free name such as a constant
+    fn read(&self, name: &str) -> Expr {
+        match self.resolve(name) {
+            Some(_) if self.escaped.contains(name) => Expr::Opaque(name.to_string()),
+            Some(local) => Expr::Var(local),
+            None => Expr::Var(name.to_string()),
+        }
+    }
+
+    /// Assign a value to a place
+    fn assign(&mut self, place: &syn::Expr, value: Expr) {
+        match place {
+            syn::Expr::Path(path) => {
+                if let Some(ident) = path.path.get_ident() {
+                    let name = ident.to_string();
+                    if let Some(local) = self.resolve(&name) {
+                        self.iterators.remove(&local);
+                        self.set(&name, local, value);
+                    }
+                }
+            }
+            syn::Expr::Paren(paren) => self.assign(&paren.expr, value),
+            // assigning through a reference changes what the local is taken to be
+            syn::Expr::Unary(unary) if matches!(unary.op, syn::UnOp::Deref(_)) => {
+                self.assign(&unary.expr, value);
+            }
+            place => {
+                if let syn::Expr::Index(index) = place {
+                    self.expr(&index.index);
+                }
+                if let Some(local) = self.root_local(place) {
+                    self.push(Statement::Havoc(local));
+                }
+            }
+        }
+    }
+
+    /// The local a place is part of, like `v` for `v[i].x`
+    fn root_local(&self, place: &syn::Expr) -> Option<String> {
+        self.resolve(&name_of_place(place)?)
+    }
+
+    /// Lower a binary operation, a compound assignment, or a `&&` or `||` whose right side
+    /// has effects and so only happens depending on the left
+    fn binary(&mut self, binary: &syn::ExprBinary) -> Expr {
+        let Some(op) = BinOp::from_syn(&binary.op) else {
+            return Expr::Opaque(text(binary));
+        };
+        if is_compound_assignment(&binary.op) {
+            let right = self.expr(&binary.right);
+            let left = self.expr(&binary.left);
+            self.assign(&binary.left, Expr::binary(op, left, right));
+            return unit();
+        }
+        let left = self.expr(&binary.left);
+        if matches!(op, BinOp::And | BinOp::Or) && has_effects(&binary.right) {
+            let result = self.temp(Ty::Bool);
+            self.push(Statement::Assign {
+                local: result.clone(),
+                value: left.clone(),
+            });
+            let right_block = self.new_block(binary.right.span());
+            let join = self.new_block(binary.span());
+            match op {
+                BinOp::And => self.branch(left, right_block, join),
+                _ => self.branch(left, join, right_block),
+            }
+            self.switch_to(right_block);
+            let right = self.expr(&binary.right);
+            self.push(Statement::Assign {
+                local: result.clone(),
+                value: right,
+            });
+            self.goto(join);
+            self.switch_to(join);
+            return Expr::Var(result);
+        }
+        let right = self.expr(&binary.right);
+        Expr::binary(op, left, right)
+    }
+
+    /// Lower a condition as a jump to `then` if it holds and to `otherwise` if not,
+    /// binding the names of any `let` in it in the innermost scope on the way to `then`
+    fn condition(&mut self, condition: &syn::Expr, then: BlockId, otherwise: BlockId) {
+        match condition {
+            syn::Expr::Paren(paren) => self.condition(&paren.expr, then, otherwise),
+            syn::Expr::Group(group) => self.condition(&group.expr, then, otherwise),
+            syn::Expr::Unary(unary)
+                if matches!(unary.op, syn::UnOp::Not(_)) && has_effects(&unary.expr) =>
+            {
+                self.condition(&unary.expr, otherwise, then);
+            }
+            syn::Expr::Binary(binary)
+                if matches!(binary.op, syn::BinOp::And(_)) && has_effects(condition) =>
+            {
+                let right = self.new_block(binary.right.span());
+                self.condition(&binary.left, right, otherwise);
+                self.switch_to(right);
+                self.condition(&binary.right, then, otherwise);
+            }
+            syn::Expr::Binary(binary)
+                if matches!(binary.op, syn::BinOp::Or(_)) && has_effects(condition) =>
+            {
+                let right = self.new_block(binary.right.span());
+                self.condition(&binary.left, then, right);
+                self.switch_to(right);
+                self.condition(&binary.right, then, otherwise);
+            }
+            syn::Expr::Let(expr) => {
+                let value = self.expr(&expr.expr);
+                let value = self.stable(value);
+                let bind = self.new_block(expr.span());
+                let matches = self.pattern_condition(&value, &expr.pat);
+                self.branch(matches, bind, otherwise);
+                self.switch_to(bind);
+                self.bind_pat(&expr.pat, value, None);
+                self.goto(then);
+            }
+            condition => {
+                let value = self.expr(condition);
+                self.branch(value, then, otherwise);
+            }
+        }
+    }
+
+    /// Lower an `if`, including `if let`
+    fn if_else(&mut self, expr: &syn::ExprIf) -> Expr {
+        let then = self.new_block(expr.then_branch.span());
+        let otherwise = self.new_block(expr.span());
+        let join = self.new_block(expr.span());
+        let mut result = None;
+        self.scopes.push(HashMap::new());
+        self.condition(&expr.cond, then, otherwise);
+        self.switch_to(then);
+        let value = self.block(&expr.then_branch);
+        self.scopes.pop();
+        self.set_result(&mut result, value);
+        self.goto(join);
+        self.switch_to(otherwise);
+        if let Some((_, else_branch)) = &expr.else_branch {
+            let value = self.expr(else_branch);
+            self.set_result(&mut result, value);
+        }
+        self.goto(join);
+        self.switch_to(join);
+        result.map_or_else(unit, Expr::Var)
+    }
+
+    /// Lower a `match` as a chain of tests, one arm after another
+    fn match_arms(&mut self, expr: &syn::ExprMatch) -> Expr {
+        let value = self.expr(&expr.expr);
+        let value = self.stable(value);
+        let join = self.new_block(expr.span());
+        let mut result = None;
+        // what holds once the arms before have failed to match
+        let mut refuted: Vec<Expr> = vec![];
+        for arm in &expr.arms {
+            let body = self.new_block(arm.body.span());
+            let next = self.new_block(expr.span());
+            let mut matches = self.pattern_condition(&value, &arm.pat);
+            // like `None` after `Some(x)`
+            if refuted.contains(&matches) {
+                matches = Expr::Bool(true);
+            }
+            if arm.guard.is_none() {
+                refuted.push(matches.clone().negated());
+            }
+            self.branch(matches, body, next);
+            self.switch_to(body);
+            self.scopes.push(HashMap::new());
+            self.bind_pat(&arm.pat, value.clone(), None);
+            if let Some((_, guard)) = &arm.guard {
+                let guarded = self.new_block(arm.body.span());
+                self.condition(guard, guarded, next);
+                self.switch_to(guarded);
+            }
+            let value = self.expr(&arm.body);
+            self.scopes.pop();
+            self.set_result(&mut result, value);
+            self.goto(join);
+            self.switch_to(next);
+        }
+        // matches are exhaustive, so no value gets past the last arm
+        self.terminate(Terminator::Unreachable);
+        self.switch_to(join);
+        result.map_or_else(unit, Expr::Var)
+    }
+
+    /// Lower a `loop`, whose head is a block of its own
+    fn loop_body(&mut self, expr: &syn::ExprLoop) -> Expr {
+        let header = self.new_block(expr.span());
+        let exit = self.new_block(expr.span());
+        self.headers.insert(header);
+        self.goto(header);
+        self.switch_to(header);
+        self.targets.push(Target {
+            label: label_name(expr.label.as_ref()),
+            break_to: exit,
+            continue_to: Some(header),
+            result: None,
+        });
+        self.block(&expr.body);
+        self.goto(header);
+        let target = self.targets.pop();
+        self.switch_to(exit);
+        target
+            .and_then(|target| target.result)
+            .map_or_else(unit, Expr::Var)
+    }
+
+    /// Set up the counting of the items of an iterator: an integer range is counted through with a counter,
+    /// and an iterator over a collection, or adapting one, with a ghost counter of the items it has left.
+    /// Any other iterator, like `cycle()`, `from_fn` or one the crate implements, may have another item every time
+    fn iteration(&mut self, iterator: &syn::Expr) -> Iteration {
+        let iterator = without_parens(iterator);
+        if let syn::Expr::Range(range) = iterator {
+            if let Some(start) = &range.start {
+                let start = self.expr(start);
+                let source = format!("next({})", text(iterator));
+                let counter = self.temp_for(self.ty_of(&start), source);
+                self.push(Statement::Assign {
+                    local: counter.clone(),
+                    value: start,
+                });
+                let condition = range.end.as_ref().map(|end| {
+                    let end = self.expr(end);
+                    let end = self.copy(end);
+                    let op = match range.limits {
+                        syn::RangeLimits::HalfOpen(_) => BinOp::Lt,
+                        syn::RangeLimits::Closed(_) => BinOp::Le,
+                    };
+                    Expr::binary(op, Expr::Var(counter.clone()), end)
+                });
+                let next = Expr::binary(BinOp::Add, Expr::Var(counter.clone()), Expr::Int(1));
+                return Iteration {
+                    item: Expr::Var(counter.clone()),
+                    condition,
+                    step: Some((counter, next)),
+                };
+            }
+        }
+        if let syn::Expr::MethodCall(call) = iterator {
+            if let syn::Expr::Range(range) = without_parens(&call.receiver) {
+                if let (Some(start), Some(end), true) =
+                    (&range.start, &range.end, call.method == "rev")
+                {
+                    let start = self.expr(start);
+                    let start = self.copy(start);
+                    let end = self.expr(end);
+                    let source = format!("next({})", text(iterator));
+                    let counter = self.temp_for(self.ty_of(&end), source);
+                    let first = match range.limits {
+                        syn::RangeLimits::HalfOpen(_) => {
+                            Expr::binary(BinOp::Sub, end, Expr::Int(1))
+                        }
+                        syn::RangeLimits::Closed(_) => end,
+                    };
+                    self.push(Statement::Assign {
+                        local: counter.clone(),
+                        value: first,
+                    });
+                    let condition = Expr::binary(BinOp::Ge, Expr::Var(counter.clone()), start);
+                    let next = Expr::binary(BinOp::Sub, Expr::Var(counter.clone()), Expr::Int(1));
+                    return Iteration {
+                        item: Expr::Var(counter.clone()),
+                        condition: Some(condition),
+                        step: Some((counter, next)),
+                    };
+                }
+            }
+        }
+        self.expr(iterator);
+        let item = Expr::Opaque(format!("next item of {}", text(iterator)));
+        if is_infinite_iterator(iterator) {
+            return Iteration {
+                item,
+                condition: None,
+                step: None,
+            };
+        }
+        if !self.is_finite_iterator(iterator) {
+            // whether there is another item is told afresh every time, so nothing counts down
+            let more = self.temp(Ty::Bool);
+            self.push(Statement::Havoc(more.clone()));
+            let step = Expr::Opaque(format!("whether {} has another item", text(iterator)));
+            return Iteration {
+                item,
+                condition: Some(Expr::Var(more.clone())),
+                step: Some((more, step)),
+            };
+        }
+        let remaining = self.temp_for(Ty::Unsigned, format!("remaining({})", text(iterator)));
+        self.push(Statement::Havoc(remaining.clone()));
+        self.push(Statement::Assume(Expr::binary(
+            BinOp::Ge,
+            Expr::Var(remaining.clone()),
+            Expr::Int(0),
+        )));
+        let condition = Expr::binary(BinOp::Gt, Expr::Var(remaining.clone()), Expr::Int(0));
+        let next = Expr::binary(BinOp::Sub, Expr::Var(remaining.clone()), Expr::Int(1));
+        Iteration {
+            item,
+            condition: Some(condition),
+            step: Some((remaining, next)),
+        }
+    }
+
+    /// Take the next item of an iterator whose items are counted, stepping its counter,
+    /// and give what `next()` gives, which `Some(item)` matches exactly when there was an item
+    fn next_item(&mut self, iteration: Iteration) -> Expr {
+        let Iteration {
+            mut item,
+            mut condition,
+            step,
+        } = iteration;
+        if let Some((counter, next)) = step {
+            let before = self.temp(self.ty_of(&Expr::Var(counter.clone())));
+            self.push(Statement::Assign {
+                local: before.clone(),
+                value: Expr::Var(counter.clone()),
+            });
+            self.push(Statement::Assign {
+                local: counter.clone(),
+                value: next,
+            });
+            item = renamed(&item, &counter, &before);
+            condition = condition.map(|condition| renamed(&condition, &counter, &before));
+        }
+        let option = self.temp(Ty::Other);
+        self.push(Statement::Havoc(option.clone()));
+        self.options.insert(
+            option.clone(),
+            (condition.unwrap_or(Expr::Bool(true)), item),
+        );
+        Expr::Var(option)
+    }
+
+    /// The counted iterator a `next()` call takes an item from, if it is one
+    fn counted(&self, iterator: &syn::Expr) -> Option<Iteration> {
+        let local = self.resolve(&name_of_place(iterator)?)?;
+        self.iterators.get(&local).cloned()
+    }
+
+    /// Whether an iterator has only so many items: one over a bounded range or a collection,
+    /// or an adapter of one which takes no more items than it is given, or `take(n)` of any iterator
+    fn is_finite_iterator(&self, expr: &syn::Expr) -> bool {
+        match expr {
+            syn::Expr::Paren(paren) => self.is_finite_iterator(&paren.expr),
+            syn::Expr::Reference(reference) => self.is_finite_iterator(&reference.expr),
+            syn::Expr::Range(range) => range.start.is_some() && range.end.is_some(),
+            syn::Expr::Array(_) | syn::Expr::Repeat(_) => true,
+            syn::Expr::Lit(lit) => matches!(lit.lit, syn::Lit::Str(_)),
+            syn::Expr::Macro(mac) => mac.mac.path.is_ident("vec"),
+            syn::Expr::Path(path) => path
+                .path
+                .get_ident()
+                .and_then(|ident| self.resolve(&ident.to_string()))
+                .is_some_and(|local| self.collections.contains(&local)),
+            syn::Expr::MethodCall(call) => {
+                let method = call.method.to_string();
+                match method.as_str() {
+                    "take" => true,
+                    "zip" => {
+                        self.is_finite_iterator(&call.receiver)
+                            || call.args.iter().any(|arg| self.is_finite_iterator(arg))
+                    }
+                    "chain" => {
+                        self.is_finite_iterator(&call.receiver)
+                            && call.args.iter().all(|arg| self.is_finite_iterator(arg))
+                    }
+                    "iter" | "iter_mut" | "into_iter" | "drain" | "chars" | "bytes"
+                    | "char_indices" | "lines" | "split_whitespace" | "keys" | "values"
+                    | "values_mut" | "windows" | "chunks" | "map" | "filter" | "filter_map"
+                    | "enumerate" | "rev" | "skip" | "step_by" | "skip_while" | "take_while"
+                    | "map_while" | "inspect" | "copied" | "cloned" | "peekable" | "fuse"
+                    | "by_ref" => self.is_finite_iterator(&call.receiver),
+                    _ => false,
+                }
+            }
+            _ => false,
+        }
+    }
+
+    /// Keep a value in a temporary unless it is a literal, so later changes to the locals it reads don't change it
+    fn copy(&mut self, value: Expr) -> Expr {
+        match value {
+            Expr::Int(_) | Expr::Bool(_) | Expr::Opaque(_) => value,
+            value => {
+                let local = self.temp_for(self.ty_of(&value), value.to_string());
+                self.push(Statement::Assign {
+                    local: local.clone(),
+                    value,
+                });
+                Expr::Var(local)
+            }
+        }
+    }
+
+    /// Lower a labeled block, which `break 'label` leaves
+    fn labeled_block(
+        &mut self,
+        label: &syn::Label,
+        block: &syn::Block,
+        span: proc_macro2::Span,
+    ) -> Expr {
+        let exit = self.new_block(span);
+        self.targets.push(Target {
+            label: label_name(Some(label)),
+            break_to: exit,
+            continue_to: None,
+            result: None,
+        });
+        let value = self.block(block);
+        let mut result = self.targets.pop().and_then(|target| target.result);
+        self.set_result(&mut result, value);
+        self.goto(exit);
+        self.switch_to(exit);
+        result.map_or_else(unit, Expr::Var)
+    }
+
+    /// The loop or labeled block a `break` or `continue` goes to, innermost first
+    fn target(&self, label: Option<&syn::Lifetime>) -> Option<usize> {
+        (0..self.targets.len()).rev().find(|&i| match label {
+            Some(label) => self.targets[i].label == Some(label.ident.to_string()),
+            // a bare `break` or `continue` goes to the innermost loop, skipping labeled blocks
+            None => self.targets[i].continue_to.is_some(),
+        })
+    }
+
+    /// Lower a `break`, with its value if any
+    fn break_to(&mut self, expr: &syn::ExprBreak) -> Expr {
+        let value = expr.expr.as_ref().map(|value| self.expr(value));
+        match self.target(expr.label.as_ref()) {
+            Some(i) => {
+                if let Some(value) = value {
+                    let mut result = self.targets[i].result.take();
+                    self.set_result(&mut result, value);
+                    self.targets[i].result = result;
+                }
+                let exit = self.targets[i].break_to;
+                self.goto(exit);
+            }
+            // a `break` outside of any loop doesn't compile
+            None => self.terminate(Terminator::Unreachable),
+        }
+        never()
+    }
+
+    /// Lower a `continue`
+    fn continue_to(&mut self, expr: &syn::ExprContinue) -> Expr {
+        match self
+            .target(expr.label.as_ref())
+            .and_then(|i| self.targets[i].continue_to)
+        {
+            Some(header) => self.goto(header),
+            None => self.terminate(Terminator::Unreachable),
+        }
+        never()
+    }
+
+    /// Lower a call to a function, which doesn't return if it is one of those which never do
+    fn call(&mut self, call: &syn::ExprCall) -> Expr {
+        if let (syn::Expr::Path(path), [iterator]) =
+            (&*call.func, &*call.args.iter().collect::<Vec<_>>())
+        {
+            if path
+                .path
+                .segments
+                .last()
+                .is_some_and(|segment| segment.ident == "next")
+            {
+                if let Some(iteration) = self.counted(iterator) {
+                    return self.next_item(iteration);
+                }
+            }
+        }
+        let callee = match &*call.func {
+            syn::Expr::Path(path) => Callee::Path(
+                path.path
+                    .segments
+                    .iter()
+                    .map(|segment| segment.ident.to_string())
+                    .collect(),
+            ),
+            func => Callee::Opaque(self.expr(func).to_string()),
+        };
+        let mut args = vec![];
+        for arg in &call.args {
+            args.push(self.expr(arg));
+        }
+        let divergence = match &callee {
+            Callee::Path(path) => DIVERGES
+                .iter()
+                .find(|(name, _)| path.last().is_some_and(|last| last == name))
+                .map(|(_, divergence)| *divergence),
+            _ => None,
+        };
+        let dest = self.temp(Ty::Other);
+        self.push(Statement::Call {
+            dest: dest.clone(),
+            callee,
+            args,
+            span: call.func.span().into(),
+        });
+        match divergence {
+            Some(divergence) => {
+                self.terminate(Terminator::Diverge(divergence));
+                never()
+            }
+            None => Expr::Var(dest),
+        }
+    }
+
+    /// Lower a call to a method, which may panic if it unwraps,
+    /// and may change its receiver unless it is known not to
+    fn method_call(&mut self, call: &syn::ExprMethodCall) -> Expr {
+        if call.method == "next" && call.args.is_empty() {
+            if let Some(iteration) = self.counted(&call.receiver) {
+                return self.next_item(iteration);
+            }
+        }
+        let mut args = vec![self.expr(&call.receiver)];
+        for arg in &call.args {
+            args.push(self.expr(arg));
+        }
+        let method = call.method.to_string();
+        let dest = self.temp(Ty::Other);
+        self.push(Statement::Call {
+            dest: dest.clone(),
+            callee: Callee::Method(method.clone()),
+            args,
+            span: call.method.span().into(),
+        });
+        if UNWRAPS.contains(&method.as_str()) {
+            let panics = self.new_block(call.span());
+            let ok = self.new_block(call.span());
+            let condition = Expr::Opaque(format!("{} panics", text(call)));
+            self.branch(condition, panics, ok);
+            self.switch_to(panics);
+            self.terminate(Terminator::Diverge(Divergence::Panic));
+            self.switch_to(ok);
+        } else if !PURE_METHODS.contains(&method.as_str()) {
+            if let Some(local) = self.root_local(&call.receiver) {
+                let ty = self.ty_of(&Expr::Var(local.clone()));
+                if !ty.is_integer() && ty != Ty::Bool {
+                    self.push(Statement::Havoc(local));
+                }
+            }
+        }
+        Expr::Var(dest)
+    }
+
+    /// Lower a macro: a panic, an assertion which may panic, or anything else with its arguments
+    fn mac(&mut self, mac: &syn::Macro, span: SourceSpan) -> Expr {
+        let name = mac
+            .path
+            .segments
+            .last()
+            .map(|segment| segment.ident.to_string())
+            .unwrap_or_default();
+        let parser = Punctuated::<syn::Expr, syn::Token![,]>::parse_terminated;
+        let args: Vec<syn::Expr> = mac
+            .parse_body_with(parser)
+            .map(|args| args.into_iter().collect())
+            .unwrap_or_default();
+        if PANICS.contains(&name.as_str()) {
+            for arg in &args {
+                self.expr(arg);
+            }
+            self.terminate(Terminator::Diverge(Divergence::Panic));
+            return never();
+        }
+        if ASSERTS.contains(&name.as_str()) && !args.is_empty() {
+            let holds = self.new_block(span);
+            let fails = self.new_block(span);
+            match (name.trim_start_matches("debug_"), args.as_slice()) {
+                ("assert_eq" | "assert_ne", [left, right, ..]) => {
+                    let left = self.expr(left);
+                    let right = self.expr(right);
+                    let op = if name.ends_with("eq") {
+                        BinOp::Eq
+                    } else {
+                        BinOp::Ne
+                    };
+                    self.branch(Expr::binary(op, left, right), holds, fails);
+                }
+                ("assert", [condition, ..]) => self.condition(condition, holds, fails),
+                _ => self.branch(Expr::Opaque(text(mac)), holds, fails),
+            }
+            self.switch_to(fails);
+            self.terminate(Terminator::Diverge(Divergence::Panic));
+            self.switch_to(holds);
+            return unit();
+        }
+        for arg in &args {
+            self.expr(arg);
+        }
+        Expr::Opaque(format!("{}!(…)", name))
+    }
+
+    /// Skip the blocks which do nothing but jump, other than the heads of loops,
+    /// then drop the blocks which can't be reached, numbering the rest in the order they were made
+    fn finish(self, name: String, params: Vec<String>, span: SourceSpan) -> Body {
+        let skip: Vec<Option<BlockId>> = (0..self.blocks.len())
+            .map(|block| match &self.blocks[block] {
+                BasicBlock {
+                    statements,
+                    terminator: Terminator::Goto(target),
+                    ..
+                } if statements.is_empty() && block != ENTRY && !self.headers.contains(&block) => {
+                    Some(*target)
+                }
+                _ => None,
+            })
+            .collect();
+        let resolve = |mut block: BlockId| {
+            // a cycle of empty blocks is an empty loop, which keeps its last block
+            for _ in 0..skip.len() {
+                match skip[block] {
+                    Some(target) => block = target,
+                    None => break,
+                }
+            }
+            block
+        };
+        let mut blocks = self.blocks.clone();
+        for block in &mut blocks {
+            block.terminator = match block.terminator.clone() {
+                Terminator::Goto(target) => Terminator::Goto(resolve(target)),
+                Terminator::Branch {
+                    condition,
+                    then,
+                    otherwise,
+                } => Terminator::Branch {
+                    condition,
+                    then: resolve(then),
+                    otherwise: resolve(otherwise),
+                },
+                terminator => terminator,
+            };
+        }
+        let mut body = Body {
+            name,
+            params,
+            locals: self.locals,
+            blocks,
+            span,
+        };
+        let mut reachable = body.reverse_postorder();
+        reachable.sort_unstable();
+        let mut index = vec![ENTRY; body.blocks.len()];
+        for (new, &old) in reachable.iter().enumerate() {
+            index[old] = new;
+        }
+        body.blocks = reachable
+            .iter()
+            .map(|&old| {
+                let mut block = body.blocks[old].clone();
+                block.terminator = match block.terminator {
+                    Terminator::Goto(target) => Terminator::Goto(index[target]),
+                    Terminator::Branch {
+                        condition,
+                        then,
+                        otherwise,
+                    } => Terminator::Branch {
+                        condition,
+                        then: index[then],
+                        otherwise: index[otherwise],
+                    },
+                    terminator => terminator,
+                };
+                block
+            })
+            .collect();
+        body
+    }
+}
+
+/// The name of the local which holds the return value
+pub const RETURN: &str = "return";
+
+/// The value of an expression which gives nothing, like a statement
+fn unit() -> Expr {
+    Expr::Opaque("()".to_string())
+}
+
+/// The value of an expression which never finishes, like `return` or `break`
+fn never() -> Expr {
+    Expr::Opaque("!".to_string())
+}
+
+/// Code in words, as its tokens without the spaces `quote` puts around punctuation like `.` and `(`
+fn text(tokens: &impl ToTokens) -> String {
+    let spaced = tokens.to_token_stream().to_string();
+    let mut text = String::with_capacity(spaced.len());
+    let chars: Vec<char> = spaced.chars().collect();
+    for (i, &c) in chars.iter().enumerate() {
+        let before = i.checked_sub(1).map(|i| chars[i]);
+        let after = chars.get(i + 1).copied();
+        let word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || "_)]>".contains(c));
+        let dropped = c == ' '
+            && (matches!(after, Some('.' | ',' | ')' | ']' | '?' | ';'))
+                || matches!(before, Some('.' | '(' | '[' | '&'))
+                || (after == Some('(') && word(text.chars().last()))
+                || (after == Some('!')
+                    && chars.get(i + 2) == Some(&' ')
+                    && chars.get(i + 3) == Some(&'(')));
+        let dropped = dropped || (c == ' ' && before == Some('!') && after == Some('('));
+        if !dropped {
+            text.push(c);
+        }
+    }
+    text
+}
+
+/// The value of a literal, if it is an integer or boolean
+fn literal(lit: &syn::Lit) -> Expr {
+    match lit {
+        syn::Lit::Int(int) => int
+            .base10_parse::<i128>()
+            .map_or_else(|_| Expr::Opaque(text(lit)), Expr::Int),
+        syn::Lit::Bool(b) => Expr::Bool(b.value),
+        lit => Expr::Opaque(text(lit)),
+    }
+}
+
+/// The name of a label, without its quote
+fn label_name(label: Option<&syn::Label>) -> Option<String> {
+    label.map(|label| label.name.ident.to_string())
+}
+
+/// Whether an identifier in a pattern binds a name, rather than naming a constant or unit variant like `None`
+fn is_binding(ident: &syn::Ident) -> bool {
+    !ident.to_string().starts_with(char::is_uppercase)
+}
+
+/// Whether a pattern matches every value
+fn is_irrefutable(pat: &syn::Pat) -> bool {
+    match pat {
+        syn::Pat::Wild(_) | syn::Pat::Rest(_) => true,
+        syn::Pat::Ident(ident) => is_binding(&ident.ident) && ident.subpat.is_none(),
+        syn::Pat::Tuple(tuple) => tuple.elems.iter().all(is_irrefutable),
+        syn::Pat::Paren(paren) => is_irrefutable(&paren.pat),
+        syn::Pat::Reference(reference) => is_irrefutable(&reference.pat),
+        syn::Pat::Type(typed) => is_irrefutable(&typed.pat),
+        _ => false,
+    }
+}
+
+/// A part of a value, unless the value is opaque, when the part is too
+fn part_of(value: &Expr, part: String) -> Expr {
+    match value {
+        Expr::Opaque(text) => Expr::Opaque(format!("{} of {}", part, text)),
+        value => Expr::Part(Box::new(value.clone()), part),
+    }
+}
+
+/// Whether an index is a range like `1..` or `2..n`, starting from a positive literal
+fn is_tail_range(index: &syn::Expr) -> bool {
+    let syn::Expr::Range(range) = index else {
+        return false;
+    };
+    matches!(
+        range.start.as_deref(),
+        Some(syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Int(int), .. }))
+            if int.base10_parse::<u128>().is_ok_and(|start| start >= 1)
+    )
+}
+
+/// The name of the struct or variant a path names, its last segment
+fn variant_name(path: &syn::Path) -> String {
+    path.segments
+        .last()
+        .map_or_else(String::new, |segment| segment.ident.to_string())
+}
+
+/// The names a pattern binds, in order
+fn names_from_pat(pat: &syn::Pat) -> Vec<String> {
+    /// Collects the identifiers bound by a pattern
+    #[derive(Default)]
+    struct NameCollector {
+        /// The names found so far
+        names: Vec<String>,
+    }
+    impl<'ast> Visit<'ast> for NameCollector {
+        fn visit_pat_ident(&mut self, pat: &'ast syn::PatIdent) {
+            let name = pat.ident.to_string();
+            if is_binding(&pat.ident) && !self.names.contains(&name) {
+                self.names.push(name);
+            }
+            syn::visit::visit_pat_ident(self, pat);
+        }
+    }
+    let mut collector = NameCollector::default();
+    collector.visit_pat(pat);
+    collector.names
+}
+
+/// The name of the variable a place is part of, like `v` for `v[i].x`
+fn name_of_place(place: &syn::Expr) -> Option<String> {
+    match place {
+        syn::Expr::Path(path) => Some(path.path.get_ident()?.to_string()),
+        syn::Expr::Field(field) => name_of_place(&field.base),
+        syn::Expr::Index(index) => name_of_place(&index.expr),
+        syn::Expr::Paren(paren) => name_of_place(&paren.expr),
+        syn::Expr::Reference(reference) => name_of_place(&reference.expr),
+        syn::Expr::Unary(unary) => name_of_place(&unary.expr),
+        _ => None,
+    }
+}
+
+/// An expression without the parentheses around it
+fn without_parens(expr: &syn::Expr) -> &syn::Expr {
+    match expr {
+        syn::Expr::Paren(paren) => without_parens(&paren.expr),
+        syn::Expr::Group(group) => without_parens(&group.expr),
+        expr => expr,
+    }
+}
+
+/// An expression with one local read in place of another
+fn renamed(expr: &Expr, from: &str, to: &str) -> Expr {
+    match expr {
+        Expr::Var(name) if name == from => Expr::Var(to.to_string()),
+        Expr::Unary(op, inner) => Expr::Unary(*op, Box::new(renamed(inner, from, to))),
+        Expr::Binary(op, left, right) => {
+            Expr::binary(*op, renamed(left, from, to), renamed(right, from, to))
+        }
+        expr => expr.clone(),
+    }
+}
+
+/// The iterator a `let` binds, if its items can be counted:
+/// what `IntoIterator::into_iter` is called on, as in a desugared `for`, or a range
+fn iterator_from_expr(init: &syn::Expr) -> Option<&syn::Expr> {
+    match without_parens(init) {
+        syn::Expr::Call(call) if call.args.len() == 1 => match &*call.func {
+            syn::Expr::Path(path)
+                if path
+                    .path
+                    .segments
+                    .last()
+                    .is_some_and(|segment| segment.ident == "into_iter") =>
+            {
+                call.args.first()
+            }
+            _ => None,
+        },
+        range @ syn::Expr::Range(_) => Some(range),
+        _ => None,
+    }
+}
+
+/// Whether a compound assignment like `+=`
+fn is_compound_assignment(op: &syn::BinOp) -> bool {
+    matches!(
+        op,
+        syn::BinOp::AddAssign(_)
+            | syn::BinOp::SubAssign(_)
+            | syn::BinOp::MulAssign(_)
+            | syn::BinOp::DivAssign(_)
+            | syn::BinOp::RemAssign(_)
+            | syn::BinOp::BitAndAssign(_)
+            | syn::BinOp::BitOrAssign(_)
+            | syn::BinOp::BitXorAssign(_)
+            | syn::BinOp::ShlAssign(_)
+            | syn::BinOp::ShrAssign(_)
+    )
+}
+
+/// Whether evaluating an expression may do more than compute a value:
+/// call something, assign, bind a name, or jump
+fn has_effects(expr: &syn::Expr) -> bool {
+    /// Looks for an expression with effects
+    struct EffectFinder {
+        /// Whether one was found
+        found: bool,
+    }
+    impl<'ast> Visit<'ast> for EffectFinder {
+        fn visit_expr(&mut self, expr: &'ast syn::Expr) {
+            match expr {
+                syn::Expr::Call(_)
+                | syn::Expr::MethodCall(_)
+                | syn::Expr::Macro(_)
+                | syn::Expr::Assign(_)
+                | syn::Expr::Let(_)
+                | syn::Expr::Break(_)
+                | syn::Expr::Continue(_)
+                | syn::Expr::Return(_)
+                | syn::Expr::Try(_)
+                | syn::Expr::If(_)
+                | syn::Expr::Match(_)
+                | syn::Expr::Loop(_)
+                | syn::Expr::While(_)
+                | syn::Expr::ForLoop(_)
+                | syn::Expr::Block(_)
+                | syn::Expr::Unsafe(_) => self.found = true,
+                syn::Expr::Binary(binary) if is_compound_assignment(&binary.op) => {
+                    self.found = true;
+                }
+                // a closure does nothing until it is called
+                syn::Expr::Closure(_) | syn::Expr::Async(_) => return,
+                _ => {}
+            }
+            syn::visit::visit_expr(self, expr);
+        }
+    }
+    let mut finder = EffectFinder { found: false };
+    finder.visit_expr(expr);
+    finder.found
+}
+
+/// Whether an iterator never runs out: `(n..)`, `repeat(x)`, `repeat_with(f)`,
+/// or `cycle()` over a non-empty literal range or array
+fn is_infinite_iterator(expr: &syn::Expr) -> bool {
+    match expr {
+        syn::Expr::Range(range) => range.start.is_some() && range.end.is_none(),
+        syn::Expr::Paren(paren) => is_infinite_iterator(&paren.expr),
+        syn::Expr::Call(call) => matches!(
+            &*call.func,
+            syn::Expr::Path(path) if path.path.segments.last().is_some_and(|segment| {
+                segment.ident == "repeat" || segment.ident == "repeat_with"
+            })
+        ),
+        syn::Expr::MethodCall(call) if call.method == "cycle" => is_non_empty(&call.receiver),
+        // adapters which map or inspect items one to one keep an iterator infinite
+        syn::Expr::MethodCall(call)
+            if ["map", "enumerate", "inspect", "iter", "into_iter", "by_ref"]
+                .iter()
+                .any(|method| call.method == method) =>
+        {
+            is_infinite_iterator(&call.receiver)
+        }
+        _ => false,
+    }
+}
+
+/// The standard collections, which have only so many items
+const COLLECTIONS: [&str; 10] = [
+    "Vec",
+    "VecDeque",
+    "String",
+    "str",
+    "HashMap",
+    "BTreeMap",
+    "HashSet",
+    "BTreeSet",
+    "BinaryHeap",
+    "LinkedList",
+];
+
+/// Whether a type is a slice, an array or one of the standard collections, or a reference to one
+fn is_collection_type(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Reference(reference) => is_collection_type(&reference.elem),
+        syn::Type::Paren(paren) => is_collection_type(&paren.elem),
+        syn::Type::Slice(_) | syn::Type::Array(_) => true,
+        syn::Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| COLLECTIONS.iter().any(|c| segment.ident == c)),
+        _ => false,
+    }
+}
+
+/// Whether an expression makes a collection: an array, `vec![..]`, a string literal,
+/// or a new standard collection, like `Vec::new()`, or a reference to one
+fn is_collection(expr: &syn::Expr) -> bool {
+    match expr {
+        syn::Expr::Paren(paren) => is_collection(&paren.expr),
+        syn::Expr::Reference(reference) => is_collection(&reference.expr),
+        syn::Expr::Array(_) | syn::Expr::Repeat(_) => true,
+        syn::Expr::Lit(lit) => matches!(lit.lit, syn::Lit::Str(_)),
+        syn::Expr::Macro(mac) => mac.mac.path.is_ident("vec"),
+        syn::Expr::Call(call) => match &*call.func {
+            syn::Expr::Path(path) => {
+                let mut segments = path.path.segments.iter().rev();
+                let constructor = segments.next().is_some_and(|segment| {
+                    ["new", "with_capacity", "from"]
+                        .iter()
+                        .any(|c| segment.ident == c)
+                });
+                let collection = segments
+                    .next()
+                    .is_some_and(|segment| COLLECTIONS.iter().any(|c| segment.ident == c));
+                constructor && collection
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Whether an iterable is a literal range or array with at least one element
+fn is_non_empty(expr: &syn::Expr) -> bool {
+    let int = |expr: &Option<Box<syn::Expr>>| match expr.as_deref() {
+        Some(syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Int(int),
+            ..
+        })) => int.base10_parse::<i128>().ok(),
+        _ => None,
+    };
+    match expr {
+        syn::Expr::Range(range) => match (int(&range.start), int(&range.end), &range.limits) {
+            (Some(start), Some(end), syn::RangeLimits::HalfOpen(_)) => start < end,
+            (Some(start), Some(end), syn::RangeLimits::Closed(_)) => start <= end,
+            _ => false,
+        },
+        syn::Expr::Array(array) => !array.elems.is_empty(),
+        syn::Expr::Paren(paren) => is_non_empty(&paren.expr),
+        syn::Expr::Reference(reference) => is_non_empty(&reference.expr),
+        syn::Expr::MethodCall(call) if call.method == "iter" || call.method == "into_iter" => {
+            is_non_empty(&call.receiver)
+        }
+        _ => false,
+    }
+}
+
+/// Collects the names whose values may change without an assignment to them:
+/// those borrowed mutably, and those a closure assigns to
+#[derive(Default)]
+struct EscapeCollector {
+    /// The names found so far
+    names: HashSet<String>,
+    /// Whether the current expression is inside a closure
+    in_closure: bool,
+}
+
+impl<'ast> Visit<'ast> for EscapeCollector {
+    fn visit_expr_reference(&mut self, expr: &'ast syn::ExprReference) {
+        if expr.mutability.is_some() {
+            self.names.extend(name_of_place(&expr.expr));
+        }
+        syn::visit::visit_expr_reference(self, expr);
+    }
+
+    fn visit_expr_assign(&mut self, expr: &'ast syn::ExprAssign) {
+        if self.in_closure {
+            self.names.extend(name_of_place(&expr.left));
+        }
+        syn::visit::visit_expr_assign(self, expr);
+    }
+
+    fn visit_expr_binary(&mut self, expr: &'ast syn::ExprBinary) {
+        if self.in_closure && is_compound_assignment(&expr.op) {
+            self.names.extend(name_of_place(&expr.left));
+        }
+        syn::visit::visit_expr_binary(self, expr);
+    }
+
+    fn visit_expr_closure(&mut self, expr: &'ast syn::ExprClosure) {
+        let in_closure = self.in_closure;
+        self.in_closure = true;
+        syn::visit::visit_expr_closure(self, expr);
+        self.in_closure = in_closure;
+    }
+
+    // nested functions have locals of their own
+    fn visit_item_fn(&mut self, _: &'ast syn::ItemFn) {}
+}
+
+/// Lower a function to a control-flow graph.
+///
+/// # Arguments
+///
+/// * `ast` - The AST of the function.
+///
+/// # Returns
+///
+/// The body of the function as basic blocks entered at block 0,
+/// with a local for each parameter, named as in the source, and for each binding and temporary.
+pub fn body_from_ast(ast: &syn::ItemFn) -> Body {
+    let ast = &normalized_from_ast(ast);
+    let mut escapes = EscapeCollector::default();
+    escapes.visit_block(&ast.block);
+    let mut builder = Builder::new(ast.block.span().into(), escapes.names);
+    let mut params = vec![];
+    for input in &ast.sig.inputs {
+        match input {
+            syn::FnArg::Receiver(_) => params.push(builder.declare("self", Ty::Other, true)),
+            syn::FnArg::Typed(typed) => {
+                let ty = Ty::from_type(&typed.ty);
+                match &*typed.pat {
+                    syn::Pat::Ident(ident) if ident.subpat.is_none() => {
+                        let param = builder.declare(&ident.ident.to_string(), ty, true);
+                        if is_collection_type(&typed.ty) {
+                            builder.collections.insert(param.clone());
+                        }
+                        params.push(param);
+                    }
+                    pat => {
+                        let param = builder.temp(ty);
+                        if let Some(local) = builder.locals.last_mut() {
+                            local.is_param = true;
+                        }
+                        builder.bind_pat(pat, Expr::Var(param.clone()), Some(ty));
+                        params.push(param);
+                    }
+                }
+            }
+        }
+    }
+    let ty = match &ast.sig.output {
+        syn::ReturnType::Type(_, ty) => Ty::from_type(ty),
+        syn::ReturnType::Default => Ty::Other,
+    };
+    builder.locals.push(Local {
+        name: RETURN.to_string(),
+        ty,
+        is_param: false,
+        source: None,
+    });
+    let value = builder.block(&ast.block);
+    if value != unit() && value != never() {
+        builder.push(Statement::Assign {
+            local: RETURN.to_string(),
+            value,
+        });
+    }
+    builder.terminate(Terminator::Return);
+    builder.finish(
+        ast.sig.ident.to_string(),
+        params,
+        ast.sig.ident.span().into(),
+    )
+}
+
+/// Lower an expression on its own, such as a condition in a test,
+/// with every name in it free.
+///
+/// # Returns
+///
+/// The value of the expression, whatever the statements it takes to compute.
+pub fn expr_from_syn(expr: &syn::Expr) -> Expr {
+    let mut builder = Builder::new(expr.span().into(), HashSet::new());
+    builder.expr(&normalized_from_expr(expr))
+}
+
+/// tests for lowering function bodies to control-flow graphs
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Lower a function for the tests
+    fn body(code: &str) -> Body {
+        body_from_ast(&syn::parse_str(code).unwrap())
+    }
+
+    /// Test a body is lowered to blocks joined by explicit jumps, with conditions as in the source
+    #[test]
+    fn test_body_from_ast() {
+        let factorial =
+            body("fn factorial(n: u32) -> u32 { if n == 0 { return 1; } n * factorial(n - 1) }");
+        assert_eq!(
+            factorial.to_string(),
+            "fn factorial(n) {
+    bb0: {
+        if n == 0 goto bb1 else bb2;
+    }
+    bb1: {
+        return = 1;
+        return;
+    }
+    bb2: {
+        _1 = factorial(n - 1);
+        return = n * _1;
+        return;
+    }
+}"
+        );
+        assert_eq!(factorial.local("n").unwrap().ty, Ty::Unsigned);
+
+        // shadowed bindings get locals of their own, and a `match` tests its arms in order
+        let shadowed = body(
+            "fn f(n: i32) -> i32 { let n = n + 1; match n { 0 | 1 => n, _ => { let n = 2; n } } }",
+        );
+        assert!(shadowed.local("n'1").is_some() && shadowed.local("n'2").is_some());
+        assert!(matches!(
+            &shadowed.blocks[ENTRY].terminator,
+            Terminator::Branch { condition, .. } if condition.to_string() == "n'1 == 0 || n'1 == 1"
+        ));
+
+        // a panic, `process::exit` and `?` leave the body without returning a value
+        let diverging = body("fn f(x: Option<u32>) -> Option<u32> { let y = x?; if y > 9 { panic!() } std::process::exit(1) }");
+        let terminators: Vec<String> = diverging
+            .blocks
+            .iter()
+            .map(|block| block.terminator.to_string())
+            .collect();
+        assert!(terminators.contains(&"panic".to_string()), "{}", diverging);
+        assert!(terminators.contains(&"exit".to_string()), "{}", diverging);
+        assert!(terminators.contains(&"return".to_string()), "{}", diverging);
+    }
+
+    /// Test every block is dominated by the blocks every path to it goes through, and only those
+    #[test]
+    fn test_dominators() {
+        let body = body("fn f(n: u32) -> u32 { let m = if n > 1 { g(n) } else { h(n) }; m + 1 }");
+        let dominators = body.dominators();
+        let Terminator::Branch {
+            then, otherwise, ..
+        } = body.blocks[ENTRY].terminator
+        else {
+            panic!("{}", body);
+        };
+        let Terminator::Goto(join) = body.blocks[then].terminator else {
+            panic!("{}", body);
+        };
+        for block in 0..body.blocks.len() {
+            assert!(dominators.dominates(ENTRY, block));
+        }
+        assert_eq!(dominators.immediate_dominator(join), Some(ENTRY));
+        assert!(!dominators.dominates(then, join));
+        assert!(!dominators.dominates(otherwise, join));
+        assert!(dominators.dominates(join, join));
+    }
+
+    /// Test loops are found from their back edges, nested in one another, with the edges which leave them
+    #[test]
+    fn test_loop_forest() {
+        let body = body(
+            "fn f(n: u32) { let mut i = 0; 'rows: while i < n { for j in 0..i { if j == 3 { continue 'rows; } } i += 1; } }",
+        );
+        let forest = body.loop_forest();
+        assert_eq!(forest.loops.len(), 2);
+        let (rows, columns) = (&forest.loops[0], &forest.loops[1]);
+        assert_eq!((rows.depth, rows.parent), (0, None));
+        assert_eq!((columns.depth, columns.parent), (1, Some(0)));
+        assert!(rows.blocks.is_superset(&columns.blocks));
+        assert_eq!(forest.innermost(columns.header), Some(1));
+        // the `while` is left when `i < n` fails, the `for` also by `continue 'rows`
+        assert_eq!(rows.exits(&body).len(), 1);
+        assert_eq!(columns.exits(&body).len(), 2);
+
+        // `next()` on a range is counted, and an open range never runs out
+        let counted = body_from_ast(
+            &syn::parse_str("fn f(n: u32) { let mut it = 0..n; while let Some(i) = it.next() { g(i); } for _ in 0.. {} }")
+                .unwrap(),
+        );
+        let exits: Vec<usize> = counted
+            .loop_forest()
+            .loops
+            .iter()
+            .map(|iteration| iteration.exits(&counted).len())
+            .collect();
+        assert_eq!(exits, vec![1, 0], "{}", counted);
+        assert!(
+            counted.to_string().contains("if _3 < _2 goto"),
+            "{}",
+            counted
+        );
+
+        // a loop which always leaves never gets back to its start
+        assert!(
+            body_from_ast(&syn::parse_str("fn f() { loop { break; } }").unwrap())
+                .loop_forest()
+                .loops
+                .is_empty()
+        );
+    }
+}
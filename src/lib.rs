@@ -47,5 +47,32 @@
 // Ensure that all non-test functions are used
 #![cfg_attr(test, warn(unused))]
 
+pub mod absint;
+pub mod base_case;
+pub mod call_graph;
+pub mod driver;
 pub mod halts;
+pub mod input;
+pub mod interp;
+pub mod interval;
+pub mod ir;
+pub mod linear;
+pub mod normalize;
+pub mod omega;
+pub mod paradox;
+pub mod portfolio;
+pub mod precondition;
+pub mod ranking;
+pub mod rational;
+pub mod recurrent;
+pub mod report;
+pub mod scan;
+pub mod simplex;
+pub mod size_change;
+pub mod source;
+pub mod structural;
+pub mod symbolic;
+pub mod verdict;
 pub use halts::*;
+pub use input::{Domain, Input};
+pub use verdict::*;
@@ -0,0 +1,333 @@
+//! halts/src/linear.rs
+//! Linear integer expressions and constraints over named variables.
+//! Guards like `n > 0` and updates like `n - 1` are the raw material of termination arguments;
+//! whatever of them is linear can be reasoned about exactly, and the rest is left alone.
+//! Integers are mathematical integers here: overflow is not modeled.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::ir::{BinOp, Expr, UnOp};
+
+/// A linear combination of integer variables plus a constant, like `2*n - m + 1`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct LinearExpr {
+    /// The coefficient of each variable; zero coefficients are never stored
+    pub terms: BTreeMap<String, i128>,
+    /// The constant term
+    pub constant: i128,
+}
+
+impl LinearExpr {
+    /// A constant expression
+    pub fn constant(constant: i128) -> Self {
+        LinearExpr {
+            terms: BTreeMap::new(),
+            constant,
+        }
+    }
+
+    /// A single variable
+    pub fn variable(name: &str) -> Self {
+        LinearExpr {
+            terms: BTreeMap::from([(name.to_string(), 1)]),
+            constant: 0,
+        }
+    }
+
+    /// The coefficient of a variable, zero if it doesn't appear
+    pub fn coefficient(&self, name: &str) -> i128 {
+        self.terms.get(name).copied().unwrap_or(0)
+    }
+
+    /// Whether no variable appears
+    pub fn is_constant(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// The sum of two expressions, or `None` on overflow
+    pub fn checked_add(&self, other: &LinearExpr) -> Option<LinearExpr> {
+        let mut sum = self.clone();
+        for (name, coefficient) in &other.terms {
+            let entry = sum.terms.entry(name.clone()).or_insert(0);
+            *entry = entry.checked_add(*coefficient)?;
+            if *entry == 0 {
+                sum.terms.remove(name);
+            }
+        }
+        sum.constant = sum.constant.checked_add(other.constant)?;
+        Some(sum)
+    }
+
+    /// The difference of two expressions, or `None` on overflow
+    pub fn checked_sub(&self, other: &LinearExpr) -> Option<LinearExpr> {
+        self.checked_add(&other.checked_scale(-1)?)
+    }
+
+    /// The expression multiplied by a constant, or `None` on overflow
+    pub fn checked_scale(&self, factor: i128) -> Option<LinearExpr> {
+        if factor == 0 {
+            return Some(LinearExpr::default());
+        }
+        let mut terms = BTreeMap::new();
+        for (name, coefficient) in &self.terms {
+            terms.insert(name.clone(), coefficient.checked_mul(factor)?);
+        }
+        let constant = self.constant.checked_mul(factor)?;
+        Some(LinearExpr { terms, constant })
+    }
+
+    /// The value of the expression, given values for its variables
+    pub fn eval(&self, value_of: &dyn Fn(&str) -> Option<i128>) -> Option<i128> {
+        self.terms
+            .iter()
+            .try_fold(self.constant, |sum, (name, coefficient)| {
+                sum.checked_add(coefficient.checked_mul(value_of(name)?)?)
+            })
+    }
+
+    /// Replace every variable by an expression, or `None` if a variable has no replacement
+    pub fn substitute(&self, replacement: &dyn Fn(&str) -> Option<LinearExpr>) -> Option<Self> {
+        self.terms.iter().try_fold(
+            LinearExpr::constant(self.constant),
+            |sum, (name, coefficient)| {
+                sum.checked_add(&replacement(name)?.checked_scale(*coefficient)?)
+            },
+        )
+    }
+}
+
+impl fmt::Display for LinearExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        for (name, &coefficient) in &self.terms {
+            let magnitude = coefficient.unsigned_abs();
+            match (first, coefficient < 0) {
+                (true, true) => write!(f, "-")?,
+                (true, false) => {}
+                (false, true) => write!(f, " - ")?,
+                (false, false) => write!(f, " + ")?,
+            }
+            if magnitude == 1 {
+                write!(f, "{}", name)?;
+            } else {
+                write!(f, "{}*{}", magnitude, name)?;
+            }
+            first = false;
+        }
+        match (first, self.constant) {
+            (true, constant) => write!(f, "{}", constant),
+            (false, 0) => Ok(()),
+            (false, constant) if constant < 0 => write!(f, " - {}", constant.unsigned_abs()),
+            (false, constant) => write!(f, " + {}", constant),
+        }
+    }
+}
+
+/// How a linear expression relates to zero
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Relation {
+    /// `expr >= 0`
+    Ge,
+    /// `expr == 0`
+    Eq,
+    /// `expr != 0`
+    Ne,
+}
+
+/// A linear constraint `expr >= 0`, `expr == 0` or `expr != 0`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Atom {
+    /// The expression compared with zero
+    pub expr: LinearExpr,
+    /// How it compares
+    pub relation: Relation,
+}
+
+impl Atom {
+    /// The constraint `expr >= 0`
+    pub fn ge_zero(expr: LinearExpr) -> Self {
+        Atom {
+            expr,
+            relation: Relation::Ge,
+        }
+    }
+
+    /// Whether the constraint holds, given values for its variables
+    pub fn holds(&self, value_of: &dyn Fn(&str) -> Option<i128>) -> Option<bool> {
+        let value = self.expr.eval(value_of)?;
+        Some(match self.relation {
+            Relation::Ge => value >= 0,
+            Relation::Eq => value == 0,
+            Relation::Ne => value != 0,
+        })
+    }
+
+    /// The negation of the constraint, which over the integers is again a single constraint
+    pub fn negate(&self) -> Option<Atom> {
+        Some(match self.relation {
+            // !(e >= 0) <=> e < 0 <=> -e - 1 >= 0
+            Relation::Ge => Atom::ge_zero(
+                self.expr
+                    .checked_scale(-1)?
+                    .checked_sub(&LinearExpr::constant(1))?,
+            ),
+            Relation::Eq => Atom {
+                expr: self.expr.clone(),
+                relation: Relation::Ne,
+            },
+            Relation::Ne => Atom {
+                expr: self.expr.clone(),
+                relation: Relation::Eq,
+            },
+        })
+    }
+}
+
+impl fmt::Display for Atom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let relation = match self.relation {
+            Relation::Ge => ">=",
+            Relation::Eq => "==",
+            Relation::Ne => "!=",
+        };
+        write!(f, "{} {} 0", self.expr, relation)
+    }
+}
+
+/// Convert an expression into a linear expression over the variables it names.
+///
+/// # Arguments
+///
+/// * `expr` - An expression made of integer literals, variables, `+`, `-` and multiplication by constants.
+///
+/// # Returns
+///
+/// The linear expression, or `None` if the expression is not linear or not made of those parts.
+pub fn linear_from_expr(expr: &Expr) -> Option<LinearExpr> {
+    match expr {
+        Expr::Int(int) => Some(LinearExpr::constant(*int)),
+        Expr::Var(name) => Some(LinearExpr::variable(name)),
+        Expr::Unary(UnOp::Neg, inner) => linear_from_expr(inner)?.checked_scale(-1),
+        Expr::Binary(op, left, right) => {
+            let left = linear_from_expr(left)?;
+            let right = linear_from_expr(right)?;
+            match op {
+                BinOp::Add => left.checked_add(&right),
+                BinOp::Sub => left.checked_sub(&right),
+                BinOp::Mul if left.is_constant() => right.checked_scale(left.constant),
+                BinOp::Mul if right.is_constant() => left.checked_scale(right.constant),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Convert a condition into a conjunction of linear constraints.
+///
+/// # Arguments
+///
+/// * `condition` - A boolean expression of comparisons between linear expressions, `&&`, `||` and `!`.
+/// * `holds` - Whether the condition is taken to be true or false.
+///
+/// # Returns
+///
+/// Constraints which together are equivalent to the condition having that truth value,
+/// or `None` if that is not a conjunction of linear constraints.
+pub fn atoms_from_condition(condition: &Expr, holds: bool) -> Option<Vec<Atom>> {
+    match condition {
+        Expr::Unary(UnOp::Not, inner) => atoms_from_condition(inner, !holds),
+        Expr::Bool(b) => Some(if *b == holds {
+            vec![]
+        } else {
+            // a contradiction
+            vec![Atom::ge_zero(LinearExpr::constant(-1))]
+        }),
+        Expr::Binary(op, left, right) => {
+            // a && b is a conjunction when true, and !a || !b when false
+            let conjunction = match op {
+                BinOp::And => Some(true),
+                BinOp::Or => Some(false),
+                _ => None,
+            };
+            if let Some(conjunctive) = conjunction {
+                if holds != conjunctive {
+                    return None;
+                }
+                let mut atoms = atoms_from_condition(left, holds)?;
+                atoms.extend(atoms_from_condition(right, holds)?);
+                return Some(atoms);
+            }
+            let left = linear_from_expr(left)?;
+            let right = linear_from_expr(right)?;
+            let one = LinearExpr::constant(1);
+            let atom = match op {
+                BinOp::Ge => Atom::ge_zero(left.checked_sub(&right)?),
+                BinOp::Gt => Atom::ge_zero(left.checked_sub(&right)?.checked_sub(&one)?),
+                BinOp::Le => Atom::ge_zero(right.checked_sub(&left)?),
+                BinOp::Lt => Atom::ge_zero(right.checked_sub(&left)?.checked_sub(&one)?),
+                BinOp::Eq => Atom {
+                    expr: left.checked_sub(&right)?,
+                    relation: Relation::Eq,
+                },
+                BinOp::Ne => Atom {
+                    expr: left.checked_sub(&right)?,
+                    relation: Relation::Ne,
+                },
+                _ => return None,
+            };
+            Some(vec![if holds { atom } else { atom.negate()? }])
+        }
+        _ => None,
+    }
+}
+
+/// tests for linear expressions and constraints
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ir::expr_from_syn;
+
+    /// Parse and lower an expression for the tests
+    fn expr(code: &str) -> Expr {
+        expr_from_syn(&syn::parse_str(code).unwrap())
+    }
+
+    /// Test linear expressions are recognized, and non-linear ones refused
+    #[test]
+    fn test_linear_from_expr() {
+        let linear = linear_from_expr(&expr("2 * (hi - lo) + 1 - hi")).unwrap();
+        assert_eq!(linear.to_string(), "hi - 2*lo + 1");
+        assert_eq!(linear_from_expr(&expr("-n")).unwrap().to_string(), "-n");
+        assert!(linear_from_expr(&expr("n * m")).is_none());
+        assert!(linear_from_expr(&expr("n / 2")).is_none());
+    }
+
+    /// Test conditions become conjunctions of constraints, with negation
+    #[test]
+    fn test_atoms_from_condition() {
+        let atoms = atoms_from_condition(&expr("n > 0 && m <= n"), true).unwrap();
+        let printed: Vec<String> = atoms.iter().map(|atom| atom.to_string()).collect();
+        assert_eq!(printed, vec!["n - 1 >= 0", "-m + n >= 0"]);
+
+        let atoms = atoms_from_condition(&expr("!(n == 0)"), true).unwrap();
+        assert_eq!(atoms[0].to_string(), "n != 0");
+        let atoms = atoms_from_condition(&expr("n < 2"), false).unwrap();
+        assert_eq!(atoms[0].to_string(), "n - 2 >= 0");
+        assert!(atoms_from_condition(&expr("a || b"), true).is_none());
+    }
+
+    /// Test constraints are evaluated
+    #[test]
+    fn test_holds() {
+        let atoms = atoms_from_condition(&expr("i < len"), true).unwrap();
+        let value_of = |name: &str| match name {
+            "i" => Some(3),
+            "len" => Some(4),
+            _ => None,
+        };
+        assert_eq!(atoms[0].holds(&value_of), Some(true));
+        assert_eq!(atoms[0].negate().unwrap().holds(&value_of), Some(false));
+    }
+}
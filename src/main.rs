@@ -1,12 +1,253 @@
 //! src/main.rs
+//! `cargo halts`: decide every function of a crate, print a table of verdicts,
+//! and exit with failure if any verdict is in a category which is denied, LOOP and PARADOX unless told otherwise.
+//! `cargo halts check file_path::function_path`: decide one function, and print the evidence and the normalized code.
 
-use halts::halts::string_from_ast;
+use std::env;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
 
-/// code for the halts module as a const str inside the halts module
-const CODE: &str = include_str!("./halts.rs");
+use halts::report::report_from_query;
+use halts::scan::{scan_from_manifest_dir, Scan};
 
-fn main() {
-    let ast = syn::parse_str::<syn::ItemFn>(CODE).unwrap();
-    let ast_str = string_from_ast(&ast);
-    println!("{}", ast_str);
+/// How to use the command
+const USAGE: &str = "\
+usage: cargo halts [--manifest-path <Cargo.toml>] [--deny <categories>] [--timeout <seconds>] [--tests]
+       cargo halts check <file_path::function_path> [--json]
+
+  --manifest-path  the crate to scan, found from the working directory by default
+  --deny           comma-separated categories which fail the scan: halt, halt-when, loop, paradox, unknown
+                   (default: loop,paradox; `--deny none` fails on nothing)
+  --timeout        how long each function may be analyzed for, after which it is UNKNOWN (default: 10)
+  --tests          also decide `#[test]` functions and `#[cfg(test)]` items
+  check            decide one function, like `src/foo.rs::module::func`, with the file relative to the crate root
+                   (or `Type::method`, `<Type as Trait>::method`, `outer::inner_fn`, `outer::{closure#0}`)
+  --json           print the decision of `check` as JSON";
+
+/// The categories every verdict falls in, as they're named on the command line
+const CATEGORIES: [&str; 5] = ["halt", "halt-when", "loop", "paradox", "unknown"];
+
+/// What the command was asked to do
+#[derive(Debug)]
+struct Options {
+    /// The directory of the crate's `Cargo.toml`
+    manifest_dir: PathBuf,
+    /// The categories which fail the scan
+    deny: Vec<String>,
+    /// How long each function may be analyzed for
+    timeout: Duration,
+    /// Whether test code is scanned too
+    tests: bool,
+}
+
+/// What the command was asked to do
+#[derive(Debug)]
+enum Command {
+    /// Decide every function of a crate
+    Scan(Options),
+    /// Decide one function
+    Check {
+        /// The function, as `file_path::function_path`
+        query: String,
+        /// Whether to print the decision as JSON
+        json: bool,
+    },
+}
+
+/// Read the command from the command line, after the program's name
+fn command_from_args(mut args: Vec<String>) -> Result<Command, String> {
+    // run as `cargo halts`, cargo passes the subcommand's name on
+    if args.first().is_some_and(|arg| arg == "halts") {
+        args.remove(0);
+    }
+    if args.first().is_none_or(|arg| arg != "check") {
+        return options_from_args(args).map(Command::Scan);
+    }
+    let mut query = None;
+    let mut json = false;
+    for arg in args.into_iter().skip(1) {
+        match arg.as_str() {
+            "--json" => json = true,
+            _ if query.is_none() && !arg.starts_with("--") => query = Some(arg),
+            _ => return Err(format!("unexpected argument: {}", arg)),
+        }
+    }
+    let query = query.ok_or("check needs a function, like `src/foo.rs::module::func`")?;
+    Ok(Command::Check { query, json })
+}
+
+/// Read the options of a scan from the command line
+fn options_from_args(args: Vec<String>) -> Result<Options, String> {
+    let mut manifest_path = None;
+    let mut deny = vec!["loop".to_string(), "paradox".to_string()];
+    let mut timeout = Duration::from_secs(10);
+    let mut tests = false;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--manifest-path" => {
+                let path = args.next().ok_or("--manifest-path needs a path")?;
+                manifest_path = Some(PathBuf::from(path));
+            }
+            "--deny" => {
+                let categories = args.next().ok_or("--deny needs categories")?;
+                deny = categories
+                    .split(',')
+                    .map(|category| category.trim().to_lowercase())
+                    .filter(|category| category != "none")
+                    .collect();
+                if let Some(unknown) = deny.iter().find(|c| !CATEGORIES.contains(&c.as_str())) {
+                    return Err(format!("no such category: {}", unknown));
+                }
+            }
+            "--timeout" => {
+                let seconds = args.next().ok_or("--timeout needs a number of seconds")?;
+                let seconds: f64 = seconds
+                    .parse()
+                    .map_err(|_| format!("not a number of seconds: {}", seconds))?;
+                timeout = Duration::try_from_secs_f64(seconds).map_err(|e| e.to_string())?;
+            }
+            "--tests" => tests = true,
+            _ => return Err(format!("unexpected argument: {}", arg)),
+        }
+    }
+    let manifest_dir = match manifest_path {
+        Some(path) => path.parent().unwrap_or(Path::new(".")).to_path_buf(),
+        None => {
+            let cwd = env::current_dir().map_err(|e| e.to_string())?;
+            cwd.ancestors()
+                .find(|dir| dir.join("Cargo.toml").is_file())
+                .ok_or("no Cargo.toml here or in any parent directory")?
+                .to_path_buf()
+        }
+    };
+    Ok(Options {
+        manifest_dir,
+        deny,
+        timeout,
+        tests,
+    })
+}
+
+/// A row of the table, its cells padded to line up; the last is as long as it is
+fn row_from_cells(cells: &[&str], widths: &[usize]) -> String {
+    let padded: Vec<String> = cells
+        .iter()
+        .zip(widths.iter().chain([&0]))
+        .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+        .collect();
+    padded.join("  ").trim_end().to_string()
+}
+
+/// The exit code once the output is written, quietly stopping early if its reader went away, as `head` does
+fn exit_from_written(written: io::Result<ExitCode>) -> ExitCode {
+    match written {
+        Ok(code) => code,
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Decide one function, and print what was decided and how
+fn check(query: &str, json: bool) -> ExitCode {
+    let report = match report_from_query(query) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::from(2);
+        }
+    };
+    let mut out = io::stdout().lock();
+    let written = if json {
+        writeln!(out, "{}", report.to_json())
+    } else {
+        write!(out, "{}", report)
+    };
+    exit_from_written(
+        written
+            .and_then(|()| out.flush())
+            .map(|()| ExitCode::SUCCESS),
+    )
+}
+
+/// Decide every function of a crate, and print a table of the verdicts
+fn scan(options: &Options) -> ExitCode {
+    let scan = match scan_from_manifest_dir(&options.manifest_dir, options.tests) {
+        Ok(scan) => scan,
+        Err(e) => {
+            eprintln!("error: {}: {}", options.manifest_dir.display(), e);
+            return ExitCode::from(2);
+        }
+    };
+    for error in &scan.errors {
+        eprintln!("warning: {}", error);
+    }
+    exit_from_written(write_scan(&mut io::stdout().lock(), &scan, options))
+}
+
+/// Decide the functions of a scan one by one, writing each row of the table as it's decided
+fn write_scan(out: &mut impl Write, scan: &Scan, options: &Options) -> io::Result<ExitCode> {
+    // rows are printed as they're decided, so the rule, whose width isn't known before, comes last
+    let header = ["VERDICT", "FUNCTION", "KIND", "LOCATION", "RULE"];
+    let locations: Vec<String> = scan.functions.iter().map(|f| scan.location(f)).collect();
+    let widest = |cells: Vec<usize>, title: &str| cells.into_iter().fold(title.len(), usize::max);
+    let widths = [
+        "HALT WHEN".len(),
+        widest(
+            scan.functions
+                .iter()
+                .map(|f| f.path.chars().count())
+                .collect(),
+            header[1],
+        ),
+        "closure".len(),
+        widest(
+            locations.iter().map(|l| l.chars().count()).collect(),
+            header[3],
+        ),
+    ];
+    writeln!(out, "{}", row_from_cells(&header, &widths))?;
+    let mut denied = 0;
+    for (function, location) in scan.functions.iter().zip(&locations) {
+        let decision = scan.decision(function, options.timeout);
+        let category = decision.verdict.category();
+        if options
+            .deny
+            .contains(&category.to_lowercase().replace(' ', "-"))
+        {
+            denied += 1;
+        }
+        let kind = function.kind.to_string();
+        let rule = decision.verdict.evidence().rule.to_string();
+        let cells = [category, &function.path, &kind, location, &rule];
+        writeln!(out, "{}", row_from_cells(&cells, &widths))?;
+    }
+    writeln!(
+        out,
+        "\n{} functions decided, {} in a denied category",
+        scan.functions.len(),
+        denied
+    )?;
+    out.flush()?;
+    Ok(if denied > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+fn main() -> ExitCode {
+    match command_from_args(env::args().skip(1).collect()) {
+        Ok(Command::Scan(options)) => scan(&options),
+        Ok(Command::Check { query, json }) => check(&query, json),
+        Err(why) => {
+            eprintln!("error: {}\n\n{}", why, USAGE);
+            ExitCode::from(2)
+        }
+    }
 }
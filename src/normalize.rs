@@ -0,0 +1,304 @@
+//! halts/src/normalize.rs
+//! Desugaring of high-level Rust syntax into a small core, before anything is analyzed.
+//! `for`, `while`, `while let`, `if let`, `?` and `let ... else` are rewritten the way the compiler reads them,
+//! into `loop`, `match`, `if`, `break` and `return`, with the `next()` calls of iterators made explicit:
+//!
+//! ```text
+//! for x in xs { body }
+//! ```
+//!
+//! becomes
+//!
+//! ```text
+//! { let mut __iter = IntoIterator::into_iter(xs); loop { match Iterator::next(&mut __iter) { Some(x) => { body } None => break, } } }
+//! ```
+//!
+//! so `loop` is the only loop left to reason about.
+//! The result is still Rust, so `string_from_ast` prints what the analyzer actually sees,
+//! and the new code takes the spans of the code it replaces, so evidence still points into the source.
+
+use quote::{quote, quote_spanned};
+use syn::parse_quote_spanned;
+use syn::spanned::Spanned;
+use syn::visit::Visit;
+use syn::visit_mut::VisitMut;
+
+/// The name of the iterator a desugared `for` loop takes its items from
+const ITERATOR: &str = "__iter";
+
+/// Rewrites each expression and statement into the core, innermost first
+struct Normalizer;
+
+impl VisitMut for Normalizer {
+    fn visit_expr_mut(&mut self, expr: &mut syn::Expr) {
+        syn::visit_mut::visit_expr_mut(self, expr);
+        if let Some(core) = core_from_expr(expr) {
+            *expr = core;
+        }
+    }
+
+    fn visit_block_mut(&mut self, block: &mut syn::Block) {
+        syn::visit_mut::visit_block_mut(self, block);
+        for stmt in &mut block.stmts {
+            if let syn::Stmt::Local(local) = stmt {
+                if let Some(core) = core_from_let_else(local) {
+                    *stmt = core;
+                }
+            }
+        }
+    }
+}
+
+/// The core an expression desugars to, if it isn't part of the core already
+fn core_from_expr(expr: &syn::Expr) -> Option<syn::Expr> {
+    let span = expr.span();
+    match expr {
+        syn::Expr::ForLoop(expr) => {
+            let syn::ExprForLoop {
+                label,
+                pat,
+                expr: iterable,
+                body,
+                ..
+            } = expr;
+            let iterator = syn::Ident::new(ITERATOR, span);
+            Some(parse_quote_spanned! {span=>
+                {
+                    let mut #iterator = IntoIterator::into_iter(#iterable);
+                    #label loop {
+                        match Iterator::next(&mut #iterator) {
+                            Some(#pat) => #body
+                            None => break,
+                        }
+                    }
+                }
+            })
+        }
+        syn::Expr::While(expr) => {
+            let syn::ExprWhile {
+                label, cond, body, ..
+            } = expr;
+            match &**cond {
+                syn::Expr::Let(condition) => {
+                    let syn::ExprLet {
+                        pat,
+                        expr: scrutinee,
+                        ..
+                    } = condition;
+                    Some(parse_quote_spanned! {span=>
+                        #label loop {
+                            match #scrutinee {
+                                #pat => #body
+                                _ => break,
+                            }
+                        }
+                    })
+                }
+                // a plain condition, or a chain with a `let` in it which `match` can't express
+                cond => {
+                    let test: syn::Expr =
+                        parse_quote_spanned! {span=> if #cond #body else { break } };
+                    let test = core_from_expr(&test).unwrap_or(test);
+                    Some(parse_quote_spanned! {span=> #label loop { #test } })
+                }
+            }
+        }
+        syn::Expr::If(expr) => {
+            let syn::Expr::Let(condition) = &*expr.cond else {
+                return None;
+            };
+            let syn::ExprLet {
+                pat,
+                expr: scrutinee,
+                ..
+            } = condition;
+            let then = &expr.then_branch;
+            let otherwise = match &expr.else_branch {
+                Some((_, otherwise)) => (**otherwise).clone(),
+                None => parse_quote_spanned! {span=> {} },
+            };
+            Some(parse_quote_spanned! {span=>
+                match #scrutinee {
+                    #pat => #then
+                    _ => #otherwise,
+                }
+            })
+        }
+        syn::Expr::Try(expr) => {
+            let operand = &expr.expr;
+            Some(parse_quote_spanned! {span=>
+                match Try::branch(#operand) {
+                    ControlFlow::Continue(value) => value,
+                    ControlFlow::Break(residual) => return FromResidual::from_residual(residual),
+                }
+            })
+        }
+        _ => None,
+    }
+}
+
+/// The core a `let ... else` desugars to: a `let` of the names its pattern binds,
+/// from a `match` which diverges if the pattern is refuted
+fn core_from_let_else(local: &syn::Local) -> Option<syn::Stmt> {
+    let init = local.init.as_ref()?;
+    let (_, otherwise) = init.diverge.as_ref()?;
+    let span = local.span();
+    // types can't be ascribed in a `match` arm
+    let pat = match &local.pat {
+        syn::Pat::Type(typed) => &*typed.pat,
+        pat => pat,
+    };
+    let scrutinee = &init.expr;
+    let bindings = bindings_from_pat(pat);
+    let names = bindings.iter().map(|(name, _)| name);
+    let declared = bindings
+        .iter()
+        .map(|(name, mutability)| quote_spanned! {span=> #mutability #name });
+    let (declared, values) = match bindings.len() {
+        1 => (quote!(#(#declared)*), quote!(#(#names)*)),
+        _ => (quote!((#(#declared),*)), quote!((#(#names),*))),
+    };
+    Some(parse_quote_spanned! {span=>
+        let #declared = match #scrutinee {
+            #pat => #values,
+            _ => #otherwise,
+        };
+    })
+}
+
+/// The names a pattern binds, with whether each is bound mutably, in order
+fn bindings_from_pat(pat: &syn::Pat) -> Vec<(syn::Ident, Option<syn::Token![mut]>)> {
+    /// Collects the identifiers bound by a pattern
+    #[derive(Default)]
+    struct BindingCollector {
+        /// The bindings found so far
+        bindings: Vec<(syn::Ident, Option<syn::Token![mut]>)>,
+    }
+    impl<'ast> Visit<'ast> for BindingCollector {
+        fn visit_pat_ident(&mut self, pat: &'ast syn::PatIdent) {
+            // an uppercase name is a constant or a unit variant like `None`
+            if !pat.ident.to_string().starts_with(char::is_uppercase) {
+                self.bindings.push((pat.ident.clone(), pat.mutability));
+            }
+            syn::visit::visit_pat_ident(self, pat);
+        }
+    }
+    let mut collector = BindingCollector::default();
+    collector.visit_pat(pat);
+    collector.bindings
+}
+
+/// Desugar a function into the core.
+///
+/// # Arguments
+///
+/// * `ast` - The AST of the function.
+///
+/// # Returns
+///
+/// The same function with no `for`, `while`, `if let`, `?` or `let ... else` left in it,
+/// nested closures and functions included.
+pub fn normalized_from_ast(ast: &syn::ItemFn) -> syn::ItemFn {
+    let mut normalized = ast.clone();
+    Normalizer.visit_item_fn_mut(&mut normalized);
+    normalized
+}
+
+/// Desugar an expression into the core, like `normalized_from_ast`.
+pub fn normalized_from_expr(expr: &syn::Expr) -> syn::Expr {
+    let mut normalized = expr.clone();
+    Normalizer.visit_expr_mut(&mut normalized);
+    normalized
+}
+
+/// tests for desugaring into the core
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::halts::string_from_ast;
+
+    /// Desugar a function and print it, for the tests
+    fn normalized(code: &str) -> String {
+        string_from_ast(&normalized_from_ast(&syn::parse_str(code).unwrap()))
+    }
+
+    /// The lines a node spans
+    fn lines_of(node: &impl Spanned) -> (usize, usize) {
+        let span = node.span();
+        (span.start().line, span.end().line)
+    }
+
+    /// Test `for` and `while` become `loop` with explicit `next()` calls and `break`s, keeping labels
+    #[test]
+    fn test_loops() {
+        let code = normalized(
+            "fn f(v: Vec<u32>) { 'outer: for x in v { while x > 0 { continue 'outer; } } }",
+        );
+        assert!(
+            !code.contains("for ") && !code.contains("while "),
+            "{}",
+            code
+        );
+        assert!(
+            code.contains("let mut __iter = IntoIterator :: into_iter (v) ; 'outer : loop { match Iterator :: next (& mut __iter) { Some (x) =>"),
+            "{}",
+            code
+        );
+        assert!(
+            code.contains("loop { if x > 0 { continue 'outer ; } else { break } }"),
+            "{}",
+            code
+        );
+        let code = normalized(
+            "fn f(mut stack: Vec<u32>) { while let Some(top) = stack.pop() { g(top); } }",
+        );
+        assert!(
+            code.contains(
+                "loop { match stack . pop () { Some (top) => { g (top) ; } _ => break , } }"
+            ),
+            "{}",
+            code
+        );
+    }
+
+    /// Test `if let`, `?` and `let ... else` become `match`
+    #[test]
+    fn test_matches() {
+        let code =
+            normalized("fn f(x: Option<u32>) -> u32 { if let Some(y) = x { y } else { 0 } }");
+        assert!(
+            code.contains("match x { Some (y) => { y } _ => { 0 } , }"),
+            "{}",
+            code
+        );
+        let code = normalized("fn f(x: Result<u32, E>) -> Result<u32, E> { Ok(x? + 1) }");
+        assert!(
+            code.contains("Ok (match Try :: branch (x) { ControlFlow :: Continue (value) => value , ControlFlow :: Break (residual) => return FromResidual :: from_residual (residual) , } + 1)"),
+            "{}",
+            code
+        );
+        let code = normalized(
+            "fn f(p: Option<(u32, u32)>) { let Some((mut a, b)) = p else { return; }; }",
+        );
+        assert!(
+            code.contains("let (mut a , b) = match p { Some ((mut a , b)) => (a , b) , _ => { return ; } , } ;"),
+            "{}",
+            code
+        );
+    }
+
+    /// Test the desugared code keeps the spans of the code it replaces
+    #[test]
+    fn test_spans() {
+        let ast: syn::ItemFn = syn::parse_str(
+            "fn f(n: u32) {\n    let mut i = 0;\n    while i < n {\n        i += 1;\n    }\n}",
+        )
+        .unwrap();
+        let normalized = normalized_from_ast(&ast);
+        let syn::Stmt::Expr(desugared, _) = &normalized.block.stmts[1] else {
+            panic!("{}", string_from_ast(&normalized));
+        };
+        assert!(matches!(desugared, syn::Expr::Loop(_)));
+        assert_eq!(lines_of(desugared), (3, 5));
+    }
+}
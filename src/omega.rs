@@ -0,0 +1,384 @@
+//! halts/src/omega.rs
+//! A decision procedure for conjunctions of linear constraints over the integers, needing no solver outside the crate:
+//! Fourier–Motzkin elimination, tightened for integers as in Pugh's Omega test.
+//! Equalities are solved for a variable with a unit coefficient and substituted away.
+//! Every inequality is divided through by the gcd of its coefficients, rounding its constant down,
+//! which cuts off the rational solutions between integers.
+//! A variable is eliminated by pairing each lower bound on it with each upper bound, giving two shadows of the system:
+//! the real shadow, which has a solution if the system has a rational one,
+//! and the dark shadow, which has one only if the system has an integer one.
+//! Where every pair has a unit coefficient, as is usual, the two are the same.
+//! There is no integer solution if the real shadow has none, and there is one if the dark shadow has one,
+//! which back-substitution finds; in between, where the Omega test would splinter, the answer is unknown.
+//! `!=` is split into `<` or `>`, for the first few of them.
+
+use std::collections::BTreeMap;
+
+use crate::linear::{Atom, LinearExpr, Relation};
+
+/// The most `!=` constraints split into two cases each; the rest are only checked against a solution found
+const MAX_SPLITS: usize = 6;
+
+/// The most inequalities in a system before giving up, since elimination may square their number
+const MAX_INEQUALITIES: usize = 400;
+
+/// Whether a conjunction of linear constraints has an integer solution
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Satisfiability {
+    /// It has one: a value for each variable, as near zero as came easily
+    Satisfiable(BTreeMap<String, i128>),
+    /// It has none
+    Unsatisfiable,
+    /// It couldn't be told, for want of splintering or for overflow
+    Unknown,
+}
+
+/// The greatest common divisor of two integers, or `None` if it doesn't fit an `i128`
+fn gcd(a: i128, b: i128) -> Option<i128> {
+    let (mut a, mut b) = (a.unsigned_abs(), b.unsigned_abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    i128::try_from(a).ok()
+}
+
+/// The greatest common divisor of the coefficients of an expression, or `None` on overflow
+fn content(expr: &LinearExpr) -> Option<i128> {
+    expr.terms
+        .values()
+        .try_fold(0, |divisor, &coefficient| gcd(divisor, coefficient))
+}
+
+/// An inequality `expr >= 0` divided through by the gcd of its coefficients, with its constant rounded down,
+/// or `None` on overflow
+fn tighten(expr: LinearExpr) -> Option<LinearExpr> {
+    let divisor = content(&expr)?;
+    if divisor <= 1 {
+        return Some(expr);
+    }
+    Some(LinearExpr {
+        terms: expr
+            .terms
+            .iter()
+            .map(|(name, coefficient)| (name.clone(), coefficient / divisor))
+            .collect(),
+        constant: expr.constant.div_euclid(divisor),
+    })
+}
+
+/// The value of an expression, with variables the model leaves out taken as zero, or `None` on overflow
+fn eval(expr: &LinearExpr, model: &BTreeMap<String, i128>) -> Option<i128> {
+    expr.eval(&|name| Some(model.get(name).copied().unwrap_or(0)))
+}
+
+/// The least integer at least `a / b`, for `b > 0`
+fn div_ceil(a: i128, b: i128) -> i128 {
+    -((-a).div_euclid(b))
+}
+
+/// Find a solution to inequalities `expr >= 0`, eliminating one variable at a time.
+///
+/// # Arguments
+///
+/// * `inequalities` - The system.
+/// * `dark` - Whether to take dark shadows, whose solutions extend to integer solutions of the system,
+///   rather than real shadows, whose lack of solutions shows the system has none.
+///
+/// # Returns
+///
+/// A solution, `Some(None)` if the shadows taken have none, or `None` if the system grew too big or overflowed.
+/// A solution through real shadows may not be an integer solution of the system.
+fn eliminate(inequalities: Vec<LinearExpr>, dark: bool) -> Option<Option<BTreeMap<String, i128>>> {
+    // parallel inequalities keep only the tightest, and constant ones are either true or a contradiction
+    let mut tightest: BTreeMap<BTreeMap<String, i128>, i128> = BTreeMap::new();
+    for inequality in inequalities {
+        let inequality = tighten(inequality)?;
+        if inequality.is_constant() {
+            if inequality.constant < 0 {
+                return Some(None);
+            }
+            continue;
+        }
+        let constant = tightest.entry(inequality.terms).or_insert(i128::MAX);
+        *constant = (*constant).min(inequality.constant);
+    }
+    if tightest.len() > MAX_INEQUALITIES {
+        return None;
+    }
+    let inequalities: Vec<LinearExpr> = tightest
+        .into_iter()
+        .map(|(terms, constant)| LinearExpr { terms, constant })
+        .collect();
+    let Some(variable) = choose(&inequalities) else {
+        return Some(Some(BTreeMap::new()));
+    };
+
+    let (bounds, rest): (Vec<LinearExpr>, Vec<LinearExpr>) = inequalities
+        .into_iter()
+        .partition(|inequality| inequality.coefficient(&variable) != 0);
+    let (lower, upper): (Vec<LinearExpr>, Vec<LinearExpr>) = bounds
+        .into_iter()
+        .partition(|inequality| inequality.coefficient(&variable) > 0);
+    let mut shadow = rest;
+    for l in &lower {
+        for u in &upper {
+            // a*x + L >= 0 and -b*x + U >= 0 give b*L + a*U >= 0, and for an integer x between, (a - 1)*(b - 1) more
+            let a = l.coefficient(&variable);
+            let b = u.coefficient(&variable).checked_neg()?;
+            let mut combined = l.checked_scale(b)?.checked_add(&u.checked_scale(a)?)?;
+            if dark {
+                let slack = a.checked_sub(1)?.checked_mul(b.checked_sub(1)?)?;
+                combined = combined.checked_sub(&LinearExpr::constant(slack))?;
+            }
+            shadow.push(combined);
+        }
+    }
+    let Some(mut model) = eliminate(shadow, dark)? else {
+        return Some(None);
+    };
+
+    // the variable may be anything between its greatest lower bound and its least upper bound,
+    // given the others, which the model has and it doesn't, so evaluating a bound leaves it out
+    let mut least = i128::MIN;
+    for l in &lower {
+        let a = l.coefficient(&variable);
+        least = least.max(div_ceil(eval(l, &model)?.checked_neg()?, a));
+    }
+    let mut greatest = i128::MAX;
+    for u in &upper {
+        let b = u.coefficient(&variable).checked_neg()?;
+        greatest = greatest.min(eval(u, &model)?.div_euclid(b));
+    }
+    let value = if least <= greatest {
+        0.clamp(least, greatest)
+    } else if dark {
+        // the dark shadow leaves an integer between, unless something overflowed
+        return None;
+    } else {
+        // a solution through real shadows is only there to say there is one
+        least
+    };
+    model.insert(variable, value);
+    Some(Some(model))
+}
+
+/// The variable to eliminate next: one whose shadows are exact if there is one, and then the one with the fewest pairs
+fn choose(inequalities: &[LinearExpr]) -> Option<String> {
+    let mut variables: BTreeMap<&String, (usize, usize, bool, bool)> = BTreeMap::new();
+    for inequality in inequalities {
+        for (name, &coefficient) in &inequality.terms {
+            let (lower, upper, unit_lower, unit_upper) =
+                variables.entry(name).or_insert((0, 0, true, true));
+            if coefficient > 0 {
+                *lower += 1;
+                *unit_lower &= coefficient == 1;
+            } else {
+                *upper += 1;
+                *unit_upper &= coefficient == -1;
+            }
+        }
+    }
+    variables
+        .into_iter()
+        .min_by_key(|(_, (lower, upper, unit_lower, unit_upper))| {
+            (!(*unit_lower || *unit_upper), lower.saturating_mul(*upper))
+        })
+        .map(|(name, _)| name.clone())
+}
+
+/// Decide a system of equalities and inequalities, `expr == 0` and `expr >= 0`.
+fn decide(
+    mut equalities: Vec<LinearExpr>,
+    mut inequalities: Vec<LinearExpr>,
+) -> Option<Satisfiability> {
+    // solve each equality for a variable with a unit coefficient, and substitute it everywhere else
+    let mut solved: Vec<(String, LinearExpr)> = vec![];
+    while let Some(equality) = equalities.pop() {
+        let divisor = content(&equality)?;
+        if equality.is_constant() {
+            if equality.constant != 0 {
+                return Some(Satisfiability::Unsatisfiable);
+            }
+            continue;
+        }
+        if equality.constant % divisor != 0 {
+            return Some(Satisfiability::Unsatisfiable);
+        }
+        let unit = equality
+            .terms
+            .iter()
+            .find(|(_, coefficient)| coefficient.unsigned_abs() == 1)
+            .map(|(name, &coefficient)| (name.clone(), coefficient));
+        let Some((variable, coefficient)) = unit else {
+            // without a unit coefficient, the equality is two inequalities, which lose only integrality
+            inequalities.push(equality.checked_scale(-1)?);
+            inequalities.push(equality);
+            continue;
+        };
+        // c*x + rest == 0 with c = ±1 means x == -c * rest
+        let mut rest = equality.clone();
+        rest.terms.remove(&variable);
+        let value = rest.checked_scale(coefficient.checked_neg()?)?;
+        let substitute = |expr: &LinearExpr| {
+            expr.substitute(&|name| {
+                Some(if name == variable {
+                    value.clone()
+                } else {
+                    LinearExpr::variable(name)
+                })
+            })
+        };
+        equalities = equalities.iter().map(substitute).collect::<Option<_>>()?;
+        inequalities = inequalities.iter().map(substitute).collect::<Option<_>>()?;
+        solved.push((variable, value));
+    }
+
+    if let Some(mut model) = eliminate(inequalities.clone(), true)? {
+        for (variable, value) in solved.iter().rev() {
+            let value = eval(value, &model)?;
+            model.insert(variable.clone(), value);
+        }
+        return Some(Satisfiability::Satisfiable(model));
+    }
+    Some(match eliminate(inequalities, false)? {
+        None => Satisfiability::Unsatisfiable,
+        Some(_) => Satisfiability::Unknown,
+    })
+}
+
+/// Decide whether a conjunction of linear constraints has an integer solution.
+///
+/// # Arguments
+///
+/// * `atoms` - The constraints, over integer variables.
+///
+/// # Returns
+///
+/// A solution, that there is none, or that it couldn't be told.
+pub fn satisfiability(atoms: &[Atom]) -> Satisfiability {
+    let mut equalities = vec![];
+    let mut inequalities = vec![];
+    let mut disequalities = vec![];
+    for atom in atoms {
+        match atom.relation {
+            Relation::Eq => equalities.push(atom.expr.clone()),
+            Relation::Ge => inequalities.push(atom.expr.clone()),
+            Relation::Ne => disequalities.push(atom.expr.clone()),
+        }
+    }
+    let splits = disequalities.len().min(MAX_SPLITS);
+    let mut unknown = false;
+    // each case takes `e < 0` or `e > 0` for each split `e != 0`, by the bits of its number
+    for case in 0..(1u32 << splits) {
+        let mut inequalities = inequalities.clone();
+        for (bit, expr) in disequalities.iter().take(splits).enumerate() {
+            let side = if case >> bit & 1 == 1 {
+                expr.checked_sub(&LinearExpr::constant(1))
+            } else {
+                expr.checked_scale(-1)
+                    .and_then(|negated| negated.checked_sub(&LinearExpr::constant(1)))
+            };
+            match side {
+                Some(side) => inequalities.push(side),
+                None => return Satisfiability::Unknown,
+            }
+        }
+        match decide(equalities.clone(), inequalities) {
+            Some(Satisfiability::Unsatisfiable) => {}
+            Some(Satisfiability::Satisfiable(model)) => {
+                let value_of = |name: &str| Some(model.get(name).copied().unwrap_or(0));
+                if atoms.iter().all(|atom| atom.holds(&value_of) == Some(true)) {
+                    let mut model = model;
+                    for atom in atoms {
+                        for name in atom.expr.terms.keys() {
+                            model.entry(name.clone()).or_insert(0);
+                        }
+                    }
+                    return Satisfiability::Satisfiable(model);
+                }
+                // a `!=` left unsplit, or overflow, spoiled the solution
+                unknown = true;
+            }
+            Some(Satisfiability::Unknown) | None => unknown = true,
+        }
+    }
+    if unknown {
+        Satisfiability::Unknown
+    } else {
+        Satisfiability::Unsatisfiable
+    }
+}
+
+/// tests for the decision procedure
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ir::expr_from_syn;
+    use crate::linear::atoms_from_condition;
+
+    /// The satisfiability of a condition written in Rust
+    fn satisfiability_of(code: &str) -> Satisfiability {
+        let condition = expr_from_syn(&syn::parse_str(code).unwrap());
+        satisfiability(&atoms_from_condition(&condition, true).unwrap())
+    }
+
+    /// Test systems without integer solutions are refuted, including those with rational ones
+    #[test]
+    fn test_unsatisfiable() {
+        for code in [
+            "x >= 0 && y >= 0 && x + y <= -1",
+            "x > y && y > x",
+            "2 * x == 1",
+            "3 <= 2 * x && 2 * x <= 3",
+            "x == y + 1 && y == z + 1 && z == x",
+            "x >= 1 && x <= 1 && x != 1",
+            "3 * x + 3 * y == 4",
+        ] {
+            assert_eq!(
+                satisfiability_of(code),
+                Satisfiability::Unsatisfiable,
+                "{}",
+                code
+            );
+        }
+    }
+
+    /// Test systems with integer solutions get one
+    #[test]
+    fn test_satisfiable() {
+        for code in [
+            "x >= 3 && y >= x + 2 && x + y <= 20",
+            "x != 0 && x >= -1 && x <= 1",
+            "n == 2 * m && m >= 1 && n <= 3",
+            "x - y >= 5 && y != 0",
+            "true",
+        ] {
+            let condition = expr_from_syn(&syn::parse_str(code).unwrap());
+            let atoms = atoms_from_condition(&condition, true).unwrap();
+            let Satisfiability::Satisfiable(model) = satisfiability(&atoms) else {
+                panic!("{}", code);
+            };
+            let value_of = |name: &str| model.get(name).copied();
+            assert!(
+                atoms.iter().all(|atom| atom.holds(&value_of) == Some(true)),
+                "{}: {:?}",
+                code,
+                model
+            );
+        }
+        let Satisfiability::Satisfiable(model) = satisfiability_of("x >= -5 && x <= 7") else {
+            unreachable!()
+        };
+        assert_eq!(model["x"], 0);
+    }
+
+    /// Test the tightening of inequalities to the integers
+    #[test]
+    fn test_tighten() {
+        let mut expr = LinearExpr::variable("x").checked_scale(2).unwrap();
+        expr.constant = -3;
+        let tightened = tighten(expr).unwrap();
+        assert_eq!(tightened.coefficient("x"), 1);
+        assert_eq!(tightened.constant, -2);
+    }
+}
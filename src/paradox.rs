@@ -0,0 +1,808 @@
+//! halts/src/paradox.rs
+//! Detect functions which ask the oracle about themselves.
+//! The classic diagonal argument builds a function `g` which calls `halts(g)`
+//! and then does the opposite of whatever the answer was:
+//! if the oracle says HALT, `g` loops, and if it says LOOP, `g` halts.
+//! Such a function is neither HALT nor LOOP; it is a PARADOX.
+
+use std::collections::{HashMap, HashSet};
+
+use syn::spanned::Spanned;
+use syn::visit::Visit;
+
+use crate::call_graph::FunctionNode;
+use crate::verdict::{Evidence, ParadoxKind, Reason, Rule, SourceSpan, Verdict};
+
+/// The names the oracle is exported under by this crate
+const ORACLES: [&str; 2] = ["halts", "verdict_from_function"];
+
+/// The answers an oracle can give about a function
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Answer {
+    /// The oracle says the function halts
+    Halts,
+    /// The oracle says the function loops
+    Loops,
+    /// The oracle says anything else: an error, a paradox, or that it doesn't know
+    Other,
+}
+
+/// The shape of the value returned by an oracle, which determines how patterns on it are read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Form {
+    /// `Result<bool, ParadoxError>`, as returned by `halts`
+    Result,
+    /// `bool`, as in `halts(g).unwrap()`
+    Bool,
+    /// `Verdict`, as returned by `verdict_from_function`
+    Verdict,
+}
+
+/// The names, aliases and re-exports under which the oracle can be called.
+#[derive(Debug, Clone)]
+pub struct OracleNames {
+    /// The identifiers which name the oracle
+    names: HashSet<String>,
+    /// The oracle's names which a function or `use` of the file gives to something else
+    shadowed: HashSet<String>,
+}
+
+impl Default for OracleNames {
+    fn default() -> Self {
+        OracleNames {
+            names: ORACLES.iter().map(|name| name.to_string()).collect(),
+            shadowed: HashSet::new(),
+        }
+    }
+}
+
+/// Collects every `use` item and every function, wherever they are
+#[derive(Default)]
+struct Declarations<'ast> {
+    /// The trees of the `use` items found so far
+    trees: Vec<&'ast syn::UseTree>,
+    /// The functions found so far, nested ones included
+    functions: Vec<&'ast syn::ItemFn>,
+}
+
+impl<'ast> Visit<'ast> for Declarations<'ast> {
+    fn visit_item_use(&mut self, item: &'ast syn::ItemUse) {
+        self.trees.push(&item.tree);
+    }
+
+    fn visit_item_fn(&mut self, item: &'ast syn::ItemFn) {
+        self.functions.push(item);
+        syn::visit::visit_item_fn(self, item);
+    }
+}
+
+impl OracleNames {
+    /// Collect the oracle names visible in a file, following `use` aliases and re-exports.
+    ///
+    /// # Arguments
+    ///
+    /// * `items` - The items of the file, including those of inline modules and function bodies
+    ///
+    /// # Returns
+    ///
+    /// The crate's own oracle names, plus every alias which refers to one of them,
+    /// less those a function or `use` of the file takes for something else.
+    pub fn from_items(items: &[syn::Item]) -> Self {
+        let mut declarations = Declarations::default();
+        for item in items {
+            declarations.visit_item(item);
+        }
+        let mut oracles = OracleNames::default();
+        oracles.add_declarations(&declarations);
+        oracles
+    }
+
+    /// The oracle names within a function, whose nested functions may take them for themselves
+    pub fn within(&self, function: &syn::ItemFn) -> Self {
+        let mut declarations = Declarations::default();
+        declarations.visit_block(&function.block);
+        let mut oracles = self.clone();
+        oracles.add_declarations(&declarations);
+        oracles
+    }
+
+    /// Shadow the oracle's names by functions of the same name, then follow `use` items
+    fn add_declarations(&mut self, declarations: &Declarations<'_>) {
+        for function in &declarations.functions {
+            let name = function.sig.ident.to_string();
+            if self.names.contains(&name) && !is_oracle_definition(function) {
+                self.shadowed.insert(name);
+            }
+        }
+        // an alias of an alias is an alias, so iterate to a fixpoint
+        loop {
+            let known = (self.names.len(), self.shadowed.len());
+            for tree in &declarations.trees {
+                self.add_aliases(&[], tree);
+            }
+            if (self.names.len(), self.shadowed.len()) == known {
+                return;
+            }
+        }
+    }
+
+    /// Add the names a `use` tree gives the oracle, and shadow those it gives to anything else
+    fn add_aliases(&mut self, prefix: &[String], tree: &syn::UseTree) {
+        let path_to = |ident: &syn::Ident| {
+            let mut path = prefix.to_vec();
+            path.push(ident.to_string());
+            path
+        };
+        match tree {
+            syn::UseTree::Path(path) => self.add_aliases(&path_to(&path.ident), &path.tree),
+            syn::UseTree::Group(group) => {
+                group.items.iter().for_each(|t| self.add_aliases(prefix, t))
+            }
+            syn::UseTree::Rename(rename) => {
+                let name = rename.rename.to_string();
+                if self.is_oracle_path(&path_to(&rename.ident)) {
+                    self.names.insert(name);
+                } else if self.names.contains(&name) {
+                    self.shadowed.insert(name);
+                }
+            }
+            syn::UseTree::Name(use_name) => {
+                let name = use_name.ident.to_string();
+                if self.names.contains(&name) && !self.is_oracle_path(&path_to(&use_name.ident)) {
+                    self.shadowed.insert(name);
+                }
+            }
+            syn::UseTree::Glob(_) => {}
+        }
+    }
+
+    /// Add the names known to another set of oracle names, such as those of another file
+    pub fn extend(&mut self, other: &OracleNames) {
+        self.names.extend(other.names.iter().cloned());
+        self.shadowed.extend(other.shadowed.iter().cloned());
+    }
+
+    /// Whether a path names the oracle, e.g. `halts`, `crate::halts::halts` or an alias,
+    /// but not `other::halts`, nor a `halts` the file defines or imports for itself
+    pub fn is_oracle(&self, path: &syn::Path) -> bool {
+        let segments: Vec<String> = path
+            .segments
+            .iter()
+            .map(|segment| segment.ident.to_string())
+            .collect();
+        self.is_oracle_path(&segments)
+    }
+
+    /// Whether the segments of a path lead to the oracle: one of its names in this crate,
+    /// or the oracle's own path through the `halts` crate or module
+    fn is_oracle_path(&self, segments: &[String]) -> bool {
+        let rest: Vec<&str> = segments
+            .iter()
+            .map(String::as_str)
+            .skip_while(|segment| matches!(*segment, "crate" | "self" | "super"))
+            .collect();
+        match rest.as_slice() {
+            [name] => self.names.contains(*name) && !self.shadowed.contains(*name),
+            ["halts", name] | ["halts", "halts", name] => ORACLES.contains(name),
+            _ => false,
+        }
+    }
+
+    /// The form of the value returned by the oracle at `path`
+    fn form(&self, path: &syn::Path) -> Form {
+        match path.segments.last() {
+            Some(segment) if segment.ident == "verdict_from_function" => Form::Verdict,
+            _ => Form::Result,
+        }
+    }
+}
+
+/// Whether a function named like the oracle is the oracle itself, by what it returns
+fn is_oracle_definition(function: &syn::ItemFn) -> bool {
+    let syn::ReturnType::Type(_, ty) = &function.sig.output else {
+        return false;
+    };
+    let returns = quote::quote!(#ty).to_string().replace(' ', "");
+    match function.sig.ident.to_string().as_str() {
+        "halts" => returns.ends_with("Result<bool,ParadoxError>"),
+        "verdict_from_function" => returns.ends_with("Verdict"),
+        _ => false,
+    }
+}
+
+/// A branch on the oracle's answer: which answers select which code.
+#[derive(Debug)]
+pub struct Branch<'ast> {
+    /// The `match` or `if` which branches on the answer
+    pub span: SourceSpan,
+    /// The code which runs if the oracle says HALT, if it can be told
+    pub if_halts: Option<Vec<syn::Stmt>>,
+    /// The code which runs if the oracle says LOOP, if it can be told
+    pub if_loops: Option<Vec<syn::Stmt>>,
+    /// The statements of the function body which follow the branch, when it is a top-level statement
+    pub continuation: Option<&'ast [syn::Stmt]>,
+}
+
+/// A call to the oracle whose argument resolves back to the enclosing function.
+#[derive(Debug)]
+pub struct SelfReference<'ast> {
+    /// The call to the oracle
+    pub span: SourceSpan,
+    /// How the answer steers control flow, if it does
+    pub branch: Option<Branch<'ast>>,
+}
+
+/// Find the calls in a function which ask the oracle about the function itself.
+///
+/// # Arguments
+///
+/// * `function` - The function to search, with the path it is found at in its file.
+/// * `oracles` - The names the oracle can be called by.
+///
+/// # Returns
+///
+/// Every self-referential oracle call, together with the branch on its answer, if any.
+pub fn find_self_references<'ast>(
+    function: &'ast FunctionNode,
+    oracles: &OracleNames,
+) -> Vec<SelfReference<'ast>> {
+    let ast = &function.ast;
+    let oracles = oracles.within(ast);
+    let mut finder = SelfReferenceFinder {
+        oracles: &oracles,
+        function,
+        aliases: HashSet::new(),
+        answers: HashMap::new(),
+        top_level: &ast.block.stmts,
+        statement: None,
+        found: vec![],
+        claimed: HashSet::new(),
+    };
+    for (i, stmt) in ast.block.stmts.iter().enumerate() {
+        finder.statement = Some(i);
+        finder.visit_stmt(stmt);
+    }
+    finder.found
+}
+
+/// Walks a function body looking for oracle calls about the function
+struct SelfReferenceFinder<'a, 'ast> {
+    /// The names of the oracle
+    oracles: &'a OracleNames,
+    /// The enclosing function
+    function: &'ast FunctionNode,
+    /// Locals bound to the enclosing function, as in `let me = g;`
+    aliases: HashSet<String>,
+    /// Locals bound to the oracle's answer about the enclosing function, and its form
+    answers: HashMap<String, Form>,
+    /// The statements of the function body
+    top_level: &'ast [syn::Stmt],
+    /// The index of the top-level statement being visited, while no deeper block has been entered
+    statement: Option<usize>,
+    /// The self-references found so far
+    found: Vec<SelfReference<'ast>>,
+    /// The oracle calls already accounted for by a branch
+    claimed: HashSet<SourceSpan>,
+}
+
+impl<'a, 'ast> SelfReferenceFinder<'a, 'ast> {
+    /// Whether a path leads to the enclosing function, from the module the function is in
+    fn is_self_path(&self, path: &syn::Path) -> bool {
+        let function = self.function;
+        let segments: Vec<String> = path
+            .segments
+            .iter()
+            .map(|segment| segment.ident.to_string())
+            .collect();
+        let full: Vec<String> = function
+            .module
+            .iter()
+            .chain(&function.path)
+            .cloned()
+            .collect();
+        let within = |module: &[String], rest: &[String]| module.iter().chain(rest).eq(full.iter());
+        match segments.as_slice() {
+            // a bare name is the function's own, unless it is a method, which is only called through a path
+            [name] => {
+                self.aliases.contains(name)
+                    || (function.owner.is_none() && function.path.last() == Some(name))
+            }
+            // where the file is mounted in its crate is unknown, so `crate::` paths need only end alike
+            [first, rest @ ..] if first == "crate" => rest.ends_with(&full),
+            [first, rest @ ..] if first == "self" => within(&function.module, rest),
+            [first, rest @ ..] if first == "Self" => match &function.owner {
+                Some(owner) => within(
+                    &function.module,
+                    &[std::slice::from_ref(owner), rest].concat(),
+                ),
+                None => false,
+            },
+            [first, ..] if first == "super" => {
+                let supers = segments.iter().take_while(|s| *s == "super").count();
+                match function.module.len().checked_sub(supers) {
+                    Some(depth) => within(&function.module[..depth], &segments[supers..]),
+                    None => segments[supers..].ends_with(&full),
+                }
+            }
+            segments => within(&function.module, segments),
+        }
+    }
+
+    /// Whether an expression evaluates to the enclosing function
+    fn is_self(&self, expr: &syn::Expr) -> bool {
+        match expr {
+            syn::Expr::Path(path) => self.is_self_path(&path.path),
+            syn::Expr::Reference(reference) => self.is_self(&reference.expr),
+            syn::Expr::Paren(paren) => self.is_self(&paren.expr),
+            // `|| g()` is as good as `g`
+            syn::Expr::Closure(closure) => match &*closure.body {
+                syn::Expr::Call(call) => call.args.is_empty() && self.is_self(&call.func),
+                syn::Expr::Block(block) => match block.block.stmts.as_slice() {
+                    [syn::Stmt::Expr(syn::Expr::Call(call), None)] => {
+                        call.args.is_empty() && self.is_self(&call.func)
+                    }
+                    _ => false,
+                },
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// If an expression is an oracle call about the enclosing function, its span and form
+    fn self_oracle_call(&self, expr: &syn::Expr) -> Option<(SourceSpan, Form)> {
+        match expr {
+            syn::Expr::Call(call) => match &*call.func {
+                syn::Expr::Path(func) if self.oracles.is_oracle(&func.path) => {
+                    if call.args.iter().any(|arg| self.is_self(arg)) {
+                        Some((call.span().into(), self.oracles.form(&func.path)))
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            },
+            syn::Expr::Paren(paren) => self.self_oracle_call(&paren.expr),
+            _ => None,
+        }
+    }
+
+    /// If an expression is the oracle's answer about the enclosing function, its span and form
+    fn self_answer(&self, expr: &syn::Expr) -> Option<(SourceSpan, Form)> {
+        if let Some(found) = self.self_oracle_call(expr) {
+            return Some(found);
+        }
+        match expr {
+            syn::Expr::Path(path) => {
+                let form = self.answers.get(&path.path.get_ident()?.to_string())?;
+                Some((path.span().into(), *form))
+            }
+            // `halts(g).unwrap()`, `halts(g).expect("...")` and `halts(g)?` all unwrap to a bool
+            syn::Expr::MethodCall(call) if call.method == "unwrap" || call.method == "expect" => {
+                match self.self_answer(&call.receiver)? {
+                    (span, Form::Result) => Some((span, Form::Bool)),
+                    _ => None,
+                }
+            }
+            syn::Expr::Try(try_expr) => match self.self_answer(&try_expr.expr)? {
+                (span, Form::Result) => Some((span, Form::Bool)),
+                _ => None,
+            },
+            syn::Expr::Paren(paren) => self.self_answer(&paren.expr),
+            _ => None,
+        }
+    }
+
+    /// If a condition tests the oracle's answer about the enclosing function, the span of the call,
+    /// and the answers which make the condition true, or `None` if they can't be told
+    fn answers_when_true(
+        &self,
+        condition: &syn::Expr,
+    ) -> Option<(SourceSpan, Option<Vec<Answer>>)> {
+        match condition {
+            // `if !halts(g).unwrap() { .. }`
+            syn::Expr::Unary(syn::ExprUnary {
+                op: syn::UnOp::Not(_),
+                expr,
+                ..
+            }) => {
+                let (span, answers) = self.answers_when_true(expr)?;
+                Some((span, answers.map(|answers| complement(&answers))))
+            }
+            syn::Expr::Paren(paren) => self.answers_when_true(&paren.expr),
+            // `if let Ok(true) = halts(g) { .. }`
+            syn::Expr::Let(let_expr) => {
+                let (span, form) = self.self_answer(&let_expr.expr)?;
+                Some((span, answers_from_pat(&let_expr.pat, form)))
+            }
+            // `if halts(g) == Ok(true) { .. }`, either way around, or with `!=`
+            syn::Expr::Binary(binary)
+                if matches!(binary.op, syn::BinOp::Eq(_) | syn::BinOp::Ne(_)) =>
+            {
+                let ((span, form), other) = match self.self_answer(&binary.left) {
+                    Some(answer) => (answer, &binary.right),
+                    None => (self.self_answer(&binary.right)?, &binary.left),
+                };
+                let answers = answers_from_expr(other, form);
+                match binary.op {
+                    syn::BinOp::Ne(_) => Some((span, answers.map(|answers| complement(&answers)))),
+                    _ => Some((span, answers)),
+                }
+            }
+            // `if halts(g).unwrap() { .. }`
+            condition => match self.self_answer(condition)? {
+                (span, Form::Bool) => Some((span, Some(vec![Answer::Halts]))),
+                _ => None,
+            },
+        }
+    }
+
+    /// Record a self-reference, with the branch on its answer if there is one
+    fn record(&mut self, span: SourceSpan, branch: Option<Branch<'ast>>) {
+        if branch.is_some() {
+            self.claimed.insert(span);
+        } else if self.claimed.contains(&span) {
+            return;
+        }
+        self.found.push(SelfReference { span, branch });
+    }
+
+    /// The statements of the function body after the current top-level statement
+    fn continuation(&self) -> Option<&'ast [syn::Stmt]> {
+        self.statement.map(|i| &self.top_level[i + 1..])
+    }
+}
+
+impl<'a, 'ast> Visit<'ast> for SelfReferenceFinder<'a, 'ast> {
+    fn visit_local(&mut self, local: &'ast syn::Local) {
+        if let (syn::Pat::Ident(pat), Some(init)) = (&local.pat, &local.init) {
+            let name = pat.ident.to_string();
+            if self.is_self(&init.expr) {
+                self.aliases.insert(name.clone());
+            }
+            if let Some((_, form)) = self.self_oracle_call(&init.expr) {
+                self.answers.insert(name, form);
+            }
+        }
+        syn::visit::visit_local(self, local);
+    }
+
+    // once inside a nested block, arm or closure, what follows is no longer the rest of the body
+    fn visit_block(&mut self, block: &'ast syn::Block) {
+        let statement = self.statement.take();
+        syn::visit::visit_block(self, block);
+        self.statement = statement;
+    }
+
+    fn visit_arm(&mut self, arm: &'ast syn::Arm) {
+        let statement = self.statement.take();
+        syn::visit::visit_arm(self, arm);
+        self.statement = statement;
+    }
+
+    fn visit_expr_closure(&mut self, closure: &'ast syn::ExprClosure) {
+        let statement = self.statement.take();
+        syn::visit::visit_expr_closure(self, closure);
+        self.statement = statement;
+    }
+
+    fn visit_expr_match(&mut self, expr: &'ast syn::ExprMatch) {
+        if let Some((span, form)) = self.self_answer(&expr.expr) {
+            let body_for = |answer: Answer| {
+                // the first arm whose pattern covers the answer runs; a guard makes it uncertain
+                expr.arms
+                    .iter()
+                    .find_map(|arm| match answers_from_pat(&arm.pat, form) {
+                        Some(answers) if !answers.contains(&answer) => None,
+                        Some(_) if arm.guard.is_none() => Some(Some(stmts_from_expr(&arm.body))),
+                        _ => Some(None),
+                    })
+                    .flatten()
+            };
+            let branch = Branch {
+                span: expr.span().into(),
+                if_halts: body_for(Answer::Halts),
+                if_loops: body_for(Answer::Loops),
+                continuation: self.continuation(),
+            };
+            self.record(span, Some(branch));
+        }
+        syn::visit::visit_expr_match(self, expr);
+    }
+
+    fn visit_expr_if(&mut self, expr: &'ast syn::ExprIf) {
+        let then_branch = expr.then_branch.stmts.clone();
+        let else_branch = match &expr.else_branch {
+            Some((_, else_expr)) => stmts_from_expr(else_expr),
+            None => vec![],
+        };
+        if let Some((span, answers)) = self.answers_when_true(&expr.cond) {
+            let pick = |answer| match &answers {
+                Some(answers) if answers.contains(&answer) => Some(then_branch.clone()),
+                Some(_) => Some(else_branch.clone()),
+                None => None,
+            };
+            let branch = Branch {
+                span: expr.span().into(),
+                if_halts: pick(Answer::Halts),
+                if_loops: pick(Answer::Loops),
+                continuation: self.continuation(),
+            };
+            self.record(span, Some(branch));
+        }
+        syn::visit::visit_expr_if(self, expr);
+    }
+
+    // the answer never changes, so a loop it lets in never ends but by `break`, and one it keeps out never runs
+    fn visit_expr_while(&mut self, expr: &'ast syn::ExprWhile) {
+        if let Some((span, answers)) = self.answers_when_true(&expr.cond) {
+            let (label, body) = (&expr.label, &expr.body);
+            let endless: syn::Stmt = syn::parse_quote!(#label loop #body);
+            let pick = |answer| match &answers {
+                Some(answers) if answers.contains(&answer) => Some(vec![endless.clone()]),
+                Some(_) => Some(vec![]),
+                None => None,
+            };
+            let branch = Branch {
+                span: expr.span().into(),
+                if_halts: pick(Answer::Halts),
+                if_loops: pick(Answer::Loops),
+                continuation: self.continuation(),
+            };
+            self.record(span, Some(branch));
+        }
+        syn::visit::visit_expr_while(self, expr);
+    }
+
+    fn visit_expr_call(&mut self, call: &'ast syn::ExprCall) {
+        if let Some((span, _)) = self.self_oracle_call(&syn::Expr::Call(call.clone())) {
+            self.record(span, None);
+        }
+        syn::visit::visit_expr_call(self, call);
+    }
+}
+
+/// The oracle answers a pattern matches, or `None` if the pattern can't be read
+fn answers_from_pat(pat: &syn::Pat, form: Form) -> Option<Vec<Answer>> {
+    let all = vec![Answer::Halts, Answer::Loops, Answer::Other];
+    match (pat, form) {
+        (syn::Pat::Wild(_), _) | (syn::Pat::Ident(syn::PatIdent { subpat: None, .. }), _) => {
+            Some(all)
+        }
+        (syn::Pat::Or(or), _) => {
+            let mut answers = vec![];
+            for case in &or.cases {
+                answers.extend(answers_from_pat(case, form)?);
+            }
+            Some(answers)
+        }
+        (syn::Pat::Paren(paren), _) => answers_from_pat(&paren.pat, form),
+        (syn::Pat::Lit(lit), Form::Bool) => match &lit.lit {
+            syn::Lit::Bool(b) if b.value => Some(vec![Answer::Halts]),
+            syn::Lit::Bool(_) => Some(vec![Answer::Loops]),
+            _ => None,
+        },
+        (syn::Pat::TupleStruct(tuple), Form::Result) => {
+            let variant = tuple.path.segments.last()?.ident.to_string();
+            match (variant.as_str(), tuple.elems.first()) {
+                ("Ok", Some(inner)) => answers_from_pat(inner, Form::Bool),
+                ("Err", _) => Some(vec![Answer::Other]),
+                _ => None,
+            }
+        }
+        (syn::Pat::TupleStruct(syn::PatTupleStruct { path, .. }), Form::Verdict)
+        | (syn::Pat::Path(syn::PatPath { path, .. }), Form::Verdict) => {
+            match path.segments.last()?.ident.to_string().as_str() {
+                "Halts" => Some(vec![Answer::Halts]),
+                "Loops" => Some(vec![Answer::Loops]),
+                _ => Some(vec![Answer::Other]),
+            }
+        }
+        _ => None,
+    }
+}
+
+/// The oracle answers an expression compared with the answer stands for, or `None` if it can't be read
+fn answers_from_expr(expr: &syn::Expr, form: Form) -> Option<Vec<Answer>> {
+    match (expr, form) {
+        (syn::Expr::Paren(paren), _) => answers_from_expr(&paren.expr, form),
+        (
+            syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Bool(b),
+                ..
+            }),
+            Form::Bool,
+        ) => match b.value {
+            true => Some(vec![Answer::Halts]),
+            false => Some(vec![Answer::Loops]),
+        },
+        (syn::Expr::Call(call), Form::Result) => match (&*call.func, call.args.first()) {
+            (syn::Expr::Path(func), Some(inner)) if func.path.is_ident("Ok") => {
+                answers_from_expr(inner, Form::Bool)
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The oracle answers which are not among some answers
+fn complement(answers: &[Answer]) -> Vec<Answer> {
+    [Answer::Halts, Answer::Loops, Answer::Other]
+        .into_iter()
+        .filter(|answer| !answers.contains(answer))
+        .collect()
+}
+
+/// The statements an expression runs, unwrapping a block
+fn stmts_from_expr(expr: &syn::Expr) -> Vec<syn::Stmt> {
+    match expr {
+        syn::Expr::Block(block) if block.label.is_none() => block.block.stmts.clone(),
+        expr => vec![syn::Stmt::Expr(expr.clone(), None)],
+    }
+}
+
+/// Decide what the oracle's answers about a function make of it.
+///
+/// # Arguments
+///
+/// * `self_references` - The self-referential oracle calls in the function.
+/// * `judge` - Decides the code which runs after a given answer.
+///
+/// # Returns
+///
+/// PARADOX if the function does the opposite of whatever the oracle answers,
+/// UNKNOWN if its behavior hinges on the answer in any other way,
+/// and `None` if no answer steers its control flow.
+pub fn verdict_from_self_references(
+    self_references: &[SelfReference<'_>],
+    judge: &mut dyn FnMut(Vec<syn::Stmt>) -> Verdict,
+) -> Option<Verdict> {
+    let mut steering = None;
+    for self_reference in self_references {
+        let Some(branch) = &self_reference.branch else {
+            continue;
+        };
+        let spans = vec![self_reference.span, branch.span];
+        // what follows a nested branch is unknown, so only top-level branches are judged
+        let (Some(if_halts), Some(if_loops), Some(continuation)) =
+            (&branch.if_halts, &branch.if_loops, branch.continuation)
+        else {
+            steering.get_or_insert((spans, vec![]));
+            continue;
+        };
+        let after = |stmts: &Vec<syn::Stmt>| {
+            let mut stmts = stmts.clone();
+            if !continuation.is_empty() {
+                if let Some(syn::Stmt::Expr(_, semi @ None)) = stmts.last_mut() {
+                    *semi = Some(Default::default());
+                }
+            }
+            stmts.extend(continuation.iter().cloned());
+            stmts
+        };
+        let halts_verdict = judge(after(if_halts));
+        let loops_verdict = judge(after(if_loops));
+        let inverted = matches!(halts_verdict, Verdict::Loops(_))
+            && matches!(loops_verdict, Verdict::Halts(_));
+        let mut evidence = Evidence::new(Rule::Diagonalization);
+        evidence.spans = spans;
+        evidence.sub_verdicts = vec![halts_verdict, loops_verdict];
+        if inverted {
+            return Some(Verdict::Paradox(ParadoxKind::Inversion, evidence));
+        }
+        steering.get_or_insert((evidence.spans, evidence.sub_verdicts));
+    }
+    steering.map(|(spans, sub_verdicts)| {
+        let mut evidence = Evidence::new(Rule::SelfReference);
+        evidence.spans = spans;
+        evidence.sub_verdicts = sub_verdicts;
+        Verdict::Unknown(Reason::OracleDependent, evidence)
+    })
+}
+
+/// tests for paradox detection
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::call_graph::CallGraph;
+
+    /// Judges code which contains a `loop` as LOOP, and anything else as HALT
+    fn judge(stmts: Vec<syn::Stmt>) -> Verdict {
+        let code = quote::quote! { #(#stmts)* }.to_string();
+        if code.contains("loop") {
+            Verdict::Loops(Evidence::new(Rule::EndlessIteration))
+        } else {
+            Verdict::Halts(Evidence::new(Rule::NoLoopingConstructs))
+        }
+    }
+
+    /// Run the detector on some code, with the judge above
+    fn verdict_from_code(code: &str) -> Option<Verdict> {
+        let file: syn::File = syn::parse_str(code).unwrap();
+        let oracles = OracleNames::from_items(&file.items);
+        let ast = file
+            .items
+            .iter()
+            .find_map(|item| match item {
+                syn::Item::Fn(f) if f.sig.ident == "g" => Some(f),
+                _ => None,
+            })
+            .unwrap();
+        let (graph, root) = CallGraph::from_file_with_root(&file, ast);
+        let self_references = find_self_references(&graph.functions[root], &oracles);
+        verdict_from_self_references(&self_references, &mut judge)
+    }
+
+    /// Test the classic diagonal function is an inversion paradox
+    #[test]
+    fn test_diagonal_match() {
+        let code = "fn g() { match halts(g) { Err(_) | Ok(true) => loop {}, Ok(false) => {} } }";
+        let verdict = verdict_from_code(code).unwrap();
+        assert!(matches!(
+            verdict,
+            Verdict::Paradox(ParadoxKind::Inversion, _)
+        ));
+        assert_eq!(verdict.evidence().rule, Rule::Diagonalization);
+        assert_eq!(verdict.evidence().spans.len(), 2);
+    }
+
+    /// Test inversions through `if`, aliases, paths and re-exports
+    #[test]
+    fn test_diagonal_variants() {
+        let codes = [
+            "fn g() { if halts(g).unwrap() { loop {} } }",
+            "fn g() { if !crate::halts::halts(g).unwrap() {} else { loop {} } }",
+            "use halts::halts as oracle; pub use oracle as h; fn g() { let me = g; if let Ok(true) = h(me) { loop {} } }",
+            "fn g() { let answer = verdict_from_function(|| g()); match answer { Verdict::Halts(_) => loop {}, _ => () } }",
+            "fn g() { if halts(g) == Ok(true) { loop {} } }",
+            "fn g() { if Ok(false) != crate::halts(self::g) { loop {} } }",
+            "fn g() { if halts(g).unwrap() == true { loop {} } }",
+            "fn g() { while halts(g).unwrap() {} }",
+            "fn g() { while let Ok(true) = halts::halts::halts(crate::g) {} }",
+            "pub fn halts<F>(f: F) -> Result<bool, ParadoxError> { todo!() } fn g() { if halts(g).unwrap() { loop {} } }",
+        ];
+        for code in codes {
+            let verdict = verdict_from_code(code);
+            assert!(
+                matches!(verdict, Some(Verdict::Paradox(ParadoxKind::Inversion, _))),
+                "{}: {:?}",
+                code,
+                verdict
+            );
+        }
+    }
+
+    /// Test that self-reference which agrees with the oracle is not a paradox
+    #[test]
+    fn test_consistent_self_reference() {
+        let code = "fn g() { match halts(g) { Ok(true) => {}, _ => loop {} } }";
+        let verdict = verdict_from_code(code).unwrap();
+        assert!(matches!(
+            verdict,
+            Verdict::Unknown(Reason::OracleDependent, _)
+        ));
+
+        // asking about itself without using the answer changes nothing
+        assert!(verdict_from_code("fn g() { let _ = halts(g); }").is_none());
+        // asking about another function is no self-reference at all
+        assert!(verdict_from_code("fn g() { if halts(f).unwrap() { loop {} } }").is_none());
+        assert!(verdict_from_code("fn g() { if halts(other::g).unwrap() { loop {} } }").is_none());
+    }
+
+    /// Test that a function which only shares the oracle's name is no oracle
+    #[test]
+    fn test_not_the_oracle() {
+        let codes = [
+            "fn g() { fn halts(_: fn()) -> Result<bool, ()> { Ok(true) } if halts(g).unwrap() { loop {} } }",
+            "fn halts(_: fn()) -> bool { true } fn g() { if halts(g) { loop {} } }",
+            "mod other { pub fn halts(_: fn()) -> bool { true } } fn g() { if other::halts(g) { loop {} } }",
+            "use other::halts; fn g() { if halts(g).unwrap() { loop {} } }",
+            "use other::check as halts; fn g() { if halts(g).unwrap() { loop {} } }",
+        ];
+        for code in codes {
+            let verdict = verdict_from_code(code);
+            assert!(verdict.is_none(), "{}: {:?}", code, verdict);
+        }
+    }
+}
@@ -0,0 +1,457 @@
+//! halts/src/portfolio.rs
+//! A portfolio of the engines, each run on a thread of its own, the first conclusive verdict winning.
+//! Each worker decides the function with one engine alone, under the refinement driver, so its LOOPs are still replayed.
+//! The first worker to reach a verdict other than UNKNOWN raises a shared flag, which the others see the next time
+//! they would try a rule, and they wind down with an UNKNOWN while the portfolio returns. Workers which finished by then
+//! are compared: engines are sound, so two conclusive verdicts which disagree mean a bug, which is reported rather than settled.
+//! At the deadline the portfolio returns an UNKNOWN without waiting, and the workers still in a rule are let go
+//! to finish it on their own, while fresh ones take their places.
+//! `syn`'s syntax trees can't be sent between threads, so each worker is given a source, which finds the function
+//! and its context on the worker's own thread. Workers stay up from one decision to the next,
+//! so a source may keep what it parsed on a worker's thread for the next function of the same crate.
+
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::call_graph::Context;
+use crate::halts::{decision_from_ast_when, decision_from_ast_within};
+use crate::verdict::{Decision, Engine, Evidence, Reason, Rule, Verdict};
+
+/// What a decision may do: the engines it may use, and a flag which tells it to stop.
+#[derive(Debug, Clone)]
+pub struct Scope {
+    /// The engines whose rules may be tried
+    pub engines: Vec<Engine>,
+    /// Whether the decision is no longer wanted, shared with whoever may cancel it
+    pub cancelled: Arc<AtomicBool>,
+}
+
+impl Default for Scope {
+    fn default() -> Self {
+        Scope {
+            engines: Engine::ALL.to_vec(),
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+impl Scope {
+    /// The scope of a worker which uses one engine, until a flag it shares is raised
+    pub fn worker(engine: Engine, cancelled: &Arc<AtomicBool>) -> Self {
+        Scope {
+            engines: vec![engine],
+            cancelled: Arc::clone(cancelled),
+        }
+    }
+
+    /// Whether the decision is no longer wanted
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Whether the rules of an engine may be tried now
+    pub fn allows(&self, engine: Engine) -> bool {
+        !self.is_cancelled() && self.engines.contains(&engine)
+    }
+
+    /// Try the rules of an engine, if they may be tried now
+    pub fn run<T>(&self, engine: Engine, rules: impl FnOnce() -> T) -> Option<T> {
+        self.allows(engine).then(rules)
+    }
+}
+
+/// Finds the function to decide and the context it is defined in, or tells why it can't,
+/// on whichever thread calls it, so each worker parses what it needs itself
+pub type Source = Arc<dyn Fn() -> Result<(syn::ItemFn, Context), String> + Send + Sync>;
+
+/// Work for a worker to do on its thread
+type Job = Box<dyn FnOnce() + Send>;
+
+/// What a worker tells the portfolio
+enum Report {
+    /// It found the function, so the time it is given starts
+    Started,
+    /// It decided the function, as the worker it is among this thread's workers
+    Decided(usize, Box<Decision>),
+}
+
+/// The worker which looks for a condition the function halts under, after those of the engines
+const PRECONDITION_WORKER: usize = Engine::ALL.len();
+
+thread_local! {
+    /// The workers of this thread's portfolio, by the engine they run, and then the one which looks for preconditions
+    static WORKERS: RefCell<Vec<mpsc::Sender<Job>>> = RefCell::default();
+}
+
+/// A worker which does each job it is given, one after another, until it is let go
+fn new_worker() -> mpsc::Sender<Job> {
+    let (sender, receiver) = mpsc::channel::<Job>();
+    thread::spawn(move || {
+        for job in receiver {
+            job();
+        }
+    });
+    sender
+}
+
+/// Let a worker go, to finish the job it is doing on its own, and start another in its place
+fn retire(worker: usize) {
+    WORKERS.with(|workers| {
+        if let Some(sender) = workers.borrow_mut().get_mut(worker) {
+            *sender = new_worker();
+        }
+    });
+}
+
+/// Give a job to one of this thread's workers, starting it if it hasn't been, or if it is gone
+fn run_on_worker(worker: usize, job: Job) {
+    WORKERS.with(|workers| {
+        let mut workers = workers.borrow_mut();
+        while workers.len() <= worker {
+            workers.push(new_worker());
+        }
+        if let Err(mpsc::SendError(job)) = workers[worker].send(job) {
+            workers[worker] = new_worker();
+            // a worker which was just started is listening
+            let _ = workers[worker].send(job);
+        }
+    });
+}
+
+/// The decision for a function whose source is unavailable
+fn unavailable(why: String) -> Decision {
+    Decision::new(
+        Verdict::Unknown(
+            Reason::SourceUnavailable(why),
+            Evidence::new(Rule::Unanalyzable),
+        ),
+        0,
+    )
+}
+
+/// The decision for a function whose analysis panicked, so one function can't stop the rest from being decided
+fn panicked(what: String) -> Decision {
+    Decision::new(
+        Verdict::Unknown(
+            Reason::InternalError(format!("{} panicked", what)),
+            Evidence::new(Rule::Unanalyzable),
+        ),
+        0,
+    )
+}
+
+/// Run a job on a worker which finds the function on its own thread, says when it has, then decides it
+fn run_decision(
+    worker: usize,
+    source: &Source,
+    sender: &mpsc::Sender<Report>,
+    decide: impl FnOnce(syn::ItemFn, Context) -> Decision + Send + 'static,
+) {
+    let (source, sender) = (Arc::clone(source), sender.clone());
+    run_on_worker(
+        worker,
+        Box::new(move || {
+            let decided = panic::catch_unwind(AssertUnwindSafe(|| match source() {
+                Ok((ast, context)) => {
+                    // the portfolio may have stopped listening, which is no matter
+                    let _ = sender.send(Report::Started);
+                    decide(ast, context)
+                }
+                Err(why) => unavailable(why),
+            }));
+            let decision = decided.unwrap_or_else(|_| match Engine::ALL.get(worker) {
+                Some(engine) => panicked(format!("the {} engine", engine)),
+                None => panicked("the search for a precondition".to_string()),
+            });
+            let _ = sender.send(Report::Decided(worker, Box::new(decision)));
+        }),
+    );
+}
+
+/// The next decision a worker reports, with the deadline set once the first worker has found the function,
+/// so the time it takes to parse the crate isn't counted
+fn next_decision(
+    receiver: &mpsc::Receiver<Report>,
+    deadline: &mut Option<Instant>,
+    timeout: Duration,
+) -> Result<(usize, Decision), RecvTimeoutError> {
+    loop {
+        let report = match deadline {
+            Some(deadline) => {
+                receiver.recv_timeout(deadline.saturating_duration_since(Instant::now()))?
+            }
+            None => receiver
+                .recv()
+                .map_err(|_| RecvTimeoutError::Disconnected)?,
+        };
+        match report {
+            Report::Started => {
+                deadline.get_or_insert_with(|| Instant::now() + timeout);
+            }
+            Report::Decided(worker, decision) => return Ok((worker, *decision)),
+        }
+    }
+}
+
+/// Decide a function by running every engine at once, each on a worker of its own,
+/// then look for a condition it halts under if it loops or none was conclusive.
+///
+/// # Arguments
+///
+/// * `source` - Finds the function and the context it is defined in, on each worker's thread.
+/// * `timeout` - How long the function may be analyzed for, from when the first worker has found it.
+///
+/// # Returns
+///
+/// The first conclusive decision, with the verdicts of any workers which had finished by then added to its notes,
+/// or an UNKNOWN for an internal error if two conclusive verdicts disagree,
+/// or an UNKNOWN with every worker's verdict under it if none was conclusive, or for a timeout if time ran out first;
+/// a HALT WHEN in place of a LOOP or an inconclusive UNKNOWN if a condition was found in time.
+pub fn decision_from_portfolio(source: &Source, timeout: Duration) -> Decision {
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let (sender, receiver) = mpsc::channel();
+    for (worker, &engine) in Engine::ALL.iter().enumerate() {
+        let scope = Scope::worker(engine, &cancelled);
+        run_decision(worker, source, &sender, move |ast, context| {
+            decision_from_ast_within(&ast, &context, &scope)
+        });
+    }
+
+    let mut deadline = None;
+    let mut decided: Vec<(usize, Decision)> = vec![];
+    let mut timed_out = false;
+    while decided.len() < Engine::ALL.len()
+        && !decided
+            .iter()
+            .any(|(_, decision)| decision.verdict.is_conclusive())
+    {
+        match next_decision(&receiver, &mut deadline, timeout) {
+            Ok(worker_decision) => decided.push(worker_decision),
+            Err(RecvTimeoutError::Timeout) => {
+                timed_out = true;
+                break;
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    cancelled.store(true, Ordering::Relaxed);
+    // the workers which finished alongside the first are compared with it; the rest wind down on their own
+    decided.extend(receiver.try_iter().filter_map(|report| match report {
+        Report::Decided(worker, decision) => Some((worker, *decision)),
+        Report::Started => None,
+    }));
+    let busy: Vec<usize> = (0..Engine::ALL.len())
+        .filter(|worker| !decided.iter().any(|(done, _)| done == worker))
+        .collect();
+    let (conclusive, inconclusive): (Vec<_>, Vec<_>) = decided
+        .into_iter()
+        .map(|(worker, decision)| (Engine::ALL[worker], decision))
+        .partition(|(_, decision)| decision.verdict.is_conclusive());
+    if timed_out && conclusive.is_empty() {
+        busy.into_iter().for_each(retire);
+        let mut decision = decision_from_workers(conclusive, inconclusive);
+        let mut evidence = decision.verdict.evidence().clone();
+        evidence.notes = vec![format!(
+            "the analysis ran out of time after {}s",
+            timeout.as_secs_f64()
+        )];
+        decision.verdict = Verdict::Unknown(Reason::Timeout, evidence);
+        return decision;
+    }
+
+    let decision = decision_from_workers(conclusive, inconclusive);
+    if !matches!(
+        decision.verdict,
+        Verdict::Loops(_) | Verdict::Unknown(Reason::Inconclusive, _)
+    ) {
+        return decision;
+    }
+    let (sender, receiver) = mpsc::channel();
+    let decided = decision.clone();
+    let scope = Scope::default();
+    let searching = scope.clone();
+    run_decision(PRECONDITION_WORKER, source, &sender, move |ast, context| {
+        decision_from_ast_when(&ast, &context, decided, &searching)
+    });
+    match next_decision(&receiver, &mut deadline, timeout) {
+        Ok((_, found)) => found,
+        Err(_) => {
+            scope.cancelled.store(true, Ordering::Relaxed);
+            retire(PRECONDITION_WORKER);
+            let mut decision = decision;
+            decision.verdict.evidence_mut().notes.push(format!(
+                "no condition it halts under was found before the analysis ran out of time after {}s",
+                timeout.as_secs_f64()
+            ));
+            decision
+        }
+    }
+}
+
+/// The decision of the portfolio from the decisions of its workers:
+/// the first conclusive one, unless another disagrees, or an UNKNOWN with each under it if none was conclusive
+fn decision_from_workers(
+    conclusive: Vec<(Engine, Decision)>,
+    mut inconclusive: Vec<(Engine, Decision)>,
+) -> Decision {
+    let Some((_, first)) = conclusive.first() else {
+        let mut evidence = Evidence::new(Rule::Unanalyzable);
+        evidence
+            .notes
+            .push("no engine of the portfolio was conclusive".to_string());
+        inconclusive.sort_by_key(|(engine, _)| Engine::ALL.iter().position(|e| e == engine));
+        let rounds = inconclusive
+            .iter()
+            .map(|(_, decision)| decision.rounds)
+            .max()
+            .unwrap_or(0);
+        evidence.sub_verdicts = inconclusive
+            .into_iter()
+            .map(|(_, decision)| decision.verdict)
+            .collect();
+        return Decision::new(Verdict::Unknown(Reason::Inconclusive, evidence), rounds);
+    };
+    if let Some((engine, disagreeing)) = conclusive
+        .iter()
+        .find(|(_, decision)| decision.verdict.category() != first.verdict.category())
+    {
+        let why = format!(
+            "the {} engine says {}, but the {} engine says {}",
+            first.engine, first.verdict, engine, disagreeing.verdict
+        );
+        let mut evidence = Evidence::new(first.verdict.evidence().rule);
+        evidence.notes.push(why.clone());
+        evidence.sub_verdicts = conclusive
+            .iter()
+            .map(|(_, decision)| decision.verdict.clone())
+            .collect();
+        return Decision::new(
+            Verdict::Unknown(Reason::InternalError(why), evidence),
+            first.rounds,
+        );
+    }
+    let mut decision = first.clone();
+    for (engine, agreeing) in conclusive.iter().skip(1) {
+        decision.verdict.evidence_mut().notes.push(format!(
+            "the {} engine agrees: {}",
+            engine, agreeing.verdict
+        ));
+    }
+    decision
+}
+
+/// tests for the portfolio
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A source which parses a file, and finds a function at its top level by name
+    fn source_from_str(code: &str, name: &str) -> Source {
+        let (code, name) = (code.to_string(), name.to_string());
+        Arc::new(move || {
+            let file = syn::parse_file(&code).map_err(|e| e.to_string())?;
+            let ast = file
+                .items
+                .iter()
+                .find_map(|item| match item {
+                    syn::Item::Fn(function) if function.sig.ident == name => Some(function.clone()),
+                    _ => None,
+                })
+                .ok_or_else(|| format!("no function named `{}`", name))?;
+            let path = [name.clone()];
+            Ok((ast, Context::from_file(&file).at(&path)))
+        })
+    }
+
+    /// Decide a function of a file by the portfolio, with time enough for every engine
+    fn decision_from_str(code: &str, name: &str) -> Decision {
+        decision_from_portfolio(&source_from_str(code, name), Duration::from_secs(600))
+    }
+
+    /// Test that the portfolio reaches the verdicts the engines reach one after another
+    #[test]
+    fn test_portfolio() {
+        let source = "
+            fn count() { let mut i = 0; while i < 10 { i += 1; } }
+            fn spin() { let mut x = 1; while x > 0 { x += 0; } }
+            fn down(n: u32) -> u32 { if n == 0 { 0 } else { down(n - 1) } }
+            fn opaque(v: Vec<i64>) { let mut i = 0; while v.get(i).is_some() { i += 1; } }
+        ";
+        let decision = decision_from_str(source, "count");
+        assert!(
+            matches!(decision.verdict, Verdict::Halts(_)),
+            "{}",
+            decision
+        );
+        let decision = decision_from_str(source, "spin");
+        assert!(
+            matches!(decision.verdict, Verdict::Loops(_)),
+            "{}",
+            decision
+        );
+        let decision = decision_from_str(source, "down");
+        assert!(
+            matches!(decision.verdict, Verdict::Halts(_)),
+            "{}",
+            decision
+        );
+        let decision = decision_from_str(source, "opaque");
+        assert!(!decision.verdict.is_conclusive(), "{}", decision);
+        assert_eq!(
+            decision.verdict.evidence().sub_verdicts.len(),
+            Engine::ALL.len()
+        );
+        let decision = decision_from_str(source, "missing");
+        assert!(
+            matches!(
+                decision.verdict,
+                Verdict::Unknown(Reason::SourceUnavailable(_), _)
+            ),
+            "{}",
+            decision
+        );
+    }
+
+    /// Test that a decision which takes longer than it is given is UNKNOWN at the deadline, without waiting for the rules
+    #[test]
+    fn test_timeout() {
+        let source = "
+            fn collatz(n: u64) {
+                let mut x = n;
+                while x > 1 { if x % 2 == 0 { x /= 2; } else { x = 3 * x + 1; } }
+            }
+        ";
+        let source = source_from_str(source, "collatz");
+        let decision = decision_from_portfolio(&source, Duration::from_millis(1));
+        assert!(
+            matches!(decision.verdict, Verdict::Unknown(Reason::Timeout, _)),
+            "{}",
+            decision
+        );
+        // given time, no engine is conclusive, but a condition it halts under is found
+        let decision = decision_from_portfolio(&source, Duration::from_secs(600));
+        assert!(
+            matches!(decision.verdict, Verdict::HaltsWhen(..)),
+            "{}",
+            decision
+        );
+    }
+
+    /// Test that a scope allows its engines until it is cancelled
+    #[test]
+    fn test_scope() {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let scope = Scope::worker(Engine::Interpreter, &cancelled);
+        assert!(scope.allows(Engine::Interpreter));
+        assert!(!scope.allows(Engine::Syntactic));
+        assert_eq!(scope.run(Engine::Interpreter, || 1), Some(1));
+        cancelled.store(true, Ordering::Relaxed);
+        assert!(!scope.allows(Engine::Interpreter));
+        assert_eq!(scope.run(Engine::Interpreter, || 1), None);
+    }
+}
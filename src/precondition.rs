@@ -0,0 +1,230 @@
+//! halts/src/precondition.rs
+//! Infer a condition on the integer parameters of a function under which it halts,
+//! for functions which halt on only part of their inputs, like `while n != 0 { n -= 1 }`, which halts when `n >= 0`.
+//! The conditions tried bound one parameter by a constant of the function, like `n >= 0` or `n <= 9`,
+//! and each is judged by deciding the function for the inputs which meet it, as a domain.
+//! Of the conditions it halts under, those no other implies are joined by `||`, since the function halts under either.
+//! Then the inputs which fail them all are judged too: if the function loops on some, the condition can't be done without.
+
+use std::collections::BTreeSet;
+
+use syn::visit::Visit;
+
+use crate::input::{Domain, Input};
+use crate::interp::IntTy;
+use crate::verdict::{Evidence, Precondition, Rule, Verdict};
+
+/// The most constants the parameters are compared to
+const MAX_CONSTANTS: usize = 8;
+
+/// The most integer parameters bounds are tried on
+const MAX_PARAMS: usize = 4;
+
+/// The integer literals of a function, which are the constants its parameters are compared to
+#[derive(Default)]
+struct Constants {
+    /// Each literal which fits an `i128`
+    constants: BTreeSet<i128>,
+}
+
+impl<'ast> Visit<'ast> for Constants {
+    fn visit_lit_int(&mut self, lit: &'ast syn::LitInt) {
+        if let Ok(constant) = lit.base10_parse::<i128>() {
+            self.constants.insert(constant);
+        }
+    }
+}
+
+/// A bound on one parameter: the least and greatest values it may have, either of which may be unbounded
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Bound {
+    /// The name of the parameter
+    param: String,
+    /// The least value, if any
+    least: Option<i128>,
+    /// The greatest value, if any
+    greatest: Option<i128>,
+}
+
+impl Bound {
+    /// Whether every value this bound allows, the other does too
+    fn implies(&self, other: &Bound) -> bool {
+        self.param == other.param
+            && other
+                .least
+                .is_none_or(|o| self.least.is_some_and(|s| s >= o))
+            && other
+                .greatest
+                .is_none_or(|o| self.greatest.is_some_and(|s| s <= o))
+    }
+
+    /// The bound as a condition in Rust
+    fn condition(&self) -> String {
+        match (self.least, self.greatest) {
+            (Some(least), None) => format!("{} >= {}", self.param, least),
+            (None, Some(greatest)) => format!("{} <= {}", self.param, greatest),
+            (Some(least), Some(greatest)) => {
+                format!(
+                    "{} <= {} && {} <= {}",
+                    least, self.param, self.param, greatest
+                )
+            }
+            (None, None) => "true".to_string(),
+        }
+    }
+}
+
+/// Each bound to try on the integer parameters of a function, weakest first for each constant
+fn candidates(ast: &syn::ItemFn) -> Vec<Bound> {
+    let mut constants = Constants::default();
+    constants.visit_block(&ast.block);
+    constants.constants.insert(0);
+    // the constants nearest zero are the likeliest to matter
+    let mut constants: Vec<i128> = constants.constants.into_iter().collect();
+    constants.sort_by_key(|constant| constant.unsigned_abs());
+    constants.truncate(MAX_CONSTANTS);
+    let params = ast.sig.inputs.iter().filter_map(|input| match input {
+        syn::FnArg::Typed(param) => match (&*param.pat, IntTy::from_type(&param.ty)) {
+            (syn::Pat::Ident(ident), Some(_)) => Some(ident.ident.to_string()),
+            _ => None,
+        },
+        syn::FnArg::Receiver(_) => None,
+    });
+    let mut candidates = vec![];
+    for param in params.take(MAX_PARAMS) {
+        for &constant in &constants {
+            let bound = |least, greatest| Bound {
+                param: param.clone(),
+                least,
+                greatest,
+            };
+            for bound in [
+                bound(Some(constant), None),
+                bound(None, Some(constant)),
+                bound(constant.checked_add(1), None),
+                bound(None, constant.checked_sub(1)),
+            ] {
+                if !candidates.contains(&bound) {
+                    candidates.push(bound);
+                }
+            }
+        }
+    }
+    candidates
+}
+
+/// Infer a condition on the integer parameters of a function under which it halts.
+///
+/// # Arguments
+///
+/// * `ast` - The AST of the function, which halts on only some of its inputs, or isn't known to halt on all of them.
+/// * `judge` - Decides the function for a domain of its inputs.
+///
+/// # Returns
+///
+/// The condition, with evidence whose sub-verdicts are the verdicts under each part of the condition
+/// and the verdict for the inputs which fail it, or `None` if no bound was found which the function halts under.
+pub fn precondition_from_ast(
+    ast: &syn::ItemFn,
+    judge: &mut dyn FnMut(&Input) -> Verdict,
+) -> Option<(Precondition, Evidence)> {
+    let mut halting: Vec<(Bound, Verdict)> = vec![];
+    for bound in candidates(ast) {
+        // a bound implied by one already found adds nothing to the condition
+        if halting.iter().any(|(found, _)| bound.implies(found)) {
+            continue;
+        }
+        let domain = Domain::new().such_that(&bound.condition()).ok()?;
+        let verdict = judge(&domain.into());
+        if matches!(verdict, Verdict::Halts(_)) {
+            halting.retain(|(found, _)| !found.implies(&bound));
+            halting.push((bound, verdict));
+        }
+    }
+    if halting.is_empty() {
+        return None;
+    }
+    let parts: Vec<String> = halting.iter().map(|(bound, _)| bound.condition()).collect();
+    let condition = if parts.len() == 1 {
+        parts[0].clone()
+    } else {
+        parts
+            .iter()
+            .map(|part| format!("({})", part))
+            .collect::<Vec<String>>()
+            .join(" || ")
+    };
+    let otherwise = Domain::new().such_that(&format!("!({})", condition)).ok()?;
+    let otherwise = judge(&otherwise.into());
+    let mut evidence = Evidence::new(Rule::Precondition);
+    for (bound, verdict) in &halting {
+        evidence.notes.push(format!(
+            "halts when {}, by {}",
+            bound.condition(),
+            verdict.evidence().rule
+        ));
+    }
+    let loops_otherwise = matches!(otherwise, Verdict::Loops(_));
+    if loops_otherwise {
+        evidence.notes.push(format!(
+            "loops on some input when !({}), by {}",
+            condition,
+            otherwise.evidence().rule
+        ));
+    }
+    evidence
+        .sub_verdicts
+        .extend(halting.into_iter().map(|(_, verdict)| verdict));
+    evidence.sub_verdicts.push(otherwise);
+    Some((
+        Precondition {
+            condition,
+            loops_otherwise,
+        },
+        evidence,
+    ))
+}
+
+/// tests for preconditions
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The conditions of the bounds tried on a function
+    fn conditions(code: &str) -> Vec<String> {
+        let ast: syn::ItemFn = syn::parse_str(code).unwrap();
+        candidates(&ast).iter().map(Bound::condition).collect()
+    }
+
+    /// Test that the bounds tried compare integer parameters to the function's constants
+    #[test]
+    fn test_candidates() {
+        assert_eq!(
+            conditions("fn f(n: i64, b: bool) { while n != 0 { n -= 1; } }"),
+            vec!["n >= 0", "n <= 0", "n >= 1", "n <= -1", "n <= 1", "n >= 2"]
+        );
+        assert!(conditions("fn f(v: Vec<i64>) { }").is_empty());
+    }
+
+    /// Test that the weakest bounds the function halts under are joined, and the rest judged
+    #[test]
+    fn test_precondition() {
+        let ast: syn::ItemFn = syn::parse_str("fn f(n: i64) { if n == 1 {} }").unwrap();
+        // halts on `n <= 0` and on `n >= 2`, which the judge knows only by looking at the domain
+        let mut judge = |input: &Input| {
+            let input = input.to_string();
+            let evidence = Evidence::new(Rule::RankingFunction);
+            if input.contains('!') {
+                Verdict::Loops(evidence)
+            } else if input.contains("n <= 0") || input.contains("n >= 2") {
+                Verdict::Halts(evidence)
+            } else {
+                Verdict::Unknown(crate::verdict::Reason::Inconclusive, evidence)
+            }
+        };
+        let (precondition, evidence) = precondition_from_ast(&ast, &mut judge).unwrap();
+        assert_eq!(precondition.condition, "(n <= 0) || (n >= 2)");
+        assert!(precondition.loops_otherwise);
+        assert_eq!(evidence.sub_verdicts.len(), 3);
+    }
+}
@@ -0,0 +1,1015 @@
+//! halts/src/ranking.rs
+//! Linear ranking functions, the strongest evidence a loop halts.
+//! A ranking function of a loop is a function of its locals which every iteration decreases by at least one,
+//! and which is bounded below wherever the loop goes round again, like `hi - lo` for a binary search,
+//! so the loop can only go round finitely many times.
+//!
+//! Each way through one iteration of a loop, from its header back to its header, is a transition:
+//! linear constraints over the values of the locals at the start of the iteration, the values it leaves them with,
+//! and fresh names for what can't be told, like the result of a call or what a nested loop leaves behind.
+//! A linear function `f` ranks every transition exactly when, by Farkas' lemma, multipliers exist which
+//! derive `f(x) - f(x') >= 1` and a lower bound of `f(x)` from its constraints,
+//! which is a linear program solved exactly with the simplex method, so no external solver is needed.
+//! The constraints are taken over the rationals and whatever isn't linear is dropped,
+//! so a ranking function found is a real one, but one may exist which isn't found.
+//!
+//! Where no single function will do, like a loop over pairs `(i, j)` written as one loop,
+//! a lexicographic tuple is built one component at a time, each decreasing as many of the remaining transitions as it can
+//! without increasing the others. Where that fails too, like `while x > 0 { x += y; y -= 1; }`,
+//! which goes up before it comes down, a multiphase function is tried: `y` decreases until it is below zero,
+//! and from then on so does `x`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::absint::Fixpoint;
+use crate::interval::{intervals_from_body, Intervals};
+use crate::ir::{BinOp, BlockId, Body, Expr, Loop, LoopForest, Statement, Terminator, Ty, UnOp};
+use crate::linear::{atoms_from_condition, Atom, LinearExpr, Relation};
+use crate::rational::{gcd, Rational};
+use crate::simplex::{Comparison, LinearProgram};
+
+/// The most blocks visited while following the ways through one iteration, before giving up
+const MAX_STEPS: usize = 256;
+
+/// The most phases of a multiphase ranking function looked for
+const MAX_PHASES: usize = 3;
+
+/// The most transitions times locals and a constant term a ranking function is looked for over,
+/// past which the linear programs grow too big to solve
+const MAX_SIZE: usize = 256;
+
+/// One way through an iteration of a loop, as linear constraints.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Transition {
+    /// What holds of the locals at the start of the iteration and of the fresh names along the way
+    pub guard: Vec<Atom>,
+    /// The value the iteration leaves each local it changes with, over the same names;
+    /// the locals missing keep their values
+    pub updates: BTreeMap<String, LinearExpr>,
+}
+
+impl Transition {
+    /// The locals the transition constrains or changes, without the fresh names
+    pub fn locals(&self) -> BTreeSet<String> {
+        self.guard
+            .iter()
+            .map(|atom| &atom.expr)
+            .chain(self.updates.values())
+            .flat_map(|expr| expr.terms.keys())
+            .chain(self.updates.keys())
+            .filter(|name| !is_fresh(name))
+            .cloned()
+            .collect()
+    }
+
+    /// The constraints as expressions which are at least zero, over the locals, their primed values and the fresh names
+    fn rows(&self, locals: &[String]) -> Option<Vec<LinearExpr>> {
+        let mut rows = vec![];
+        let mut equalities = vec![];
+        for local in locals {
+            let update = self
+                .updates
+                .get(local)
+                .cloned()
+                .unwrap_or_else(|| LinearExpr::variable(local));
+            equalities.push(LinearExpr::variable(&primed(local)).checked_sub(&update)?);
+        }
+        for atom in &self.guard {
+            match atom.relation {
+                Relation::Ge => rows.push(atom.expr.clone()),
+                Relation::Eq => equalities.push(atom.expr.clone()),
+                // a disequality is no convex constraint, and leaving it out only allows more
+                Relation::Ne => {}
+            }
+        }
+        for equality in equalities {
+            rows.push(equality.checked_scale(-1)?);
+            rows.push(equality);
+        }
+        Some(rows)
+    }
+}
+
+/// How the components of a ranking function show a loop halts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RankingKind {
+    /// A single function decreases at every iteration and is bounded below
+    Linear,
+    /// Every iteration decreases some component, which is bounded below, and increases none before it
+    Lexicographic,
+    /// Every iteration decreases the first component; each other decreases once the one before it is below zero,
+    /// and the last is never below zero
+    Multiphase,
+}
+
+impl fmt::Display for RankingKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RankingKind::Linear => write!(f, "linear"),
+            RankingKind::Lexicographic => write!(f, "lexicographic"),
+            RankingKind::Multiphase => write!(f, "multiphase"),
+        }
+    }
+}
+
+/// Linear functions of the locals of a loop which show it halts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RankingFunction {
+    /// How the components show it
+    pub kind: RankingKind,
+    /// The components, with whole coefficients; only those of a multiphase function have constant terms
+    pub components: Vec<LinearExpr>,
+}
+
+impl RankingFunction {
+    /// The function with each temporary named for what it stands for in the source,
+    /// like `n - next(0..n)` rather than `_2 - _1`, so it can be checked against the code
+    pub fn in_source(&self, body: &Body) -> RankingFunction {
+        let components = self
+            .components
+            .iter()
+            .map(|component| {
+                let mut terms = BTreeMap::new();
+                for (name, coefficient) in &component.terms {
+                    let source = body
+                        .local(name)
+                        .and_then(|local| local.source.clone())
+                        .unwrap_or_else(|| name.clone());
+                    *terms.entry(source).or_insert(0) += coefficient;
+                }
+                terms.retain(|_, coefficient| *coefficient != 0);
+                LinearExpr {
+                    terms,
+                    constant: component.constant,
+                }
+            })
+            .collect();
+        RankingFunction {
+            kind: self.kind,
+            components,
+        }
+    }
+}
+
+/// Write a component with the terms with positive coefficients first, so `hi - lo` rather than `-lo + hi`
+fn write_component(f: &mut fmt::Formatter<'_>, expr: &LinearExpr) -> fmt::Result {
+    let mut terms: Vec<(&String, i128)> = expr
+        .terms
+        .iter()
+        .map(|(name, &coefficient)| (name, coefficient))
+        .collect();
+    terms.sort_by_key(|&(_, coefficient)| coefficient < 0);
+    for (i, (name, coefficient)) in terms.iter().enumerate() {
+        match (i, *coefficient < 0) {
+            (0, true) => write!(f, "-")?,
+            (0, false) => {}
+            (_, true) => write!(f, " - ")?,
+            (_, false) => write!(f, " + ")?,
+        }
+        match coefficient.unsigned_abs() {
+            1 => write!(f, "{}", name)?,
+            magnitude => write!(f, "{}*{}", magnitude, name)?,
+        }
+    }
+    match (terms.is_empty(), expr.constant) {
+        (true, constant) => write!(f, "{}", constant),
+        (false, 0) => Ok(()),
+        (false, constant) if constant < 0 => write!(f, " - {}", constant.unsigned_abs()),
+        (false, constant) => write!(f, " + {}", constant),
+    }
+}
+
+impl fmt::Display for RankingFunction {
+    /// A linear function as it is, like `n - i`, and the components of any other as a tuple, like `(m - i, n - j)`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let [component] = self.components.as_slice() {
+            return write_component(f, component);
+        }
+        write!(f, "(")?;
+        for (i, component) in self.components.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write_component(f, component)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Whether a name was made up for a value which can't be told, rather than naming a local
+fn is_fresh(name: &str) -> bool {
+    name.starts_with('?')
+}
+
+/// The name of the value a local is left with at the end of an iteration
+fn primed(local: &str) -> String {
+    format!("{}'", local)
+}
+
+/// The local a statement sets, if any
+fn written(statement: &Statement) -> Option<&str> {
+    match statement {
+        Statement::Assign { local, .. } | Statement::Havoc(local) => Some(local),
+        Statement::Call { dest, .. } => Some(dest),
+        Statement::Assume(_) => None,
+    }
+}
+
+/// A way being followed through an iteration
+#[derive(Debug, Clone, Default)]
+struct Path {
+    /// What holds so far
+    guard: Vec<Atom>,
+    /// The value of each local changed so far
+    values: BTreeMap<String, LinearExpr>,
+}
+
+impl Path {
+    /// The value of a local at this point of the path
+    fn value(&self, local: &str) -> LinearExpr {
+        self.values
+            .get(local)
+            .cloned()
+            .unwrap_or_else(|| LinearExpr::variable(local))
+    }
+
+    /// Carry on only where a condition has a truth value; what isn't linear is left out
+    fn assume(&mut self, condition: &Expr, holds: bool) {
+        for atom in atoms_from_condition(condition, holds).unwrap_or_default() {
+            if let Some(expr) = atom.expr.substitute(&|name| Some(self.value(name))) {
+                self.guard.push(Atom {
+                    expr,
+                    relation: atom.relation,
+                });
+            }
+        }
+    }
+}
+
+/// Follows the ways through one iteration of a loop
+struct TransitionBuilder<'a> {
+    /// The body the loop is part of
+    body: &'a Body,
+    /// The loop
+    iteration: &'a Loop,
+    /// Every loop of the body, to find those nested in this one
+    forest: LoopForest,
+    /// What interval analysis knows at each block and edge
+    intervals: Fixpoint<Intervals>,
+    /// How many fresh names have been made up
+    fresh: usize,
+    /// What holds on each way out of the loop found so far
+    exits: Vec<Vec<Atom>>,
+}
+
+impl TransitionBuilder<'_> {
+    /// Whether a local holds an unsigned integer, which can't go below zero without panicking
+    fn is_unsigned(&self, local: &str) -> bool {
+        self.body
+            .local(local)
+            .is_some_and(|local| local.ty == Ty::Unsigned)
+    }
+
+    /// Set a local to a value which can't be told
+    fn havoc(&mut self, path: &mut Path, local: &str) {
+        self.fresh += 1;
+        let value = LinearExpr::variable(&format!("?{}", self.fresh));
+        if self.is_unsigned(local) {
+            path.guard.push(Atom::ge_zero(value.clone()));
+        }
+        path.values.insert(local.to_string(), value);
+    }
+
+    /// The values an expression can take along a path, as linear expressions with what must hold for each,
+    /// or `None` if it isn't linear.
+    /// Division by a positive constant rounds towards zero, so it splits on the sign of what is divided.
+    fn linearize(&mut self, path: &Path, expr: &Expr) -> Option<Vec<(LinearExpr, Vec<Atom>)>> {
+        match expr {
+            Expr::Int(int) => Some(vec![(LinearExpr::constant(*int), vec![])]),
+            Expr::Var(name) => Some(vec![(path.value(name), vec![])]),
+            Expr::Unary(UnOp::Neg, inner) => self
+                .linearize(path, inner)?
+                .into_iter()
+                .map(|(value, atoms)| Some((value.checked_scale(-1)?, atoms)))
+                .collect(),
+            Expr::Binary(op @ (BinOp::Add | BinOp::Sub | BinOp::Mul), left, right) => {
+                let left = self.linearize(path, left)?;
+                let right = self.linearize(path, right)?;
+                let mut values = vec![];
+                for (left, left_atoms) in &left {
+                    for (right, right_atoms) in &right {
+                        let value = match op {
+                            BinOp::Add => left.checked_add(right)?,
+                            BinOp::Sub => left.checked_sub(right)?,
+                            _ if left.is_constant() => right.checked_scale(left.constant)?,
+                            _ if right.is_constant() => left.checked_scale(right.constant)?,
+                            _ => return None,
+                        };
+                        values.push((value, [left_atoms.clone(), right_atoms.clone()].concat()));
+                    }
+                }
+                Some(values)
+            }
+            Expr::Binary(BinOp::Div, dividend, divisor) => {
+                let Expr::Int(divisor) = **divisor else {
+                    return None;
+                };
+                if divisor <= 0 {
+                    return None;
+                }
+                let mut values = vec![];
+                for (dividend, atoms) in self.linearize(path, dividend)? {
+                    self.fresh += 1;
+                    let quotient = LinearExpr::variable(&format!("?{}", self.fresh));
+                    let product = quotient.checked_scale(divisor)?;
+                    let slack = LinearExpr::constant(divisor - 1);
+                    // a nonnegative dividend is at most `divisor - 1` above the product
+                    let mut nonnegative = atoms.clone();
+                    nonnegative.push(Atom::ge_zero(dividend.clone()));
+                    nonnegative.push(Atom::ge_zero(dividend.checked_sub(&product)?));
+                    nonnegative.push(Atom::ge_zero(
+                        product.checked_add(&slack)?.checked_sub(&dividend)?,
+                    ));
+                    // and a nonpositive one at most `divisor - 1` below it
+                    let mut nonpositive = atoms;
+                    nonpositive.push(Atom::ge_zero(dividend.checked_scale(-1)?));
+                    nonpositive.push(Atom::ge_zero(product.checked_sub(&dividend)?));
+                    nonpositive.push(Atom::ge_zero(
+                        dividend.checked_add(&slack)?.checked_sub(&product)?,
+                    ));
+                    values.push((quotient.clone(), nonnegative));
+                    values.push((quotient, nonpositive));
+                }
+                Some(values)
+            }
+            _ => None,
+        }
+    }
+
+    /// The paths after a statement
+    fn statement(&mut self, mut path: Path, statement: &Statement) -> Vec<Path> {
+        match statement {
+            Statement::Assign { local, value } => match self.linearize(&path, value) {
+                Some(values) => values
+                    .into_iter()
+                    .map(|(value, atoms)| {
+                        let mut next = path.clone();
+                        next.guard.extend(atoms);
+                        if self.is_unsigned(local) {
+                            // going below zero panics, which leaves the loop
+                            if let Some(below) = Atom::ge_zero(value.clone()).negate() {
+                                let mut panics = next.guard.clone();
+                                panics.push(below);
+                                self.exits.push(panics);
+                            }
+                            next.guard.push(Atom::ge_zero(value.clone()));
+                        }
+                        next.values.insert(local.clone(), value);
+                        next
+                    })
+                    .collect(),
+                None => {
+                    self.havoc(&mut path, local);
+                    vec![path]
+                }
+            },
+            Statement::Call { dest: local, .. } | Statement::Havoc(local) => {
+                self.havoc(&mut path, local);
+                vec![path]
+            }
+            Statement::Assume(condition) => {
+                path.assume(condition, true);
+                vec![path]
+            }
+        }
+    }
+
+    /// Where a path goes from the end of a block, and what holds on the way
+    fn step(&mut self, block: BlockId, path: Path) -> Vec<(BlockId, Path)> {
+        let mut paths = vec![path];
+        for statement in &self.body.blocks[block].statements {
+            paths = paths
+                .into_iter()
+                .flat_map(|path| self.statement(path, statement))
+                .collect();
+        }
+        let mut next = vec![];
+        for path in paths {
+            match &self.body.blocks[block].terminator {
+                Terminator::Goto(target) => next.push((*target, path)),
+                Terminator::Branch {
+                    then, otherwise, ..
+                } if then == otherwise => next.push((*then, path)),
+                Terminator::Branch {
+                    condition,
+                    then,
+                    otherwise,
+                } => {
+                    for (target, holds) in [(*then, true), (*otherwise, false)] {
+                        let mut path = path.clone();
+                        path.assume(condition, holds);
+                        next.push((target, path));
+                    }
+                }
+                Terminator::Return | Terminator::Diverge(_) => self.exits.push(path.guard),
+                Terminator::Unreachable => {}
+            }
+        }
+        next.retain(|(target, _)| self.intervals.is_taken(block, *target));
+        next
+    }
+
+    /// Where a path goes when it enters a nested loop, which is taken to end,
+    /// leaving whatever it sets at values which can't be told
+    fn leave(&mut self, nested: &Loop, mut path: Path) -> Vec<(BlockId, Path)> {
+        let written: BTreeSet<&str> = nested
+            .blocks
+            .iter()
+            .flat_map(|&block| &self.body.blocks[block].statements)
+            .filter_map(written)
+            .collect();
+        for local in written {
+            self.havoc(&mut path, local);
+        }
+        let mut next = vec![];
+        for (from, to) in nested.exits(self.body) {
+            if !self.intervals.is_taken(from, to) {
+                continue;
+            }
+            let mut path = path.clone();
+            if let Terminator::Branch {
+                condition,
+                then,
+                otherwise,
+            } = &self.body.blocks[from].terminator
+            {
+                if then != otherwise {
+                    path.assume(condition, to == *then);
+                }
+            }
+            next.push((to, path));
+        }
+        next
+    }
+
+    /// Every way through an iteration, or `None` if there are too many to follow
+    fn transitions(&mut self) -> Option<Vec<Transition>> {
+        let header = self.iteration.header;
+        let mut start = Path::default();
+        for (local, range) in self.intervals.states[header].bounded() {
+            let local = LinearExpr::variable(local);
+            if let Some(lo) = range.lo {
+                start
+                    .guard
+                    .push(Atom::ge_zero(local.checked_sub(&LinearExpr::constant(lo))?));
+            }
+            if let Some(hi) = range.hi {
+                start
+                    .guard
+                    .push(Atom::ge_zero(LinearExpr::constant(hi).checked_sub(&local)?));
+            }
+        }
+        let mut transitions = vec![];
+        let mut work = vec![(header, start)];
+        let mut steps = 0;
+        while let Some((block, path)) = work.pop() {
+            steps += 1;
+            if steps > MAX_STEPS {
+                return None;
+            }
+            let nested = self
+                .forest
+                .loops
+                .iter()
+                .find(|nested| nested.header == block && block != header)
+                .cloned();
+            let next = match nested {
+                Some(nested) => self.leave(&nested, path),
+                None => self.step(block, path),
+            };
+            for (target, path) in next {
+                if target == header {
+                    transitions.push(Transition {
+                        guard: path.guard,
+                        updates: path.values,
+                    });
+                } else if self.iteration.blocks.contains(&target) {
+                    work.push((target, path));
+                } else {
+                    self.exits.push(path.guard);
+                }
+            }
+        }
+        Some(transitions)
+    }
+}
+
+/// Collect the ways through one iteration of a loop.
+///
+/// # Arguments
+///
+/// * `body` - The control-flow graph the loop is part of.
+/// * `iteration` - The loop.
+///
+/// # Returns
+///
+/// A transition for each way from the header of the loop back to it which interval analysis can't rule out,
+/// with the header's invariant as part of each guard, or `None` if there are too many ways to follow.
+/// Nested loops are taken to end, and calls to return.
+pub fn transitions_from_loop(body: &Body, iteration: &Loop) -> Option<Vec<Transition>> {
+    paths_from_loop(body, iteration).map(|paths| paths.transitions)
+}
+
+/// The ways through one iteration of a loop: those which go round again, and those which leave it.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct LoopPaths {
+    /// The ways back to the header
+    pub transitions: Vec<Transition>,
+    /// What holds of the locals at the start of the iteration, and of the fresh names along the way,
+    /// on each way out of the loop: a `break`, a `return`, a panic, or an unsigned integer going below zero
+    pub exits: Vec<Vec<Atom>>,
+}
+
+/// Collect the ways through one iteration of a loop, and the ways out of it.
+///
+/// # Arguments
+///
+/// * `body` - The control-flow graph the loop is part of.
+/// * `iteration` - The loop.
+///
+/// # Returns
+///
+/// The ways back to the header and the ways out which interval analysis can't rule out,
+/// with the header's invariant as part of each guard, or `None` if there are too many ways to follow.
+/// Nested loops are taken to end, and calls to return.
+pub fn paths_from_loop(body: &Body, iteration: &Loop) -> Option<LoopPaths> {
+    let mut builder = TransitionBuilder {
+        body,
+        iteration,
+        forest: body.loop_forest(),
+        intervals: intervals_from_body(body),
+        fresh: 0,
+        exits: vec![],
+    };
+    let transitions = builder.transitions()?;
+    Some(LoopPaths {
+        transitions,
+        exits: builder.exits,
+    })
+}
+
+/// The least common multiple of two positive integers, or `None` on overflow
+fn lcm(a: i128, b: i128) -> Option<i128> {
+    (a / gcd(a, b)).checked_mul(b)
+}
+
+/// What the conclusion of an implication must be at least
+#[derive(Debug, Clone, Copy)]
+enum Bound {
+    /// A constant
+    Constant(i128),
+    /// A variable of the linear program
+    Variable(usize),
+}
+
+/// A linear program over the coefficients of some linear functions of the locals and the Farkas multipliers
+/// which show what the transitions imply about them.
+/// Each coefficient is free, so it is the difference of two variables at least zero.
+struct Synthesis<'a> {
+    /// The locals the functions range over
+    locals: &'a [String],
+    /// The program
+    program: LinearProgram,
+}
+
+impl<'a> Synthesis<'a> {
+    /// A program over some functions of the locals, with a coefficient for each local and a constant term
+    fn new(locals: &'a [String], functions: usize) -> Self {
+        Synthesis {
+            locals,
+            program: LinearProgram::new(2 * (locals.len() + 1) * functions),
+        }
+    }
+
+    /// The coefficient of a local in a function, or of its constant term for `None`, times a sign
+    fn coefficient(
+        &self,
+        function: usize,
+        local: Option<usize>,
+        sign: i128,
+    ) -> [(usize, Rational); 2] {
+        let index = function * (self.locals.len() + 1) + local.unwrap_or(self.locals.len());
+        [
+            (2 * index, Rational::from_int(sign)),
+            (2 * index + 1, Rational::from_int(-sign)),
+        ]
+    }
+
+    /// A new variable, at least zero
+    fn variable(&mut self) -> usize {
+        self.program.variables += 1;
+        self.program.variables - 1
+    }
+
+    /// Require the constraints of a transition to imply that a sum of functions is at least a bound,
+    /// or at least some constant for `None`.
+    /// Each term is a function, whether it is taken after the transition rather than before, and a sign.
+    fn implies(
+        &mut self,
+        rows: &[LinearExpr],
+        terms: &[(usize, bool, i128)],
+        at_least: Option<Bound>,
+    ) {
+        let mut names: BTreeSet<String> = rows
+            .iter()
+            .flat_map(|row| row.terms.keys().cloned())
+            .collect();
+        names.extend(
+            self.locals
+                .iter()
+                .flat_map(|local| [local.clone(), primed(local)]),
+        );
+        let first = self.program.variables;
+        self.program.variables += rows.len();
+        // by Farkas' lemma, multipliers of the rows must add up to the conclusion, name by name
+        for name in &names {
+            let mut coefficients: Vec<(usize, Rational)> = rows
+                .iter()
+                .enumerate()
+                .filter(|(_, row)| row.coefficient(name) != 0)
+                .map(|(r, row)| (first + r, Rational::from_int(row.coefficient(name))))
+                .collect();
+            for &(function, after, sign) in terms {
+                let local = self
+                    .locals
+                    .iter()
+                    .position(|local| *name == if after { primed(local) } else { local.clone() });
+                if let Some(local) = local {
+                    coefficients.extend(self.coefficient(function, Some(local), -sign));
+                }
+            }
+            self.program
+                .constrain(coefficients, Comparison::Eq, Rational::ZERO);
+        }
+        // and their constants to at most the conclusion's
+        let Some(bound) = at_least else {
+            return;
+        };
+        let mut coefficients: Vec<(usize, Rational)> = rows
+            .iter()
+            .enumerate()
+            .map(|(r, row)| (first + r, Rational::from_int(row.constant)))
+            .collect();
+        for &(function, _, sign) in terms {
+            coefficients.extend(self.coefficient(function, None, -sign));
+        }
+        let rhs = match bound {
+            Bound::Constant(constant) => Rational::from_int(-constant),
+            Bound::Variable(variable) => {
+                coefficients.push((variable, Rational::ONE));
+                Rational::ZERO
+            }
+        };
+        self.program.constrain(coefficients, Comparison::Le, rhs);
+    }
+
+    /// Aim for the smallest coefficients, which make the plainest certificate
+    fn minimize_coefficients(&mut self, functions: usize) {
+        let width = self.locals.len() + 1;
+        self.program.objective = (0..functions)
+            .flat_map(|function| (0..self.locals.len()).map(move |local| function * width + local))
+            .flat_map(|index| [(2 * index, Rational::ONE), (2 * index + 1, Rational::ONE)])
+            .collect();
+    }
+
+    /// The coefficients of a function in a solution, its constant term last
+    fn function(&self, values: &[Rational], function: usize) -> Option<Vec<Rational>> {
+        (0..=self.locals.len())
+            .map(|local| {
+                let local = (local < self.locals.len()).then_some(local);
+                let [(positive, _), (negative, _)] = self.coefficient(function, local, 1);
+                values[positive].checked_sub(&values[negative])
+            })
+            .collect()
+    }
+
+    /// Functions with whole coefficients, scaled together by a positive factor, with or without their constant terms
+    fn integral(&self, functions: &[Vec<Rational>], constants: bool) -> Option<Vec<LinearExpr>> {
+        let mut denominator = 1;
+        for value in functions.iter().flatten() {
+            denominator = lcm(denominator, value.denominator())?;
+        }
+        let mut integral = vec![];
+        for function in functions {
+            let mut expr = LinearExpr::default();
+            for (i, value) in function.iter().enumerate() {
+                let scaled = value
+                    .checked_mul(&Rational::from_int(denominator))?
+                    .numerator();
+                match self.locals.get(i) {
+                    Some(local) if scaled != 0 => {
+                        expr.terms.insert(local.clone(), scaled);
+                    }
+                    None if constants => expr.constant = scaled,
+                    _ => {}
+                }
+            }
+            integral.push(expr);
+        }
+        let divisor = integral
+            .iter()
+            .flat_map(|expr| expr.terms.values().chain([&expr.constant]))
+            .fold(0, |divisor, &coefficient| gcd(divisor, coefficient));
+        if divisor > 1 {
+            for expr in &mut integral {
+                for coefficient in expr.terms.values_mut() {
+                    *coefficient /= divisor;
+                }
+                expr.constant /= divisor;
+            }
+        }
+        Some(integral)
+    }
+}
+
+/// One component of a lexicographic ranking function: a function which no remaining transition increases,
+/// which is bounded below on all of them, and which as many as possible decrease.
+///
+/// # Returns
+///
+/// The function, and whether it decreases each transition, or `None` if it decreases none.
+fn lexicographic_component(
+    locals: &[String],
+    transitions: &[Vec<LinearExpr>],
+) -> Option<(LinearExpr, Vec<bool>)> {
+    // first, find which transitions can be decreased at once, by how much each is decreased up to one
+    let mut synthesis = Synthesis::new(locals, 1);
+    let mut decrease = vec![];
+    for rows in transitions {
+        let by = synthesis.variable();
+        synthesis
+            .program
+            .constrain(vec![(by, Rational::ONE)], Comparison::Le, Rational::ONE);
+        synthesis.implies(
+            rows,
+            &[(0, false, 1), (0, true, -1)],
+            Some(Bound::Variable(by)),
+        );
+        synthesis.implies(rows, &[(0, false, 1)], None);
+        decrease.push(by);
+    }
+    synthesis.program.objective = decrease
+        .iter()
+        .map(|&by| (by, Rational::from_int(-1)))
+        .collect();
+    let values = synthesis.program.minimize()?;
+    let decreased: Vec<bool> = decrease
+        .iter()
+        .map(|&by| values[by].is_positive())
+        .collect();
+    if !decreased.contains(&true) {
+        return None;
+    }
+    // then, the plainest function which decreases those by at least one
+    let mut synthesis = Synthesis::new(locals, 1);
+    for (rows, &decreases) in transitions.iter().zip(&decreased) {
+        let by = Bound::Constant(i128::from(decreases));
+        synthesis.implies(rows, &[(0, false, 1), (0, true, -1)], Some(by));
+        synthesis.implies(rows, &[(0, false, 1)], None);
+    }
+    synthesis.minimize_coefficients(1);
+    let values = synthesis.program.minimize()?;
+    let function = synthesis.function(&values, 0)?;
+    let component = synthesis.integral(&[function], false)?.pop()?;
+    Some((component, decreased))
+}
+
+/// A lexicographic ranking function, found one component at a time,
+/// each taking out the transitions it decreases, or `None` if some transitions can't be decreased
+fn lexicographic(locals: &[String], transitions: &[Vec<LinearExpr>]) -> Option<Vec<LinearExpr>> {
+    let mut remaining = transitions.to_vec();
+    let mut components = vec![];
+    while !remaining.is_empty() {
+        let (component, decreased) = lexicographic_component(locals, &remaining)?;
+        let mut decreased = decreased.into_iter();
+        remaining.retain(|_| !decreased.next().unwrap_or(false));
+        components.push(component);
+    }
+    Some(components)
+}
+
+/// A multiphase ranking function with some number of phases: the first function decreases at every transition,
+/// each other decreases once the one before it is below zero, and the last is never below zero,
+/// or `None` if there is none
+fn multiphase(
+    locals: &[String],
+    transitions: &[Vec<LinearExpr>],
+    phases: usize,
+) -> Option<Vec<LinearExpr>> {
+    let mut synthesis = Synthesis::new(locals, phases);
+    for rows in transitions {
+        synthesis.implies(
+            rows,
+            &[(0, false, 1), (0, true, -1)],
+            Some(Bound::Constant(1)),
+        );
+        for phase in 1..phases {
+            // f(x) - f(x') + g(x) >= 1, for g the function of the phase before
+            synthesis.implies(
+                rows,
+                &[(phase, false, 1), (phase, true, -1), (phase - 1, false, 1)],
+                Some(Bound::Constant(1)),
+            );
+        }
+        synthesis.implies(rows, &[(phases - 1, false, 1)], Some(Bound::Constant(0)));
+    }
+    synthesis.minimize_coefficients(phases);
+    let values = synthesis.program.minimize()?;
+    let functions = (0..phases)
+        .map(|phase| synthesis.function(&values, phase))
+        .collect::<Option<Vec<_>>>()?;
+    synthesis.integral(&functions, true)
+}
+
+/// Find a ranking function which ranks every transition:
+/// a linear one if there is one, else a lexicographic one, else a multiphase one.
+///
+/// # Arguments
+///
+/// * `transitions` - The ways through one iteration of a loop.
+///
+/// # Returns
+///
+/// Functions of the locals with whole coefficients as small as the linear programs found,
+/// which show every run through the transitions is finite, or `None` if none was found,
+/// or if there are too many transitions over too many locals to look.
+pub fn ranking_function_from_transitions(transitions: &[Transition]) -> Option<RankingFunction> {
+    let locals: Vec<String> = transitions
+        .iter()
+        .flat_map(Transition::locals)
+        .collect::<BTreeSet<String>>()
+        .into_iter()
+        .collect();
+    if transitions.len() * (locals.len() + 1) > MAX_SIZE {
+        return None;
+    }
+    let rows = transitions
+        .iter()
+        .map(|transition| transition.rows(&locals))
+        .collect::<Option<Vec<_>>>()?;
+    if let Some(components) = lexicographic(&locals, &rows) {
+        return Some(match components.len() {
+            // no transition at all is ranked by anything
+            0 => RankingFunction {
+                kind: RankingKind::Linear,
+                components: vec![LinearExpr::default()],
+            },
+            1 => RankingFunction {
+                kind: RankingKind::Linear,
+                components,
+            },
+            _ => RankingFunction {
+                kind: RankingKind::Lexicographic,
+                components,
+            },
+        });
+    }
+    (2..=MAX_PHASES).find_map(|phases| {
+        Some(RankingFunction {
+            kind: RankingKind::Multiphase,
+            components: multiphase(&locals, &rows, phases)?,
+        })
+    })
+}
+
+/// Find a ranking function of a loop.
+///
+/// # Arguments
+///
+/// * `body` - The control-flow graph the loop is part of.
+/// * `iteration` - The loop.
+///
+/// # Returns
+///
+/// Linear functions of the locals which show the loop goes round finitely many times, and how,
+/// or `None` if none were found.
+pub fn ranking_function(body: &Body, iteration: &Loop) -> Option<RankingFunction> {
+    ranking_function_from_transitions(&transitions_from_loop(body, iteration)?)
+}
+
+/// tests for ranking functions
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::halts::ast_from_str;
+    use crate::ir::body_from_ast;
+
+    /// The ranking function of each loop of a function, printed, for the tests
+    fn ranking_functions(code: &str) -> Vec<Option<String>> {
+        let body = body_from_ast(&ast_from_str(code).unwrap());
+        body.loop_forest()
+            .loops
+            .iter()
+            .map(|iteration| ranking_function(&body, iteration).map(|f| f.to_string()))
+            .collect()
+    }
+
+    /// Test the transitions of a loop carry its guard, its invariant and its updates
+    #[test]
+    fn test_transitions_from_loop() {
+        let body = body_from_ast(
+            &ast_from_str("fn f(n: u32) { let mut i = 0; while i < n { i += 1; } }").unwrap(),
+        );
+        let iteration = &body.loop_forest().loops[0];
+        let transitions = transitions_from_loop(&body, iteration).unwrap();
+        assert_eq!(transitions.len(), 1);
+        let guard: Vec<String> = transitions[0].guard.iter().map(Atom::to_string).collect();
+        assert!(guard.contains(&"i >= 0".to_string()), "{:?}", guard);
+        assert!(
+            guard.contains(&"-i + n - 1 >= 0".to_string()),
+            "{:?}",
+            guard
+        );
+        assert_eq!(transitions[0].updates["i"].to_string(), "i + 1");
+    }
+
+    /// Test ranking functions are found for loops which count, search and nest
+    #[test]
+    fn test_ranking_function() {
+        assert_eq!(
+            ranking_functions("fn f(n: u32) { let mut i = 0; while i < n { i += 1; } }"),
+            vec![Some("n - i".to_string())]
+        );
+        assert_eq!(
+            ranking_functions("fn f(mut n: i64) { while n > 0 { n -= 3; } }"),
+            vec![Some("n".to_string())]
+        );
+        let search = "fn search(v: &[u32], t: u32) -> usize {
+            let mut lo: usize = 0;
+            let mut hi: usize = v.len();
+            while lo < hi {
+                let mid = lo + (hi - lo) / 2;
+                if v[mid] < t { lo = mid + 1; } else { hi = mid; }
+            }
+            lo
+        }";
+        // over the rationals `hi - lo` may only fall by a half when `hi` moves, so the smallest certificate weighs `hi` double
+        assert_eq!(
+            ranking_functions(search),
+            vec![Some("2*hi - lo".to_string())]
+        );
+        let nested = "fn f(n: u32) { let mut i = 0; while i < n { let mut j = 0; while j < i { j += 1; } i += 1; } }";
+        assert_eq!(
+            ranking_functions(nested),
+            vec![Some("n - i".to_string()), Some("i - j".to_string())]
+        );
+    }
+
+    /// Test tuples are found where no single function will do, and which kind they are
+    #[test]
+    fn test_lexicographic_and_multiphase() {
+        let kinds = |code: &str| {
+            let body = body_from_ast(&ast_from_str(code).unwrap());
+            body.loop_forest()
+                .loops
+                .iter()
+                .map(|iteration| {
+                    ranking_function(&body, iteration).map(|f| format!("{} {}", f.kind, f))
+                })
+                .collect::<Vec<_>>()
+        };
+        let pairs = "fn f(n: u32, m: u32) { let mut i = 0; let mut j = 0; while i < n { if j < m { j += 1; } else { j = 0; i += 1; } } }";
+        assert_eq!(
+            kinds(pairs),
+            vec![Some("lexicographic (n - i, m - j)".to_string())]
+        );
+        assert_eq!(
+            kinds("fn f(mut x: i64, mut y: i64) { while x > 0 { x += y; y -= 1; } }"),
+            vec![Some("multiphase (y + 1, x - 1)".to_string())]
+        );
+        assert_eq!(
+            kinds("fn f(mut x: i64, mut y: i64, mut z: i64) { while x > 0 { x += y; y += z; z -= 1; } }"),
+            vec![Some("multiphase (z + 1, y + 1, x - 1)".to_string())]
+        );
+    }
+
+    /// Test no ranking function is made up for loops which need not end
+    #[test]
+    fn test_no_ranking_function() {
+        assert_eq!(
+            ranking_functions("fn f(mut x: i64) { while x != 0 { x -= 2; } }"),
+            vec![None]
+        );
+        assert_eq!(
+            ranking_functions(
+                "fn f(mut n: u32) { let mut i = 0; while i < n { i += 1; n += 1; } }"
+            ),
+            vec![None]
+        );
+        assert_eq!(
+            ranking_functions("fn f(done: bool) { let mut i = 0; while !done { i += 1; } }"),
+            vec![None]
+        );
+    }
+}
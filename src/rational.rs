@@ -0,0 +1,157 @@
+//! halts/src/rational.rs
+//! Exact rational numbers, for solving linear programs without rounding.
+//! A certificate of termination is only as good as the arithmetic which found it,
+//! so every operation is checked and gives `None` rather than a wrong answer on overflow.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+/// A fraction in lowest terms with a positive denominator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rational {
+    /// The numerator, which carries the sign
+    num: i128,
+    /// The denominator, always positive
+    den: i128,
+}
+
+/// The greatest common divisor of two integers, which is never negative
+pub fn gcd(a: i128, b: i128) -> i128 {
+    let (mut a, mut b) = (a.unsigned_abs(), b.unsigned_abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    i128::try_from(a).unwrap_or(i128::MAX)
+}
+
+impl Rational {
+    /// Zero
+    pub const ZERO: Rational = Rational { num: 0, den: 1 };
+
+    /// One
+    pub const ONE: Rational = Rational { num: 1, den: 1 };
+
+    /// The fraction `num / den` in lowest terms, or `None` if `den` is zero or it overflows
+    pub fn new(num: i128, den: i128) -> Option<Self> {
+        if den == 0 {
+            return None;
+        }
+        let divisor = gcd(num, den).max(1);
+        let (num, den) = (num / divisor, den / divisor);
+        if den < 0 {
+            Some(Rational {
+                num: num.checked_neg()?,
+                den: den.checked_neg()?,
+            })
+        } else {
+            Some(Rational { num, den })
+        }
+    }
+
+    /// An integer as a fraction
+    pub fn from_int(int: i128) -> Self {
+        Rational { num: int, den: 1 }
+    }
+
+    /// The numerator in lowest terms
+    pub fn numerator(&self) -> i128 {
+        self.num
+    }
+
+    /// The denominator in lowest terms, which is positive
+    pub fn denominator(&self) -> i128 {
+        self.den
+    }
+
+    /// Whether the number is zero
+    pub fn is_zero(&self) -> bool {
+        self.num == 0
+    }
+
+    /// Whether the number is greater than zero
+    pub fn is_positive(&self) -> bool {
+        self.num > 0
+    }
+
+    /// Whether the number is less than zero
+    pub fn is_negative(&self) -> bool {
+        self.num < 0
+    }
+
+    /// `-self`, or `None` on overflow
+    pub fn checked_neg(&self) -> Option<Self> {
+        Some(Rational {
+            num: self.num.checked_neg()?,
+            den: self.den,
+        })
+    }
+
+    /// `self + other`, or `None` on overflow
+    pub fn checked_add(&self, other: &Rational) -> Option<Self> {
+        let den = self.den.checked_mul(other.den)?;
+        let num = self
+            .num
+            .checked_mul(other.den)?
+            .checked_add(other.num.checked_mul(self.den)?)?;
+        Rational::new(num, den)
+    }
+
+    /// `self - other`, or `None` on overflow
+    pub fn checked_sub(&self, other: &Rational) -> Option<Self> {
+        self.checked_add(&other.checked_neg()?)
+    }
+
+    /// `self * other`, or `None` on overflow
+    pub fn checked_mul(&self, other: &Rational) -> Option<Self> {
+        // cancelling across first keeps the products small
+        let a = gcd(self.num, other.den).max(1);
+        let b = gcd(other.num, self.den).max(1);
+        Rational::new(
+            (self.num / a).checked_mul(other.num / b)?,
+            (self.den / b).checked_mul(other.den / a)?,
+        )
+    }
+
+    /// `self / other`, or `None` if `other` is zero or on overflow
+    pub fn checked_div(&self, other: &Rational) -> Option<Self> {
+        self.checked_mul(&Rational::new(other.den, other.num)?)
+    }
+
+    /// How `self` compares with `other`, or `None` on overflow
+    pub fn checked_cmp(&self, other: &Rational) -> Option<Ordering> {
+        Some(self.checked_sub(other)?.num.cmp(&0))
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.den == 1 {
+            write!(f, "{}", self.num)
+        } else {
+            write!(f, "{}/{}", self.num, self.den)
+        }
+    }
+}
+
+/// tests for exact rational arithmetic
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Test fractions are kept in lowest terms with a positive denominator
+    #[test]
+    fn test_rational() {
+        let half = Rational::new(2, -4).unwrap();
+        assert_eq!(half.to_string(), "-1/2");
+        assert_eq!((half.numerator(), half.denominator()), (-1, 2));
+        let third = Rational::new(1, 3).unwrap();
+        assert_eq!(half.checked_add(&third).unwrap().to_string(), "-1/6");
+        assert_eq!(half.checked_mul(&third).unwrap().to_string(), "-1/6");
+        assert_eq!(third.checked_div(&half).unwrap().to_string(), "-2/3");
+        assert_eq!(half.checked_cmp(&third), Some(Ordering::Less));
+        assert!(Rational::new(1, 0).is_none());
+        assert!(Rational::from_int(i128::MAX)
+            .checked_add(&Rational::ONE)
+            .is_none());
+    }
+}
@@ -0,0 +1,546 @@
+//! halts/src/recurrent.rs
+//! Proofs that a loop runs forever, by recurrent sets.
+//! A recurrent set of a loop is a set of states at its header which no way out of the loop leaves from,
+//! and which every way round the loop maps back into, so a run which gets into it stays in the loop for good.
+//! The sets tried are conjunctions of linear constraints, taken from the negations of the conditions which leave the loop
+//! and from the conditions along the ways round it, and each is checked with the simplex method:
+//! a constraint holds again after an iteration because the opposite is infeasible.
+//! That a run gets into the set is shown by running the function up to the loop on concrete values,
+//! so the proof is a lasso: a stem from the entry of the function, then a cycle which never ends.
+//! Where the run needs values for the parameters, they are found by symbolic execution up to the loop,
+//! solving the condition of a path there together with the set, and the run on them confirms it.
+//! Whatever the constraints leave out only allows more ways out and round, so a set found is a real one.
+//! Something which may panic without the control-flow graph saying so, like a call, indexing or division by a variable,
+//! leaves the loop in a way the constraints can't see, so a loop which does such things gets no proof.
+//! Integers are mathematical integers, except that an unsigned one going below zero panics.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::ir::{BinOp, BlockId, Body, Expr, Loop, Statement, Terminator, Ty, UnOp, ENTRY};
+use crate::linear::{Atom, LinearExpr, Relation};
+use crate::ranking::{paths_from_loop, LoopPaths};
+use crate::rational::Rational;
+use crate::simplex::{Comparison, LinearProgram};
+use crate::symbolic::paths_to_block;
+use crate::verdict::Lasso;
+
+/// The most constraints tried as parts of a recurrent set
+const MAX_CANDIDATES: usize = 16;
+
+/// The most constraints in a recurrent set
+const MAX_CONSTRAINTS: usize = 2;
+
+/// The most blocks the stem runs through before giving up
+const MAX_STEPS: usize = 10_000;
+
+/// The most times symbolic execution goes round to the head of a loop looking for inputs which get into a recurrent set
+const MAX_VISITS: usize = 4;
+
+/// The most inputs found by symbolic execution which are each run to a loop
+const MAX_INPUTS: usize = 16;
+
+/// A concrete value of a local
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Value {
+    /// An integer
+    Int(i128),
+    /// A boolean
+    Bool(bool),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(int) => write!(f, "{}", int),
+            Value::Bool(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+/// Whether evaluating an expression may panic where the control-flow graph doesn't say so
+fn may_panic(expr: &Expr) -> bool {
+    match expr {
+        Expr::Int(_) | Expr::Bool(_) | Expr::Var(_) => false,
+        Expr::Opaque(_) => true,
+        Expr::Part(_, part) if part.starts_with('[') => true,
+        Expr::Part(inner, _) | Expr::Unary(_, inner) => may_panic(inner),
+        Expr::Binary(op, left, right) => {
+            let constant = match (op, &**right) {
+                (BinOp::Div | BinOp::Rem, Expr::Int(divisor)) => *divisor != 0,
+                (BinOp::Shl | BinOp::Shr, Expr::Int(shift)) => (0..64).contains(shift),
+                (BinOp::Div | BinOp::Rem | BinOp::Shl | BinOp::Shr, _) => false,
+                _ => true,
+            };
+            !constant || may_panic(left) || may_panic(right)
+        }
+    }
+}
+
+/// Whether something in a loop may leave it by a panic which the control-flow graph doesn't show
+fn has_hidden_exits(body: &Body, iteration: &Loop) -> bool {
+    iteration.blocks.iter().any(|&block| {
+        let block = &body.blocks[block];
+        let statements = block.statements.iter().any(|statement| match statement {
+            Statement::Assign { value, .. } => may_panic(value),
+            Statement::Assume(condition) => may_panic(condition),
+            Statement::Call { .. } => true,
+            Statement::Havoc(_) => false,
+        });
+        let terminator = match &block.terminator {
+            Terminator::Branch { condition, .. } => may_panic(condition),
+            _ => false,
+        };
+        statements || terminator
+    })
+}
+
+/// Whether no rational values meet every constraint, leaving out disequalities;
+/// `false` if that can't be told for overflow
+fn is_infeasible(atoms: &[&Atom]) -> bool {
+    let mut names: Vec<&String> = atoms
+        .iter()
+        .flat_map(|atom| atom.expr.terms.keys())
+        .collect();
+    names.sort();
+    names.dedup();
+    // each variable is free, the difference of two which are at least zero
+    let mut program = LinearProgram::new(2 * names.len());
+    for atom in atoms {
+        let comparison = match atom.relation {
+            Relation::Ge => Comparison::Ge,
+            Relation::Eq => Comparison::Eq,
+            Relation::Ne => continue,
+        };
+        let mut coefficients = vec![];
+        for (name, &coefficient) in &atom.expr.terms {
+            let Ok(index) = names.binary_search(&name) else {
+                continue;
+            };
+            coefficients.push((2 * index, Rational::from_int(coefficient)));
+            coefficients.push((2 * index + 1, Rational::from_int(-coefficient)));
+        }
+        program.constrain(
+            coefficients,
+            comparison,
+            Rational::from_int(-atom.expr.constant),
+        );
+    }
+    program.is_feasible() == Some(false)
+}
+
+/// The constraints which together say a constraint doesn't hold, one way or another, over the integers
+fn negations(atom: &Atom) -> Vec<Atom> {
+    match atom.relation {
+        Relation::Ge | Relation::Ne => atom.negate().into_iter().collect(),
+        Relation::Eq => [1, -1]
+            .iter()
+            .filter_map(|&sign| {
+                let above = atom.expr.checked_scale(sign)?;
+                Some(Atom::ge_zero(above.checked_sub(&LinearExpr::constant(1))?))
+            })
+            .collect(),
+    }
+}
+
+/// Whether a constraint is over the locals alone, without fresh names for what can't be told
+fn is_over_locals(atom: &Atom) -> bool {
+    atom.expr.terms.keys().all(|name| !name.starts_with('?'))
+}
+
+/// The constraints tried as parts of a recurrent set:
+/// what keeps each way out shut, and what holds along the ways round
+fn candidates(paths: &LoopPaths) -> Vec<Atom> {
+    let shut = paths
+        .exits
+        .iter()
+        .flatten()
+        .filter(|atom| is_over_locals(atom))
+        .flat_map(negations);
+    let along = paths
+        .transitions
+        .iter()
+        .flat_map(|transition| &transition.guard)
+        .filter(|atom| is_over_locals(atom) && atom.relation != Relation::Ne)
+        .cloned();
+    let mut candidates: Vec<Atom> = vec![];
+    for atom in shut.chain(along) {
+        if !candidates.contains(&atom) && candidates.len() < MAX_CANDIDATES {
+            candidates.push(atom);
+        }
+    }
+    candidates
+}
+
+/// Whether no way out of the loop leaves from a set, and every way round maps it back into itself
+fn is_recurrent(set: &[Atom], paths: &LoopPaths) -> bool {
+    let shut = paths.exits.iter().all(|exit| {
+        let atoms: Vec<&Atom> = set.iter().chain(exit).collect();
+        is_infeasible(&atoms)
+    });
+    shut && paths.transitions.iter().all(|transition| {
+        set.iter().all(|atom| {
+            let Some(after) = atom.expr.substitute(&|name| {
+                Some(
+                    transition
+                        .updates
+                        .get(name)
+                        .cloned()
+                        .unwrap_or_else(|| LinearExpr::variable(name)),
+                )
+            }) else {
+                return false;
+            };
+            let after = Atom {
+                expr: after,
+                relation: atom.relation,
+            };
+            negations(&after).iter().all(|broken| {
+                let atoms: Vec<&Atom> = set
+                    .iter()
+                    .chain(&transition.guard)
+                    .chain([broken])
+                    .collect();
+                is_infeasible(&atoms)
+            })
+        })
+    })
+}
+
+/// The recurrent sets among the conjunctions of a few candidate constraints, smallest first
+fn recurrent_sets(paths: &LoopPaths) -> Vec<Vec<Atom>> {
+    let candidates = candidates(paths);
+    let mut sets: Vec<Vec<Atom>> = vec![vec![]];
+    let mut frontier: Vec<(usize, Vec<Atom>)> = vec![(0, vec![])];
+    for _ in 0..MAX_CONSTRAINTS {
+        let mut next = vec![];
+        for (start, set) in &frontier {
+            for (i, candidate) in candidates.iter().enumerate().skip(*start) {
+                let mut larger = set.clone();
+                larger.push(candidate.clone());
+                next.push((i + 1, larger));
+            }
+        }
+        sets.extend(next.iter().map(|(_, set)| set.clone()));
+        frontier = next;
+    }
+    sets.into_iter()
+        .filter(|set| is_recurrent(set, paths))
+        .collect()
+}
+
+/// The value of an expression, given the values of the locals, or `None` if it can't be told or panics
+fn eval(expr: &Expr, env: &BTreeMap<String, Value>) -> Option<Value> {
+    Some(match expr {
+        Expr::Int(int) => Value::Int(*int),
+        Expr::Bool(b) => Value::Bool(*b),
+        Expr::Var(name) => *env.get(name)?,
+        Expr::Unary(UnOp::Neg, inner) => match eval(inner, env)? {
+            Value::Int(int) => Value::Int(int.checked_neg()?),
+            Value::Bool(_) => return None,
+        },
+        Expr::Unary(UnOp::Not, inner) => match eval(inner, env)? {
+            Value::Int(int) => Value::Int(!int),
+            Value::Bool(b) => Value::Bool(!b),
+        },
+        Expr::Binary(op, left, right) => match (eval(left, env)?, eval(right, env)?) {
+            (Value::Int(a), Value::Int(b)) => match op {
+                BinOp::Add => Value::Int(a.checked_add(b)?),
+                BinOp::Sub => Value::Int(a.checked_sub(b)?),
+                BinOp::Mul => Value::Int(a.checked_mul(b)?),
+                BinOp::Div => Value::Int(a.checked_div(b)?),
+                BinOp::Rem => Value::Int(a.checked_rem(b)?),
+                BinOp::Shl => Value::Int(a.checked_shl(u32::try_from(b).ok()?)?),
+                BinOp::Shr => Value::Int(a.checked_shr(u32::try_from(b).ok()?)?),
+                BinOp::BitAnd => Value::Int(a & b),
+                BinOp::BitOr => Value::Int(a | b),
+                BinOp::BitXor => Value::Int(a ^ b),
+                BinOp::Eq => Value::Bool(a == b),
+                BinOp::Ne => Value::Bool(a != b),
+                BinOp::Lt => Value::Bool(a < b),
+                BinOp::Le => Value::Bool(a <= b),
+                BinOp::Gt => Value::Bool(a > b),
+                BinOp::Ge => Value::Bool(a >= b),
+                BinOp::And | BinOp::Or => return None,
+            },
+            (Value::Bool(a), Value::Bool(b)) => match op {
+                BinOp::And | BinOp::BitAnd => Value::Bool(a && b),
+                BinOp::Or | BinOp::BitOr => Value::Bool(a || b),
+                BinOp::BitXor | BinOp::Ne => Value::Bool(a != b),
+                BinOp::Eq => Value::Bool(a == b),
+                _ => return None,
+            },
+            _ => return None,
+        },
+        Expr::Part(..) | Expr::Opaque(_) => return None,
+    })
+}
+
+/// Run a body from its entry on concrete values until it reaches the head of a loop in one of some sets.
+///
+/// # Arguments
+///
+/// * `body` - The control-flow graph of the function.
+/// * `header` - The head of the loop.
+/// * `sets` - The recurrent sets of the loop.
+/// * `input` - The values of the parameters, if any.
+///
+/// # Returns
+///
+/// The blocks run through, the values of the locals at the end, and the index of the set reached,
+/// or `None` if the run needs a value which can't be told, like a parameter without one or the result of a call,
+/// or stops, or doesn't get there in time.
+fn stem(
+    body: &Body,
+    header: BlockId,
+    sets: &[Vec<Atom>],
+    input: BTreeMap<String, Value>,
+) -> Option<(Vec<BlockId>, BTreeMap<String, Value>, usize)> {
+    let mut env = input;
+    let mut blocks = vec![];
+    let mut block = ENTRY;
+    for _ in 0..MAX_STEPS {
+        blocks.push(block);
+        if block == header {
+            let value_of = |name: &str| match env.get(name) {
+                Some(Value::Int(int)) => Some(*int),
+                _ => None,
+            };
+            if let Some(index) = sets
+                .iter()
+                .position(|set| set.iter().all(|atom| atom.holds(&value_of) == Some(true)))
+            {
+                return Some((blocks, env, index));
+            }
+        }
+        for statement in &body.blocks[block].statements {
+            match statement {
+                Statement::Assign { local, value } => {
+                    let value = eval(value, &env)?;
+                    let unsigned = body
+                        .local(local)
+                        .is_some_and(|local| local.ty == Ty::Unsigned);
+                    // an unsigned integer going below zero panics
+                    if unsigned && matches!(value, Value::Int(int) if int < 0) {
+                        return None;
+                    }
+                    env.insert(local.clone(), value);
+                }
+                Statement::Assume(condition) => {
+                    if eval(condition, &env)? != Value::Bool(true) {
+                        return None;
+                    }
+                }
+                Statement::Call { .. } | Statement::Havoc(_) => return None,
+            }
+        }
+        block = match &body.blocks[block].terminator {
+            Terminator::Goto(target) => *target,
+            Terminator::Branch {
+                condition,
+                then,
+                otherwise,
+            } => match eval(condition, &env)? {
+                Value::Bool(true) => *then,
+                Value::Bool(false) => *otherwise,
+                Value::Int(_) => return None,
+            },
+            Terminator::Return | Terminator::Diverge(_) | Terminator::Unreachable => return None,
+        };
+    }
+    None
+}
+
+/// Values for the parameters which symbolic execution finds get to the head of a loop in one of some sets
+fn inputs_from_loop(
+    body: &Body,
+    header: BlockId,
+    sets: &[Vec<Atom>],
+) -> Vec<BTreeMap<String, Value>> {
+    if body.params.is_empty() {
+        return vec![];
+    }
+    let exploration = paths_to_block(body, header, MAX_VISITS);
+    let mut inputs = vec![];
+    for path in &exploration.arrivals {
+        for set in sets {
+            if let Some(model) = path.witness(set) {
+                let input = model
+                    .into_iter()
+                    .map(|(name, int)| (name, Value::Int(int)))
+                    .collect();
+                if !inputs.contains(&input) {
+                    inputs.push(input);
+                }
+                if inputs.len() >= MAX_INPUTS {
+                    return inputs;
+                }
+            }
+        }
+    }
+    inputs
+}
+
+/// A constraint in words, like `x <= -1` for `-x - 1 >= 0`
+fn describe(atom: &Atom) -> String {
+    let mut left = atom.expr.clone();
+    let mut right = -left.constant;
+    left.constant = 0;
+    let mut relation = match atom.relation {
+        Relation::Ge => ">=",
+        Relation::Eq => "==",
+        Relation::Ne => "!=",
+    };
+    if left.terms.values().all(|&coefficient| coefficient < 0) {
+        if let Some(negated) = left.checked_scale(-1) {
+            left = negated;
+            right = -right;
+            if relation == ">=" {
+                relation = "<=";
+            }
+        }
+    }
+    format!("{} {} {}", left, relation, right)
+}
+
+/// Whether a local is a temporary the lowering made up, rather than one named in the source
+fn is_temporary(local: &str) -> bool {
+    local == "return"
+        || local
+            .strip_prefix('_')
+            .is_some_and(|rest| !rest.is_empty() && rest.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Prove a loop runs forever by a lasso: a run from the entry of its function into a recurrent set.
+///
+/// # Arguments
+///
+/// * `body` - The control-flow graph of the function, which is run from its entry.
+/// * `iteration` - The loop.
+///
+/// # Returns
+///
+/// The stem, the values it reaches the loop with, parameters included, and the recurrent set it reaches,
+/// or `None` if no recurrent set was found which a run from the entry gets into.
+pub fn lasso_from_loop(body: &Body, iteration: &Loop) -> Option<Lasso> {
+    if has_hidden_exits(body, iteration) {
+        return None;
+    }
+    let paths = paths_from_loop(body, iteration)?;
+    let sets = recurrent_sets(&paths);
+    if sets.is_empty() {
+        return None;
+    }
+    let (input, (blocks, env, index)) = stem(body, iteration.header, &sets, BTreeMap::new())
+        .map(|stem| (BTreeMap::new(), stem))
+        .or_else(|| {
+            inputs_from_loop(body, iteration.header, &sets)
+                .into_iter()
+                .find_map(|input| {
+                    Some((input.clone(), stem(body, iteration.header, &sets, input)?))
+                })
+        })?;
+    Some(Lasso {
+        stem: blocks,
+        input: input
+            .iter()
+            .map(|(param, value)| (param.clone(), value.to_string()))
+            .collect(),
+        state: env
+            .iter()
+            .filter(|(local, _)| !is_temporary(local))
+            .map(|(local, value)| (local.clone(), value.to_string()))
+            .collect(),
+        recurrent_set: sets[index].iter().map(describe).collect(),
+    })
+}
+
+/// tests for recurrent sets and lassos
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::halts::ast_from_str;
+    use crate::ir::body_from_ast;
+
+    /// The lasso of the first loop of a function, if one is found
+    fn lasso_of(code: &str) -> Option<Lasso> {
+        let body = body_from_ast(&ast_from_str(code).unwrap());
+        let iteration = body.loop_forest().loops[0].clone();
+        lasso_from_loop(&body, &iteration)
+    }
+
+    /// Test a loop which steps over its exit is caught in the states below it
+    #[test]
+    fn test_step_over_exit() {
+        let lasso = lasso_of("fn f() { let mut x = 7; while x != 0 { x -= 2; } }").unwrap();
+        assert_eq!(lasso.recurrent_set, vec!["x <= -1"]);
+        assert_eq!(lasso.state, vec![("x".to_string(), "-1".to_string())]);
+        // four times round the loop before `x` is below zero
+        assert_eq!(
+            lasso
+                .stem
+                .iter()
+                .filter(|&&block| block == lasso.stem[1])
+                .count(),
+            5
+        );
+        assert_eq!(
+            lasso.to_string(),
+            format!(
+                "stem {} reaches the loop with x = -1, then the cycle keeps x <= -1 forever",
+                lasso
+                    .stem
+                    .iter()
+                    .map(|block| format!("bb{}", block))
+                    .collect::<Vec<String>>()
+                    .join(" → ")
+            )
+        );
+    }
+
+    /// Test recurrent sets of two constraints, and of loops which grow away from their exit
+    #[test]
+    fn test_recurrent_sets() {
+        let lasso = lasso_of("fn f() { let mut x = 1; let mut y = 2; while x > 0 { if y <= x { break; } x += 1; y += 2; } }").unwrap();
+        assert_eq!(lasso.recurrent_set, vec!["-x + y >= 1"]);
+        let lasso = lasso_of(
+            "fn f() { let mut i: u64 = 10; while i >= 10 { i += 1; if i == 5 { break; } } }",
+        )
+        .unwrap();
+        // what is known at the head of the loop already shuts every way out
+        assert!(lasso.recurrent_set.is_empty());
+    }
+
+    /// Test the parameters a stem needs are found by symbolic execution
+    #[test]
+    fn test_symbolic_stem() {
+        let lasso = lasso_of("fn f(n: i64) { let mut x = n; while x != 0 { x -= 2; } }").unwrap();
+        assert_eq!(lasso.recurrent_set, vec!["x <= -1"]);
+        assert!(lasso.state.contains(&("x".to_string(), "-1".to_string())));
+        assert!(lasso.state.contains(&("n".to_string(), "-1".to_string())));
+        assert_eq!(lasso.input, vec![("n".to_string(), "-1".to_string())]);
+        // a way into the loop which only a few inputs take
+        let lasso = lasso_of(
+            "fn f(a: i64, b: i64) { let mut x = a - b; if a + b == 10 { while x != 0 { x -= 2; } } }",
+        )
+        .unwrap();
+        assert_eq!(lasso.recurrent_set, vec!["x <= -1"]);
+    }
+
+    /// Test no lasso is claimed for loops which end, may panic, or depend on what can't be told
+    #[test]
+    fn test_no_lasso() {
+        for code in [
+            // ends
+            "fn f() { let mut x = 8; while x != 0 { x -= 2; } }",
+            // an unsigned integer going below zero panics
+            "fn f() { let mut x: u32 = 7; while x != 0 { x -= 2; } }",
+            // indexing may panic
+            "fn f() { let v = [1]; let mut x = 1; while x > 0 { x += v[0]; } }",
+            // the stem depends on the result of a call
+            "fn f() { let mut x = g(); while x != 0 { x -= 2; } }",
+        ] {
+            assert_eq!(lasso_of(code), None, "{}", code);
+        }
+    }
+}
@@ -0,0 +1,256 @@
+//! halts/src/report.rs
+//! A decision about one function, for a person or for a script.
+//! As text, the evidence is a chain: the verdict, the spans and notes it was reached by, and the verdicts it was built from,
+//! each indented under the one it supports. As JSON, the same tree, with the code the analyzer saw after normalization.
+//! JSON is written by hand, which is little enough for strings, numbers, arrays and objects.
+
+use std::fmt;
+
+use ast_from_path::resolved_from_path;
+
+use crate::call_graph::Context;
+use crate::halts::{decision_from_ast_in, string_from_ast};
+use crate::normalize::normalized_from_ast;
+use crate::scan::context_from_file;
+use crate::verdict::{Decision, Evidence, Lasso, SourceSpan, Verdict};
+
+/// What was decided about one function, and what the analyzer saw of it.
+#[derive(Debug, Clone)]
+pub struct Report {
+    /// The function, as `file_path::function_path`
+    pub query: String,
+    /// The decision about it
+    pub decision: Decision,
+    /// The function after normalization, as Rust
+    pub normalized: String,
+}
+
+impl Report {
+    /// The report as a JSON object with the function, its decision, the evidence tree and the normalized code
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"function\":{},\"engine\":{},\"rounds\":{},\"verdict\":{},\"normalized\":{}}}",
+            json_from_str(&self.query),
+            json_from_str(&self.decision.engine.to_string()),
+            self.decision.rounds,
+            json_from_verdict(&self.decision.verdict),
+            json_from_str(&self.normalized)
+        )
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.query)?;
+        writeln!(f, "{}", self.decision)?;
+        writeln!(f, "\nevidence:")?;
+        write!(f, "{}", chain_from_verdict(&self.decision.verdict))?;
+        writeln!(f, "\nnormalized:")?;
+        writeln!(f, "{}", self.normalized)
+    }
+}
+
+/// Decide the function at a path like `src/foo.rs::module::func`, relative to the crate root.
+///
+/// # Arguments
+///
+/// * `query` - The file the function is in, then the path of the function within the file.
+///
+/// # Returns
+///
+/// The report of the decision, in the context of the file the function is written in,
+/// which is another than the queried one when the path goes through an out-of-line module.
+///
+/// # Errors
+///
+/// If the query is malformed, or a file can't be read or parsed, or there is no such function.
+pub fn report_from_query(query: &str) -> syn::Result<Report> {
+    let resolved = resolved_from_path(query).map_err(|e| error(e.to_string()))?;
+    let ast = resolved.callable.to_item_fn();
+    let context = context_from_file(&resolved.path, &resolved.location)
+        .unwrap_or_else(|| Context::from_file(&resolved.file).at(&resolved.location));
+    Ok(Report {
+        query: query.to_string(),
+        decision: decision_from_ast_in(&ast, &context),
+        normalized: string_from_ast(&normalized_from_ast(&ast)),
+    })
+}
+
+/// The evidence behind a verdict as an indented chain, one line per verdict, span, note and lasso
+pub fn chain_from_verdict(verdict: &Verdict) -> String {
+    let mut chain = String::new();
+    add_to_chain(verdict, 0, &mut chain);
+    chain
+}
+
+/// Add a verdict to a chain, at some depth, then the verdicts it was built from one deeper
+fn add_to_chain(verdict: &Verdict, depth: usize, chain: &mut String) {
+    let indent = "  ".repeat(depth);
+    let evidence = verdict.evidence();
+    chain.push_str(&format!("{}{}\n", indent, verdict));
+    for span in &evidence.spans {
+        chain.push_str(&format!("{}  at {}\n", indent, span));
+    }
+    for note in &evidence.notes {
+        chain.push_str(&format!("{}  - {}\n", indent, note));
+    }
+    if let Some(lasso) = &evidence.lasso {
+        chain.push_str(&format!("{}  - lasso: {}\n", indent, lasso));
+    }
+    for sub_verdict in &evidence.sub_verdicts {
+        add_to_chain(sub_verdict, depth + 1, chain);
+    }
+}
+
+/// A verdict as a JSON object, with its category, what it says in words, and its evidence
+fn json_from_verdict(verdict: &Verdict) -> String {
+    format!(
+        "{{\"category\":{},\"summary\":{},\"evidence\":{}}}",
+        json_from_str(verdict.category()),
+        json_from_str(&verdict.to_string()),
+        json_from_evidence(verdict.evidence())
+    )
+}
+
+/// Evidence as a JSON object, the verdicts it was built from included
+fn json_from_evidence(evidence: &Evidence) -> String {
+    let spans: Vec<String> = evidence.spans.iter().map(json_from_span).collect();
+    let notes: Vec<String> = evidence.notes.iter().map(|n| json_from_str(n)).collect();
+    let sub_verdicts: Vec<String> = evidence
+        .sub_verdicts
+        .iter()
+        .map(json_from_verdict)
+        .collect();
+    let lasso = match &evidence.lasso {
+        Some(lasso) => json_from_lasso(lasso),
+        None => "null".to_string(),
+    };
+    format!(
+        "{{\"rule\":{},\"spans\":[{}],\"notes\":[{}],\"lasso\":{},\"sub_verdicts\":[{}]}}",
+        json_from_str(&evidence.rule.to_string()),
+        spans.join(","),
+        notes.join(","),
+        lasso,
+        sub_verdicts.join(",")
+    )
+}
+
+/// A span as a JSON object of where it starts and ends
+fn json_from_span(span: &SourceSpan) -> String {
+    format!(
+        "{{\"start\":{{\"line\":{},\"column\":{}}},\"end\":{{\"line\":{},\"column\":{}}}}}",
+        span.start.line, span.start.column, span.end.line, span.end.column
+    )
+}
+
+/// A lasso as a JSON object, its values as objects from names to values in words
+fn json_from_lasso(lasso: &Lasso) -> String {
+    let values = |pairs: &[(String, String)]| {
+        let members: Vec<String> = pairs
+            .iter()
+            .map(|(name, value)| format!("{}:{}", json_from_str(name), json_from_str(value)))
+            .collect();
+        format!("{{{}}}", members.join(","))
+    };
+    let stem: Vec<String> = lasso.stem.iter().map(usize::to_string).collect();
+    let recurrent_set: Vec<String> = lasso
+        .recurrent_set
+        .iter()
+        .map(|c| json_from_str(c))
+        .collect();
+    format!(
+        "{{\"stem\":[{}],\"input\":{},\"state\":{},\"recurrent_set\":[{}]}}",
+        stem.join(","),
+        values(&lasso.input),
+        values(&lasso.state),
+        recurrent_set.join(",")
+    )
+}
+
+/// A string as a JSON string, quoted and escaped
+fn json_from_str(s: &str) -> String {
+    let mut json = String::with_capacity(s.len() + 2);
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c < ' ' => json.push_str(&format!("\\u{:04x}", u32::from(c))),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// A `syn::Error` for a function which could not be found
+fn error(message: String) -> syn::Error {
+    syn::Error::new(proc_macro2::Span::call_site(), message)
+}
+
+/// tests for reports
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::verdict::Rule;
+
+    /// Test the chain indents each verdict under the one it supports
+    #[test]
+    fn test_chain_from_verdict() {
+        let mut inner = Evidence::new(Rule::RankingFunction);
+        inner
+            .notes
+            .push("linear ranking function n - i".to_string());
+        let mut outer = Evidence::new(Rule::RankingFunction);
+        outer.sub_verdicts.push(Verdict::Halts(inner));
+        let chain = chain_from_verdict(&Verdict::Halts(outer));
+        assert_eq!(
+            chain,
+            "HALT by ranking-function\n  HALT by ranking-function\n    - linear ranking function n - i\n"
+        );
+    }
+
+    /// Test a function is found by its path, decided, and reported as text and as JSON
+    #[test]
+    fn test_report_from_query() {
+        let report = report_from_query("src/halts.rs::test::loop_forever").unwrap();
+        assert_eq!(report.decision.verdict.category(), "LOOP");
+        let text = report.to_string();
+        assert!(
+            text.contains("evidence:\nLOOP by endless-iteration\n  at "),
+            "{}",
+            text
+        );
+        assert!(text.contains("pub fn loop_forever () { loop {"), "{}", text);
+        let json = report.to_json();
+        assert!(json.starts_with(
+            "{\"function\":\"src/halts.rs::test::loop_forever\",\"engine\":\"abstract-interpretation\",\"rounds\":0,"
+        ));
+        assert!(json.contains("\"category\":\"LOOP\""), "{}", json);
+        assert_eq!(json_from_str("a \"b\"\n\\"), "\"a \\\"b\\\"\\n\\\\\"");
+    }
+
+    /// Test a function found through an out-of-line module is decided in the context of its own file
+    #[test]
+    fn test_report_through_module() {
+        // `count_up` overflows its `u32` before the replay could show it loops, so the claim is UNKNOWN either way
+        for (function, category) in [
+            ("recursive_cycle_b", "LOOP"),
+            ("count_up_from_one", "UNKNOWN"),
+        ] {
+            let through_lib = report_from_query(&format!("src/lib.rs::halts::test::{}", function));
+            let direct = report_from_query(&format!("src/halts.rs::test::{}", function));
+            let (through_lib, direct) = (through_lib.unwrap(), direct.unwrap());
+            assert_eq!(
+                through_lib.decision.verdict.category(),
+                category,
+                "{}",
+                through_lib
+            );
+            assert_eq!(through_lib.decision.verdict, direct.decision.verdict);
+        }
+    }
+}
@@ -0,0 +1,624 @@
+//! halts/src/scan.rs
+//! Find every function of a crate, to decide them all: free functions, methods, trait default methods and closures.
+//! The crate's targets come from its `Cargo.toml`, and each target's modules are followed from its root file
+//! the way `rustc` follows them: `mod foo;` is `foo.rs` or `foo/mod.rs` beside a root or `mod.rs` file,
+//! and in the directory named after any other file, unless a `#[path = "..."]` attribute says where it is.
+//! A file which can't be read or parsed is noted, and the scan goes on without it.
+
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+use ast_from_path::{
+    item_fn_from_closure, item_fn_from_parts, last_segment_of_type, ModuleDir, Nested,
+};
+use syn::spanned::Spanned;
+
+use crate::call_graph::Context;
+use crate::portfolio::{decision_from_portfolio, Source};
+use crate::verdict::Decision;
+
+/// The kinds of function a crate is scanned for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    /// A free function, at the top of a module or nested in another function's body
+    Function,
+    /// A function in an `impl` block, or a default method of a trait
+    Method,
+    /// A closure, numbered among those of the function it is written in
+    Closure,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Kind::Function => write!(f, "fn"),
+            Kind::Method => write!(f, "method"),
+            Kind::Closure => write!(f, "closure"),
+        }
+    }
+}
+
+/// A source file of the crate.
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    /// Where the file is
+    pub path: PathBuf,
+    /// The path of the module the file holds, from the root of its target
+    pub module: Vec<String>,
+    /// The index of the file's target, among the scan's contexts
+    pub target: usize,
+    /// The file's syntax tree, the context its functions are decided in
+    pub ast: syn::File,
+}
+
+/// A function found in the crate.
+#[derive(Debug, Clone)]
+pub struct Function {
+    /// The path of the function from the root of its target, like `list::List::len` or `main::{closure#0}`
+    pub path: String,
+    /// The same path as the call graph has it, a method under its type alone, like `["list", "List", "len"]`
+    pub segments: Vec<String>,
+    /// What kind of function it is
+    pub kind: Kind,
+    /// The index of the file it is written in, among the scan's files
+    pub file: usize,
+    /// The line it starts on
+    pub line: usize,
+    /// The function as a free function; a closure's parameters without types are given `_`
+    pub ast: syn::ItemFn,
+}
+
+/// Every function of a crate, and the files they're in.
+#[derive(Debug, Clone, Default)]
+pub struct Scan {
+    /// The directory of the crate's `Cargo.toml`
+    pub manifest_dir: PathBuf,
+    /// Whether test code was scanned too
+    pub tests: bool,
+    /// The source files, each target's root first
+    pub files: Vec<SourceFile>,
+    /// The context of each target: every function of its files
+    pub contexts: Vec<Context>,
+    /// The functions, in the order they're written
+    pub functions: Vec<Function>,
+    /// Why files which are declared couldn't be scanned
+    pub errors: Vec<String>,
+}
+
+impl Scan {
+    /// Decide one of the functions, in the context of its target, by the portfolio of engines.
+    /// Each worker of the portfolio scans the crate again on its own thread, once, and finds the function in its scan.
+    ///
+    /// # Arguments
+    ///
+    /// * `function` - One of the scan's functions.
+    /// * `timeout` - How long the function may be analyzed for, after the crate is scanned again;
+    ///   at the deadline, the decision is UNKNOWN, and any rule still running is left to finish on its own.
+    ///
+    /// # Returns
+    ///
+    /// The decision, which is UNKNOWN for a timeout if time ran out first, or for an internal error if the analysis panicked,
+    /// so one function can't stop the rest of the crate from being decided.
+    pub fn decision(&self, function: &Function, timeout: Duration) -> Decision {
+        let manifest_dir = self.manifest_dir.clone();
+        let tests = self.tests;
+        let (file, line, path) = (function.file, function.line, function.path.clone());
+        let source: Source = Arc::new(move || {
+            let scan = scan_from_cache(&manifest_dir, tests)
+                .ok_or_else(|| format!("{} can't be scanned", manifest_dir.display()))?;
+            let function = scan
+                .functions
+                .iter()
+                .find(|function| {
+                    function.file == file && function.line == line && function.path == path
+                })
+                .ok_or_else(|| format!("`{}` is no longer where it was", path))?;
+            let context = scan.contexts[scan.files[function.file].target].at(&function.segments);
+            Ok((function.ast.clone(), context))
+        });
+        decision_from_portfolio(&source, timeout)
+    }
+
+    /// Where a function is, as `file:line` with the file relative to the crate
+    pub fn location(&self, function: &Function) -> String {
+        let path = &self.files[function.file].path;
+        let relative = path.strip_prefix(&self.manifest_dir).unwrap_or(path);
+        format!("{}:{}", relative.display(), function.line)
+    }
+
+    /// Read and parse a file, then scan its items, unless it was scanned already
+    fn file(&mut self, path: &Path, module: &[String], dir: ModuleDir, tests: bool) {
+        if self.files.iter().any(|file| file.path == path) {
+            return;
+        }
+        let ast = match fs::read_to_string(path) {
+            Ok(code) => match syn::parse_file(&code) {
+                Ok(ast) => ast,
+                Err(e) => {
+                    let start = e.span().start();
+                    let at = format!("{}:{}:{}", path.display(), start.line, start.column + 1);
+                    self.errors.push(format!("{}: {}", at, e));
+                    return;
+                }
+            },
+            Err(e) => {
+                self.errors.push(format!("{}: {}", path.display(), e));
+                return;
+            }
+        };
+        self.files.push(SourceFile {
+            path: path.to_path_buf(),
+            module: module.to_vec(),
+            target: self.contexts.len(),
+            ast: ast.clone(),
+        });
+        let mut scope = Module {
+            path: module.to_vec(),
+            file: self.files.len() - 1,
+            dir,
+            tests,
+        };
+        self.items(&ast.items, &mut scope);
+    }
+
+    /// Scan the items of a module
+    fn items(&mut self, items: &[syn::Item], module: &mut Module) {
+        for item in items {
+            if !module.tests && is_test(item_attrs(item)) {
+                continue;
+            }
+            match item {
+                syn::Item::Fn(function) => {
+                    let name = function.sig.ident.to_string();
+                    let segments = module.segments(&[&name]);
+                    let path = module.join(&name);
+                    self.function(path, segments, Kind::Function, function.clone(), module);
+                }
+                syn::Item::Impl(implementation) => {
+                    let Some(type_name) = last_segment_of_type(&implementation.self_ty) else {
+                        continue;
+                    };
+                    let owner = match &implementation.trait_ {
+                        Some((_, trait_path, _)) => match trait_path.segments.last() {
+                            Some(trait_name) => format!("<{} as {}>", type_name, trait_name.ident),
+                            None => type_name.clone(),
+                        },
+                        None => type_name.clone(),
+                    };
+                    for impl_item in &implementation.items {
+                        if let syn::ImplItem::Fn(method) = impl_item {
+                            if !module.tests && is_test(&method.attrs) {
+                                continue;
+                            }
+                            let name = method.sig.ident.to_string();
+                            let segments = module.segments(&[&type_name, &name]);
+                            let path = module.join(&format!("{}::{}", owner, name));
+                            let ast = item_fn_from_parts(
+                                &method.attrs,
+                                &method.vis,
+                                &method.sig,
+                                &method.block,
+                            );
+                            self.function(path, segments, Kind::Method, ast, module);
+                        }
+                    }
+                }
+                syn::Item::Trait(trait_item) => {
+                    for item in &trait_item.items {
+                        if let syn::TraitItem::Fn(syn::TraitItemFn {
+                            attrs,
+                            sig,
+                            default: Some(block),
+                            ..
+                        }) = item
+                        {
+                            let (owner, name) =
+                                (trait_item.ident.to_string(), sig.ident.to_string());
+                            let segments = module.segments(&[&owner, &name]);
+                            let path = module.join(&format!("{}::{}", owner, name));
+                            let ast =
+                                item_fn_from_parts(attrs, &syn::Visibility::Inherited, sig, block);
+                            self.function(path, segments, Kind::Method, ast, module);
+                        }
+                    }
+                }
+                syn::Item::Mod(inner) => {
+                    let name = inner.ident.to_string();
+                    let mut path = module.path.clone();
+                    path.push(name.clone());
+                    match &inner.content {
+                        Some((_, items)) => {
+                            let mut scope = Module {
+                                path,
+                                file: module.file,
+                                dir: module.dir.inline(&name),
+                                tests: module.tests,
+                            };
+                            self.items(items, &mut scope);
+                        }
+                        None => match module.dir.locate(&name, &inner.attrs) {
+                            (file, dir) if file.is_file() => {
+                                self.file(&file, &path, dir, module.tests)
+                            }
+                            _ => self.errors.push(format!(
+                                "{}: no file for `mod {};`",
+                                self.files[module.file].path.display(),
+                                name
+                            )),
+                        },
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Add a function, then the functions nested in it and its closures
+    fn function(
+        &mut self,
+        path: String,
+        segments: Vec<String>,
+        kind: Kind,
+        ast: syn::ItemFn,
+        module: &Module,
+    ) {
+        let nested = Nested::from_block(&ast.block);
+        let inner =
+            |name: String| -> Vec<String> { segments.iter().cloned().chain([name]).collect() };
+        self.functions.push(Function {
+            path: path.clone(),
+            segments: segments.clone(),
+            kind,
+            file: module.file,
+            line: ast.sig.ident.span().start().line,
+            ast,
+        });
+        for (index, closure) in nested.closures.iter().enumerate() {
+            let name = format!("{{closure#{}}}", index);
+            self.functions.push(Function {
+                path: format!("{}::{}", path, name),
+                segments: inner(name),
+                kind: Kind::Closure,
+                file: module.file,
+                line: closure.span().start().line,
+                ast: item_fn_from_closure(closure),
+            });
+        }
+        for function in nested.functions {
+            if module.tests || !is_test(&function.attrs) {
+                let name = function.sig.ident.to_string();
+                let path = format!("{}::{}", path, name);
+                self.function(path, inner(name), Kind::Function, function, module);
+            }
+        }
+    }
+}
+
+/// Where the items being scanned are
+#[derive(Debug, Clone)]
+struct Module {
+    /// The path of the module from the root of its target
+    path: Vec<String>,
+    /// The index of the file the module is written in
+    file: usize,
+    /// Where the module's `mod foo;` declarations are found
+    dir: ModuleDir,
+    /// Whether test code is scanned
+    tests: bool,
+}
+
+impl Module {
+    /// The segments of the path of an item of the module
+    fn segments(&self, names: &[&str]) -> Vec<String> {
+        let names = names.iter().map(|name| name.to_string());
+        self.path.iter().cloned().chain(names).collect()
+    }
+
+    /// The path of an item of the module
+    fn join(&self, name: &str) -> String {
+        self.path
+            .iter()
+            .map(String::as_str)
+            .chain([name])
+            .collect::<Vec<_>>()
+            .join("::")
+    }
+}
+
+/// The attributes of an item, or none for the kinds a scan passes over anyway
+fn item_attrs(item: &syn::Item) -> &[syn::Attribute] {
+    match item {
+        syn::Item::Fn(function) => &function.attrs,
+        syn::Item::Impl(implementation) => &implementation.attrs,
+        syn::Item::Trait(trait_item) => &trait_item.attrs,
+        syn::Item::Mod(module) => &module.attrs,
+        _ => &[],
+    }
+}
+
+/// Whether attributes mark test code: `#[test]`, or `#[cfg(test)]`
+fn is_test(attrs: &[syn::Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        attr.path().is_ident("test")
+            || (attr.path().is_ident("cfg")
+                && attr
+                    .parse_args::<syn::Ident>()
+                    .is_ok_and(|ident| ident == "test"))
+    })
+}
+
+/// Find the root files of a crate's targets from its manifest.
+///
+/// Only as much of the TOML is read as it takes to find the `path` of `[lib]` and each `[[bin]]`;
+/// targets Cargo discovers for itself, `src/lib.rs`, `src/main.rs` and `src/bin/*.rs`, are added when they exist.
+///
+/// # Arguments
+///
+/// * `manifest_dir` - The directory of the crate's `Cargo.toml`.
+/// * `manifest` - The text of the `Cargo.toml`.
+///
+/// # Returns
+///
+/// The root file of every target, without repeats, in the order they're found.
+pub fn roots_from_manifest(manifest_dir: &Path, manifest: &str) -> Vec<PathBuf> {
+    let mut roots = vec![];
+    let mut section = String::new();
+    for line in manifest.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.starts_with('[') {
+            section = line
+                .trim_matches(|c| c == '[' || c == ']')
+                .trim()
+                .to_string();
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        if (section == "lib" || section == "bin") && key.trim() == "path" {
+            let path = value.trim().trim_matches(|c| c == '"' || c == '\'');
+            roots.push(manifest_dir.join(path));
+        }
+    }
+    let src = manifest_dir.join("src");
+    roots.push(src.join("lib.rs"));
+    roots.push(src.join("main.rs"));
+    if let Ok(entries) = fs::read_dir(src.join("bin")) {
+        let mut bins: Vec<PathBuf> = entries
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.extension().is_some_and(|extension| extension == "rs"))
+            .collect();
+        bins.sort();
+        roots.extend(bins);
+    }
+    let mut seen = BTreeSet::new();
+    roots
+        .into_iter()
+        .filter(|root| root.is_file() && seen.insert(root.clone()))
+        .collect()
+}
+
+/// Find the names a crate's targets are compiled under from its manifest, as `std::any::type_name` spells them.
+///
+/// Only the `name` of `[package]`, `[lib]` and each `[[bin]]` is read, with dashes made underscores.
+///
+/// # Arguments
+///
+/// * `manifest` - The text of the `Cargo.toml`.
+///
+/// # Returns
+///
+/// The name of every target which names itself or takes the package's, without repeats.
+pub fn names_from_manifest(manifest: &str) -> Vec<String> {
+    let mut names = vec![];
+    let mut section = String::new();
+    for line in manifest.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.starts_with('[') {
+            section = line
+                .trim_matches(|c| c == '[' || c == ']')
+                .trim()
+                .to_string();
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        if matches!(section.as_str(), "package" | "lib" | "bin") && key.trim() == "name" {
+            let name = value
+                .trim()
+                .trim_matches(|c| c == '"' || c == '\'')
+                .replace('-', "_");
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+/// Find every function of a crate.
+///
+/// # Arguments
+///
+/// * `manifest_dir` - The directory of the crate's `Cargo.toml`.
+/// * `tests` - Whether to scan test code too: `#[test]` functions and `#[cfg(test)]` items.
+///
+/// # Returns
+///
+/// The crate's source files and functions, and why any file couldn't be scanned.
+///
+/// # Errors
+///
+/// If the crate's `Cargo.toml` can't be read.
+pub fn scan_from_manifest_dir(manifest_dir: &Path, tests: bool) -> io::Result<Scan> {
+    let manifest = fs::read_to_string(manifest_dir.join("Cargo.toml"))?;
+    let mut scan = Scan {
+        manifest_dir: manifest_dir.to_path_buf(),
+        tests,
+        ..Scan::default()
+    };
+    for root in roots_from_manifest(manifest_dir, &manifest) {
+        let first = scan.files.len();
+        scan.file(&root, &[], ModuleDir::from_root(&root), tests);
+        let files: Vec<(Vec<String>, &syn::File)> = scan.files[first..]
+            .iter()
+            .map(|file| (file.module.clone(), &file.ast))
+            .collect();
+        let context = Context::from_files(&files);
+        scan.contexts.push(context);
+    }
+    Ok(scan)
+}
+
+/// The scan of a crate, made once per thread, or `None` if its `Cargo.toml` can't be read
+fn scan_from_cache(manifest_dir: &Path, tests: bool) -> Option<Rc<Scan>> {
+    thread_local! {
+        /// The scans of the crates asked about so far, by the directory of their `Cargo.toml` and whether tests were scanned
+        static SCANS: RefCell<HashMap<(PathBuf, bool), Rc<Scan>>> = RefCell::default();
+    }
+    let key = (manifest_dir.to_path_buf(), tests);
+    SCANS.with(|scans| {
+        if let Some(scan) = scans.borrow().get(&key) {
+            return Some(Rc::clone(scan));
+        }
+        let scan = Rc::new(scan_from_manifest_dir(manifest_dir, tests).ok()?);
+        scans.borrow_mut().insert(key, Rc::clone(&scan));
+        Some(scan)
+    })
+}
+
+/// The context of a function of a crate, among every function of the target its file belongs to.
+/// The crate is the one whose `Cargo.toml` is nearest above the file, scanned with its tests once per thread.
+///
+/// # Arguments
+///
+/// * `path` - The file the function is written in.
+/// * `location` - The path of the function within the file, like `["module", "Type", "method"]`.
+///
+/// # Returns
+///
+/// The context, or `None` if the file is in no crate, or no target of it declares the file.
+pub fn context_from_file(path: &Path, location: &[String]) -> Option<Context> {
+    let path = fs::canonicalize(path).ok()?;
+    let manifest_dir = path
+        .ancestors()
+        .find(|dir| dir.join("Cargo.toml").is_file())?;
+    let scan = scan_from_cache(manifest_dir, true)?;
+    let file = scan
+        .files
+        .iter()
+        .find(|file| fs::canonicalize(&file.path).is_ok_and(|found| found == path))?;
+    let full: Vec<String> = file.module.iter().chain(location).cloned().collect();
+    Some(scan.contexts[file.target].at(&full))
+}
+
+/// tests for scanning a crate
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Test that this crate's targets and modules are found, without its tests
+    #[test]
+    fn test_scan_from_manifest_dir() {
+        let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let scan = scan_from_manifest_dir(manifest_dir, false).unwrap();
+        assert!(scan.errors.is_empty(), "{:?}", scan.errors);
+        assert!(scan.files[0].path.ends_with("src/lib.rs"));
+        assert!(scan
+            .files
+            .iter()
+            .any(|file| file.path.ends_with("src/scan.rs")));
+        let paths: Vec<&str> = scan.functions.iter().map(|f| f.path.as_str()).collect();
+        assert!(paths.contains(&"scan::roots_from_manifest"));
+        assert!(paths.contains(&"scan::Scan::decision"));
+        assert!(paths.contains(&"scan::<Kind as Display>::fmt"));
+        assert!(!paths.iter().any(|path| path.starts_with("scan::test::")));
+        let with_tests = scan_from_manifest_dir(manifest_dir, true).unwrap();
+        assert!(with_tests.functions.len() > scan.functions.len());
+    }
+
+    /// Test that functions are decided among every function of their crate,
+    /// so calls between files are followed, and a call to a path of the crate which names no function is no HALT
+    #[test]
+    fn test_crate_wide() {
+        let dir = std::env::temp_dir().join(format!("halts-crate-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("Cargo.toml"), "[package]\nname = \"x\"\n").unwrap();
+        fs::write(dir.join("src/lib.rs"), "mod a;\nmod b;\n").unwrap();
+        fs::write(dir.join("src/a.rs"), "pub fn f() { crate::b::g() }\n").unwrap();
+        fs::write(
+            dir.join("src/b.rs"),
+            "pub fn g() { crate::a::f() }\npub fn h() { crate::c::k() }\n",
+        )
+        .unwrap();
+        let scan = scan_from_manifest_dir(&dir, false).unwrap();
+        // the workers which decide the functions scan the crate again
+        let verdicts: Vec<(&str, &str)> = scan
+            .functions
+            .iter()
+            .map(|f| {
+                let decision = scan.decision(f, Duration::from_secs(60));
+                (f.path.as_str(), decision.verdict.category())
+            })
+            .collect();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            verdicts,
+            vec![("a::f", "LOOP"), ("b::g", "LOOP"), ("b::h", "UNKNOWN")]
+        );
+    }
+
+    /// Test that nested functions and closures are found, and numbered, in a file of their own
+    #[test]
+    fn test_nested() {
+        let dir = std::env::temp_dir().join(format!("halts-scan-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src/inner")).unwrap();
+        fs::write(dir.join("Cargo.toml"), "[package]\nname = \"x\"\n").unwrap();
+        fs::write(
+            dir.join("src/lib.rs"),
+            "mod inner;\n#[path = \"elsewhere.rs\"]\nmod moved;\nmod flat;\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("src/inner/mod.rs"),
+            "pub fn outer() { fn helper() {} let f = |x| x + 1; let g = |y: u8| { y }; }\n",
+        )
+        .unwrap();
+        fs::write(dir.join("src/elsewhere.rs"), "fn there() {}\nmod beside;\n").unwrap();
+        fs::write(dir.join("src/beside.rs"), "fn here() {}\n").unwrap();
+        fs::create_dir_all(dir.join("src/flat")).unwrap();
+        fs::write(dir.join("src/flat.rs"), "mod child;\n").unwrap();
+        fs::write(dir.join("src/flat/child.rs"), "fn below() {}\n").unwrap();
+        let scan = scan_from_manifest_dir(&dir, false).unwrap();
+        let decision = scan.decision(&scan.functions[1], Duration::from_secs(60));
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(scan.errors.is_empty(), "{:?}", scan.errors);
+        let found: Vec<(&str, Kind, usize)> = scan
+            .functions
+            .iter()
+            .map(|f| (f.path.as_str(), f.kind, f.line))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("inner::outer", Kind::Function, 1),
+                ("inner::outer::{closure#0}", Kind::Closure, 1),
+                ("inner::outer::{closure#1}", Kind::Closure, 1),
+                ("inner::outer::helper", Kind::Function, 1),
+                ("moved::there", Kind::Function, 1),
+                ("moved::beside::here", Kind::Function, 1),
+                ("flat::child::below", Kind::Function, 1),
+            ]
+        );
+        assert_eq!(decision.verdict.category(), "HALT");
+        assert_eq!(scan.location(&scan.functions[4]), "src/elsewhere.rs:1");
+    }
+}
@@ -0,0 +1,296 @@
+//! halts/src/simplex.rs
+//! A linear program solver over exact rationals, by the two-phase simplex method with Bland's rule.
+//! The programs the analyzer asks about, like the multipliers of a Farkas certificate, are small,
+//! so a dense tableau is plenty, and exact arithmetic means a solution it gives is a solution.
+
+use std::cmp::Ordering;
+
+use crate::rational::Rational;
+
+/// The most pivots made before giving up, which Bland's rule never needs but overflow might
+const MAX_PIVOTS: usize = 10_000;
+
+/// How the left side of a constraint compares with its right side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Comparison {
+    /// `<=`
+    Le,
+    /// `==`
+    Eq,
+    /// `>=`
+    Ge,
+}
+
+/// A linear constraint `sum of coefficient * variable <comparison> rhs`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Constraint {
+    /// The coefficient of each variable which appears, by index
+    pub coefficients: Vec<(usize, Rational)>,
+    /// How the sum compares with the right side
+    pub comparison: Comparison,
+    /// The right side
+    pub rhs: Rational,
+}
+
+/// A linear program over variables which are all at least zero:
+/// minimize an objective subject to linear constraints.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinearProgram {
+    /// How many variables there are
+    pub variables: usize,
+    /// The constraints, all of which must hold
+    pub constraints: Vec<Constraint>,
+    /// The coefficient of each variable in the objective, by index
+    pub objective: Vec<(usize, Rational)>,
+}
+
+/// A simplex tableau: the constraints as equations over a basis, and the reduced costs of the objective
+struct Tableau {
+    /// Each row's coefficients, with its right side last
+    rows: Vec<Vec<Rational>>,
+    /// The reduced cost of each column, with minus the objective's value last
+    costs: Vec<Rational>,
+    /// The column basic in each row
+    basis: Vec<usize>,
+}
+
+impl Tableau {
+    /// The index of the right side within a row
+    fn rhs(&self) -> usize {
+        self.costs.len() - 1
+    }
+
+    /// Make a column basic in a row, or `None` on overflow
+    fn pivot(&mut self, row: usize, column: usize) -> Option<()> {
+        let pivot = self.rows[row][column];
+        for value in &mut self.rows[row] {
+            *value = value.checked_div(&pivot)?;
+        }
+        let pivot_row = self.rows[row].clone();
+        let eliminate = |target: &mut Vec<Rational>| -> Option<()> {
+            let factor = target[column];
+            if !factor.is_zero() {
+                for (value, pivot_value) in target.iter_mut().zip(&pivot_row) {
+                    *value = value.checked_sub(&factor.checked_mul(pivot_value)?)?;
+                }
+            }
+            Some(())
+        };
+        for other in 0..self.rows.len() {
+            if other != row {
+                eliminate(&mut self.rows[other])?;
+            }
+        }
+        eliminate(&mut self.costs)?;
+        self.basis[row] = column;
+        Some(())
+    }
+
+    /// Pivot until no allowed column has a negative reduced cost.
+    ///
+    /// # Returns
+    ///
+    /// `Some(true)` at an optimum, `Some(false)` if the objective is unbounded below, or `None` on overflow.
+    fn optimize(&mut self, allowed: usize) -> Option<bool> {
+        for _ in 0..MAX_PIVOTS {
+            // Bland's rule: the first improving column, and the first row among the tightest
+            let Some(column) = (0..allowed).find(|&column| self.costs[column].is_negative()) else {
+                return Some(true);
+            };
+            let rhs = self.rhs();
+            let mut leaving: Option<(usize, Rational)> = None;
+            for row in 0..self.rows.len() {
+                let entry = self.rows[row][column];
+                if !entry.is_positive() {
+                    continue;
+                }
+                let ratio = self.rows[row][rhs].checked_div(&entry)?;
+                let better = match &leaving {
+                    None => true,
+                    Some((best, best_ratio)) => match ratio.checked_cmp(best_ratio)? {
+                        Ordering::Less => true,
+                        Ordering::Equal => self.basis[row] < self.basis[*best],
+                        Ordering::Greater => false,
+                    },
+                };
+                if better {
+                    leaving = Some((row, ratio));
+                }
+            }
+            let Some((row, _)) = leaving else {
+                return Some(false);
+            };
+            self.pivot(row, column)?;
+        }
+        None
+    }
+}
+
+impl LinearProgram {
+    /// An empty program over some variables
+    pub fn new(variables: usize) -> Self {
+        LinearProgram {
+            variables,
+            ..LinearProgram::default()
+        }
+    }
+
+    /// Add a constraint
+    pub fn constrain(
+        &mut self,
+        coefficients: Vec<(usize, Rational)>,
+        comparison: Comparison,
+        rhs: Rational,
+    ) {
+        self.constraints.push(Constraint {
+            coefficients,
+            comparison,
+            rhs,
+        });
+    }
+
+    /// Solve the program.
+    ///
+    /// # Returns
+    ///
+    /// A value for each variable which meets every constraint and minimizes the objective,
+    /// or a feasible one if the objective is unbounded, or `None` if no values meet the constraints
+    /// or the arithmetic overflowed.
+    pub fn minimize(&self) -> Option<Vec<Rational>> {
+        self.solve().flatten()
+    }
+
+    /// Whether some values meet every constraint, or `None` if the arithmetic overflowed before that was found
+    pub fn is_feasible(&self) -> Option<bool> {
+        self.solve().map(|values| values.is_some())
+    }
+
+    /// Solve the program, telling no values meeting the constraints, which is `Some(None)`,
+    /// apart from overflow, which is `None`
+    fn solve(&self) -> Option<Option<Vec<Rational>>> {
+        let slacks = self
+            .constraints
+            .iter()
+            .filter(|constraint| constraint.comparison != Comparison::Eq)
+            .count();
+        let artificial = self.variables + slacks;
+        let columns = artificial + self.constraints.len();
+        let mut rows = vec![];
+        let mut slack = self.variables;
+        for (i, constraint) in self.constraints.iter().enumerate() {
+            let mut row = vec![Rational::ZERO; columns + 1];
+            for &(variable, coefficient) in &constraint.coefficients {
+                row[variable] = row[variable].checked_add(&coefficient)?;
+            }
+            match constraint.comparison {
+                Comparison::Le => row[slack] = Rational::ONE,
+                Comparison::Ge => row[slack] = Rational::ONE.checked_neg()?,
+                Comparison::Eq => {}
+            }
+            if constraint.comparison != Comparison::Eq {
+                slack += 1;
+            }
+            row[columns] = constraint.rhs;
+            // each row starts with an artificial variable as its basis, which needs a right side at least zero
+            if constraint.rhs.is_negative() {
+                for value in &mut row {
+                    *value = value.checked_neg()?;
+                }
+            }
+            row[artificial + i] = Rational::ONE;
+            rows.push(row);
+        }
+        // phase one minimizes the sum of the artificial variables, whose reduced costs start as minus the column sums
+        let mut costs = vec![Rational::ZERO; columns + 1];
+        for row in &rows {
+            for column in (0..artificial).chain([columns]) {
+                costs[column] = costs[column].checked_sub(&row[column])?;
+            }
+        }
+        let mut tableau = Tableau {
+            basis: (artificial..columns).collect(),
+            rows,
+            costs,
+        };
+        tableau.optimize(artificial)?;
+        if !tableau.costs[columns].is_zero() {
+            return Some(None);
+        }
+        // drive the artificial variables out of the basis where a real column can take their place
+        for row in 0..tableau.rows.len() {
+            if tableau.basis[row] >= artificial {
+                if let Some(column) =
+                    (0..artificial).find(|&column| !tableau.rows[row][column].is_zero())
+                {
+                    tableau.pivot(row, column)?;
+                }
+            }
+        }
+        // phase two prices the real objective against the basis
+        let mut costs = vec![Rational::ZERO; columns + 1];
+        for &(variable, coefficient) in &self.objective {
+            costs[variable] = costs[variable].checked_add(&coefficient)?;
+        }
+        for (row, &basic) in tableau.rows.iter().zip(&tableau.basis) {
+            let cost = costs[basic];
+            if cost.is_zero() {
+                continue;
+            }
+            for column in 0..=columns {
+                costs[column] = costs[column].checked_sub(&cost.checked_mul(&row[column])?)?;
+            }
+        }
+        tableau.costs = costs;
+        tableau.optimize(artificial)?;
+        let mut values = vec![Rational::ZERO; self.variables];
+        for (row, &basic) in tableau.rows.iter().zip(&tableau.basis) {
+            if basic < self.variables {
+                values[basic] = row[columns];
+            }
+        }
+        Some(Some(values))
+    }
+}
+
+/// tests for the simplex method
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A whole number as a rational, for the tests
+    fn int(int: i128) -> Rational {
+        Rational::from_int(int)
+    }
+
+    /// Test an optimum is found at a vertex, exactly
+    #[test]
+    fn test_minimize() {
+        // maximize x + y subject to x + 2y <= 4, 3x + y <= 6
+        let mut program = LinearProgram::new(2);
+        program.constrain(vec![(0, int(1)), (1, int(2))], Comparison::Le, int(4));
+        program.constrain(vec![(0, int(3)), (1, int(1))], Comparison::Le, int(6));
+        program.objective = vec![(0, int(-1)), (1, int(-1))];
+        let values = program.minimize().unwrap();
+        assert_eq!(
+            values,
+            vec![Rational::new(8, 5).unwrap(), Rational::new(6, 5).unwrap()]
+        );
+    }
+
+    /// Test equalities and lower bounds, and that contradictions are infeasible
+    #[test]
+    fn test_feasibility() {
+        // x + y == 3, x >= 2, minimize y
+        let mut program = LinearProgram::new(2);
+        program.constrain(vec![(0, int(1)), (1, int(1))], Comparison::Eq, int(3));
+        program.constrain(vec![(0, int(1))], Comparison::Ge, int(2));
+        program.objective = vec![(1, int(1))];
+        assert_eq!(program.minimize().unwrap(), vec![int(3), int(0)]);
+
+        assert_eq!(program.is_feasible(), Some(true));
+
+        program.constrain(vec![(0, int(1))], Comparison::Le, int(-1));
+        assert!(program.minimize().is_none());
+        assert_eq!(program.is_feasible(), Some(false));
+    }
+}
@@ -0,0 +1,398 @@
+//! halts/src/size_change.rs
+//! Size-change termination for recursion, after Lee, Jones and Ben-Amram.
+//! Each recursive call gets a graph of how the sizes of the callee's parameters relate to the caller's:
+//! an arc from `x` to `y` says `y` is no bigger than `x` was, and a strict one says it is smaller.
+//! Composing the graphs along chains of calls until no new one turns up gives every way sizes can change
+//! around a cycle of calls. If every graph from a function back to itself which composes with itself to itself
+//! has a strict arc from some parameter to itself, an endless chain of calls would shrink that parameter forever,
+//! which no size can do, so every chain of calls ends.
+//! The size of an integer is how far it is above zero, and the size of anything else is how much of it there is:
+//! a field, a variant's payload or a slice without its first elements is smaller than the value it came out of.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt;
+
+use crate::base_case::{lower_bound, paths_from_recursion, Path, RecursiveCall};
+use crate::call_graph::{CallGraph, Recursion};
+use crate::ir::{body_from_ast, BinOp, Expr, Ty};
+use crate::linear::{atoms_from_condition, linear_from_expr, Atom, LinearExpr};
+use crate::structural::param_types;
+use crate::verdict::SourceSpan;
+
+/// The most graphs the closure grows to before giving up
+const MAX_GRAPHS: usize = 10_000;
+
+/// How a size changes from a parameter of the caller to a parameter of the callee.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Change {
+    /// The callee's is no bigger than the caller's
+    NonStrict,
+    /// The callee's is smaller than the caller's
+    Strict,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::NonStrict => write!(f, ">="),
+            Change::Strict => write!(f, ">"),
+        }
+    }
+}
+
+/// How a call, or a chain of calls, changes the sizes of parameters.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SizeChangeGraph {
+    /// The function called from, by its index in the call graph
+    pub from: usize,
+    /// The function called in the end
+    pub to: usize,
+    /// The strongest change known from each parameter of `from` to each parameter of `to`, by position
+    pub arcs: BTreeMap<(usize, usize), Change>,
+}
+
+impl SizeChangeGraph {
+    /// The graph of this chain of calls followed by another, or `None` if the other doesn't start where this ends
+    pub fn compose(&self, next: &SizeChangeGraph) -> Option<SizeChangeGraph> {
+        if self.to != next.from {
+            return None;
+        }
+        let mut arcs = BTreeMap::new();
+        for (&(i, j), &first) in &self.arcs {
+            for (&(_, k), &second) in next.arcs.range((j, 0)..=(j, usize::MAX)) {
+                let change = first.max(second);
+                let arc = arcs.entry((i, k)).or_insert(change);
+                *arc = (*arc).max(change);
+            }
+        }
+        Some(SizeChangeGraph {
+            from: self.from,
+            to: next.to,
+            arcs,
+        })
+    }
+
+    /// Whether following the chain twice changes sizes just as following it once does
+    pub fn is_idempotent(&self) -> bool {
+        self.compose(self).as_ref() == Some(self)
+    }
+
+    /// The first parameter the chain strictly shrinks from itself to itself, if any
+    pub fn decreases(&self) -> Option<usize> {
+        self.arcs
+            .iter()
+            .find(|(&(i, j), &change)| i == j && change == Change::Strict)
+            .map(|(&(i, _), _)| i)
+    }
+}
+
+/// Whether size-change graphs show a recursion ends, and why.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SizeChange {
+    /// Every chain of recursive calls ends.
+    Terminates(String),
+    /// A cycle of calls can repeat without any parameter sure to shrink, which is reported.
+    Cycle(String),
+    /// The graphs couldn't be built or closed.
+    Unknown(String),
+}
+
+/// A parameter of a member of a recursion
+#[derive(Debug, Clone)]
+struct Param {
+    /// The local the parameter is bound to
+    name: String,
+    /// What the parameter can hold
+    ty: Ty,
+    /// Whether its type holds a pointer like `Rc`, through which its parts may come back around to it
+    shared: bool,
+}
+
+/// The parameters of each member of a recursion, by its index in the call graph
+fn params_from_recursion(recursion: &Recursion, graph: &CallGraph) -> HashMap<usize, Vec<Param>> {
+    recursion
+        .members
+        .iter()
+        .map(|&member| {
+            let body = body_from_ast(&graph.functions[member].ast);
+            let owner = graph.functions[member].owner.as_deref();
+            let params = body
+                .params
+                .iter()
+                .zip(param_types(graph, member))
+                .map(|(name, declared)| Param {
+                    name: name.clone(),
+                    ty: body.local(name).map_or(Ty::Other, |local| local.ty),
+                    shared: graph.types.shared_pointer(&declared, owner).is_some(),
+                })
+                .collect();
+            (member, params)
+        })
+        .collect()
+}
+
+/// The linear constraints which hold along a path, from the types of the parameters and the guards
+fn atoms_from_path(params: &[Param], path: &Path) -> Vec<Atom> {
+    let mut atoms: Vec<Atom> = params
+        .iter()
+        .filter(|param| param.ty == Ty::Unsigned)
+        .map(|param| Atom::ge_zero(LinearExpr::variable(&param.name)))
+        .collect();
+    for guard in &path.guards {
+        if let Some(condition) = &guard.condition {
+            atoms.extend(atoms_from_condition(condition, guard.holds).unwrap_or_default());
+        }
+    }
+    atoms
+}
+
+/// How the size of an argument compares with the size of a parameter of the caller, if it can be told.
+///
+/// An integer argument like `n - 1`, or `n / 2`, is no bigger than `n`, and smaller where `n` is at least one.
+/// An argument which is a part of a parameter, like `tail` out of `list` or `&xs[1..]`, is smaller than it,
+/// unless the parameter's data may be shared.
+fn change_from_arg(param: &Param, arg: &Expr, atoms: &[Atom]) -> Option<Change> {
+    let positive =
+        || lower_bound(atoms, &LinearExpr::variable(&param.name)).is_some_and(|bound| bound >= 1);
+    if param.ty.is_integer() {
+        // truncating `n - k` for `k >= 0` by at least a half stays between zero and `n`
+        if let Expr::Binary(op @ (BinOp::Div | BinOp::Shr), numerator, divisor) = arg {
+            let least = if *op == BinOp::Div { 2 } else { 1 };
+            let change =
+                linear_from_expr(numerator)?.checked_sub(&LinearExpr::variable(&param.name))?;
+            let shrinks = matches!(**divisor, Expr::Int(divisor) if divisor >= least);
+            return (shrinks && change.is_constant() && change.constant <= 0).then(|| {
+                if positive() {
+                    Change::Strict
+                } else {
+                    Change::NonStrict
+                }
+            });
+        }
+        let change = linear_from_expr(arg)?.checked_sub(&LinearExpr::variable(&param.name))?;
+        return match change.is_constant().then_some(change.constant)? {
+            0 => Some(Change::NonStrict),
+            constant if constant < 0 && positive() => Some(Change::Strict),
+            constant if constant < 0 => Some(Change::NonStrict),
+            _ => None,
+        };
+    }
+    let mut whole = arg;
+    let mut parts = 0;
+    while let Expr::Part(inner, _) = whole {
+        whole = inner;
+        parts += 1;
+    }
+    match whole {
+        // a part of shared data may be the whole again, or something bigger
+        Expr::Var(name) if *name == param.name && parts > 0 && param.shared => None,
+        Expr::Var(name) if *name == param.name && parts > 0 => Some(Change::Strict),
+        Expr::Var(name) if *name == param.name => Some(Change::NonStrict),
+        _ => None,
+    }
+}
+
+/// The size-change graph of a recursive call along a path
+fn graph_from_call(
+    member: usize,
+    call: &RecursiveCall,
+    params: &HashMap<usize, Vec<Param>>,
+    atoms: &[Atom],
+) -> SizeChangeGraph {
+    let mut arcs = BTreeMap::new();
+    let callee = &params[&call.callee];
+    for (j, (arg, target)) in call.args.iter().zip(callee).enumerate() {
+        let Some(arg) = arg else {
+            continue;
+        };
+        for (i, param) in params[&member].iter().enumerate() {
+            // an integer's size and a value's size aren't measured alike, so they aren't compared
+            if param.ty.is_integer() != target.ty.is_integer() {
+                continue;
+            }
+            if let Some(change) = change_from_arg(param, arg, atoms) {
+                arcs.insert((i, j), change);
+            }
+        }
+    }
+    SizeChangeGraph {
+        from: member,
+        to: call.callee,
+        arcs,
+    }
+}
+
+/// A graph in words, like `n > n, m >= m`, naming each parameter of the caller and the callee
+fn describe_graph(graph: &SizeChangeGraph, params: &HashMap<usize, Vec<Param>>) -> String {
+    if graph.arcs.is_empty() {
+        return "no size known to shrink or stay".to_string();
+    }
+    graph
+        .arcs
+        .iter()
+        .map(|(&(i, j), change)| {
+            format!(
+                "{} {} {}'",
+                params[&graph.from][i].name, change, params[&graph.to][j].name
+            )
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Decide whether a recursion ends by the size-change principle.
+///
+/// # Arguments
+///
+/// * `recursion` - The recursion to check.
+/// * `graph` - The call graph the recursion is part of.
+///
+/// # Returns
+///
+/// That every chain of recursive calls ends, with the parameters which shrink around each cycle,
+/// or the first cycle of calls found after which no parameter is sure to have shrunk,
+/// or why the graphs couldn't be built.
+pub fn size_change(recursion: &Recursion, graph: &CallGraph) -> SizeChange {
+    let members = match paths_from_recursion(recursion, graph) {
+        Ok(members) => members,
+        Err(why) => return SizeChange::Unknown(why),
+    };
+    let params = params_from_recursion(recursion, graph);
+    let mut calls: Vec<(SizeChangeGraph, Vec<SourceSpan>)> = vec![];
+    for (member, paths) in &members {
+        for path in paths
+            .iter()
+            .filter(|path| !path.is_base_case() && !path.is_infeasible())
+        {
+            let atoms = atoms_from_path(&params[member], path);
+            for call in &path.calls {
+                let call_graph = graph_from_call(*member, call, &params, &atoms);
+                if !calls.iter().any(|(known, _)| *known == call_graph) {
+                    calls.push((call_graph, vec![call.span]));
+                }
+            }
+        }
+    }
+    // close under composition breadth first, so each graph's chain of calls is as short as can be
+    let mut closure: Vec<(SizeChangeGraph, Vec<SourceSpan>)> = vec![];
+    let mut seen = HashSet::new();
+    let mut work: VecDeque<(SizeChangeGraph, Vec<SourceSpan>)> = calls.iter().cloned().collect();
+    while let Some((chain, spans)) = work.pop_front() {
+        if !seen.insert(chain.clone()) {
+            continue;
+        }
+        if seen.len() > MAX_GRAPHS {
+            return SizeChange::Unknown(format!(
+                "the size-change graphs grow past {} under composition",
+                MAX_GRAPHS
+            ));
+        }
+        for (call, call_spans) in &calls {
+            if let Some(next) = chain.compose(call) {
+                if !seen.contains(&next) {
+                    work.push_back((next, [spans.clone(), call_spans.clone()].concat()));
+                }
+            }
+        }
+        closure.push((chain, spans));
+    }
+    let mut shrinking = BTreeSet::new();
+    for (chain, spans) in &closure {
+        if chain.from != chain.to || !chain.is_idempotent() {
+            continue;
+        }
+        match chain.decreases() {
+            Some(param) => {
+                shrinking.insert(format!(
+                    "`{}` of `{}`",
+                    params[&chain.from][param].name,
+                    graph.functions[chain.from].name()
+                ));
+            }
+            None => {
+                let spans: Vec<String> = spans.iter().map(SourceSpan::to_string).collect();
+                return SizeChange::Cycle(format!(
+                    "`{}` can call itself again through the calls at {} with no parameter sure to shrink: {}",
+                    graph.functions[chain.from].name(),
+                    spans.join(", "),
+                    describe_graph(chain, &params)
+                ));
+            }
+        }
+    }
+    SizeChange::Terminates(format!(
+        "{} size-change graphs of the recursive calls close into {} under composition, and every cycle among them shrinks one of {}",
+        calls.len(),
+        closure.len(),
+        shrinking.into_iter().collect::<Vec<String>>().join(", ")
+    ))
+}
+
+/// tests for size-change termination
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Whether the one recursion in some code ends by size-change
+    fn size_change_from_code(code: &str) -> SizeChange {
+        let file = syn::parse_file(code).unwrap();
+        let graph = CallGraph::from_file(&file);
+        let recursions = graph.recursions();
+        assert_eq!(recursions.len(), 1, "{}", code);
+        size_change(&recursions[0], &graph)
+    }
+
+    /// Test parts of values and halved integers shrink
+    #[test]
+    fn test_terminates() {
+        for code in [
+            "fn sum(xs: &[u32]) -> u32 { if xs.is_empty() { return 0; } xs[0] + sum(&xs[1..]) }",
+            "fn len(list: &List) -> u32 { match list { List::Cons(_, tail) => 1 + len(tail), List::Nil => 0 } }",
+            "fn depth(e: &Expr) -> u32 { match e { Expr::Add(a, b) => depth(a).max(depth(&**b)) + 1, Expr::Lit(_) => 0 } }",
+            "fn bits(n: u32) -> u32 { if n == 0 { 0 } else { 1 + bits(n / 2) } }",
+            "fn first([head, rest @ ..]: &[u32]) -> u32 { if *head == 0 { 0 } else { first(rest) } }",
+        ] {
+            let result = size_change_from_code(code);
+            assert!(matches!(result, SizeChange::Terminates(_)), "{}: {:?}", code, result);
+        }
+    }
+
+    /// Test a recursion which needs the composed graphs, as in Ackermann's function and swapped arguments
+    #[test]
+    fn test_closure() {
+        let ackermann = "fn ack(m: u64, n: u64) -> u64 {
+            if m == 0 { return n + 1; }
+            if n == 0 { return ack(m - 1, 1); }
+            ack(m - 1, ack(m, n - 1))
+        }";
+        let SizeChange::Terminates(why) = size_change_from_code(ackermann) else {
+            panic!("{}", ackermann);
+        };
+        assert!(
+            why.contains("`m` of `ack`") && why.contains("`n` of `ack`"),
+            "{}",
+            why
+        );
+        // neither argument shrinks at every call, but one of them shrinks every other call
+        let swap = "fn swap(x: &List, y: &List) -> u32 {
+            match x { List::Cons(_, tail) => swap(y, tail), List::Nil => 0 }
+        }";
+        assert!(matches!(
+            size_change_from_code(swap),
+            SizeChange::Terminates(_)
+        ));
+    }
+
+    /// Test the offending cycle is reported where nothing is sure to shrink
+    #[test]
+    fn test_cycle() {
+        // below zero, `n - 1` doesn't bring `n` any nearer zero
+        let code = "fn down(n: i64) -> i64 { if n == 0 { return 0; } down(n - 1) }";
+        let SizeChange::Cycle(why) = size_change_from_code(code) else {
+            panic!("{}", code);
+        };
+        assert!(why.contains("n >= n'"), "{}", why);
+        let code = "fn grow(list: List) -> u32 { match list { List::Nil => 0, list => grow(List::Cons(1, Box::new(list))) } }";
+        assert!(matches!(size_change_from_code(code), SizeChange::Cycle(_)));
+    }
+}
@@ -0,0 +1,284 @@
+//! halts/src/source.rs
+//! Recover the source code of a function from nothing but its type.
+//! `std::any::type_name` names a function item by its full path, e.g. `halts::halts::test::unit`,
+//! and the crate's `src/` directory mirrors that path closely enough
+//! to find the file and hand the rest of the path to `ast_from_path`.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ast_from_path::{ast_from_path, item_fn_from_closure};
+use syn::punctuated::Punctuated;
+use syn::visit::Visit;
+
+use crate::call_graph::Context;
+use crate::scan::{context_from_file, names_from_manifest};
+
+/// The segment `type_name` uses for a closure defined inside a function
+const CLOSURE_SEGMENT: &str = "{{closure}}";
+
+/// An index from item paths to the source files of one crate.
+#[derive(Debug, Clone)]
+pub struct SourceIndex {
+    /// The `src/` directory of the crate
+    src_dir: PathBuf,
+    /// The names the crate is compiled under, which begin the type names of its functions
+    crate_names: Vec<String>,
+}
+
+impl SourceIndex {
+    /// Index the crate whose manifest lives in `manifest_dir`.
+    ///
+    /// If the manifest can't be read, the crate has no name, and no type name is found in it.
+    pub fn new(manifest_dir: &Path) -> Self {
+        let manifest = fs::read_to_string(manifest_dir.join("Cargo.toml")).unwrap_or_default();
+        SourceIndex {
+            src_dir: manifest_dir.join("src"),
+            crate_names: names_from_manifest(&manifest),
+        }
+    }
+
+    /// Index the crate currently being built, run or tested.
+    ///
+    /// Cargo sets `CARGO_MANIFEST_DIR` for `cargo run` and `cargo test`,
+    /// which points at the crate under analysis rather than at this one.
+    pub fn for_current_crate() -> Self {
+        let manifest_dir = env::var_os("CARGO_MANIFEST_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")));
+        SourceIndex::new(&manifest_dir)
+    }
+
+    /// The crate root file: `src/lib.rs` if there is one, otherwise `src/main.rs`
+    fn crate_root(&self) -> PathBuf {
+        let lib = self.src_dir.join("lib.rs");
+        if lib.is_file() {
+            lib
+        } else {
+            self.src_dir.join("main.rs")
+        }
+    }
+
+    /// Find the file defining an item, and the item's path within that file.
+    ///
+    /// # Arguments
+    ///
+    /// * `item_path` - The path of the item relative to the crate root, e.g. `["halts", "test", "unit"]`
+    ///
+    /// # Returns
+    ///
+    /// The file of the deepest module along `item_path` which lives in a file of its own,
+    /// and the remainder of `item_path` inside that file.
+    pub fn locate(&self, item_path: &[String]) -> (PathBuf, Vec<String>) {
+        for depth in (1..item_path.len()).rev() {
+            let module_dir = item_path[..depth]
+                .iter()
+                .fold(self.src_dir.clone(), |dir, segment| dir.join(segment));
+            let candidates = [module_dir.with_extension("rs"), module_dir.join("mod.rs")];
+            if let Some(file) = candidates.into_iter().find(|file| file.is_file()) {
+                return (file, item_path[depth..].to_vec());
+            }
+        }
+        (self.crate_root(), item_path.to_vec())
+    }
+
+    /// Find the file, the path within it, and the closure depth of the function a type name refers to
+    fn locate_type_name(&self, type_name: &str) -> syn::Result<(PathBuf, Vec<String>, usize)> {
+        let mut segments = segments_from_type_name(type_name);
+        let closures = segments
+            .iter()
+            .rev()
+            .take_while(|segment| *segment == CLOSURE_SEGMENT)
+            .count();
+        segments.truncate(segments.len() - closures);
+        // the first segment names the crate itself
+        if segments.len() < 2 {
+            return Err(error(format!("`{}` does not name a function", type_name)));
+        }
+        if !self.crate_names.contains(&segments[0]) {
+            return Err(error(format!(
+                "`{}` names a function of another crate than `{}`",
+                type_name,
+                self.crate_names.join("` or `")
+            )));
+        }
+        let (file, inline_path) = self.locate(&segments[1..]);
+        Ok((file, inline_path, closures))
+    }
+
+    /// Convert a type name, as given by `std::any::type_name`, into the AST of the function it names.
+    ///
+    /// `type_name` spells every closure `{{closure}}` without telling which, so a closure is only found
+    /// if it is the one closure without arguments in the function it is written in, and not nested in another.
+    ///
+    /// # Errors
+    ///
+    /// If the type name does not name a function of this crate,
+    /// if its source file cannot be read or parsed,
+    /// or if it names a closure which cannot be told apart from its siblings.
+    pub fn ast_from_type_name(&self, type_name: &str) -> syn::Result<syn::ItemFn> {
+        let (file, inline_path, closures) = self.locate_type_name(type_name)?;
+        let query = format!("{}::{}", file.display(), inline_path.join("::"));
+        let function = ast_from_path(&query).map_err(|e| error(e.to_string()))?;
+        match closures {
+            0 => Ok(function),
+            1 => ast_from_closure_in(&function),
+            _ => Err(error(format!(
+                "nested closures are not supported: `{}`",
+                type_name
+            ))),
+        }
+    }
+
+    /// Convert a type name, as given by `std::any::type_name`, into the context of the function it names:
+    /// every function of its crate's target, or of the file defining it if the crate can't be scanned.
+    ///
+    /// # Errors
+    ///
+    /// If the type name does not name a function of this crate,
+    /// or if its source file cannot be read or parsed.
+    pub fn context_from_type_name(&self, type_name: &str) -> syn::Result<Context> {
+        let (file, mut inline_path, closures) = self.locate_type_name(type_name)?;
+        // the one closure `ast_from_closure_in` finds is the first
+        inline_path.extend((0..closures).map(|_| "{closure#0}".to_string()));
+        if let Some(context) = context_from_file(&file, &inline_path) {
+            return Ok(context);
+        }
+        let code =
+            fs::read_to_string(&file).map_err(|e| error(format!("{}: {}", file.display(), e)))?;
+        Ok(Context::from_file(&syn::parse_file(&code)?).at(&inline_path))
+    }
+}
+
+/// Split a type name into path segments, dropping generic arguments.
+///
+/// # Arguments
+///
+/// * `type_name` - A type name like `my_crate::module::function<u32>::{{closure}}`
+///
+/// # Returns
+///
+/// The path segments, like `["my_crate", "module", "function", "{{closure}}"]`
+pub fn segments_from_type_name(type_name: &str) -> Vec<String> {
+    let mut depth = 0usize;
+    let mut without_generics = String::with_capacity(type_name.len());
+    for c in type_name.chars() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth = depth.saturating_sub(1),
+            _ if depth == 0 => without_generics.push(c),
+            _ => {}
+        }
+    }
+    without_generics
+        .split("::")
+        .filter(|segment| !segment.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Turn the one argumentless closure in a function into a function of its own.
+fn ast_from_closure_in(function: &syn::ItemFn) -> syn::Result<syn::ItemFn> {
+    /// Collects the closures which take no arguments, the only ones an `Fn()` can be
+    #[derive(Default)]
+    struct ClosureCollector {
+        /// The closures found so far
+        closures: Vec<syn::ExprClosure>,
+    }
+    impl<'ast> Visit<'ast> for ClosureCollector {
+        fn visit_expr_closure(&mut self, closure: &'ast syn::ExprClosure) {
+            if closure.inputs.is_empty() {
+                self.closures.push(closure.clone());
+            }
+            syn::visit::visit_expr_closure(self, closure);
+        }
+
+        // closures are often written inside macros, e.g. `assert!(halts(|| {}).unwrap())`
+        fn visit_macro(&mut self, mac: &'ast syn::Macro) {
+            let parser = Punctuated::<syn::Expr, syn::Token![,]>::parse_terminated;
+            if let Ok(args) = mac.parse_body_with(parser) {
+                for arg in &args {
+                    self.visit_expr(arg);
+                }
+            }
+        }
+    }
+
+    let mut collector = ClosureCollector::default();
+    collector.visit_block(&function.block);
+    match collector.closures.as_slice() {
+        [closure] => Ok(item_fn_from_closure(closure)),
+        closures => Err(error(format!(
+            "expected exactly one closure without arguments in `{}`, found {}",
+            function.sig.ident,
+            closures.len()
+        ))),
+    }
+}
+
+/// A `syn::Error` for source which could not be recovered
+fn error(message: String) -> syn::Error {
+    syn::Error::new(proc_macro2::Span::call_site(), message)
+}
+
+/// tests for source recovery
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Test that generic arguments are dropped and closures kept
+    #[test]
+    fn test_segments_from_type_name() {
+        assert_eq!(
+            segments_from_type_name("a::b::f<alloc::vec::Vec<u8>>::{{closure}}"),
+            vec!["a", "b", "f", "{{closure}}"]
+        );
+        assert_eq!(
+            segments_from_type_name("halts::halts::halts"),
+            vec!["halts", "halts", "halts"]
+        );
+    }
+
+    /// Test that items are located in the file of their deepest out-of-line module
+    #[test]
+    fn test_locate() {
+        let index = SourceIndex::new(Path::new(env!("CARGO_MANIFEST_DIR")));
+        let path = ["halts", "test", "unit"].map(String::from);
+        let (file, inline_path) = index.locate(&path);
+        assert!(file.ends_with("src/halts.rs"));
+        assert_eq!(inline_path, vec!["test", "unit"]);
+
+        let (file, inline_path) = index.locate(&path[..1]);
+        assert!(file.ends_with("src/lib.rs"));
+        assert_eq!(inline_path, vec!["halts"]);
+    }
+
+    /// Test that the function behind a type name is recovered
+    #[test]
+    fn test_ast_from_type_name() {
+        let index = SourceIndex::for_current_crate();
+        let ast = index
+            .ast_from_type_name("halts::halts::test::unit")
+            .unwrap();
+        assert_eq!(ast.sig.ident, "unit");
+        assert!(index.ast_from_type_name("halts").is_err());
+    }
+
+    /// Test that a type name from another crate isn't looked for in this one, even where its path would fit
+    #[test]
+    fn test_other_crate() {
+        let index = SourceIndex::for_current_crate();
+        assert_eq!(index.crate_names, vec!["halts", "cargo_halts"]);
+        let e = index
+            .ast_from_type_name("other::halts::test::unit")
+            .unwrap_err();
+        assert!(e.to_string().contains("another crate"));
+        assert_eq!(
+            names_from_manifest(
+                "[package]\nname = \"my-tool\"\n[[bin]]\nname = 'cli' # the command\n"
+            ),
+            vec!["my_tool", "cli"]
+        );
+    }
+}
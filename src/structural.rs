@@ -0,0 +1,396 @@
+//! halts/src/structural.rs
+//! Structural recursion: recursion which only ever recurses on parts destructured from an argument,
+//! like a walker over `enum Expr { Add(Box<Expr>, Box<Expr>), Lit(i64) }`.
+//! Owned data is a finite tree, holding its parts by value or in boxes and never itself,
+//! so taking it apart can only go so deep, whatever the value.
+//! That doesn't hold of data shared through an `Rc` or behind a `RefCell`, which may be tied into a cycle,
+//! so recursion over it is left unknown.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::base_case::paths_from_recursion;
+use crate::call_graph::{CallGraph, Recursion};
+use crate::ir::{body_from_ast, Expr};
+
+/// The pointers through which data may be shared, and so may come back around to itself
+const SHARED_POINTERS: &[&str] = &[
+    "Rc",
+    "Arc",
+    "Weak",
+    "Cell",
+    "RefCell",
+    "UnsafeCell",
+    "OnceCell",
+    "Mutex",
+    "RwLock",
+];
+
+/// The pointers which own what they point to, and so don't stop a type being a tree
+const OWNING_POINTERS: &[&str] = &["Box", "Option", "Vec", "VecDeque"];
+
+/// The structs, enums and type aliases of a crate, by name.
+///
+/// Types are told apart by their last segment only,
+/// so two types of the same name in different modules are taken together, which only makes sharing more likely.
+#[derive(Debug, Clone, Default)]
+pub struct TypeDefinitions {
+    /// The types of the fields of every variant of each type, or what an alias stands for
+    fields: HashMap<String, Vec<syn::Type>>,
+    /// The type of each field of each struct, by name, or by index for a tuple struct
+    named_fields: HashMap<String, HashMap<String, syn::Type>>,
+    /// The enums each variant name belongs to
+    variants: HashMap<String, Vec<String>>,
+}
+
+impl TypeDefinitions {
+    /// Add the types defined among some items, and in the inline modules among them
+    pub fn add_items(&mut self, items: &[syn::Item]) {
+        for item in items {
+            let (name, fields): (String, Vec<syn::Type>) = match item {
+                syn::Item::Struct(item) => {
+                    let named = item.fields.iter().enumerate().map(|(index, field)| {
+                        let name = match &field.ident {
+                            Some(ident) => ident.to_string(),
+                            None => index.to_string(),
+                        };
+                        (name, field.ty.clone())
+                    });
+                    let name = item.ident.to_string();
+                    self.named_fields
+                        .entry(name.clone())
+                        .or_default()
+                        .extend(named);
+                    (
+                        name,
+                        item.fields.iter().map(|field| field.ty.clone()).collect(),
+                    )
+                }
+                syn::Item::Enum(item) => {
+                    for variant in &item.variants {
+                        self.variants
+                            .entry(variant.ident.to_string())
+                            .or_default()
+                            .push(item.ident.to_string());
+                    }
+                    (
+                        item.ident.to_string(),
+                        item.variants
+                            .iter()
+                            .flat_map(|variant| &variant.fields)
+                            .map(|field| field.ty.clone())
+                            .collect(),
+                    )
+                }
+                syn::Item::Type(alias) => (alias.ident.to_string(), vec![(*alias.ty).clone()]),
+                syn::Item::Mod(inner) => {
+                    if let Some((_, items)) = &inner.content {
+                        self.add_items(items);
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+            self.fields.entry(name).or_default().extend(fields);
+        }
+    }
+
+    /// Whether a type of this name is defined
+    pub fn is_defined(&self, name: &str) -> bool {
+        self.fields.contains_key(name)
+    }
+
+    /// The declared type of a field of a struct, by its name, or its index for a tuple struct
+    pub fn field_type(&self, ty: &str, field: &str) -> Option<&syn::Type> {
+        self.named_fields.get(ty)?.get(field)
+    }
+
+    /// The enum a variant belongs to, unless no enum or several have a variant of that name
+    pub fn enum_of_variant(&self, variant: &str) -> Option<&str> {
+        match self.variants.get(variant)?.as_slice() {
+            [only] => Some(only),
+            _ => None,
+        }
+    }
+
+    /// The type defined in the crate which a type names, looking through references, boxes and parentheses,
+    /// with `Self` standing for `owner`
+    pub fn defined_type(&self, ty: &syn::Type, owner: Option<&str>) -> Option<String> {
+        match ty {
+            syn::Type::Reference(reference) => self.defined_type(&reference.elem, owner),
+            syn::Type::Paren(paren) => self.defined_type(&paren.elem, owner),
+            syn::Type::Group(group) => self.defined_type(&group.elem, owner),
+            syn::Type::Path(path) => {
+                let last = path.path.segments.last()?;
+                let name = match last.ident.to_string() {
+                    name if name == "Self" => owner?.to_string(),
+                    name => name,
+                };
+                if name == "Box" {
+                    return self.defined_type(generic_args(last).first()?, owner);
+                }
+                self.is_defined(&name).then_some(name)
+            }
+            _ => None,
+        }
+    }
+
+    /// The first pointer a type holds through which its data may be shared, like `Rc`, if any,
+    /// looking into the fields of the crate's own types
+    pub fn shared_pointer(&self, ty: &syn::Type, owner: Option<&str>) -> Option<String> {
+        self.shared_pointer_within(ty, owner, &mut HashSet::new())
+    }
+
+    /// The first shared pointer a type holds, not looking again into the types already looked into
+    fn shared_pointer_within(
+        &self,
+        ty: &syn::Type,
+        owner: Option<&str>,
+        seen: &mut HashSet<String>,
+    ) -> Option<String> {
+        match ty {
+            syn::Type::Reference(reference) => {
+                self.shared_pointer_within(&reference.elem, owner, seen)
+            }
+            syn::Type::Paren(paren) => self.shared_pointer_within(&paren.elem, owner, seen),
+            syn::Type::Group(group) => self.shared_pointer_within(&group.elem, owner, seen),
+            syn::Type::Array(array) => self.shared_pointer_within(&array.elem, owner, seen),
+            syn::Type::Slice(slice) => self.shared_pointer_within(&slice.elem, owner, seen),
+            syn::Type::Tuple(tuple) => tuple
+                .elems
+                .iter()
+                .find_map(|elem| self.shared_pointer_within(elem, owner, seen)),
+            syn::Type::Ptr(_) => Some("raw pointer".to_string()),
+            syn::Type::Path(path) => {
+                let last = path.path.segments.last()?;
+                let name = match last.ident.to_string() {
+                    name if name == "Self" => owner?.to_string(),
+                    name => name,
+                };
+                if SHARED_POINTERS.contains(&name.as_str()) {
+                    return Some(name);
+                }
+                let in_args = generic_args(last)
+                    .into_iter()
+                    .find_map(|arg| self.shared_pointer_within(arg, owner, seen));
+                if in_args.is_some() || OWNING_POINTERS.contains(&name.as_str()) {
+                    return in_args;
+                }
+                if !seen.insert(name.clone()) {
+                    return None;
+                }
+                self.fields
+                    .get(&name)?
+                    .iter()
+                    .find_map(|field| self.shared_pointer_within(field, owner, seen))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The type arguments of a path segment, like `T` in `Box<T>`
+pub fn generic_args(segment: &syn::PathSegment) -> Vec<&syn::Type> {
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) => args
+            .args
+            .iter()
+            .filter_map(|arg| match arg {
+                syn::GenericArgument::Type(ty) => Some(ty),
+                _ => None,
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// The declared type of each parameter of a function, receiver first, with the type a receiver is of
+pub fn param_types(graph: &CallGraph, function: usize) -> Vec<syn::Type> {
+    let node = &graph.functions[function];
+    node.ast
+        .sig
+        .inputs
+        .iter()
+        .map(|input| match input {
+            syn::FnArg::Receiver(receiver) => (*receiver.ty).clone(),
+            syn::FnArg::Typed(typed) => (*typed.ty).clone(),
+        })
+        .collect()
+}
+
+/// Whether a recursion descends structurally, and why.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Structure {
+    /// Every recursive call is on a part destructured from the same argument, of a type which is a tree.
+    Descends(String),
+    /// Every recursive call is on a part destructured from the same argument,
+    /// but its type may be shared, and so may be cyclic.
+    Shared(String),
+    /// Some recursive call isn't on a part of an argument of one of the crate's own types.
+    NotStructural,
+}
+
+/// Whether an argument is a part taken out of a parameter, like `tail` out of `list`
+fn is_part_of(arg: &Expr, param: &str) -> bool {
+    let mut whole = arg;
+    let mut parts = 0;
+    while let Expr::Part(inner, _) = whole {
+        whole = inner;
+        parts += 1;
+    }
+    parts > 0 && *whole == Expr::Var(param.to_string())
+}
+
+/// Decide whether a recursion only recurses on parts destructured from one of its arguments.
+///
+/// # Arguments
+///
+/// * `recursion` - The recursion to check.
+/// * `graph` - The call graph the recursion is part of, with the crate's type definitions.
+///
+/// # Returns
+///
+/// That the recursion descends an owned tree, which it must reach the bottom of,
+/// or descends data which may be shared and cyclic, or isn't structural at all.
+pub fn structural_recursion(recursion: &Recursion, graph: &CallGraph) -> Structure {
+    let Ok(members) = paths_from_recursion(recursion, graph) else {
+        return Structure::NotStructural;
+    };
+    let owner = |member: usize| graph.functions[member].owner.as_deref();
+    let types: HashMap<usize, Vec<syn::Type>> = recursion
+        .members
+        .iter()
+        .map(|&member| (member, param_types(graph, member)))
+        .collect();
+    let params: HashMap<usize, Vec<String>> = recursion
+        .members
+        .iter()
+        .map(|&member| (member, body_from_ast(&graph.functions[member].ast).params))
+        .collect();
+    let defined = |member: usize, k: usize| {
+        types[&member]
+            .get(k)
+            .and_then(|ty| graph.types.defined_type(ty, owner(member)))
+    };
+    let arity = types.values().map(Vec::len).min().unwrap_or(0);
+    let descends = (0..arity).find(|&k| {
+        recursion
+            .members
+            .iter()
+            .all(|&member| defined(member, k).is_some())
+            && members.iter().all(|(member, paths)| {
+                paths
+                    .iter()
+                    .filter(|path| !path.is_base_case() && !path.is_infeasible())
+                    .flat_map(|path| &path.calls)
+                    .all(|call| {
+                        call.args
+                            .get(k)
+                            .and_then(Option::as_ref)
+                            .is_some_and(|arg| is_part_of(arg, &params[member][k]))
+                    })
+            })
+    });
+    let Some(k) = descends else {
+        return Structure::NotStructural;
+    };
+    let &first = recursion.members.first().unwrap_or(&0);
+    let described = format!(
+        "every recursive call is on a part destructured from `{}`, a `{}`",
+        params[&first][k],
+        defined(first, k).unwrap_or_default()
+    );
+    for &member in &recursion.members {
+        if let Some(pointer) = graph
+            .types
+            .shared_pointer(&types[&member][k], owner(member))
+        {
+            return Structure::Shared(format!(
+                "{}, which holds data through `{}`, so its parts may be shared and come back around to it",
+                described, pointer
+            ));
+        }
+    }
+    Structure::Descends(format!(
+        "{}, which owns its parts, so taking it apart reaches the bottom",
+        described
+    ))
+}
+
+/// tests for structural recursion
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The structure of the one recursion in some code
+    fn structure_from_code(code: &str) -> Structure {
+        let file = syn::parse_file(code).unwrap();
+        let graph = CallGraph::from_file(&file);
+        let recursions = graph.recursions();
+        assert_eq!(recursions.len(), 1, "{}", code);
+        structural_recursion(&recursions[0], &graph)
+    }
+
+    /// Test shared pointers are found through fields, aliases and type arguments, but not through boxes alone
+    #[test]
+    fn test_shared_pointer() {
+        let file: syn::File = syn::parse_str(
+            "enum Expr { Add(Box<Expr>, Box<Expr>), Lit(i64) }
+            struct Node { value: u32, next: Link }
+            type Link = Option<Rc<RefCell<Node>>>;
+            struct Graph { nodes: Vec<Node> }",
+        )
+        .unwrap();
+        let mut types = TypeDefinitions::default();
+        types.add_items(&file.items);
+        let shared = |ty: &str| types.shared_pointer(&syn::parse_str(ty).unwrap(), None);
+        assert_eq!(shared("&Expr"), None);
+        assert_eq!(shared("Box<Expr>"), None);
+        assert_eq!(shared("&Node"), Some("Rc".to_string()));
+        assert_eq!(shared("Graph"), Some("Rc".to_string()));
+        assert_eq!(shared("&[Cell<u32>]"), Some("Cell".to_string()));
+        let defined = |ty: &str| types.defined_type(&syn::parse_str(ty).unwrap(), Some("Expr"));
+        assert_eq!(defined("&Box<Expr>"), Some("Expr".to_string()));
+        assert_eq!(defined("&Self"), Some("Expr".to_string()));
+        assert_eq!(defined("Vec<Expr>"), None);
+    }
+
+    /// Test tree walkers descend, through methods and mutual recursion too
+    #[test]
+    fn test_descends() {
+        for code in [
+            "enum Expr { Add(Box<Expr>, Box<Expr>), Neg(Box<Expr>), Lit(i64) }
+            fn eval(e: &Expr) -> i64 {
+                match e { Expr::Add(a, b) => eval(a) + eval(b), Expr::Neg(inner) => -eval(inner), Expr::Lit(n) => *n }
+            }",
+            "enum Tree { Node { left: Box<Tree>, right: Box<Tree> }, Leaf }
+            impl Tree {
+                fn leaves(&self) -> u32 {
+                    match self { Tree::Node { left, right } => left.leaves() + right.leaves(), Tree::Leaf => 1 }
+                }
+            }",
+            "enum Expr { Block(Box<Stmt>), Lit(i64) }
+            enum Stmt { Expr(Expr), Empty }
+            fn expr(e: &Expr) -> u32 { match e { Expr::Block(s) => stmt(s), Expr::Lit(_) => 0 } }
+            fn stmt(s: &Stmt) -> u32 { match s { Stmt::Expr(e) => expr(e) + 1, Stmt::Empty => 0 } }",
+        ] {
+            let structure = structure_from_code(code);
+            assert!(matches!(structure, Structure::Descends(_)), "{}: {:?}", code, structure);
+        }
+        let rebuilds = "enum Expr { Neg(Box<Expr>), Lit(i64) }
+            fn eval(e: &Expr) -> i64 { match e { Expr::Neg(inner) => eval(&Expr::Neg(inner.clone())), Expr::Lit(n) => *n } }";
+        assert_eq!(structure_from_code(rebuilds), Structure::NotStructural);
+    }
+
+    /// Test recursion over shared data is flagged, as it may go around a cycle
+    #[test]
+    fn test_shared() {
+        let code = "struct Node { next: Option<Rc<Node>> }
+            fn walk(node: &Node) -> u32 {
+                match &node.next { Some(next) => 1 + walk(next), None => 0 }
+            }";
+        let Structure::Shared(why) = structure_from_code(code) else {
+            panic!("{}", code);
+        };
+        assert!(why.contains("`Rc`"), "{}", why);
+    }
+}
@@ -0,0 +1,318 @@
+//! halts/src/symbolic.rs
+//! Symbolic execution of a function's control-flow graph: a run on symbols for its parameters rather than on values,
+//! which follows both sides of every branch that can go either way, collecting the conditions along each path as its path condition.
+//! Locals hold expressions over the parameters, and over fresh symbols for what can't be told, like the result of a call.
+//! Path conditions are linear constraints decided by the Omega test, so a path which can't be taken is dropped where it branches.
+//! A condition which isn't linear is left out of them, which only makes more paths seem takeable, so a path dropped is never a real one.
+//! A path whose condition left nothing out is exact: any solution of its condition over the parameters takes it, which makes it a witness.
+//! Paths go round loops as often as they like, up to a bound on how long they get, which keeps the exploration finite;
+//! a path cut off by it, or by the bound on how many paths there are, leaves the exploration incomplete.
+//! Integers are mathematical integers, except that an unsigned one going below zero panics.
+
+use std::collections::BTreeMap;
+
+use crate::ir::{BlockId, Body, Expr, Statement, Terminator, Ty, ENTRY};
+use crate::linear::{atoms_from_condition, linear_from_expr, Atom, LinearExpr};
+use crate::omega::{satisfiability, Satisfiability};
+
+/// The most blocks a path runs through before it is cut off
+const MAX_BLOCKS: usize = 256;
+
+/// The most paths followed before the exploration stops
+const MAX_PATHS: usize = 256;
+
+/// The prefix of the fresh symbols, which no local can start with
+const FRESH: &str = "?";
+
+/// A path from the entry of a function, run on symbols.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolicPath {
+    /// The blocks the path runs through, from the entry
+    pub blocks: Vec<BlockId>,
+    /// What each local assigned along the path holds, in terms of the parameters and fresh symbols
+    pub env: BTreeMap<String, Expr>,
+    /// The path condition: linear constraints over the parameters and fresh symbols which the path takes
+    pub condition: Vec<Atom>,
+    /// Whether the path condition is every condition along the path, none left out for not being linear
+    pub is_exact: bool,
+    /// The integer parameters of the function, which a witness gives values to
+    inputs: Vec<String>,
+    /// How many times the path has come to the block it was looking for
+    visits: usize,
+}
+
+impl SymbolicPath {
+    /// An expression over the locals at the end of the path, in terms of the parameters and fresh symbols,
+    /// or `None` if it depends on something opaque
+    pub fn value(&self, expr: &Expr) -> Option<Expr> {
+        expr.substitute(&|name| {
+            Some(
+                self.env
+                    .get(name)
+                    .cloned()
+                    .unwrap_or_else(|| Expr::Var(name.to_string())),
+            )
+        })
+    }
+
+    /// A constraint over the locals at the end of the path, in terms of the parameters and fresh symbols,
+    /// or `None` if what a local holds isn't linear
+    pub fn constraint(&self, atom: &Atom) -> Option<Atom> {
+        let expr = atom
+            .expr
+            .substitute(&|name| linear_from_expr(&self.value(&Expr::Var(name.to_string()))?))?;
+        Some(Atom {
+            expr,
+            relation: atom.relation,
+        })
+    }
+
+    /// Find an input which takes the path and ends it with the locals meeting some constraints.
+    ///
+    /// # Arguments
+    ///
+    /// * `constraints` - Linear constraints over the locals at the end of the path.
+    ///
+    /// # Returns
+    ///
+    /// A value for each integer parameter, or `None` if the path isn't exact, the constraints can't be met,
+    /// or meeting them takes a value for something other than a parameter, like the result of a call.
+    pub fn witness(&self, constraints: &[Atom]) -> Option<BTreeMap<String, i128>> {
+        if !self.is_exact {
+            return None;
+        }
+        let mut atoms = self.condition.clone();
+        for constraint in constraints {
+            atoms.push(self.constraint(constraint)?);
+        }
+        let Satisfiability::Satisfiable(mut model) = satisfiability(&atoms) else {
+            return None;
+        };
+        if model.keys().any(|name| !self.inputs.contains(name)) {
+            return None;
+        }
+        for input in &self.inputs {
+            model.entry(input.clone()).or_insert(0);
+        }
+        Some(model)
+    }
+
+    /// Add constraints to the path condition, keeping track of whether it still has every condition
+    fn assume(&mut self, atoms: Option<Vec<Atom>>) {
+        match atoms {
+            Some(atoms) => self.condition.extend(atoms),
+            None => self.is_exact = false,
+        }
+    }
+}
+
+/// What a symbolic execution found.
+#[derive(Debug, Clone, Default)]
+pub struct Exploration {
+    /// The paths which come to the block looked for, as they were each time they came to it
+    pub arrivals: Vec<SymbolicPath>,
+    /// Whether every path was followed to its end or to the block, none cut off for length or number
+    pub is_complete: bool,
+}
+
+/// Runs a body on symbols, one path at a time
+struct Executor<'a> {
+    /// The body run
+    body: &'a Body,
+    /// How many fresh symbols have been made
+    fresh: usize,
+}
+
+impl Executor<'_> {
+    /// A symbol which stands for a value which can't be told
+    fn fresh(&mut self) -> Expr {
+        self.fresh += 1;
+        Expr::Var(format!("{}{}", FRESH, self.fresh))
+    }
+
+    /// Set a local along a path, to what an expression over the locals holds
+    fn assign(&mut self, path: &mut SymbolicPath, local: &str, value: Option<Expr>) {
+        let value = match value {
+            Some(value) => value,
+            None => self.fresh(),
+        };
+        // an unsigned integer going below zero panics, so the path goes on only where it doesn't
+        let unsigned = self
+            .body
+            .local(local)
+            .is_some_and(|local| local.ty == Ty::Unsigned);
+        if unsigned {
+            if let Some(linear) = linear_from_expr(&value) {
+                path.condition.push(Atom::ge_zero(linear));
+            }
+        }
+        path.env.insert(local.to_string(), value);
+    }
+
+    /// Follow the statements of a block along a path
+    fn statements(&mut self, block: BlockId, path: &mut SymbolicPath) {
+        for statement in &self.body.blocks[block].statements {
+            match statement {
+                Statement::Assign { local, value } => {
+                    let value = path.value(value);
+                    if value.is_none() {
+                        path.is_exact = false;
+                    }
+                    self.assign(path, local, value);
+                }
+                Statement::Call { dest, .. } => self.assign(path, dest, None),
+                Statement::Havoc(local) => self.assign(path, local, None),
+                Statement::Assume(condition) => {
+                    let atoms = path
+                        .value(condition)
+                        .and_then(|condition| atoms_from_condition(&condition, true));
+                    path.assume(atoms);
+                }
+            }
+        }
+    }
+
+    /// Follow every path from the entry until it ends, or has come to the target `visits` times.
+    fn explore(&mut self, target: BlockId, visits: usize) -> Exploration {
+        let mut start = SymbolicPath {
+            is_exact: true,
+            ..SymbolicPath::default()
+        };
+        for name in &self.body.params {
+            match self.body.local(name).map(|local| local.ty) {
+                Some(Ty::Unsigned) => {
+                    start.inputs.push(name.clone());
+                    start
+                        .condition
+                        .push(Atom::ge_zero(LinearExpr::variable(name)));
+                }
+                Some(Ty::Signed) => start.inputs.push(name.clone()),
+                _ => {}
+            }
+        }
+        let mut exploration = Exploration {
+            arrivals: vec![],
+            is_complete: true,
+        };
+        let mut work = vec![(ENTRY, start)];
+        let mut followed = 0;
+        while let Some((block, mut path)) = work.pop() {
+            followed += 1;
+            if followed > MAX_PATHS * MAX_BLOCKS || path.blocks.len() >= MAX_BLOCKS {
+                exploration.is_complete = false;
+                continue;
+            }
+            path.blocks.push(block);
+            if block == target {
+                path.visits += 1;
+                exploration.arrivals.push(path.clone());
+                if path.visits >= visits {
+                    continue;
+                }
+            }
+            self.statements(block, &mut path);
+            match &self.body.blocks[block].terminator {
+                Terminator::Goto(next) => work.push((*next, path)),
+                Terminator::Branch {
+                    condition,
+                    then,
+                    otherwise,
+                } => {
+                    let condition = path.value(condition);
+                    for (holds, next) in [(false, *otherwise), (true, *then)] {
+                        let mut side = path.clone();
+                        let atoms = condition
+                            .as_ref()
+                            .and_then(|condition| atoms_from_condition(condition, holds));
+                        let is_checked = atoms.is_some();
+                        side.assume(atoms);
+                        // a side no input takes is no path
+                        if is_checked
+                            && satisfiability(&side.condition) == Satisfiability::Unsatisfiable
+                        {
+                            continue;
+                        }
+                        work.push((next, side));
+                    }
+                    if work.len() > MAX_PATHS {
+                        exploration.is_complete = false;
+                        work.truncate(MAX_PATHS);
+                    }
+                }
+                Terminator::Return | Terminator::Diverge(_) | Terminator::Unreachable => {}
+            }
+        }
+        exploration
+    }
+}
+
+/// Run a function on symbols for its parameters, looking for the paths which come to a block.
+///
+/// # Arguments
+///
+/// * `body` - The control-flow graph of the function.
+/// * `target` - The block looked for, like the head of a loop.
+/// * `visits` - How many times a path may come to the block before it is followed no further.
+///
+/// # Returns
+///
+/// The paths as they were each time they came to the block, and whether every path was followed.
+pub fn paths_to_block(body: &Body, target: BlockId, visits: usize) -> Exploration {
+    let mut executor = Executor { body, fresh: 0 };
+    executor.explore(target, visits)
+}
+
+/// tests for symbolic execution
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::halts::ast_from_str;
+    use crate::ir::body_from_ast;
+
+    /// A function's body and the head of its first loop
+    fn body_and_header(code: &str) -> (Body, BlockId) {
+        let body = body_from_ast(&ast_from_str(code).unwrap());
+        let header = body.loop_forest().loops[0].header;
+        (body, header)
+    }
+
+    /// Test a loop behind contradictory conditions is reached by no path
+    #[test]
+    fn test_unreachable() {
+        let (body, header) =
+            body_and_header("fn f(x: i64, y: i64) { if x > y { if y > x { loop {} } } }");
+        let exploration = paths_to_block(&body, header, 1);
+        assert!(exploration.is_complete);
+        assert!(exploration.arrivals.is_empty());
+
+        let (body, header) =
+            body_and_header("fn f(x: i64, y: i64) { if x > y { if y + 2 > x { loop {} } } }");
+        let exploration = paths_to_block(&body, header, 1);
+        assert_eq!(exploration.arrivals.len(), 1);
+    }
+
+    /// Test witnesses are found for paths which reach a block with the locals as asked
+    #[test]
+    fn test_witness() {
+        let (body, header) =
+            body_and_header("fn f(n: i64) { let mut x = n + 3; while x != 0 { x -= 2; } }");
+        let exploration = paths_to_block(&body, header, 4);
+        assert_eq!(exploration.arrivals.len(), 4);
+        // after one time round, x is n + 1, which is negative when n <= -2, and n isn't -3 to go round
+        let negative = Atom::ge_zero(
+            LinearExpr::variable("x")
+                .checked_scale(-1)
+                .unwrap()
+                .checked_sub(&LinearExpr::constant(1))
+                .unwrap(),
+        );
+        let witness = exploration.arrivals[1].witness(&[negative]).unwrap();
+        assert!(witness["n"] <= -2 && witness["n"] != -3);
+
+        // a condition which isn't linear leaves the path without a witness
+        let (body, header) = body_and_header("fn f(n: i64) { if n * n == 4 { loop {} } }");
+        let exploration = paths_to_block(&body, header, 1);
+        assert!(!exploration.arrivals[0].is_exact);
+        assert_eq!(exploration.arrivals[0].witness(&[]), None);
+    }
+}
@@ -0,0 +1,512 @@
+//! halts/src/verdict.rs
+//! The answer to the ternary halting problem is not a `bool` with an error on the side:
+//! HALT, LOOP and PARADOX are three categories of equal standing,
+//! and an honest analyzer needs a fourth, UNKNOWN, for everything it cannot yet decide.
+//! Every `Verdict` carries the `Evidence` which led to it.
+
+use std::fmt;
+
+/// A position in source code, as reported by `proc_macro2` with span locations enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LineColumn {
+    /// 1-indexed line number
+    pub line: usize,
+    /// 0-indexed column, in UTF-8 characters
+    pub column: usize,
+}
+
+/// A region of source code which evidence points at.
+///
+/// `proc_macro2::Span` is neither `Send` nor `Sync`, so verdicts keep plain line/column pairs instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SourceSpan {
+    /// Where the spanned code begins
+    pub start: LineColumn,
+    /// Where the spanned code ends
+    pub end: LineColumn,
+}
+
+impl From<proc_macro2::Span> for SourceSpan {
+    fn from(span: proc_macro2::Span) -> Self {
+        let start = span.start();
+        let end = span.end();
+        SourceSpan {
+            start: LineColumn {
+                line: start.line,
+                column: start.column,
+            },
+            end: LineColumn {
+                line: end.line,
+                column: end.column,
+            },
+        }
+    }
+}
+
+impl fmt::Display for SourceSpan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}-{}:{}",
+            self.start.line, self.start.column, self.end.line, self.end.column
+        )
+    }
+}
+
+/// The kinds of paradox which can be detected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ParadoxKind {
+    /// The function asks the oracle about itself and does the opposite of the answer.
+    Inversion,
+}
+
+impl fmt::Display for ParadoxKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ParadoxKind::Inversion => write!(f, "inversion"),
+        }
+    }
+}
+
+/// Why a verdict could not be reached.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Reason {
+    /// The source code of the function could not be recovered or parsed.
+    SourceUnavailable(String),
+    /// The function's behavior depends on what the oracle answers about the function itself.
+    OracleDependent,
+    /// Every rule was tried and none of them was conclusive.
+    Inconclusive,
+    /// The analyzer contradicted itself, which is a bug: engines which are each sound reached different verdicts.
+    InternalError(String),
+    /// The analysis ran out of the time it was given.
+    Timeout,
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::SourceUnavailable(why) => write!(f, "source unavailable: {}", why),
+            Reason::OracleDependent => write!(f, "depends on the oracle's answer about itself"),
+            Reason::Inconclusive => write!(f, "inconclusive"),
+            Reason::InternalError(why) => write!(f, "internal error: {}", why),
+            Reason::Timeout => write!(f, "timeout"),
+        }
+    }
+}
+
+/// The rules which can fire to produce a verdict.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+    /// The source of the function could not be analyzed at all.
+    Unanalyzable,
+    /// The function asks `halts` about itself.
+    SelfReference,
+    /// The function asks `halts` about itself and does the opposite of the answer.
+    Diagonalization,
+    /// Recursion without a base case.
+    MissingBaseCase,
+    /// Recursion whose base case can never be reached.
+    UnreachableBaseCase,
+    /// Recursion whose base case is always reached.
+    ReachableBaseCase,
+    /// Recursion which only recurses on parts taken apart from an owned tree.
+    StructuralRecursion,
+    /// Recursion which size-change graphs show to end, since some parameter shrinks around every cycle of calls.
+    SizeChange,
+    /// Iteration which can never be exited.
+    EndlessIteration,
+    /// Iteration which reaches a set of states it never leaves, and can't exit from.
+    RecurrentSet,
+    /// Iteration which a ranking function shows to end.
+    RankingFunction,
+    /// A function without parameters which, when run, returned or came back to a state it was in before.
+    Execution,
+    /// A function which halts on the inputs which meet a condition on its parameters.
+    Precondition,
+    /// Iteration which symbolic execution shows no path from the entry of the function reaches.
+    SymbolicExecution,
+    /// No construct capable of non-termination could be found.
+    NoLoopingConstructs,
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match *self {
+            Rule::Unanalyzable => "unanalyzable",
+            Rule::SelfReference => "self-reference",
+            Rule::Diagonalization => "diagonalization",
+            Rule::MissingBaseCase => "missing-base-case",
+            Rule::UnreachableBaseCase => "unreachable-base-case",
+            Rule::ReachableBaseCase => "reachable-base-case",
+            Rule::StructuralRecursion => "structural-recursion",
+            Rule::SizeChange => "size-change",
+            Rule::EndlessIteration => "endless-iteration",
+            Rule::RecurrentSet => "recurrent-set",
+            Rule::RankingFunction => "ranking-function",
+            Rule::Execution => "execution",
+            Rule::Precondition => "precondition",
+            Rule::SymbolicExecution => "symbolic-execution",
+            Rule::NoLoopingConstructs => "no-looping-constructs",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The engines which rules belong to, in the order they are tried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Engine {
+    /// Checks on the syntax of the function and its call graph, like asking `halts` about itself or recursing without a base case.
+    Syntactic,
+    /// Abstract interpretation, like intervals which show a loop can't be left, or calls which show a base case is reached.
+    AbstractInterpretation,
+    /// Synthesis of ranking functions, recurrent sets and preconditions from templates.
+    RankingSynthesis,
+    /// Symbolic execution, which follows the paths of the function with their conditions.
+    SymbolicExecution,
+    /// The interpreter, which runs the function.
+    Interpreter,
+}
+
+impl Engine {
+    /// Every engine, in the order they are tried
+    pub const ALL: [Engine; 5] = [
+        Engine::Syntactic,
+        Engine::AbstractInterpretation,
+        Engine::RankingSynthesis,
+        Engine::SymbolicExecution,
+        Engine::Interpreter,
+    ];
+}
+
+impl From<Rule> for Engine {
+    fn from(rule: Rule) -> Self {
+        match rule {
+            Rule::Unanalyzable
+            | Rule::SelfReference
+            | Rule::Diagonalization
+            | Rule::MissingBaseCase
+            | Rule::StructuralRecursion
+            | Rule::NoLoopingConstructs => Engine::Syntactic,
+            Rule::UnreachableBaseCase
+            | Rule::ReachableBaseCase
+            | Rule::SizeChange
+            | Rule::EndlessIteration => Engine::AbstractInterpretation,
+            Rule::RecurrentSet | Rule::RankingFunction | Rule::Precondition => {
+                Engine::RankingSynthesis
+            }
+            Rule::SymbolicExecution => Engine::SymbolicExecution,
+            Rule::Execution => Engine::Interpreter,
+        }
+    }
+}
+
+impl fmt::Display for Engine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match *self {
+            Engine::Syntactic => "syntactic",
+            Engine::AbstractInterpretation => "abstract-interpretation",
+            Engine::RankingSynthesis => "ranking-synthesis",
+            Engine::SymbolicExecution => "symbolic-execution",
+            Engine::Interpreter => "interpreter",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A witness that a loop runs forever, which can be checked by following it:
+/// a stem from the entry of a function to the head of the loop,
+/// and a recurrent set of states there which every way round the loop maps back into, and no way out leaves from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lasso {
+    /// The blocks of the function's control-flow graph the stem passes through, from its entry to the head of the loop
+    pub stem: Vec<usize>,
+    /// The value of each integer parameter the stem starts from, in words, none for a function without parameters
+    pub input: Vec<(String, String)>,
+    /// The value of each local at the end of the stem, in words
+    pub state: Vec<(String, String)>,
+    /// The constraints on the locals at the head of the loop which make up the recurrent set, in words
+    pub recurrent_set: Vec<String>,
+}
+
+impl fmt::Display for Lasso {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stem: Vec<String> = self
+            .stem
+            .iter()
+            .map(|block| format!("bb{}", block))
+            .collect();
+        let state: Vec<String> = self
+            .state
+            .iter()
+            .map(|(local, value)| format!("{} = {}", local, value))
+            .collect();
+        let recurrent_set = if self.recurrent_set.is_empty() {
+            "true".to_string()
+        } else {
+            self.recurrent_set.join(" && ")
+        };
+        write!(
+            f,
+            "stem {} reaches the loop with {}, then the cycle keeps {} forever",
+            stem.join(" → "),
+            if state.is_empty() {
+                "no locals".to_string()
+            } else {
+                state.join(", ")
+            },
+            recurrent_set
+        )
+    }
+}
+
+/// A condition on the parameters of a function under which it halts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Precondition {
+    /// The condition, in Rust, like `n >= 0`
+    pub condition: String,
+    /// Whether the function loops on some input which fails the condition, so it can't be done without
+    pub loops_otherwise: bool,
+}
+
+impl fmt::Display for Precondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.loops_otherwise {
+            write!(f, "{}, and loops otherwise", self.condition)
+        } else {
+            write!(f, "{}", self.condition)
+        }
+    }
+}
+
+/// Why a verdict was reached: the rule that fired, where, and what it was built from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Evidence {
+    /// The rule which fired
+    pub rule: Rule,
+    /// The source code the rule fired on
+    pub spans: Vec<SourceSpan>,
+    /// The verdicts of the parts this verdict was built from
+    pub sub_verdicts: Vec<Verdict>,
+    /// Facts established along the way, in words, such as the cycle of a recursion
+    pub notes: Vec<String>,
+    /// A run which goes on forever, for a verdict of LOOP which has one
+    pub lasso: Option<Lasso>,
+}
+
+impl Evidence {
+    /// Evidence for a rule with no spans and no sub-verdicts yet.
+    pub fn new(rule: Rule) -> Self {
+        Evidence {
+            rule,
+            spans: vec![],
+            sub_verdicts: vec![],
+            notes: vec![],
+            lasso: None,
+        }
+    }
+
+    /// Evidence for a rule which fired on a single span.
+    pub fn at(rule: Rule, span: impl Into<SourceSpan>) -> Self {
+        let mut evidence = Evidence::new(rule);
+        evidence.spans.push(span.into());
+        evidence
+    }
+}
+
+/// A ternary answer to the halting problem, plus UNKNOWN.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    /// The function halts.
+    Halts(Evidence),
+    /// The function halts on the inputs which meet a condition.
+    HaltsWhen(Precondition, Evidence),
+    /// The function loops forever.
+    Loops(Evidence),
+    /// Whatever the oracle answers about the function, the function makes it wrong.
+    Paradox(ParadoxKind, Evidence),
+    /// The analyzer could not decide.
+    Unknown(Reason, Evidence),
+}
+
+impl Verdict {
+    /// The evidence behind the verdict
+    pub fn evidence(&self) -> &Evidence {
+        match self {
+            Verdict::Halts(evidence)
+            | Verdict::HaltsWhen(_, evidence)
+            | Verdict::Loops(evidence)
+            | Verdict::Paradox(_, evidence)
+            | Verdict::Unknown(_, evidence) => evidence,
+        }
+    }
+
+    /// Mutable access to the evidence behind the verdict
+    pub fn evidence_mut(&mut self) -> &mut Evidence {
+        match self {
+            Verdict::Halts(evidence)
+            | Verdict::HaltsWhen(_, evidence)
+            | Verdict::Loops(evidence)
+            | Verdict::Paradox(_, evidence)
+            | Verdict::Unknown(_, evidence) => evidence,
+        }
+    }
+
+    /// Whether the verdict is anything other than UNKNOWN
+    pub fn is_conclusive(&self) -> bool {
+        !matches!(self, Verdict::Unknown(..))
+    }
+
+    /// The category of the verdict as it is printed: HALT, HALT WHEN, LOOP, PARADOX or UNKNOWN
+    pub fn category(&self) -> &'static str {
+        match self {
+            Verdict::Halts(_) => "HALT",
+            Verdict::HaltsWhen(..) => "HALT WHEN",
+            Verdict::Loops(_) => "LOOP",
+            Verdict::Paradox(..) => "PARADOX",
+            Verdict::Unknown(..) => "UNKNOWN",
+        }
+    }
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Verdict::Paradox(kind, evidence) => {
+                write!(f, "{} ({}) by {}", self.category(), kind, evidence.rule)
+            }
+            Verdict::HaltsWhen(precondition, evidence) => {
+                write!(
+                    f,
+                    "{} {} by {}",
+                    self.category(),
+                    precondition,
+                    evidence.rule
+                )
+            }
+            Verdict::Unknown(reason, evidence) => {
+                write!(f, "{} ({}) by {}", self.category(), reason, evidence.rule)
+            }
+            _ => write!(f, "{} by {}", self.category(), self.evidence().rule),
+        }
+    }
+}
+
+/// A verdict, with the engine which reached it and how many times the abstraction was refined first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decision {
+    /// The verdict
+    pub verdict: Verdict,
+    /// The engine of the rule the verdict was reached by
+    pub engine: Engine,
+    /// How many claims of LOOP were shown spurious, each refining the abstraction, before the verdict stood
+    pub rounds: usize,
+}
+
+impl Decision {
+    /// A verdict reached after some rounds of refinement, by the engine of its rule,
+    /// or for HALT WHEN, of the first verdict the function was found to halt by under its condition
+    pub fn new(verdict: Verdict, rounds: usize) -> Self {
+        let rule = match &verdict {
+            Verdict::HaltsWhen(_, evidence) => evidence
+                .sub_verdicts
+                .iter()
+                .skip(1)
+                .find(|sub_verdict| matches!(sub_verdict, Verdict::Halts(_)))
+                .map_or(evidence.rule, |halting| halting.evidence().rule),
+            verdict => verdict.evidence().rule,
+        };
+        Decision {
+            engine: Engine::from(rule),
+            verdict,
+            rounds,
+        }
+    }
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}, from the {} engine after {} round{} of refinement",
+            self.verdict,
+            self.engine,
+            self.rounds,
+            if self.rounds == 1 { "" } else { "s" }
+        )
+    }
+}
+
+/// tests for verdicts
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Test that verdicts print their category, reason and rule
+    #[test]
+    fn test_verdict_display() {
+        let halts = Verdict::Halts(Evidence::new(Rule::NoLoopingConstructs));
+        assert_eq!(halts.to_string(), "HALT by no-looping-constructs");
+
+        let paradox =
+            Verdict::Paradox(ParadoxKind::Inversion, Evidence::new(Rule::Diagonalization));
+        assert_eq!(
+            paradox.to_string(),
+            "PARADOX (inversion) by diagonalization"
+        );
+
+        let halts_when = Verdict::HaltsWhen(
+            Precondition {
+                condition: "n >= 0".to_string(),
+                loops_otherwise: true,
+            },
+            Evidence::new(Rule::Precondition),
+        );
+        assert_eq!(
+            halts_when.to_string(),
+            "HALT WHEN n >= 0, and loops otherwise by precondition"
+        );
+
+        let unknown = Verdict::Unknown(Reason::Inconclusive, Evidence::new(Rule::Unanalyzable));
+        assert!(!unknown.is_conclusive());
+        assert_eq!(unknown.category(), "UNKNOWN");
+    }
+
+    /// Test that decisions are put down to the engine of their rule
+    #[test]
+    fn test_decision() {
+        let decision = Decision::new(Verdict::Loops(Evidence::new(Rule::RecurrentSet)), 1);
+        assert_eq!(decision.engine, Engine::RankingSynthesis);
+        assert_eq!(
+            decision.to_string(),
+            "LOOP by recurrent-set, from the ranking-synthesis engine after 1 round of refinement"
+        );
+
+        // the condition of a HALT WHEN is found by synthesis, but its verdicts may come from any engine
+        let mut evidence = Evidence::new(Rule::Precondition);
+        evidence
+            .sub_verdicts
+            .push(Verdict::Loops(Evidence::new(Rule::RecurrentSet)));
+        evidence
+            .sub_verdicts
+            .push(Verdict::Halts(Evidence::new(Rule::Execution)));
+        let precondition = Precondition {
+            condition: "n == 0".to_string(),
+            loops_otherwise: true,
+        };
+        let decision = Decision::new(Verdict::HaltsWhen(precondition, evidence), 0);
+        assert_eq!(decision.engine, Engine::Interpreter);
+    }
+
+    /// Test that sub-verdicts are reachable through the evidence
+    #[test]
+    fn test_sub_verdicts() {
+        let mut evidence = Evidence::new(Rule::EndlessIteration);
+        evidence
+            .sub_verdicts
+            .push(Verdict::Loops(Evidence::new(Rule::EndlessIteration)));
+        let verdict = Verdict::Loops(evidence);
+        assert_eq!(verdict.evidence().sub_verdicts.len(), 1);
+        assert!(verdict.evidence().sub_verdicts[0].is_conclusive());
+    }
+}